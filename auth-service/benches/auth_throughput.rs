@@ -0,0 +1,125 @@
+//! Signup and login throughput of the HTTP API against a fresh PostgreSQL database.
+//!
+//! Users are stored in PostgreSQL so password hashing runs as in production.
+//! Every other store is in memory to keep Redis and Postmark out of the numbers.
+//!
+//! Run with `cargo bench --bench auth_throughput`.
+//! `DATABASE_URL` must point at a server where new databases can be created.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use auth_service::{
+    app_state::state::AppState,
+    domain::{
+        data_stores::HashsetBannedTokenStore, hashmap_two_fa_code_store::HashMapTwoFACodeStore,
+    },
+    get_postgres_pool,
+    services::{
+        data_stores::PostgresUserStore, hashmap_email_outbox::HashMapEmailOutbox,
+        hashmap_email_suppression_store::HashMapEmailSuppressionStore,
+        hashmap_organization_store::HashMapOrganizationStore, mock_email_client::MockEmailClient,
+        vec_audit_store::VecAuditStore,
+    },
+    utils::constants::{test, DATABASE_URL},
+    Application,
+};
+use sqlx::{Connection, Executor, PgConnection};
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+const USERS: usize = 96;
+const CONCURRENCY: usize = 16;
+
+#[tokio::main]
+async fn main() {
+    let db_name = Uuid::new_v4().to_string();
+    let mut admin = PgConnection::connect(&DATABASE_URL)
+        .await
+        .expect("Failed to connect to Postgres");
+    admin
+        .execute(format!(r#"CREATE DATABASE "{}";"#, db_name).as_str())
+        .await
+        .expect("Failed to create database.");
+
+    let pool = get_postgres_pool(&format!("{}/{}", *DATABASE_URL, db_name))
+        .await
+        .expect("Failed to create Postgres connection pool");
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .expect("Failed to migrate the database");
+
+    let app_state = AppState::new(
+        Arc::new(PostgresUserStore::new(pool.clone())),
+        Arc::new(HashsetBannedTokenStore::default()),
+        Arc::new(HashMapTwoFACodeStore::default()),
+        Arc::new(MockEmailClient),
+        Arc::new(HashMapOrganizationStore::default()),
+        Arc::new(VecAuditStore::default()),
+        Arc::new(HashMapEmailOutbox::default()),
+        Arc::new(HashMapEmailSuppressionStore::default()),
+    );
+    let app = Application::build(app_state, test::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
+    let address = format!("http://{}", app.address);
+    tokio::spawn(app.run());
+
+    let emails: Vec<String> = (0..USERS)
+        .map(|_| format!("{}@example.com", Uuid::new_v4()))
+        .collect();
+    let signup = run("signup", &address, "/signup", &emails).await;
+    let login = run("login", &address, "/login", &emails).await;
+    println!(
+        "{} users, {} concurrent clients: signup {:.1} req/s, login {:.1} req/s",
+        USERS, CONCURRENCY, signup, login
+    );
+
+    pool.close().await;
+    admin
+        .execute(format!(r#"DROP DATABASE "{}" WITH (FORCE);"#, db_name).as_str())
+        .await
+        .expect("Failed to drop the database.");
+}
+
+/// Post credentials for every email to `path`, spread over `CONCURRENCY` clients,
+/// and return the number of requests per second
+async fn run(name: &str, address: &str, path: &str, emails: &[String]) -> f64 {
+    let started = Instant::now();
+    let mut clients = JoinSet::new();
+    for chunk in emails.chunks(emails.len().div_ceil(CONCURRENCY)) {
+        let url = format!("{}{}", address, path);
+        let chunk = chunk.to_vec();
+        clients.spawn(async move {
+            let http_client = reqwest::Client::new();
+            for email in chunk {
+                let response = http_client
+                    .post(&url)
+                    .json(&serde_json::json!({
+                        "email": email,
+                        "password": "password123",
+                        "requires2FA": false
+                    }))
+                    .send()
+                    .await
+                    .expect("Failed to send request");
+                assert!(response.status().is_success(), "{}", response.status());
+            }
+        });
+    }
+    while let Some(result) = clients.join_next().await {
+        result.expect("Client task panicked");
+    }
+
+    let elapsed = started.elapsed();
+    report(name, emails.len(), elapsed)
+}
+
+fn report(name: &str, requests: usize, elapsed: Duration) -> f64 {
+    let throughput = requests as f64 / elapsed.as_secs_f64();
+    println!("{}: {} requests in {:.2?}", name, requests, elapsed);
+    throughput
+}
@@ -0,0 +1,36 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   PRIMARY KEY (email, role)
+);
+
+INSERT INTO permissions (name, description) VALUES
+   ('users:read', 'View user accounts'),
+   ('users:write', 'Modify user accounts'),
+   ('roles:manage', 'Assign and revoke roles')
+ON CONFLICT DO NOTHING;
+
+-- The admin role is granted every permission
+INSERT INTO roles (name, description) VALUES ('admin', 'Full administrative access')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission)
+SELECT 'admin', name FROM permissions
+ON CONFLICT DO NOTHING;
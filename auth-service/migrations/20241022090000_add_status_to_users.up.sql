@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'
+      CHECK (status IN ('active', 'suspended', 'locked', 'pending_verification')),
+   ADD COLUMN IF NOT EXISTS status_reason TEXT,
+   ADD COLUMN IF NOT EXISTS status_changed_by TEXT,
+   ADD COLUMN IF NOT EXISTS status_changed_at TIMESTAMPTZ;
+
+-- Accounts disabled by an admin become suspended
+UPDATE users SET status = 'suspended' WHERE disabled;
+
+ALTER TABLE users DROP COLUMN IF EXISTS disabled;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS invitations;
+DROP TABLE IF EXISTS membership_roles;
+DROP TABLE IF EXISTS memberships;
+DROP TABLE IF EXISTS organizations;
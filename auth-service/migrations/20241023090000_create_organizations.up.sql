@@ -0,0 +1,36 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS organizations(
+   id UUID NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS memberships(
+   organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (organization_id, email)
+);
+
+CREATE INDEX IF NOT EXISTS memberships_email_idx ON memberships(email);
+
+-- Roles a member holds inside a single organization
+CREATE TABLE IF NOT EXISTS membership_roles(
+   organization_id UUID NOT NULL,
+   email TEXT NOT NULL,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   PRIMARY KEY (organization_id, email, role),
+   FOREIGN KEY (organization_id, email)
+      REFERENCES memberships(organization_id, email) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- The invitee may not have signed up yet, so email is not a foreign key
+CREATE TABLE IF NOT EXISTS invitations(
+   token TEXT NOT NULL PRIMARY KEY,
+   organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+   email TEXT NOT NULL,
+   role TEXT REFERENCES roles(name) ON DELETE SET NULL,
+   invited_by TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL,
+   accepted_at TIMESTAMPTZ
+);
@@ -0,0 +1,38 @@
+-- Add up migration script here
+-- Actor and target are not foreign keys: failed logins are recorded against
+-- unknown emails, and the trail must outlive deleted accounts
+CREATE TABLE IF NOT EXISTS audit_events(
+   id BIGSERIAL PRIMARY KEY,
+   occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   actor TEXT,
+   action TEXT NOT NULL,
+   target TEXT,
+   outcome TEXT NOT NULL CHECK (outcome IN ('success', 'failure')),
+   ip TEXT,
+   user_agent TEXT,
+   request_id TEXT
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_actor_idx ON audit_events(actor);
+CREATE INDEX IF NOT EXISTS audit_events_target_idx ON audit_events(target);
+
+-- The trail is append-only
+CREATE OR REPLACE FUNCTION reject_audit_event_changes() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+BEFORE UPDATE OR DELETE ON audit_events
+FOR EACH ROW EXECUTE FUNCTION reject_audit_event_changes();
+
+CREATE TRIGGER audit_events_no_truncate
+BEFORE TRUNCATE ON audit_events
+FOR EACH STATEMENT EXECUTE FUNCTION reject_audit_event_changes();
+
+INSERT INTO permissions (name, description) VALUES ('audit:read', 'View the audit trail')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'audit:read')
+ON CONFLICT DO NOTHING;
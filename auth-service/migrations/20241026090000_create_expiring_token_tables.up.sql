@@ -0,0 +1,26 @@
+-- Add up migration script here
+-- Replacements for the Redis keys, for deployments without Redis.
+-- Emails are not foreign keys, users may live in another database.
+CREATE TABLE IF NOT EXISTS banned_tokens(
+   token TEXT NOT NULL PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Tokens issued to the user at or before revoked_at (unix seconds) are invalid
+CREATE TABLE IF NOT EXISTS revoked_sessions(
+   email TEXT NOT NULL PRIMARY KEY,
+   revoked_at BIGINT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS two_fa_codes(
+   email TEXT NOT NULL PRIMARY KEY,
+   login_attempt_id TEXT NOT NULL,
+   code TEXT NOT NULL,
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- The sweeper deletes expired rows
+CREATE INDEX IF NOT EXISTS banned_tokens_expires_at_idx ON banned_tokens(expires_at);
+CREATE INDEX IF NOT EXISTS revoked_sessions_expires_at_idx ON revoked_sessions(expires_at);
+CREATE INDEX IF NOT EXISTS two_fa_codes_expires_at_idx ON two_fa_codes(expires_at);
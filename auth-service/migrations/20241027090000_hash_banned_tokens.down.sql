@@ -0,0 +1,3 @@
+-- Add down migration script here
+DELETE FROM banned_tokens;
+ALTER TABLE banned_tokens RENAME COLUMN token_hash TO token;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Banned tokens are kept as a keyed hash instead of the JWT itself.
+-- Existing rows hold replayable tokens and cannot be hashed here, so they are dropped.
+DELETE FROM banned_tokens;
+ALTER TABLE banned_tokens RENAME COLUMN token TO token_hash;
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_id_idx;
+
+ALTER TABLE users
+   DROP COLUMN IF EXISTS password_changed_at,
+   DROP COLUMN IF EXISTS failed_login_count,
+   DROP COLUMN IF EXISTS last_login_ip,
+   DROP COLUMN IF EXISTS last_login_at,
+   DROP COLUMN IF EXISTS updated_at,
+   DROP COLUMN IF EXISTS created_at,
+   DROP COLUMN IF EXISTS id;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Existing accounts get a fresh id and count as created and last changed now
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid(),
+   ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   ADD COLUMN IF NOT EXISTS last_login_at TIMESTAMPTZ,
+   ADD COLUMN IF NOT EXISTS last_login_ip TEXT,
+   ADD COLUMN IF NOT EXISTS failed_login_count INTEGER NOT NULL DEFAULT 0
+      CHECK (failed_login_count >= 0),
+   ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Email stays the primary key, other tables reference it
+CREATE UNIQUE INDEX IF NOT EXISTS users_id_idx ON users(id);
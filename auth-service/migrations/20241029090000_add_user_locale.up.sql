@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Language subtag the user wants emails in. NULL follows the language of their requests.
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS locale TEXT;
@@ -0,0 +1,29 @@
+-- Add up migration script here
+-- Emails written in the request path and delivered by the outbox worker.
+-- The recipient is not a foreign key, invitations go to people without an account.
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id UUID PRIMARY KEY,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   -- Emptied once the message is sent
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'dead')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   last_error TEXT,
+   next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   sent_at TIMESTAMPTZ
+);
+
+-- The worker only ever looks for due pending messages
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx ON email_outbox(next_attempt_at)
+WHERE status = 'pending';
+CREATE INDEX IF NOT EXISTS email_outbox_created_at_idx ON email_outbox(created_at);
+
+INSERT INTO permissions (name, description)
+VALUES ('emails:manage', 'View the email outbox and retry failed emails')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES ('admin', 'emails:manage')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- Addresses that bounced or complained. Not a foreign key to users,
+-- invitations go to people without an account.
+CREATE TABLE IF NOT EXISTS email_suppressions(
+   email TEXT PRIMARY KEY,
+   reason TEXT NOT NULL CHECK (reason IN ('hard_bounce', 'spam_complaint')),
+   description TEXT,
+   suppressed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS email_suppressions_suppressed_at_idx
+ON email_suppressions(suppressed_at);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS user_devices;
+
+ALTER TABLE users
+   DROP COLUMN IF EXISTS security_notices;
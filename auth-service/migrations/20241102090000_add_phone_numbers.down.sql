@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE users
+   DROP COLUMN IF EXISTS two_fa_channel,
+   DROP COLUMN IF EXISTS phone_verified,
+   DROP COLUMN IF EXISTS phone_number;
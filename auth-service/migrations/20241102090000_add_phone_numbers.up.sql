@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Phone numbers are stored in E.164 form and only receive 2FA codes once verified
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS phone_number TEXT,
+   ADD COLUMN IF NOT EXISTS phone_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   ADD COLUMN IF NOT EXISTS two_fa_channel TEXT NOT NULL DEFAULT 'email'
+      CHECK (two_fa_channel IN ('email', 'sms'));
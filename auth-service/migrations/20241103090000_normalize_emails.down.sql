@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- Addresses stay lowercased, their original spelling is gone
+DROP INDEX IF EXISTS users_email_lower_idx;
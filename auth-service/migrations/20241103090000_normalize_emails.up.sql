@@ -0,0 +1,64 @@
+-- Add up migration script here
+-- Emails are lowercased when parsed from now on. Accounts whose addresses only
+-- differ in case belong to one mailbox, and must be merged by hand first.
+-- LOWER only matches how the service normalizes ASCII addresses, since that
+-- also converts domains to punycode. Any others must be converted by hand.
+DO $$
+DECLARE
+   collisions TEXT;
+   unconverted TEXT;
+BEGIN
+   SELECT string_agg(DISTINCT email, ', ') INTO unconverted
+   FROM (
+      SELECT email FROM users
+      UNION ALL SELECT email FROM revoked_sessions
+      UNION ALL SELECT email FROM email_suppressions
+      UNION ALL SELECT email FROM invitations
+   ) AS emails
+   WHERE email ~ '[^\x01-\x7f]';
+   IF unconverted IS NOT NULL THEN
+      RAISE EXCEPTION 'Emails with non-ASCII characters must be converted first: %', unconverted;
+   END IF;
+
+   SELECT string_agg(addresses, '; ') INTO collisions
+   FROM (
+      SELECT string_agg(email, ', ' ORDER BY email) AS addresses
+      FROM users
+      GROUP BY LOWER(email)
+      HAVING COUNT(*) > 1
+   ) AS duplicates;
+   IF collisions IS NOT NULL THEN
+      RAISE EXCEPTION 'Users whose emails only differ in case must be merged first: %', collisions;
+   END IF;
+END $$;
+
+-- Roles, devices and memberships follow through ON UPDATE CASCADE
+UPDATE users SET email = LOWER(email) WHERE email <> LOWER(email);
+
+-- Also guards against rows written without going through the service
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users(LOWER(email));
+
+-- The other tables are keyed by the same address. Where two spellings meet,
+-- the later revocation and the earlier suppression win.
+INSERT INTO revoked_sessions (email, revoked_at, expires_at)
+SELECT LOWER(email), MAX(revoked_at), MAX(expires_at)
+FROM revoked_sessions
+WHERE email <> LOWER(email)
+GROUP BY LOWER(email)
+ON CONFLICT (email) DO UPDATE
+SET revoked_at = GREATEST(revoked_sessions.revoked_at, EXCLUDED.revoked_at),
+    expires_at = GREATEST(revoked_sessions.expires_at, EXCLUDED.expires_at);
+DELETE FROM revoked_sessions WHERE email <> LOWER(email);
+
+INSERT INTO email_suppressions (email, reason, description, suppressed_at)
+SELECT DISTINCT ON (LOWER(email)) LOWER(email), reason, description, suppressed_at
+FROM email_suppressions
+WHERE email <> LOWER(email)
+ORDER BY LOWER(email), suppressed_at
+ON CONFLICT (email) DO NOTHING;
+DELETE FROM email_suppressions WHERE email <> LOWER(email);
+
+-- Pending codes expire within minutes, the user can log in again
+DELETE FROM two_fa_codes WHERE email <> LOWER(email);
+
+UPDATE invitations SET email = LOWER(email) WHERE email <> LOWER(email);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+UPDATE revoked_sessions SET revoked_at = revoked_at / 1000;
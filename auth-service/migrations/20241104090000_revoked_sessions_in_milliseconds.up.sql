@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- revoked_at moves from unix seconds to milliseconds, so a login right after a
+-- revocation is not mistaken for one of the sessions it ended.
+-- A revocation in seconds covers every token issued within that second.
+UPDATE revoked_sessions SET revoked_at = revoked_at * 1000 + 999;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+INSERT INTO membership_roles (organization_id, email, role)
+SELECT organization_id, email, 'admin' FROM membership_roles WHERE role = 'org-admin'
+ON CONFLICT DO NOTHING;
+
+UPDATE invitations SET role = 'admin' WHERE role = 'org-admin';
+
+DELETE FROM roles WHERE name = 'org-admin';
@@ -0,0 +1,20 @@
+-- Add up migration script here
+-- Given to whoever creates an organization. It manages the members of the
+-- organization and their roles there, but not their accounts.
+INSERT INTO roles (name, description) VALUES ('org-admin', 'Manage the members of an organization')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+   ('org-admin', 'users:read'),
+   ('org-admin', 'users:write'),
+   ('org-admin', 'roles:manage')
+ON CONFLICT DO NOTHING;
+
+-- Organizations used to hand out the global admin role to their members
+INSERT INTO membership_roles (organization_id, email, role)
+SELECT organization_id, email, 'org-admin' FROM membership_roles WHERE role = 'admin'
+ON CONFLICT DO NOTHING;
+
+DELETE FROM membership_roles WHERE role = 'admin';
+
+UPDATE invitations SET role = 'org-admin' WHERE role = 'admin';
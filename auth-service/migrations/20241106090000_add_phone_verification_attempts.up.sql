@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Codes entered since a code was last texted, so they cannot be guessed
+ALTER TABLE users ADD COLUMN phone_verification_attempts INTEGER NOT NULL DEFAULT 0;
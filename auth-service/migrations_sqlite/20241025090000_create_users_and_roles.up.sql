@@ -0,0 +1,47 @@
+-- Add up migration script here
+-- Mirrors the user tables of the PostgreSQL migrations in a single step
+CREATE TABLE IF NOT EXISTS users(
+   email TEXT NOT NULL PRIMARY KEY,
+   password_hash TEXT NOT NULL,
+   requires_2fa BOOLEAN NOT NULL DEFAULT FALSE,
+   password_reset_required BOOLEAN NOT NULL DEFAULT FALSE,
+   status TEXT NOT NULL DEFAULT 'active'
+      CHECK (status IN ('active', 'suspended', 'locked', 'pending_verification')),
+   status_reason TEXT,
+   status_changed_by TEXT,
+   status_changed_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT NOT NULL PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   PRIMARY KEY (email, role)
+);
+
+INSERT OR IGNORE INTO permissions (name, description) VALUES
+   ('users:read', 'View user accounts'),
+   ('users:write', 'Modify user accounts'),
+   ('roles:manage', 'Assign and revoke roles'),
+   ('audit:read', 'View the audit trail');
+
+-- The admin role is granted every permission
+INSERT OR IGNORE INTO roles (name, description) VALUES ('admin', 'Full administrative access');
+
+INSERT OR IGNORE INTO role_permissions (role, permission)
+SELECT 'admin', name FROM permissions;
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS users_id_idx;
+
+ALTER TABLE users DROP COLUMN password_changed_at;
+ALTER TABLE users DROP COLUMN failed_login_count;
+ALTER TABLE users DROP COLUMN last_login_ip;
+ALTER TABLE users DROP COLUMN last_login_at;
+ALTER TABLE users DROP COLUMN updated_at;
+ALTER TABLE users DROP COLUMN created_at;
+ALTER TABLE users DROP COLUMN id;
@@ -0,0 +1,24 @@
+-- Add up migration script here
+-- Mirrors the PostgreSQL migration. Timestamps are microseconds since the
+-- epoch, and existing accounts count as created and last changed now.
+ALTER TABLE users ADD COLUMN id TEXT;
+ALTER TABLE users ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN last_login_at INTEGER;
+ALTER TABLE users ADD COLUMN last_login_ip TEXT;
+ALTER TABLE users ADD COLUMN failed_login_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN password_changed_at INTEGER NOT NULL DEFAULT 0;
+
+-- SQLite cannot generate UUIDs, so build version 4 ones from random bytes
+UPDATE users SET
+   id = lower(
+      hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+      substr(hex(randomblob(2)), 2) || '-' ||
+      substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' ||
+      hex(randomblob(6))
+   ),
+   created_at = CAST((julianday('now') - 2440587.5) * 86400000000 AS INTEGER),
+   updated_at = CAST((julianday('now') - 2440587.5) * 86400000000 AS INTEGER),
+   password_changed_at = CAST((julianday('now') - 2440587.5) * 86400000000 AS INTEGER);
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_id_idx ON users(id);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Language subtag the user wants emails in. NULL follows the language of their requests.
+ALTER TABLE users ADD COLUMN locale TEXT;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+DELETE FROM permissions WHERE name = 'emails:manage';
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- The email outbox itself lives in PostgreSQL
+INSERT OR IGNORE INTO permissions (name, description)
+VALUES ('emails:manage', 'View the email outbox and retry failed emails');
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES ('admin', 'emails:manage');
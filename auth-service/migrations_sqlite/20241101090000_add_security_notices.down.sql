@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE user_devices;
+ALTER TABLE users DROP COLUMN security_notices;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Users can opt out of notices about new logins and 2FA being turned on
+ALTER TABLE users ADD COLUMN security_notices BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Devices each user logged in from, identified by the hash of a long-lived cookie
+CREATE TABLE IF NOT EXISTS user_devices(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   device_hash TEXT NOT NULL,
+   first_seen_at INTEGER NOT NULL,
+   PRIMARY KEY (email, device_hash)
+);
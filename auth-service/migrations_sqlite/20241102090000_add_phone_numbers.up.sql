@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Phone numbers are stored in E.164 form and only receive 2FA codes once verified
+ALTER TABLE users ADD COLUMN phone_number TEXT;
+ALTER TABLE users ADD COLUMN phone_verified BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN two_fa_channel TEXT NOT NULL DEFAULT 'email'
+   CHECK (two_fa_channel IN ('email', 'sms'));
@@ -0,0 +1,21 @@
+-- Add up migration script here
+-- LOWER only matches how the service normalizes ASCII addresses, since that
+-- also converts domains to punycode. Any others must be converted by hand,
+-- so copying them into this table fails on its CHECK constraint. List them with:
+--   SELECT email FROM users WHERE length(email) <> length(CAST(email AS BLOB));
+CREATE TEMP TABLE unconverted_emails (
+    email TEXT CONSTRAINT non_ascii_emails_must_be_converted_first CHECK (email IS NULL)
+);
+INSERT INTO unconverted_emails
+SELECT email FROM users WHERE length(email) <> length(CAST(email AS BLOB));
+DROP TABLE unconverted_emails;
+
+-- Emails are lowercased when parsed from now on. Creating the index first
+-- fails with a UNIQUE constraint error if accounts only differ in case,
+-- which must be merged by hand. List them with:
+--   SELECT LOWER(email), group_concat(email) FROM users
+--   GROUP BY LOWER(email) HAVING COUNT(*) > 1;
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users(LOWER(email));
+
+-- Roles and devices follow through ON UPDATE CASCADE
+UPDATE users SET email = LOWER(email) WHERE email <> LOWER(email);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DELETE FROM user_roles WHERE role = 'org-admin';
+DELETE FROM role_permissions WHERE role = 'org-admin';
+DELETE FROM roles WHERE name = 'org-admin';
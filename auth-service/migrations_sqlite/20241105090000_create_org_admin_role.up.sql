@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- Given to whoever creates an organization. It manages the members of the
+-- organization and their roles there, but not their accounts.
+INSERT OR IGNORE INTO roles (name, description)
+VALUES ('org-admin', 'Manage the members of an organization');
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES
+   ('org-admin', 'users:read'),
+   ('org-admin', 'users:write'),
+   ('org-admin', 'roles:manage');
@@ -1,16 +1,35 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{BannedTokenStore, TwoFACodeStore, UserStore},
-    email_client::EmailClient,
+use crate::{
+    domain::{
+        data_stores::{
+            AuditStore, BannedTokenStore, EmailOutbox, EmailSuppressionStore,
+            HashsetBannedTokenStore, OrganizationStore, TwoFACodeStore, UserStore,
+        },
+        email_client::EmailClient,
+        hashmap_two_fa_code_store::HashMapTwoFACodeStore,
+        message_client::MessageClient,
+    },
+    services::{
+        capturing_email_client::CapturingEmailClient, failover_email_client::FailoverEmailClient,
+        hashmap_email_outbox::HashMapEmailOutbox,
+        hashmap_email_suppression_store::HashMapEmailSuppressionStore,
+        hashmap_organization_store::HashMapOrganizationStore, hashmap_user_store::HashMapUserStore,
+        vec_audit_store::VecAuditStore,
+    },
+    utils::basic_auth::BasicCredentials,
 };
 
 // Using a type alias to improve readability!
-pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
-pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+pub type UserStoreType = Arc<dyn UserStore + Send + Sync>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore + Send + Sync>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore + Send + Sync>;
+pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type OrganizationStoreType = Arc<dyn OrganizationStore + Send + Sync>;
+pub type AuditStoreType = Arc<dyn AuditStore + Send + Sync>;
+pub type EmailOutboxType = Arc<dyn EmailOutbox + Send + Sync>;
+pub type EmailSuppressionStoreType = Arc<dyn EmailSuppressionStore + Send + Sync>;
+pub type MessageClientType = Arc<dyn MessageClient + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -18,20 +37,88 @@ pub struct AppState {
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub organization_store: OrganizationStoreType,
+    pub audit_store: AuditStoreType,
+    /// Only written to when `email_client` delivers through it
+    pub email_outbox: EmailOutboxType,
+    /// Addresses `email_client` no longer sends to
+    pub email_suppression_store: EmailSuppressionStoreType,
+    /// Set when emails are captured instead of sent, to show them in the dev mailbox
+    pub mailbox: Option<Arc<CapturingEmailClient>>,
+    /// Postmark must present these to deliver bounce and complaint webhooks.
+    /// Without them, every webhook is rejected.
+    pub postmark_webhook: Option<BasicCredentials>,
+    /// Sends 2FA codes to phones. Without it, codes only go out by email.
+    pub message_client: Option<MessageClientType>,
+    /// Set when emails fail over between providers, to report how each is doing
+    pub email_providers: Option<Arc<FailoverEmailClient>>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
+        organization_store: OrganizationStoreType,
+        audit_store: AuditStoreType,
+        email_outbox: EmailOutboxType,
+        email_suppression_store: EmailSuppressionStoreType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            organization_store,
+            audit_store,
+            email_outbox,
+            email_suppression_store,
+            mailbox: None,
+            postmark_webhook: None,
+            message_client: None,
+            email_providers: None,
         }
     }
+
+    /// Capture emails in `mailbox` instead of sending them
+    pub fn with_mailbox(mut self, mailbox: Arc<CapturingEmailClient>) -> Self {
+        self.email_client = mailbox.clone();
+        self.mailbox = Some(mailbox);
+        self
+    }
+
+    /// Accept Postmark webhooks that present `credentials`
+    pub fn with_postmark_webhook(mut self, credentials: BasicCredentials) -> Self {
+        self.postmark_webhook = Some(credentials);
+        self
+    }
+
+    /// Offer SMS as a 2FA channel, delivered through `message_client`
+    pub fn with_message_client(mut self, message_client: MessageClientType) -> Self {
+        self.message_client = Some(message_client);
+        self
+    }
+
+    /// Report the health of the providers behind `email_client`
+    pub fn with_email_providers(mut self, email_providers: Arc<FailoverEmailClient>) -> Self {
+        self.email_providers = Some(email_providers);
+        self
+    }
+
+    /// Every store kept in memory, for demos and tests that should not depend
+    /// on PostgreSQL or Redis.
+    pub fn in_memory(email_client: EmailClientType) -> Self {
+        Self::new(
+            Arc::new(HashMapUserStore::default()),
+            Arc::new(HashsetBannedTokenStore::new()),
+            Arc::new(HashMapTwoFACodeStore::default()),
+            email_client,
+            Arc::new(HashMapOrganizationStore::default()),
+            Arc::new(VecAuditStore::default()),
+            Arc::new(HashMapEmailOutbox::default()),
+            Arc::new(HashMapEmailSuppressionStore::default()),
+        )
+    }
 }
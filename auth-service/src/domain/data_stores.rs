@@ -1,28 +1,66 @@
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::Result;
 use color_eyre::eyre::{eyre, Context, Report};
 use rand::Rng;
+use redis::aio::ConnectionManager;
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgPool, SqlitePool};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::services::postmark_email_client::PostmarkEmailClient;
+use crate::services::{
+    expired_entry_evictor::EvictExpired,
+    http_sms_client::HttpSmsClient,
+    postmark_email_client::PostmarkEmailClient,
+    smtp_email_client::{SmtpEmailClient, SmtpSettings},
+};
 use crate::utils::constants::prod::email_client::SENDER;
-use crate::utils::constants::{prod, POSTMARK_AUTH_TOKEN};
+use crate::utils::constants::{
+    prod, POSTMARK_AUTH_TOKEN, SMS_PROVIDER_TOKEN, SMS_SENDER, SMTP_CA_CERTIFICATE, SMTP_HOST,
+    SMTP_PASSWORD, SMTP_PORT, SMTP_TLS, SMTP_USERNAME,
+};
+use crate::utils::{
+    auth::{TokenHash, TOKEN_TTL_SECONDS},
+    clock::{Clock, SystemClock},
+    expiring_map::ExpiringMap,
+};
 use crate::{
-    get_postgres_pool, get_redis_client,
+    get_postgres_pool, get_redis_client, get_sqlite_pool,
     utils::constants::{DATABASE_URL, REDIS_HOST_NAME},
 };
 
 use super::parse::Parseable;
-use super::{email::Email, password::Password, user::User};
-use std::collections::HashSet;
+use super::{
+    audit::{AuditAction, AuditEvent},
+    email::Email,
+    email_client::EmailMessage,
+    email_outbox::{OutboxMessage, OutboxMessageId, OutboxStatus},
+    email_suppression::EmailSuppression,
+    locale::Locale,
+    organization::{
+        Invitation, InvitationToken, Membership, Organization, OrganizationId, OrganizationName,
+    },
+    password::Password,
+    phone_number::PhoneNumber,
+    role::{Permission, Role},
+    user::{StatusChange, TwoFactorChannel, User, UserSummary},
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+const TOKEN_TTL: Duration = Duration::from_secs(TOKEN_TTL_SECONDS as u64);
 
-pub fn configure_redis() -> redis::Connection {
+/// Open a multiplexed Redis connection that reconnects on its own after Redis restarts.
+/// Clones share the same underlying connection.
+pub async fn configure_redis() -> ConnectionManager {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
-        .get_connection()
+        .get_connection_manager()
+        .await
         .expect("Failed to get Redis connection")
 }
 
@@ -41,16 +79,54 @@ pub fn configure_postmark_email_client() -> PostmarkEmailClient {
     )
 }
 
+/// Build the SMTP email client from the `SMTP_*` variables
+pub fn configure_smtp_email_client() -> SmtpEmailClient {
+    let root_certificate = SMTP_CA_CERTIFICATE.as_ref().map(|path| {
+        std::fs::read(path)
+            .unwrap_or_else(|e| panic!("Failed to read SMTP CA certificate {}: {}", path, e))
+    });
+    let settings = SmtpSettings {
+        host: SMTP_HOST.to_owned(),
+        port: *SMTP_PORT,
+        tls: *SMTP_TLS,
+        credentials: SMTP_USERNAME
+            .clone()
+            .map(|username| (username, SMTP_PASSWORD.clone())),
+        root_certificate,
+        timeout: prod::email_client::TIMEOUT,
+    };
+
+    SmtpEmailClient::new(
+        settings,
+        Email::parse(Secret::new(SENDER.to_owned())).unwrap(),
+    )
+    .expect("Failed to build SMTP email client")
+}
+
+/// Build the SMS client for the gateway at `base_url` from the `SMS_*` variables
+pub fn configure_http_sms_client(base_url: String) -> HttpSmsClient {
+    let http_client = Client::builder()
+        .timeout(prod::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    HttpSmsClient::new(
+        base_url,
+        SMS_SENDER.to_owned(),
+        SMS_PROVIDER_TOKEN.to_owned(),
+        http_client,
+    )
+}
+
 pub async fn configure_postgresql() -> PgPool {
     // Create a new database connection pool
-    let pg_pool = get_postgres_pool(&DATABASE_URL).await.expect(
-        format!(
+    let pg_pool = get_postgres_pool(&DATABASE_URL).await.unwrap_or_else(|_| {
+        panic!(
             "Failed to create Postgres connection pool! Target url: {}",
-            DATABASE_URL.to_string()
+            *DATABASE_URL
         )
-        .as_str(),
-    );
-    println!("Created pool with: {}", DATABASE_URL.to_string());
+    });
+    println!("Created pool with: {}", *DATABASE_URL);
 
     // Run database migrations against our test database!
     sqlx::migrate!()
@@ -61,76 +137,320 @@ pub async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
+/// Open the SQLite database at `url` and run its migrations.
+/// SQLite has its own schema in `migrations_sqlite`, covering users and roles only.
+pub async fn configure_sqlite(url: &str) -> SqlitePool {
+    let sqlite_pool = get_sqlite_pool(url).await.unwrap_or_else(|_| {
+        panic!(
+            "Failed to create SQLite connection pool! Target url: {}",
+            url
+        )
+    });
+
+    sqlx::migrate!("./migrations_sqlite")
+        .run(&sqlite_pool)
+        .await
+        .expect("Failed to run SQLite migrations");
+
+    sqlite_pool
+}
+
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
-    async fn validate_user(&self, email: &Email, password: &Password)
+    /// Returns the user if the password matches, in a single lookup.
+    /// Fails with `UserNotFound` for unknown users and `InvalidCredentials`
+    /// when the password does not match.
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError>;
+    /// Assigning a role the user already has is a no-op
+    async fn assign_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError>;
+    /// Revoking a role the user does not have is a no-op
+    async fn revoke_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError>;
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError>;
+    async fn role_exists(&self, role: &Role) -> Result<bool, UserStoreError>;
+    /// Union of the permissions granted by each of the given roles
+    async fn get_permissions(&self, roles: &[Role]) -> Result<HashSet<Permission>, UserStoreError>;
+    /// Users ordered by email, optionally filtered by a case-insensitive substring
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError>;
+    async fn get_user_summary(&self, email: &Email) -> Result<UserSummary, UserStoreError>;
+    /// Records the new status along with the reason and the actor behind it
+    async fn set_status(&self, email: &Email, change: &StatusChange) -> Result<(), UserStoreError>;
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError>;
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError>;
+    /// Language to send the user's emails in. `None` follows their requests.
+    async fn set_locale(&self, email: &Email, locale: Option<Locale>)
+        -> Result<(), UserStoreError>;
+    /// Whether non-critical security notices are emailed to the user
+    async fn set_security_notices(
+        &self,
+        email: &Email,
+        enabled: bool,
+    ) -> Result<(), UserStoreError>;
+    /// Replaces the password, which also lifts a required reset
+    async fn set_password(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    /// Moves the account, with its roles and known devices, to another address.
+    /// Fails with `UserAlreadyExists` when the address is taken.
+    async fn change_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError>;
+    /// Remembers a device the user logged in from.
+    /// Returns true the first time the device is seen.
+    async fn remember_device(
+        &self,
+        email: &Email,
+        device: &TokenHash,
+    ) -> Result<bool, UserStoreError>;
+    /// Replaces the phone number, or removes it with `None`. The new number
+    /// is unverified, so 2FA codes go back to email until it is verified.
+    /// Also called to text a new code, so it resets the verification attempts.
+    async fn set_phone_number(
+        &self,
+        email: &Email,
+        phone_number: Option<&PhoneNumber>,
+    ) -> Result<(), UserStoreError>;
+    async fn set_phone_verified(&self, email: &Email, verified: bool)
         -> Result<(), UserStoreError>;
+    /// Counts an attempt to verify the phone number with a texted code.
+    /// Returns the attempts made since the phone number was last set.
+    async fn record_phone_verification_attempt(&self, email: &Email)
+        -> Result<u32, UserStoreError>;
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFactorChannel,
+    ) -> Result<(), UserStoreError>;
+    /// Stamps the time and address of a completed login and clears the
+    /// failed attempt count
+    async fn record_login(&self, email: &Email, ip: Option<&str>) -> Result<(), UserStoreError>;
+    /// Counts a login attempt with a wrong password or 2FA code
+    async fn record_failed_login(&self, email: &Email) -> Result<(), UserStoreError>;
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAXIMUM_PAGE_SIZE: u32 = 100;
+
+/// Search and pagination options for listing users.
+/// Pages are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserQuery {
+    pub search: Option<String>,
+    /// Only consider these users, e.g. the members of an organization
+    pub emails: Option<Vec<Email>>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl UserQuery {
+    /// Clamp the requested page and page size into a valid range
+    /// ```
+    /// use auth_service::domain::data_stores::{UserQuery, MAXIMUM_PAGE_SIZE};
+    /// let query = UserQuery::new(None, Some(0), Some(10_000));
+    /// assert_eq!(query.page, 1);
+    /// assert_eq!(query.per_page, MAXIMUM_PAGE_SIZE);
+    /// assert_eq!(query.offset(), 0);
+    /// ```
+    pub fn new(search: Option<String>, page: Option<u32>, per_page: Option<u32>) -> Self {
+        Self {
+            search: search.filter(|search| !search.trim().is_empty()),
+            emails: None,
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAXIMUM_PAGE_SIZE),
+        }
+    }
+
+    pub fn restricted_to(mut self, emails: Vec<Email>) -> Self {
+        self.emails = Some(emails);
+        self
+    }
+
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+}
+
+impl Default for UserQuery {
+    fn default() -> Self {
+        Self::new(None, None, None)
+    }
+}
+
+/// Cache of user lookups placed in front of a `UserStore`.
+/// Entries expire after a TTL and are dropped when the user changes.
+///
+/// Entries belong to a generation of the user, which every invalidation
+/// replaces. Reading the generation before the store means a lookup that
+/// raced with a change is cached for a generation nobody reads anymore.
+#[async_trait::async_trait]
+pub trait UserCache: Send + Sync {
+    /// Current generation of the entries for `email`
+    async fn generation(&self, email: &Email) -> Result<u64>;
+    async fn get_user(&self, email: &Email, generation: u64) -> Result<Option<User>>;
+    async fn set_user(&self, user: &User, generation: u64) -> Result<()>;
+    async fn get_roles(&self, email: &Email, generation: u64) -> Result<Option<Vec<Role>>>;
+    async fn set_roles(&self, email: &Email, roles: &[Role], generation: u64) -> Result<()>;
+    /// Drop everything cached for `email` and start a new generation
+    async fn invalidate(&self, email: &Email) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserPage {
+    pub users: Vec<UserSummary>,
+    /// Number of users matching the query across all pages
+    pub total: u64,
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn insert(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError>;
-    async fn token_exists(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError>;
+    /// Ban a token for `ttl`, the time left until it expires anyway.
+    /// Banning it again replaces the TTL, and a zero TTL bans nothing.
+    async fn insert(&self, token: &TokenHash, ttl: Duration) -> Result<(), BannedTokenStoreError>;
+    async fn token_exists(&self, token: &TokenHash) -> Result<bool, BannedTokenStoreError>;
+    /// Invalidate every token issued to `email` at or before `revoked_at` (unix milliseconds)
+    async fn revoke_sessions(
+        &self,
+        email: &Email,
+        revoked_at: i64,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn sessions_revoked_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<i64>, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
 pub enum BannedTokenStoreError {
-    #[error("Token already exists")]
-    TokenAlreadyExists,
-    #[error("Token not found")]
-    TokenNotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
 
+/// Banned tokens expire with the token and session revocations after
+/// `TOKEN_TTL_SECONDS`, like their Redis counterparts. Expired entries are
+/// freed by [`EvictExpired::evict_expired`].
 pub struct HashsetBannedTokenStore {
-    store: HashSet<String>,
+    store: RwLock<ExpiringMap<TokenHash, ()>>,
+    revoked_sessions: RwLock<ExpiringMap<Email, i64>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for HashsetBannedTokenStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HashsetBannedTokenStore {
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
-            store: HashSet::new(),
+            store: RwLock::new(ExpiringMap::default()),
+            revoked_sessions: RwLock::new(ExpiringMap::default()),
+            clock,
         }
     }
 }
 
+impl EvictExpired for HashsetBannedTokenStore {
+    fn evict_expired(&self) -> usize {
+        let now = self.clock.now();
+        self.store
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .evict_expired(now)
+            + self
+                .revoked_sessions
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .evict_expired(now)
+    }
+}
+
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
     /// Insert into the store
     /// ```
+    /// use std::time::Duration;
     /// use tokio_test;
     /// use secrecy::Secret;
     /// use crate::auth_service::domain::data_stores::{BannedTokenStore, HashsetBannedTokenStore};
+    /// use crate::auth_service::utils::auth::TokenHash;
     /// tokio_test::block_on(async {
-    /// let mut store = HashsetBannedTokenStore::new();
-    /// let sample_token = "asduashfiasbnfd".to_string();
-    /// let result = store.insert(Secret::new(sample_token.clone())).await;
+    /// let store = HashsetBannedTokenStore::new();
+    /// let sample_token = TokenHash::of(&Secret::new("asduashfiasbnfd".to_string()));
+    /// let result = store.insert(&sample_token, Duration::from_secs(60)).await;
     /// assert!(result.is_ok());
     /// });
     /// ```
-    async fn insert(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        self.store.insert(token.expose_secret().to_owned());
+    async fn insert(&self, token: &TokenHash, ttl: Duration) -> Result<(), BannedTokenStoreError> {
+        let expires_at = self.clock.now() + ttl;
+        self.store
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(token.clone(), (), expires_at);
         Ok(())
     }
 
     /// Check if token exists
     /// ```
+    /// use std::time::Duration;
     /// use secrecy::Secret;
     /// use crate::auth_service::domain::data_stores::{BannedTokenStore, HashsetBannedTokenStore};
-    /// use tokio::test;
+    /// use crate::auth_service::utils::auth::TokenHash;
     /// tokio_test::block_on(async {
-    /// let mut store = HashsetBannedTokenStore::new();
-    /// let sample_token = Secret::new("asduashfiasbnfd".to_string());
-    /// let result = store.insert(sample_token.clone()).await;
+    /// let store = HashsetBannedTokenStore::new();
+    /// let sample_token = TokenHash::of(&Secret::new("asduashfiasbnfd".to_string()));
+    /// let result = store.insert(&sample_token, Duration::from_secs(60)).await;
     /// assert_eq!(result.is_ok(), true);
     /// assert!(store.token_exists(&sample_token).await.expect("Token should exist after inserting"));
     /// });
     /// ```
-    async fn token_exists(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.store.contains(token.expose_secret()))
+    async fn token_exists(&self, token: &TokenHash) -> Result<bool, BannedTokenStoreError> {
+        Ok(self
+            .store
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(token, self.clock.now())
+            .is_some())
+    }
+
+    async fn revoke_sessions(
+        &self,
+        email: &Email,
+        revoked_at: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        let expires_at = self.clock.now() + TOKEN_TTL;
+        self.revoked_sessions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(email.clone(), revoked_at, expires_at);
+        Ok(())
+    }
+
+    async fn sessions_revoked_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        Ok(self
+            .revoked_sessions
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(email, self.clock.now())
+            .copied())
     }
 }
 
@@ -142,8 +462,8 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
-    #[error("Invalid password")]
-    InvalidPassword,
+    #[error("Role not found")]
+    RoleNotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -155,7 +475,361 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
                 | (Self::UserNotFound, Self::UserNotFound)
                 | (Self::InvalidCredentials, Self::InvalidCredentials)
-                | (Self::InvalidPassword, Self::InvalidPassword)
+                | (Self::RoleNotFound, Self::RoleNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait OrganizationStore: Send + Sync {
+    async fn create_organization(
+        &self,
+        name: OrganizationName,
+    ) -> Result<Organization, OrganizationStoreError>;
+    async fn get_organization(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<Organization, OrganizationStoreError>;
+    /// Adds `email` to the organization, or grants the extra roles if they are already a member
+    async fn add_member(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        roles: &[Role],
+    ) -> Result<(), OrganizationStoreError>;
+    /// Revoking a role the member does not have is a no-op
+    async fn revoke_member_role(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        role: &Role,
+    ) -> Result<(), OrganizationStoreError>;
+    /// Organizations the user belongs to, oldest membership first
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError>;
+    /// Fails with `MemberNotFound` if `email` does not belong to the organization
+    async fn get_member_roles(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+    ) -> Result<Vec<Role>, OrganizationStoreError>;
+    async fn list_members(&self, id: &OrganizationId)
+        -> Result<Vec<Email>, OrganizationStoreError>;
+    async fn add_invitation(&self, invitation: Invitation) -> Result<(), OrganizationStoreError>;
+    async fn get_invitation(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<Invitation, OrganizationStoreError>;
+    async fn mark_invitation_accepted(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<(), OrganizationStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OrganizationStoreError {
+    #[error("Organization not found")]
+    OrganizationNotFound,
+    #[error("Member not found")]
+    MemberNotFound,
+    #[error("Invitation not found")]
+    InvitationNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OrganizationStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::OrganizationNotFound, Self::OrganizationNotFound)
+                | (Self::MemberNotFound, Self::MemberNotFound)
+                | (Self::InvitationNotFound, Self::InvitationNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Append-only trail of security-relevant events.
+/// Events can never be changed or removed once appended.
+#[async_trait::async_trait]
+pub trait AuditStore: Send + Sync {
+    async fn append(&self, event: AuditEvent) -> Result<(), AuditStoreError>;
+    /// Events matching the query, newest first
+    async fn query(&self, query: &AuditQuery) -> Result<AuditPage, AuditStoreError>;
+}
+
+/// Filters and pagination options for reading the audit trail.
+/// Pages are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditQuery {
+    /// Only events performed by or against this account
+    pub involving: Option<Email>,
+    /// Only events performed by or against one of these accounts,
+    /// e.g. the members of an organization
+    pub emails: Option<Vec<Email>>,
+    pub action: Option<AuditAction>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl AuditQuery {
+    /// Clamp the requested page and page size into a valid range
+    /// ```
+    /// use auth_service::domain::data_stores::{AuditQuery, DEFAULT_PAGE_SIZE};
+    /// let query = AuditQuery::new(Some(3), None);
+    /// assert_eq!(query.per_page, DEFAULT_PAGE_SIZE);
+    /// assert_eq!(query.offset(), 2 * u64::from(DEFAULT_PAGE_SIZE));
+    /// ```
+    pub fn new(page: Option<u32>, per_page: Option<u32>) -> Self {
+        Self {
+            involving: None,
+            emails: None,
+            action: None,
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAXIMUM_PAGE_SIZE),
+        }
+    }
+
+    pub fn involving(mut self, email: Email) -> Self {
+        self.involving = Some(email);
+        self
+    }
+
+    pub fn restricted_to(mut self, emails: Vec<Email>) -> Self {
+        self.emails = Some(emails);
+        self
+    }
+
+    pub fn with_action(mut self, action: Option<AuditAction>) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+
+    /// Whether `event` passes every filter of the query
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.involving
+            .as_ref()
+            .is_none_or(|email| event.involves(email))
+            && self
+                .emails
+                .as_ref()
+                .is_none_or(|emails| emails.iter().any(|email| event.involves(email)))
+            && self.action.is_none_or(|action| event.action == action)
+    }
+}
+
+impl Default for AuditQuery {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditPage {
+    pub events: Vec<AuditEvent>,
+    /// Number of events matching the query across all pages
+    pub total: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum AuditStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// Emails waiting to be delivered, written in the request path and drained
+/// by `EmailOutboxWorker`, so requests never wait on the email provider.
+#[async_trait::async_trait]
+pub trait EmailOutbox: Send + Sync {
+    /// Store a message as pending and due immediately
+    async fn enqueue(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxMessageId, EmailOutboxError>;
+    /// Take up to `limit` due messages, oldest due first, and count an attempt
+    /// for each. They are hidden from other workers for `lease`, after which
+    /// they are due again in case the worker died before recording the outcome.
+    async fn claim_due(
+        &self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxMessage>, EmailOutboxError>;
+    async fn mark_sent(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError>;
+    /// Record a failed attempt. The message is retried at `retry_at`,
+    /// or dead-lettered if there is none.
+    async fn mark_failed(
+        &self,
+        id: &OutboxMessageId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxError>;
+    async fn get(&self, id: &OutboxMessageId) -> Result<OutboxMessage, EmailOutboxError>;
+    /// Messages matching the query, newest first
+    async fn list(&self, query: &OutboxQuery) -> Result<OutboxPage, EmailOutboxError>;
+    /// Make a dead-lettered message pending and due again, with a fresh
+    /// count of attempts. Fails with `NotDeadLettered` for any other message.
+    async fn requeue(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError>;
+}
+
+/// Filters and pagination options for listing outbox messages.
+/// Pages are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxQuery {
+    pub status: Option<OutboxStatus>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl OutboxQuery {
+    /// Clamp the requested page and page size into a valid range
+    /// ```
+    /// use auth_service::domain::data_stores::{OutboxQuery, MAXIMUM_PAGE_SIZE};
+    /// let query = OutboxQuery::new(Some(0), Some(500));
+    /// assert_eq!(query.page, 1);
+    /// assert_eq!(query.per_page, MAXIMUM_PAGE_SIZE);
+    /// ```
+    pub fn new(page: Option<u32>, per_page: Option<u32>) -> Self {
+        Self {
+            status: None,
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAXIMUM_PAGE_SIZE),
+        }
+    }
+
+    pub fn with_status(mut self, status: Option<OutboxStatus>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+}
+
+impl Default for OutboxQuery {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxPage {
+    pub messages: Vec<OutboxMessage>,
+    /// Number of messages matching the query across all pages
+    pub total: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum EmailOutboxError {
+    #[error("Message not found")]
+    MessageNotFound,
+    #[error("Message is not dead-lettered")]
+    NotDeadLettered,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailOutboxError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::MessageNotFound, Self::MessageNotFound)
+                | (Self::NotDeadLettered, Self::NotDeadLettered)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Addresses that must not receive email, because they bounced or complained.
+/// `SuppressingEmailClient` checks it before every send.
+#[async_trait::async_trait]
+pub trait EmailSuppressionStore: Send + Sync {
+    /// Stop sending to the address. Suppressing it again replaces the reason.
+    async fn suppress(
+        &self,
+        suppression: &EmailSuppression,
+    ) -> Result<(), EmailSuppressionStoreError>;
+    async fn get(
+        &self,
+        email: &Email,
+    ) -> Result<Option<EmailSuppression>, EmailSuppressionStoreError>;
+    /// Suppressions on the requested page, most recent first
+    async fn list(
+        &self,
+        query: &SuppressionQuery,
+    ) -> Result<SuppressionPage, EmailSuppressionStoreError>;
+    /// Send to the address again
+    async fn remove(&self, email: &Email) -> Result<(), EmailSuppressionStoreError>;
+}
+
+/// Pagination options for listing suppressions. Pages are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuppressionQuery {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl SuppressionQuery {
+    /// Clamp the requested page and page size into a valid range
+    /// ```
+    /// use auth_service::domain::data_stores::{SuppressionQuery, MAXIMUM_PAGE_SIZE};
+    /// let query = SuppressionQuery::new(Some(0), Some(500));
+    /// assert_eq!(query.page, 1);
+    /// assert_eq!(query.per_page, MAXIMUM_PAGE_SIZE);
+    /// ```
+    pub fn new(page: Option<u32>, per_page: Option<u32>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAXIMUM_PAGE_SIZE),
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+}
+
+impl Default for SuppressionQuery {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SuppressionPage {
+    pub suppressions: Vec<EmailSuppression>,
+    /// Number of suppressed addresses across all pages
+    pub total: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum EmailSuppressionStoreError {
+    #[error("Suppression not found")]
+    SuppressionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for EmailSuppressionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SuppressionNotFound, Self::SuppressionNotFound)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -163,15 +837,17 @@ impl PartialEq for UserStoreError {
 
 // This trait represents the interface all concrete 2FA code stores should implement
 #[async_trait::async_trait]
-pub trait TwoFACodeStore {
+pub trait TwoFACodeStore: Send + Sync {
+    /// Replaces any code still pending for `email`
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    /// Removing a code that does not exist is a no-op
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
@@ -184,8 +860,6 @@ pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
-    #[error("Email adread exists")]
-    EmailAlreadyExists,
 }
 
 impl PartialEq for TwoFACodeStoreError {
@@ -193,7 +867,6 @@ impl PartialEq for TwoFACodeStoreError {
         matches!(
             (self, other),
             (Self::LoginAttemptIdNotFound, Self::LoginAttemptIdNotFound)
-                | (Self::EmailAlreadyExists, Self::EmailAlreadyExists)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
@@ -7,6 +7,20 @@ use validator::{validate_email, ValidationError};
 
 use super::parse::Parseable;
 
+/// An email address in its normalized form, so the same mailbox always
+/// compares, hashes and is stored the same way however it was typed.
+///
+/// Parsing trims surrounding whitespace, converts an internationalized domain
+/// to punycode and lowercases the whole address. RFC 5321 lets the local part
+/// be case-sensitive, but no mailbox provider in use treats it so, and every
+/// store keys users by this string.
+/// ```
+/// use auth_service::domain::{email::Email, parse::Parseable};
+/// use secrecy::{ExposeSecret, Secret};
+///
+/// let email = Email::parse(Secret::new(" Bob@Bücher.Example ".to_owned())).unwrap();
+/// assert_eq!(email.as_ref().expose_secret(), "bob@xn--bcher-kva.example");
+/// ```
 #[derive(Debug, Clone)]
 pub struct Email(Secret<String>);
 
@@ -16,6 +30,7 @@ impl AsRef<Secret<String>> for Email {
     }
 }
 
+// Comparing the normalized form is comparing case-insensitively
 impl PartialEq for Email {
     fn eq(&self, other: &Self) -> bool {
         self.0.expose_secret() == other.0.expose_secret()
@@ -33,16 +48,25 @@ impl Eq for Email {}
 impl Parseable<Secret<String>> for Email {
     fn parse(email: Secret<String>) -> Result<Self> {
         let email_str = email.expose_secret();
-        if validate_email(email_str) {
-            Ok(Email(email))
-        } else {
-            let mut val_err = ValidationError::new("invalid email address");
-            val_err.add_param(Cow::Borrowed("input"), &email_str);
-            Err(val_err.into())
+        match normalize(email_str) {
+            Some(normalized) if validate_email(&normalized) => Ok(Email(Secret::new(normalized))),
+            _ => {
+                let mut val_err = ValidationError::new("invalid email address");
+                val_err.add_param(Cow::Borrowed("input"), &email_str);
+                Err(val_err.into())
+            }
         }
     }
 }
 
+/// `None` if the domain is not a valid IDNA domain name
+fn normalize(email: &str) -> Option<String> {
+    let (local, domain) = email.trim().rsplit_once('@')?;
+    // Also maps the domain to lowercase
+    let domain = idna::domain_to_ascii(domain).ok()?;
+    Some(format!("{}@{}", local.to_lowercase(), domain))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -52,7 +76,7 @@ mod test {
     #[test_case("mooomin@hotmail.com")]
     fn expect_valid_emails(valid_email: &str) {
         let email = Email::parse(Secret::new(valid_email.to_string()));
-        assert_eq!(email.is_ok(), true);
+        assert!(email.is_ok());
         assert_eq!(email.unwrap().as_ref().expose_secret(), valid_email);
     }
 
@@ -65,8 +89,34 @@ mod test {
     #[test_case(
         Email::parse(Secret::new("woo@min_at_asdsad".to_string()))
     )]
+    #[test_case(
+        Email::parse(Secret::new("bob@exa mple.com".to_string()))
+    )]
+    #[test_case(
+        Email::parse(Secret::new("   ".to_string()))
+    )]
     fn should_be_invalid_emails(invalid_email: Result<Email>) {
         // TODO: update tests to make it more robust
-        assert_eq!(invalid_email.is_err(), true);
+        assert!(invalid_email.is_err());
+    }
+
+    #[test_case("  bob@example.com\n", "bob@example.com"; "surrounding whitespace")]
+    #[test_case("bob@EXAMPLE.com", "bob@example.com"; "uppercase domain")]
+    #[test_case("Bob.Smith@Example.com", "bob.smith@example.com"; "uppercase local part")]
+    #[test_case("anna@Bücher.example", "anna@xn--bcher-kva.example"; "internationalized domain")]
+    #[test_case("anna@xn--bcher-kva.example", "anna@xn--bcher-kva.example"; "punycode domain")]
+    fn should_normalize_emails(input: &str, expected: &str) {
+        let email = Email::parse(Secret::new(input.to_string())).unwrap();
+        assert_eq!(email.as_ref().expose_secret(), expected);
+    }
+
+    #[test]
+    fn should_treat_spellings_of_one_address_as_equal() {
+        use std::collections::HashSet;
+
+        let typed = Email::parse(Secret::new("Bob@Example.com".to_string())).unwrap();
+        let stored = Email::parse(Secret::new("bob@example.com".to_string())).unwrap();
+        assert_eq!(typed, stored);
+        assert_eq!(HashSet::from([typed, stored]).len(), 1);
     }
 }
@@ -1,8 +1,31 @@
-use super::email::Email;
-use color_eyre::eyre::Result;
+use super::{email::Email, email_suppression::SuppressionReason};
+use color_eyre::eyre::{Report, Result};
+use thiserror::Error;
+
+/// A rendered email, ready to be sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+/// Returned instead of sending to an address that bounced or complained
+#[derive(Debug, Error)]
+#[error("Emails to this address are suppressed ({reason})")]
+pub struct RecipientSuppressed {
+    pub reason: SuppressionReason,
+}
+
+impl RecipientSuppressed {
+    /// Whether `send_email` failed because the recipient is suppressed
+    pub fn caused(error: &Report) -> bool {
+        error.is::<RecipientSuppressed>()
+    }
+}
 
 // This trait represents the interface all concrete email clients should implement
 #[async_trait::async_trait]
-pub trait EmailClient {
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()>;
+pub trait EmailClient: Send + Sync {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()>;
 }
@@ -0,0 +1,175 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use uuid::Uuid;
+
+use super::{email::Email, email_client::EmailMessage, parse::Parseable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutboxMessageId(Uuid);
+
+impl OutboxMessageId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for OutboxMessageId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parseable<String> for OutboxMessageId {
+    /// Outbox message ids are UUIDs
+    /// ```
+    /// use auth_service::domain::{email_outbox::OutboxMessageId, parse::Parseable};
+    /// let id = "67e55044-10b1-426f-9247-bb680e5fe0c8".to_owned();
+    /// assert_eq!(OutboxMessageId::parse(id.clone()).unwrap().to_string(), id);
+    /// assert!(OutboxMessageId::parse("latest".to_owned()).is_err());
+    /// ```
+    fn parse(id: String) -> Result<Self> {
+        let id = Uuid::parse_str(&id).wrap_err("Invalid outbox message id")?;
+        Ok(Self(id))
+    }
+}
+
+impl fmt::Display for OutboxMessageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Where a message is in its delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxStatus {
+    /// Waiting for its first attempt or for a retry
+    Pending,
+    Sent,
+    /// Given up on after too many failed attempts. Only an admin retries it.
+    Dead,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Sent => "sent",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+}
+
+impl fmt::Display for OutboxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for OutboxStatus {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "pending" => Ok(OutboxStatus::Pending),
+            "sent" => Ok(OutboxStatus::Sent),
+            "dead" => Ok(OutboxStatus::Dead),
+            _ => Err(eyre!("Unknown outbox status: {}", s)),
+        }
+    }
+}
+
+/// An email written to the outbox, with the state of its delivery.
+/// The bodies are dropped once the message is sent, since they may hold
+/// 2FA codes or links that must not linger.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxMessage {
+    pub id: OutboxMessageId,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub status: OutboxStatus,
+    /// Attempts started so far, including one in progress
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// When a pending message is due, or when its current attempt's lease runs out
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// When failed deliveries are retried, and when they are given up on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts before a message is dead-lettered
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every retry after it
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// How long to wait after the `attempts`th failed attempt,
+    /// or `None` if the message should be dead-lettered
+    /// ```
+    /// use std::time::Duration;
+    /// use auth_service::domain::email_outbox::RetryPolicy;
+    /// let policy = RetryPolicy {
+    ///     max_attempts: 5,
+    ///     base_backoff: Duration::from_secs(10),
+    ///     max_backoff: Duration::from_secs(60),
+    /// };
+    /// assert_eq!(policy.backoff(1), Some(Duration::from_secs(10)));
+    /// assert_eq!(policy.backoff(3), Some(Duration::from_secs(40)));
+    /// assert_eq!(policy.backoff(4), Some(Duration::from_secs(60)));
+    /// assert_eq!(policy.backoff(5), None);
+    /// ```
+    pub fn backoff(&self, attempts: u32) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        Some(
+            self.base_backoff
+                .saturating_mul(factor)
+                .min(self.max_backoff),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_round_trips() {
+        for status in [
+            OutboxStatus::Pending,
+            OutboxStatus::Sent,
+            OutboxStatus::Dead,
+        ] {
+            assert_eq!(status.as_str().parse::<OutboxStatus>().unwrap(), status);
+        }
+        assert!("failed".parse::<OutboxStatus>().is_err());
+    }
+
+    #[test]
+    fn test_backoff_does_not_overflow() {
+        let policy = RetryPolicy {
+            max_attempts: u32::MAX,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(3600),
+        };
+        assert_eq!(policy.backoff(200), Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_single_attempt_policy_never_retries() {
+        let policy = RetryPolicy {
+            max_attempts: 1,
+            base_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(1),
+        };
+        assert_eq!(policy.backoff(1), None);
+    }
+}
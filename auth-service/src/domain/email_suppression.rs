@@ -0,0 +1,76 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+
+use super::email::Email;
+
+/// Why an address no longer receives email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuppressionReason {
+    /// The receiving server rejected the address permanently
+    HardBounce,
+    /// The recipient marked one of our emails as spam
+    SpamComplaint,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "hard_bounce",
+            SuppressionReason::SpamComplaint => "spam_complaint",
+        }
+    }
+}
+
+impl fmt::Display for SuppressionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SuppressionReason {
+    type Err = color_eyre::eyre::Report;
+
+    /// ```
+    /// use auth_service::domain::email_suppression::SuppressionReason;
+    /// assert_eq!(
+    ///     "spam_complaint".parse::<SuppressionReason>().unwrap(),
+    ///     SuppressionReason::SpamComplaint
+    /// );
+    /// assert!("soft_bounce".parse::<SuppressionReason>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hard_bounce" => Ok(SuppressionReason::HardBounce),
+            "spam_complaint" => Ok(SuppressionReason::SpamComplaint),
+            _ => Err(eyre!("Unknown suppression reason: {}", s)),
+        }
+    }
+}
+
+/// An address that emails are no longer sent to, until an admin lifts it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailSuppression {
+    pub email: Email,
+    pub reason: SuppressionReason,
+    /// What the email provider reported, e.g. the bounce message
+    pub description: Option<String>,
+    pub suppressed_at: DateTime<Utc>,
+}
+
+impl EmailSuppression {
+    pub fn new(email: Email, reason: SuppressionReason) -> Self {
+        Self {
+            email,
+            reason,
+            description: None,
+            suppressed_at: Utc::now(),
+        }
+    }
+
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+}
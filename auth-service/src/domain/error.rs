@@ -1,5 +1,7 @@
 use color_eyre::eyre::Report;
 use thiserror::Error;
+
+use super::{email_client::RecipientSuppressed, user::AccountStatus};
 /// Enums for Authentication API-related Errors.
 /// Note: this does not map errors to HTTP status codes.
 ///
@@ -7,6 +9,19 @@ use thiserror::Error;
 ///
 /// UserAlreadyExists: Should occur if the user already exists
 /// InvalidCredentials: Username and / or password is incorrect
+/// InvalidInput: Request body is well-formed but its values are not valid
+/// UserNotFound / RoleNotFound / OrganizationNotFound: The referenced resource does not exist,
+/// or is outside the caller's organization
+/// InvitationNotFound: The invitation does not exist, has expired or was already accepted
+/// EmailNotFound: No message with that id is in the email outbox
+/// SuppressionNotFound: Emails to that address are not suppressed
+/// Forbidden: Authenticated, but missing the required permission
+/// AccountSuspended / AccountLocked / AccountPendingVerification: The account is not active
+/// PasswordResetRequired: An admin requires a new password before the next login
+/// PhoneNotVerified: The action needs a verified phone number
+/// SmsUnavailable: No SMS provider is configured
+/// TooManyAttempts: Too many wrong codes were entered, so a new one must be requested
+/// EmailSuppressed: The email could not be sent, since the address bounced or complained
 /// UnexpectedError: Any other error that does not already exists
 /// inside of the enum
 #[derive(Debug, Error)]
@@ -21,6 +36,89 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Invalid input")]
+    InvalidInput,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Role not found")]
+    RoleNotFound,
+    #[error("Organization not found")]
+    OrganizationNotFound,
+    #[error("Invitation not found")]
+    InvitationNotFound,
+    #[error("Email not found")]
+    EmailNotFound,
+    #[error("Suppression not found")]
+    SuppressionNotFound,
+    #[error("Forbidden")]
+    Forbidden,
+    #[error("Account suspended")]
+    AccountSuspended,
+    #[error("Account locked")]
+    AccountLocked,
+    #[error("Account pending verification")]
+    AccountPendingVerification,
+    #[error("Password reset required")]
+    PasswordResetRequired,
+    #[error("Phone number not verified")]
+    PhoneNotVerified,
+    #[error("SMS unavailable")]
+    SmsUnavailable,
+    #[error("Too many attempts")]
+    TooManyAttempts,
+    #[error("Email suppressed")]
+    EmailSuppressed,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
+
+impl AuthAPIError {
+    /// Stable identifier of the error, the same in every locale.
+    /// Also the key of its message in the catalogs, after `error.`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthAPIError::UserAlreadyExists => "user_already_exists",
+            AuthAPIError::InvalidCredentials => "invalid_credentials",
+            AuthAPIError::IncorrectCredentials => "incorrect_credentials",
+            AuthAPIError::MissingToken => "missing_token",
+            AuthAPIError::InvalidToken => "invalid_token",
+            AuthAPIError::InvalidInput => "invalid_input",
+            AuthAPIError::UserNotFound => "user_not_found",
+            AuthAPIError::RoleNotFound => "role_not_found",
+            AuthAPIError::OrganizationNotFound => "organization_not_found",
+            AuthAPIError::InvitationNotFound => "invitation_not_found",
+            AuthAPIError::EmailNotFound => "email_not_found",
+            AuthAPIError::SuppressionNotFound => "suppression_not_found",
+            AuthAPIError::Forbidden => "forbidden",
+            AuthAPIError::AccountSuspended => "account_suspended",
+            AuthAPIError::AccountLocked => "account_locked",
+            AuthAPIError::AccountPendingVerification => "account_pending_verification",
+            AuthAPIError::PasswordResetRequired => "password_reset_required",
+            AuthAPIError::PhoneNotVerified => "phone_not_verified",
+            AuthAPIError::SmsUnavailable => "sms_unavailable",
+            AuthAPIError::TooManyAttempts => "too_many_attempts",
+            AuthAPIError::EmailSuppressed => "email_suppressed",
+            AuthAPIError::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+
+    /// Error reported to the owner of an account with the given status.
+    /// Returns `None` for active accounts.
+    pub fn for_account_status(status: AccountStatus) -> Option<Self> {
+        match status {
+            AccountStatus::Active => None,
+            AccountStatus::Suspended => Some(AuthAPIError::AccountSuspended),
+            AccountStatus::Locked => Some(AuthAPIError::AccountLocked),
+            AccountStatus::PendingVerification => Some(AuthAPIError::AccountPendingVerification),
+        }
+    }
+
+    /// Error for an email the caller needed but that could not be sent
+    pub fn email_not_sent(error: Report) -> Self {
+        if RecipientSuppressed::caused(&error) {
+            AuthAPIError::EmailSuppressed
+        } else {
+            AuthAPIError::UnexpectedError(error)
+        }
+    }
+}
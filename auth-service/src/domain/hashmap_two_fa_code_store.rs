@@ -1,16 +1,54 @@
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        email::Email,
+    },
+    services::expired_entry_evictor::EvictExpired,
+    utils::{
+        clock::{Clock, SystemClock},
+        constants::TWO_FA_CODE_TTL_SECONDS,
+        expiring_map::ExpiringMap,
+    },
 };
 use color_eyre::eyre::Result;
-use std::collections::HashMap;
+use std::{
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+
+const TWO_FA_CODE_TTL: Duration = Duration::from_secs(TWO_FA_CODE_TTL_SECONDS);
 
-#[derive(Default)]
+/// Codes expire after `TWO_FA_CODE_TTL_SECONDS`, like the Redis store.
+/// Expired codes are freed by [`EvictExpired::evict_expired`].
 pub struct HashMapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: RwLock<ExpiringMap<Email, (LoginAttemptId, TwoFACode)>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for HashMapTwoFACodeStore {
+    fn default() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+}
+
+impl HashMapTwoFACodeStore {
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            codes: RwLock::new(ExpiringMap::default()),
+            clock,
+        }
+    }
+}
+
+impl EvictExpired for HashMapTwoFACodeStore {
+    fn evict_expired(&self) -> usize {
+        self.codes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .evict_expired(self.clock.now())
+    }
 }
 
-// TODO: implement TwoFACodeStore for HashMapTwoFACodeStore
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashMapTwoFACodeStore {
     /// Add a Two Factor code to the store.
@@ -27,40 +65,41 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
     /// use auth_service::domain::parse::Parseable;
     ///
     /// // Ingredients
-    /// let mut code_store: HashMapTwoFACodeStore = Default::default();
+    /// let code_store: HashMapTwoFACodeStore = Default::default();
     /// let login_attempt_id = LoginAttemptId::default();
     /// let code: TwoFACode = TwoFACode::default();
     /// let email = Email::parse(Secret::new("jaymo@gmail.com".to_string())).unwrap();
     ///
     /// tokio_test::block_on(async {
-    ///   // Should be okay, since it does not exist
-    ///   let add_result = code_store.add_code(email.clone(), login_attempt_id.clone(), code.clone()).await;
+    ///   let add_result = code_store.add_code(email.clone(), login_attempt_id, code).await;
     ///   assert!(add_result.is_ok());
     ///
-    ///   // Try again, we should fail
-    ///   let add_result_fail = code_store.add_code(email, login_attempt_id, code).await;
-    ///   assert!(add_result_fail.is_err());
-    ///   if let Err(e) = add_result_fail {
-    ///       assert_eq!(e, TwoFACodeStoreError::EmailAlreadyExists);
-    ///   }
+    ///   // A new login attempt replaces the pending code
+    ///   let (new_login_attempt_id, new_code) = (LoginAttemptId::default(), TwoFACode::default());
+    ///   let add_result = code_store.add_code(email.clone(), new_login_attempt_id.clone(), new_code.clone()).await;
+    ///   assert!(add_result.is_ok());
+    ///   let (stored_id, stored_code) = code_store.get_code(&email).await.unwrap();
+    ///   assert_eq!(stored_id, new_login_attempt_id);
+    ///   assert_eq!(stored_code, new_code);
     /// })
     ///
     /// ```
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        if self.codes.contains_key(&email) {
-            return Err(TwoFACodeStoreError::EmailAlreadyExists);
-        }
-        // Guaranteed to always be None since we check before inserting
-        self.codes.insert(email, (login_attempt_id, code));
+        let expires_at = self.clock.now() + TWO_FA_CODE_TTL;
+        self.codes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(email, (login_attempt_id, code), expires_at);
         Ok(())
     }
 
-    /// Remove a Two Factor code to the store.
+    /// Remove a Two Factor code from the store. Removing a code that does not
+    /// exist is not an error.
     ///
     /// ## Example
     ///
@@ -74,20 +113,15 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
     /// use auth_service::domain::parse::Parseable;
     ///
     /// // Ingredients
-    /// let mut code_store: HashMapTwoFACodeStore = Default::default();
+    /// let code_store: HashMapTwoFACodeStore = Default::default();
     /// let login_attempt_id = LoginAttemptId::default();
     /// let code: TwoFACode = TwoFACode::default();
     /// let email = Email::parse(Secret::new("jaymo@gmail.com".to_string())).unwrap();
     ///
-    /// // Should return error if it does not exist
     /// tokio_test::block_on(async {
-    /// // Try removing, should not work
+    /// // Nothing to remove yet
     /// let remove_result = code_store.remove_code(&email).await;
-    /// if let Err(e) = remove_result {
-    ///     assert_eq!(e, TwoFACodeStoreError::LoginAttemptIdNotFound);
-    /// } else {
-    ///     panic!("Cannot remove a non-existing code from store.");
-    /// }
+    /// assert!(remove_result.is_ok());
     ///
     /// // Add code
     /// let add_result = code_store.add_code(email.clone(), login_attempt_id.clone(), code.clone()).await;
@@ -99,22 +133,18 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
     ///     panic!("Should be able to remove code if email exists");
     /// }
     ///
-    /// // Removing again should result in error
-    /// let remove_result = code_store.remove_code(&email).await;
-    /// if let Err(e) = remove_result {
-    ///     assert_eq!(e, TwoFACodeStoreError::LoginAttemptIdNotFound);
-    /// } else {
-    ///     panic!("Cannot remove a non-existing code from store.");
-    /// }
+    /// // The code is gone
+    /// let get_result = code_store.get_code(&email).await;
+    /// assert_eq!(get_result.err(), Some(TwoFACodeStoreError::LoginAttemptIdNotFound));
     ///
     /// # })
     /// ```
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        if let Some(_) = self.codes.remove(&email) {
-            Ok(())
-        } else {
-            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
-        }
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.codes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(email);
+        Ok(())
     }
 
     /// Get a Two Factor code object from the store.
@@ -131,7 +161,7 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
     /// use auth_service::domain::parse::Parseable;
     ///
     /// // Ingredients
-    /// let mut code_store: HashMapTwoFACodeStore = Default::default();
+    /// let code_store: HashMapTwoFACodeStore = Default::default();
     /// let login_attempt_id = LoginAttemptId::default();
     /// let code: TwoFACode = TwoFACode::default();
     /// let email = Email::parse(Secret::new("jaymo@gmail.com".to_string())).unwrap();
@@ -163,7 +193,8 @@ impl TwoFACodeStore for HashMapTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        if let Some((id, code)) = self.codes.get(&email) {
+        let codes = self.codes.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some((id, code)) = codes.get(email, self.clock.now()) {
             // Is this the best idea? cloning each time when getting can be quite costly.
             Ok((id.to_owned(), code.to_owned()))
         } else {
@@ -0,0 +1,123 @@
+use std::{fmt, str::FromStr};
+
+use color_eyre::eyre::{eyre, Result};
+
+/// Languages users can get emails and API messages in.
+/// The string form is the language subtag, and matches the `locale` column of `users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    pub const ALL: &'static [Locale] = &[Locale::English, Locale::German];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::German => "de",
+        }
+    }
+
+    /// Locales to look a message up in, most preferred first.
+    /// Every chain ends in English, which has every message.
+    pub fn fallbacks(&self) -> &'static [Locale] {
+        match self {
+            Locale::English => &[Locale::English],
+            Locale::German => &[Locale::German, Locale::English],
+        }
+    }
+
+    /// The supported locale the client prefers most, from the value of an
+    /// `Accept-Language` header. `None` if it names no supported language.
+    ///
+    /// ```
+    /// use auth_service::domain::locale::Locale;
+    /// assert_eq!(Locale::negotiate("fr-CH, de;q=0.8, en;q=0.5"), Some(Locale::German));
+    /// assert_eq!(Locale::negotiate("de;q=0, en-GB"), Some(Locale::English));
+    /// assert_eq!(Locale::negotiate("fr"), None);
+    /// ```
+    pub fn negotiate(accept_language: &str) -> Option<Locale> {
+        let mut ranges: Vec<(Locale, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let locale = parts.next()?.trim().parse().ok()?;
+                let quality = parts
+                    .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                    .find_map(|quality| quality.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((locale, quality))
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        // Stable, so ranges of equal quality keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranges.first().map(|(locale, _)| *locale)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = color_eyre::eyre::Report;
+
+    /// Parses a language tag. Only the language matters, so regional
+    /// variants map to the same locale.
+    ///
+    /// ```
+    /// use auth_service::domain::locale::Locale;
+    /// assert_eq!("de".parse::<Locale>().unwrap(), Locale::German);
+    /// assert_eq!("de-AT".parse::<Locale>().unwrap(), Locale::German);
+    /// assert_eq!("EN_us".parse::<Locale>().unwrap(), Locale::English);
+    /// assert!("fr".parse::<Locale>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        let language = s.split(['-', '_']).next().unwrap_or_default();
+        Locale::ALL
+            .iter()
+            .find(|locale| locale.as_str().eq_ignore_ascii_case(language))
+            .copied()
+            .ok_or_else(|| eyre!("Unsupported locale: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_orders_by_quality() {
+        assert_eq!(
+            Locale::negotiate("en;q=0.4, de;q=0.9"),
+            Some(Locale::German)
+        );
+        assert_eq!(Locale::negotiate("de, en"), Some(Locale::German));
+        assert_eq!(Locale::negotiate("en, de"), Some(Locale::English));
+    }
+
+    #[test]
+    fn test_negotiate_ignores_unsupported_and_malformed_ranges() {
+        assert_eq!(
+            Locale::negotiate("*, fr;q=1, de;q=0.1"),
+            Some(Locale::German)
+        );
+        assert_eq!(Locale::negotiate("de;q=abc"), Some(Locale::German));
+        assert_eq!(Locale::negotiate(""), None);
+        assert_eq!(Locale::negotiate("*"), None);
+    }
+
+    #[test]
+    fn test_fallbacks_end_in_english() {
+        for locale in Locale::ALL {
+            assert_eq!(locale.fallbacks().first(), Some(locale));
+            assert_eq!(locale.fallbacks().last(), Some(&Locale::English));
+        }
+    }
+}
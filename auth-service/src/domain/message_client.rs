@@ -0,0 +1,10 @@
+use super::phone_number::PhoneNumber;
+use color_eyre::eyre::Result;
+
+/// Delivers short text messages, such as 2FA codes, to phone numbers.
+/// Whether they arrive as an SMS or are read out in a call is up to the
+/// implementation, so callers only pick the channel by picking the client.
+#[async_trait::async_trait]
+pub trait MessageClient: Send + Sync {
+    async fn send_message(&self, recipient: &PhoneNumber, text: &str) -> Result<()>;
+}
@@ -1,9 +1,17 @@
+pub mod audit;
 pub mod data_stores;
 pub mod email;
 pub mod email_client;
+pub mod email_outbox;
+pub mod email_suppression;
 pub mod error;
 pub mod hashmap_two_fa_code_store;
+pub mod locale;
+pub mod message_client;
+pub mod organization;
 pub mod parse;
 pub mod password;
+pub mod phone_number;
 pub mod redis_two_fa_code_store;
+pub mod role;
 pub mod user;
@@ -0,0 +1,183 @@
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use super::{email::Email, parse::Parseable, role::Role};
+
+const MAXIMUM_NAME_LEN: usize = 100;
+
+/// How long an invitation can be accepted for
+pub const INVITATION_TTL_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OrganizationId(Uuid);
+
+impl OrganizationId {
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for OrganizationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parseable<String> for OrganizationId {
+    /// Organization ids are UUIDs
+    /// ```
+    /// use auth_service::domain::{organization::OrganizationId, parse::Parseable};
+    /// let id = "67e55044-10b1-426f-9247-bb680e5fe0c8".to_owned();
+    /// assert_eq!(OrganizationId::parse(id.clone()).unwrap().to_string(), id);
+    /// assert!(OrganizationId::parse("acme".to_owned()).is_err());
+    /// ```
+    fn parse(id: String) -> Result<Self> {
+        let id = Uuid::parse_str(&id).wrap_err("Invalid organization id")?;
+        Ok(Self(id))
+    }
+}
+
+impl fmt::Display for OrganizationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrganizationName(String);
+
+impl AsRef<str> for OrganizationName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Parseable<String> for OrganizationName {
+    /// Names are trimmed and must hold between 1 and 100 characters
+    /// ```
+    /// use auth_service::domain::{organization::OrganizationName, parse::Parseable};
+    /// let name = OrganizationName::parse("  Acme Corp ".to_owned()).unwrap();
+    /// assert_eq!(name.as_ref(), "Acme Corp");
+    /// assert!(OrganizationName::parse("   ".to_owned()).is_err());
+    /// ```
+    fn parse(name: String) -> Result<Self> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAXIMUM_NAME_LEN {
+            return Err(eyre!("Invalid organization name"));
+        }
+        Ok(Self(name.to_owned()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Organization {
+    pub id: OrganizationId,
+    pub name: OrganizationName,
+}
+
+/// An organization a user belongs to, with the roles they hold inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Membership {
+    pub organization: Organization,
+    pub roles: Vec<Role>,
+}
+
+/// Which users a request with admin permissions may act on.
+///
+/// Permissions granted by global roles apply to every user, while permissions
+/// granted by organization roles only apply to members of that organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TenantScope {
+    Global,
+    Organization(OrganizationId),
+}
+
+/// Secret sent to the invitee as part of the accept link
+#[derive(Debug, Clone)]
+pub struct InvitationToken(Secret<String>);
+
+impl PartialEq for InvitationToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl InvitationToken {
+    /// Invitation tokens are UUIDs
+    /// ```
+    /// use auth_service::domain::organization::InvitationToken;
+    /// assert!(InvitationToken::parse("not-a-token".to_owned()).is_err());
+    /// ```
+    pub fn parse(token: String) -> Result<Self> {
+        let token = Uuid::parse_str(&token).wrap_err("Invalid invitation token")?;
+        Ok(Self(Secret::new(token.to_string())))
+    }
+}
+
+impl Default for InvitationToken {
+    fn default() -> Self {
+        Self(Secret::new(Uuid::new_v4().to_string()))
+    }
+}
+
+impl AsRef<Secret<String>> for InvitationToken {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+/// Invitation for `email` to join an organization, optionally with a role
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invitation {
+    pub token: InvitationToken,
+    pub organization_id: OrganizationId,
+    pub email: Email,
+    pub role: Option<Role>,
+    pub invited_by: String,
+    pub expires_at: DateTime<Utc>,
+    pub accepted: bool,
+}
+
+impl Invitation {
+    pub fn new(
+        organization_id: OrganizationId,
+        email: Email,
+        role: Option<Role>,
+        invited_by: impl Into<String>,
+    ) -> Self {
+        Self {
+            token: InvitationToken::default(),
+            organization_id,
+            email,
+            role,
+            invited_by: invited_by.into(),
+            expires_at: Utc::now() + Duration::days(INVITATION_TTL_DAYS),
+            accepted: false,
+        }
+    }
+
+    /// Accepted and expired invitations cannot be used again
+    pub fn is_pending(&self, now: DateTime<Utc>) -> bool {
+        !self.accepted && now < self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invitations_expire() {
+        let email = Email::parse(Secret::new("invitee@example.com".to_owned())).unwrap();
+        let mut invitation = Invitation::new(OrganizationId::new(), email, None, "admin");
+        assert!(invitation.is_pending(Utc::now()));
+        assert!(!invitation.is_pending(invitation.expires_at));
+
+        invitation.accepted = true;
+        assert!(!invitation.is_pending(Utc::now()));
+    }
+}
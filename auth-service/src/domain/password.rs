@@ -18,7 +18,7 @@ const MINIMUM_PASSWORD_LEN: usize = 8;
 impl Parseable<Secret<String>> for Password {
     fn parse(password: Secret<String>) -> Result<Self> {
         // Password cannot be longer
-        if &password.expose_secret().len() < &MINIMUM_PASSWORD_LEN {
+        if password.expose_secret().len() < MINIMUM_PASSWORD_LEN {
             Err(ValidationError::new("Invalid password: Must contain at least 8 characters").into())
         } else {
             Ok(Password(password))
@@ -48,14 +48,9 @@ mod tests {
         "lengthi8".to_string()
     )]
     fn should_be_valid(valid_password: String) {
-        let password = Password::parse(Secret::new(valid_password.clone()));
-        if let Err(_) = password {
-            panic!("Should be a valid password: {}", valid_password);
-        } else {
-            let unwrapped = password.unwrap();
-            let password = unwrapped.as_ref().expose_secret();
-            assert_eq!(password, &valid_password);
-        }
+        let password = Password::parse(Secret::new(valid_password.clone()))
+            .unwrap_or_else(|_| panic!("Should be a valid password: {}", valid_password));
+        assert_eq!(password.as_ref().expose_secret(), &valid_password);
     }
 
     #[test_case(
@@ -65,6 +60,6 @@ mod tests {
     fn should_be_invalid(invalid_password: String) {
         // using convenient function without providing locale
         let password = Password::parse(Secret::new(invalid_password));
-        assert_eq!(password.is_err(), true);
+        assert!(password.is_err());
     }
 }
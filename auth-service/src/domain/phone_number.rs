@@ -0,0 +1,104 @@
+use std::hash::Hash;
+
+use color_eyre::eyre::{eyre, Result};
+use secrecy::{ExposeSecret, Secret};
+
+use super::parse::Parseable;
+
+// E.164 allows at most 15 digits, including the country code
+const MAXIMUM_DIGITS: usize = 15;
+// The shortest numbers in use have a one-digit country code and seven digits
+const MINIMUM_DIGITS: usize = 8;
+
+/// A phone number in international E.164 form, e.g. `+4915112345678`.
+///
+/// Parsing accepts the spaces, dashes, dots and parentheses people usually
+/// type and drops them, so every number has a single stored form.
+/// ```
+/// use auth_service::domain::{parse::Parseable, phone_number::PhoneNumber};
+/// use secrecy::{ExposeSecret, Secret};
+///
+/// let phone = PhoneNumber::parse(Secret::new("+49 (151) 123-456.78".to_owned())).unwrap();
+/// assert_eq!(phone.as_ref().expose_secret(), "+4915112345678");
+/// assert!(PhoneNumber::parse(Secret::new("0151 12345678".to_owned())).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct PhoneNumber(Secret<String>);
+
+impl AsRef<Secret<String>> for PhoneNumber {
+    fn as_ref(&self) -> &Secret<String> {
+        &self.0
+    }
+}
+
+impl PartialEq for PhoneNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl Hash for PhoneNumber {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.expose_secret().hash(state);
+    }
+}
+
+impl Eq for PhoneNumber {}
+
+impl Parseable<Secret<String>> for PhoneNumber {
+    fn parse(phone: Secret<String>) -> Result<Self> {
+        let compact: String = phone
+            .expose_secret()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+            .collect();
+        let digits = compact
+            .strip_prefix('+')
+            .ok_or_else(|| eyre!("Phone numbers must start with + and the country code"))?;
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(eyre!("Phone numbers may only contain digits"));
+        }
+        if digits.starts_with('0') {
+            return Err(eyre!("Country codes never start with 0"));
+        }
+        if !(MINIMUM_DIGITS..=MAXIMUM_DIGITS).contains(&digits.len()) {
+            return Err(eyre!(
+                "Phone numbers have between {} and {} digits",
+                MINIMUM_DIGITS,
+                MAXIMUM_DIGITS
+            ));
+        }
+        Ok(Self(Secret::new(compact)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("+4915112345678", "+4915112345678")]
+    #[test_case(" +1 (415) 555-0100 ", "+14155550100")]
+    #[test_case("+44 20.7946.0958", "+442079460958")]
+    fn should_parse_and_compact_valid_numbers(input: &str, expected: &str) {
+        let phone = PhoneNumber::parse(Secret::new(input.to_owned())).unwrap();
+        assert_eq!(phone.as_ref().expose_secret(), expected);
+    }
+
+    #[test_case("4915112345678"; "without plus")]
+    #[test_case("+49 151 1234 567x"; "with letters")]
+    #[test_case("+049151123456"; "with a leading zero")]
+    #[test_case("+4912345"; "too short")]
+    #[test_case("+4915112345678901"; "too long")]
+    #[test_case("+"; "empty")]
+    fn should_reject_invalid_numbers(input: &str) {
+        assert!(PhoneNumber::parse(Secret::new(input.to_owned())).is_err());
+    }
+
+    #[test]
+    fn should_compare_the_compacted_form() {
+        let typed = PhoneNumber::parse(Secret::new("+49 151 12345678".to_owned())).unwrap();
+        let stored = PhoneNumber::parse(Secret::new("+4915112345678".to_owned())).unwrap();
+        assert_eq!(typed, stored);
+    }
+}
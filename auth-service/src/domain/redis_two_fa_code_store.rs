@@ -1,22 +1,23 @@
-use std::sync::Arc;
-
 use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    email::Email,
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        email::Email,
+    },
+    utils::constants::TWO_FA_CODE_TTL_SECONDS,
 };
 
+// Commands run on clones of the multiplexed connection, see `RedisBannedTokenStore`
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
@@ -25,7 +26,7 @@ impl RedisTwoFACodeStore {
 impl TwoFACodeStore for RedisTwoFACodeStore {
     #[tracing::instrument("Add two fa code", skip_all)]
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
@@ -45,27 +46,28 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
             .map_err(TwoFACodeStoreError::UnexpectedError)?; // Updated!
                                                              // 4. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL).
                                                              // The value should be the serialized 2FA tuple.
-                                                             // The expiration time should be set to TEN_MINUTES_IN_SECONDS.
+                                                             // The expiration time should be set to TWO_FA_CODE_TTL_SECONDS.
                                                              // Return TwoFACodeStoreError::UnexpectedError if casting fails or the call to set_ex fails.
-        self.conn
-            .write()
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(&key, serialized_two_fa_tuple, TWO_FA_CODE_TTL_SECONDS)
             .await
-            .set_ex(&key, serialized_two_fa_tuple, TEN_MINUTES_IN_SECONDS)
             .wrap_err("failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
     }
 
     #[tracing::instrument("Remove two fa code", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
         // 1. Create a new key using the get_key helper function.
-        let key = get_key(&email);
+        let key = get_key(email);
         // 2. Call the del command on the Redis connection to delete the 2FA code entry.
         let _: () = self
             .conn
-            .write()
-            .await
+            .clone()
             .del(&key)
+            .await
             .wrap_err("failed to delete 2FA code from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
@@ -78,8 +80,15 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         let key = get_key(email);
 
-        match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(&key)
+            .await
+            .wrap_err("failed to read 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        match value {
+            Some(value) => {
                 let data: TwoFATuple = serde_json::from_str(&value)
                     .wrap_err("failed to deserialize 2FA tuple") // New!
                     .map_err(TwoFACodeStoreError::UnexpectedError)?; // Updated!
@@ -92,7 +101,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
                 Ok((login_attempt_id, email_code))
             }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 }
@@ -100,7 +109,6 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
 
 fn get_key(email: &Email) -> String {
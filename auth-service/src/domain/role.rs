@@ -0,0 +1,140 @@
+use std::{fmt, str::FromStr};
+
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use super::parse::Parseable;
+
+/// Name of the role seeded by the migrations, which holds every permission.
+pub const ADMIN_ROLE: &str = "admin";
+/// Name of the role seeded for whoever creates an organization. It manages the
+/// members of the organization and their roles there, but not their accounts.
+pub const ORGANIZATION_ADMIN_ROLE: &str = "org-admin";
+
+const MAXIMUM_ROLE_LEN: usize = 64;
+
+/// A named group of permissions that can be assigned to users
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Role(String);
+
+impl Role {
+    pub fn admin() -> Self {
+        Self(ADMIN_ROLE.to_owned())
+    }
+
+    pub fn organization_admin() -> Self {
+        Self(ORGANIZATION_ADMIN_ROLE.to_owned())
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Parseable<String> for Role {
+    /// Role names are lowercase ASCII letters, digits, `_` or `-`
+    /// ```
+    /// use auth_service::domain::{parse::Parseable, role::Role};
+    /// assert!(Role::parse("support_agent".to_owned()).is_ok());
+    /// assert!(Role::parse("Not A Role".to_owned()).is_err());
+    /// assert!(Role::parse("".to_owned()).is_err());
+    /// ```
+    fn parse(role: String) -> Result<Self> {
+        let is_valid_char =
+            |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-';
+        if role.is_empty() || role.len() > MAXIMUM_ROLE_LEN || !role.chars().all(is_valid_char) {
+            return Err(eyre!("Invalid role name: {}", role));
+        }
+        Ok(Self(role))
+    }
+}
+
+/// Actions that can be granted to a role.
+/// The string form matches the `permissions` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    UsersRead,
+    UsersWrite,
+    RolesManage,
+    AuditRead,
+    EmailsManage,
+}
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[
+        Permission::UsersRead,
+        Permission::UsersWrite,
+        Permission::RolesManage,
+        Permission::AuditRead,
+        Permission::EmailsManage,
+    ];
+
+    /// Permissions of the organization admin role, as seeded by the migrations
+    pub const ORGANIZATION_ADMIN: &'static [Permission] = &[
+        Permission::UsersRead,
+        Permission::UsersWrite,
+        Permission::RolesManage,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::UsersRead => "users:read",
+            Permission::UsersWrite => "users:write",
+            Permission::RolesManage => "roles:manage",
+            Permission::AuditRead => "audit:read",
+            Permission::EmailsManage => "emails:manage",
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = color_eyre::eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Permission::ALL
+            .iter()
+            .find(|permission| permission.as_str() == s)
+            .copied()
+            .ok_or_else(|| eyre!("Unknown permission: {}", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("admin")]
+    #[test_case("support-agent")]
+    #[test_case("tier_2")]
+    fn should_be_valid_roles(role: &str) {
+        let parsed = Role::parse(role.to_owned()).unwrap();
+        assert_eq!(parsed.as_ref(), role);
+    }
+
+    #[test_case("Admin")]
+    #[test_case("support agent")]
+    #[test_case("")]
+    fn should_be_invalid_roles(role: &str) {
+        assert!(Role::parse(role.to_owned()).is_err());
+    }
+
+    #[test]
+    fn permissions_round_trip_through_strings() {
+        for permission in Permission::ALL {
+            assert_eq!(
+                permission.as_str().parse::<Permission>().unwrap(),
+                *permission
+            );
+        }
+        assert!("users:delete".parse::<Permission>().is_err());
+    }
+}
@@ -1,11 +1,43 @@
-use super::{email::Email, password::Password};
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use uuid::Uuid;
+
+use super::{
+    email::Email, locale::Locale, password::Password, phone_number::PhoneNumber, role::Role,
+};
 
 /// Represents a new user
 #[derive(Debug, PartialEq, Clone)]
 pub struct User {
+    /// Stable identifier, unlike the email the user signs in with
+    pub id: Uuid,
     pub email: Email,
     pub requires_2fa: bool,
     pub password: Password,
+    /// Only active accounts can log in or use their tokens
+    pub status: AccountStatus,
+    /// Set by an admin to block logins until the password is changed
+    pub password_reset_required: bool,
+    pub created_at: DateTime<Utc>,
+    /// Last change to the account itself. Logins only touch the fields below.
+    pub updated_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub last_login_ip: Option<String>,
+    /// Failed attempts since the last successful login
+    pub failed_login_count: u32,
+    pub password_changed_at: DateTime<Utc>,
+    /// Language the user chose for emails
+    pub locale: Option<Locale>,
+    /// Whether the user wants to hear about new logins and 2FA being turned on.
+    /// Changes that could lock them out are always reported.
+    pub security_notices: bool,
+    pub phone_number: Option<PhoneNumber>,
+    /// Set once the user entered a code sent to `phone_number`
+    pub phone_verified: bool,
+    /// Where 2FA codes are sent
+    pub two_fa_channel: TwoFactorChannel,
 }
 
 impl User {
@@ -23,11 +55,165 @@ impl User {
     /// let user = User::new(email, requires_two_factor_auth, password);
     /// ```
     pub fn new(email: Email, requires_2fa: bool, password: Password) -> Self {
+        let now = Utc::now();
         // Note: unwrap here is dangerous. It might be good to return
         User {
-            email: email,
+            id: Uuid::new_v4(),
+            email,
             requires_2fa,
-            password: password,
+            password,
+            status: AccountStatus::Active,
+            password_reset_required: false,
+            created_at: now,
+            updated_at: now,
+            last_login_at: None,
+            last_login_ip: None,
+            failed_login_count: 0,
+            password_changed_at: now,
+            locale: None,
+            security_notices: true,
+            phone_number: None,
+            phone_verified: false,
+            two_fa_channel: TwoFactorChannel::Email,
         }
     }
 }
+
+/// What admins get to see about a user. Never includes the password.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UserSummary {
+    pub email: Email,
+    pub requires_2fa: bool,
+    pub status: AccountStatus,
+    /// Why the status was last changed, and by whom
+    pub status_reason: Option<String>,
+    pub status_changed_by: Option<String>,
+    pub password_reset_required: bool,
+    pub roles: Vec<Role>,
+}
+
+/// Lifecycle state of an account.
+/// The string form matches the `status` column of `users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    /// Blocked by an admin
+    Suspended,
+    /// Blocked automatically, e.g. after suspicious activity
+    Locked,
+    /// Waiting for the user to confirm their email address
+    PendingVerification,
+}
+
+impl AccountStatus {
+    pub const ALL: &'static [AccountStatus] = &[
+        AccountStatus::Active,
+        AccountStatus::Suspended,
+        AccountStatus::Locked,
+        AccountStatus::PendingVerification,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Suspended => "suspended",
+            AccountStatus::Locked => "locked",
+            AccountStatus::PendingVerification => "pending_verification",
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        *self == AccountStatus::Active
+    }
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AccountStatus {
+    type Err = color_eyre::eyre::Report;
+
+    /// ```
+    /// use auth_service::domain::user::AccountStatus;
+    /// assert_eq!("locked".parse::<AccountStatus>().unwrap(), AccountStatus::Locked);
+    /// assert!("deleted".parse::<AccountStatus>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        AccountStatus::ALL
+            .iter()
+            .find(|status| status.as_str() == s)
+            .copied()
+            .ok_or_else(|| eyre!("Unknown account status: {}", s))
+    }
+}
+
+/// How a user receives their 2FA codes.
+/// The string form matches the `two_fa_channel` column of `users`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TwoFactorChannel {
+    #[default]
+    Email,
+    /// Text message to the verified phone number
+    Sms,
+}
+
+impl TwoFactorChannel {
+    pub const ALL: &'static [TwoFactorChannel] = &[TwoFactorChannel::Email, TwoFactorChannel::Sms];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TwoFactorChannel::Email => "email",
+            TwoFactorChannel::Sms => "sms",
+        }
+    }
+}
+
+impl fmt::Display for TwoFactorChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TwoFactorChannel {
+    type Err = color_eyre::eyre::Report;
+
+    /// ```
+    /// use auth_service::domain::user::TwoFactorChannel;
+    /// assert_eq!("sms".parse::<TwoFactorChannel>().unwrap(), TwoFactorChannel::Sms);
+    /// assert!("carrier pigeon".parse::<TwoFactorChannel>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        TwoFactorChannel::ALL
+            .iter()
+            .find(|channel| channel.as_str() == s)
+            .copied()
+            .ok_or_else(|| eyre!("Unknown 2FA channel: {}", s))
+    }
+}
+
+/// A status transition together with who made it and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    pub status: AccountStatus,
+    pub reason: Option<String>,
+    pub changed_by: String,
+}
+
+impl StatusChange {
+    pub fn new(status: AccountStatus, changed_by: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason: None,
+            changed_by: changed_by.into(),
+        }
+    }
+
+    pub fn with_reason(mut self, reason: Option<String>) -> Self {
+        self.reason = reason.filter(|reason| !reason.trim().is_empty());
+        self
+    }
+}
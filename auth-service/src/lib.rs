@@ -1,9 +1,11 @@
-use std::{env, error::Error};
+use std::{env, error::Error, net::SocketAddr, str::FromStr};
 
 use axum::{
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
     http::{Method, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
     serve::Serve,
     Json, Router,
 };
@@ -15,16 +17,37 @@ pub mod services;
 pub mod utils;
 
 use app_state::state::AppState;
-use domain::error::AuthAPIError;
+use domain::{error::AuthAPIError, locale::Locale, role::Permission};
 use redis::{Client, RedisResult};
-use routes::{login, logout, signup, verify_2fa, verify_token};
+use routes::{
+    accept_invitation, assign_role, change_email, change_password, create_organization,
+    disable_user, enable_user, force_password_reset, get_email_suppression, get_user,
+    invite_member, lift_email_suppression, list_audit_events, list_email_providers,
+    list_email_suppressions, list_emails, list_organizations, list_own_audit_events, list_users,
+    login, logout, postmark_webhook, report_account, request_password_reset, reset_2fa,
+    reset_password, retry_email, revoke_role, revoke_sessions, set_locale, set_notifications,
+    set_phone_number, set_two_fa_channel, set_two_factor, set_user_status, signup,
+    switch_organization, verify_2fa, verify_phone_number, verify_token,
+};
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{
+    postgres::PgPoolOptions,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    PgPool, SqlitePool,
+};
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
-use utils::tracing::{make_span_with_request_id, on_request, on_response};
+use utils::{
+    audit::record_audit_events,
+    guard::{require_auth, RequirePermission},
+    i18n::{error_message, localize_errors, ErrorCode},
+    tracing::{make_span_with_request_id, on_request, on_response, propagate_request_id},
+};
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -49,13 +72,140 @@ impl Application {
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Admin-only routes. Each group is guarded by the permission it needs.
+        let admin_routes = Router::new()
+            .route("/admin/roles/assign", post(assign_role))
+            .route("/admin/roles/revoke", post(revoke_role))
+            .route_layer(middleware::from_fn_with_state(
+                RequirePermission::new(app_state.clone(), Permission::RolesManage),
+                RequirePermission::guard,
+            ))
+            .merge(
+                Router::new()
+                    .route("/admin/users", get(list_users))
+                    .route("/admin/users/:email", get(get_user))
+                    .route_layer(middleware::from_fn_with_state(
+                        RequirePermission::new(app_state.clone(), Permission::UsersRead),
+                        RequirePermission::guard,
+                    )),
+            )
+            // Changes to whole accounts, which need a global role
+            .merge(
+                Router::new()
+                    .route("/admin/users/:email/disable", post(disable_user))
+                    .route("/admin/users/:email/enable", post(enable_user))
+                    .route("/admin/users/:email/status", post(set_user_status))
+                    .route(
+                        "/admin/users/:email/force-password-reset",
+                        post(force_password_reset),
+                    )
+                    .route("/admin/users/:email/reset-2fa", post(reset_2fa))
+                    .route("/admin/users/:email/revoke-sessions", post(revoke_sessions))
+                    .route_layer(middleware::from_fn_with_state(
+                        RequirePermission::new(app_state.clone(), Permission::UsersWrite),
+                        RequirePermission::guard,
+                    )),
+            )
+            .merge(
+                Router::new()
+                    .route("/admin/audit/events", get(list_audit_events))
+                    .route_layer(middleware::from_fn_with_state(
+                        RequirePermission::new(app_state.clone(), Permission::AuditRead),
+                        RequirePermission::guard,
+                    )),
+            )
+            .merge(
+                Router::new()
+                    .route("/admin/emails", get(list_emails))
+                    .route("/admin/emails/providers", get(list_email_providers))
+                    .route("/admin/emails/:id/retry", post(retry_email))
+                    .route("/admin/email-suppressions", get(list_email_suppressions))
+                    .route(
+                        "/admin/email-suppressions/:email",
+                        get(get_email_suppression),
+                    )
+                    .route(
+                        "/admin/email-suppressions/:email/lift",
+                        post(lift_email_suppression),
+                    )
+                    .route_layer(middleware::from_fn_with_state(
+                        RequirePermission::new(app_state.clone(), Permission::EmailsManage),
+                        RequirePermission::guard,
+                    )),
+            );
+
+        // Organization membership. Inviting needs permission to manage users,
+        // everything else only needs a signed-in user.
+        let organization_routes = Router::new()
+            .route(
+                "/organizations",
+                get(list_organizations).post(create_organization),
+            )
+            .route("/organizations/switch", post(switch_organization))
+            .route("/organizations/invitations/accept", post(accept_invitation))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                require_auth,
+            ))
+            .merge(
+                Router::new()
+                    .route("/organizations/invitations", post(invite_member))
+                    .route_layer(middleware::from_fn_with_state(
+                        RequirePermission::new(app_state.clone(), Permission::UsersWrite),
+                        RequirePermission::guard,
+                    )),
+            );
+
+        // Signed-in users can read the audit events that involve them
+        // and manage their own account
+        let account_routes = Router::new()
+            .route("/audit/events", get(list_own_audit_events))
+            .route("/account/locale", post(set_locale))
+            .route("/account/password", post(change_password))
+            .route("/account/2fa", post(set_two_factor))
+            .route("/account/email", post(change_email))
+            .route("/account/notifications", post(set_notifications))
+            .route("/account/phone", post(set_phone_number))
+            .route("/account/phone/verify", post(verify_phone_number))
+            .route("/account/2fa/channel", post(set_two_fa_channel))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                require_auth,
+            ));
+
+        // Only built into development binaries, and only served while emails are captured
+        let dev_routes = Router::new();
+        #[cfg(feature = "dev-mailbox")]
+        let dev_routes = match app_state.mailbox.clone() {
+            Some(mailbox) => dev_routes.merge(routes::dev_mailbox_routes(mailbox)),
+            None => dev_routes,
+        };
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
+            .merge(admin_routes)
+            .merge(organization_routes)
+            .merge(account_routes)
+            .merge(dev_routes)
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
+            // Authenticated by the token of a security alert, not a session
+            .route("/account/wasnt-me", post(report_account))
+            .route("/password-reset", post(request_password_reset))
+            // Authenticated by the token of a password reset link, not a session
+            .route("/password-reset/confirm", post(reset_password))
+            // Authenticated with the credentials configured for Postmark, not a session
+            .route("/webhooks/postmark", post(postmark_webhook))
+            // Persists the audit event each handler records, with the outcome and origin of the request
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                record_audit_events,
+            ))
+            // Translates error responses, including those of the guards
+            .layer(middleware::from_fn(localize_errors))
             .with_state(app_state)
             .layer(cors) // Add CORS config to our Axum router
             .layer(
@@ -67,11 +217,17 @@ impl Application {
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response),
-            );
+            )
+            // Outermost, so the request ID is set before the trace span is created
+            .layer(middleware::from_fn(propagate_request_id));
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Connection info gives the client IP recorded in audit events
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         // Create a new Application instance and return it
         Ok(Application { server, address })
@@ -89,34 +245,65 @@ pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new().max_connections(5).connect(url).await
 }
 
+/// Create a new SQLite connection pool, creating the database file if it does not exist
+pub async fn get_sqlite_pool(url: &str) -> Result<SqlitePool, sqlx::Error> {
+    let options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        // Lets readers proceed while a write is in progress
+        .journal_mode(SqliteJournalMode::Wal);
+    SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect_with(options)
+        .await
+}
+
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
 }
 
-// TODO:
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
+    /// Message in the locale the request asked for
     pub error: String,
+    /// Stable identifier of the error, for clients to match on
+    pub code: String,
 }
 
+// The message is English here and translated by `localize_errors`
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
-            AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
-            AuthAPIError::IncorrectCredentials => {
-                (StatusCode::UNAUTHORIZED, "Incorrect credentials")
-            }
-            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
-            AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
-            AuthAPIError::UnexpectedError(_) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
-            }
+        let status = match self {
+            AuthAPIError::UserAlreadyExists => StatusCode::CONFLICT,
+            AuthAPIError::InvalidCredentials => StatusCode::BAD_REQUEST,
+            AuthAPIError::IncorrectCredentials => StatusCode::UNAUTHORIZED,
+            AuthAPIError::MissingToken => StatusCode::BAD_REQUEST,
+            AuthAPIError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AuthAPIError::InvalidInput => StatusCode::BAD_REQUEST,
+            AuthAPIError::UserNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::RoleNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::OrganizationNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::InvitationNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::EmailNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::SuppressionNotFound => StatusCode::NOT_FOUND,
+            AuthAPIError::Forbidden => StatusCode::FORBIDDEN,
+            AuthAPIError::AccountSuspended => StatusCode::FORBIDDEN,
+            AuthAPIError::AccountLocked => StatusCode::LOCKED,
+            AuthAPIError::AccountPendingVerification => StatusCode::FORBIDDEN,
+            AuthAPIError::PasswordResetRequired => StatusCode::FORBIDDEN,
+            AuthAPIError::PhoneNotVerified => StatusCode::CONFLICT,
+            AuthAPIError::SmsUnavailable => StatusCode::NOT_IMPLEMENTED,
+            AuthAPIError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            AuthAPIError::EmailSuppressed => StatusCode::CONFLICT,
+            AuthAPIError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
+        let code = self.code();
         let body = Json(ErrorResponse {
-            error: error_message.to_string(),
+            error: error_message(Locale::default(), code),
+            code: code.to_owned(),
         });
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(ErrorCode(code));
+        response
     }
 }
@@ -1,48 +1,208 @@
-use std::sync::Arc;
+use std::{env, sync::Arc};
 
 use auth_service::{
-    app_state::state::AppState,
+    app_state::state::{
+        AppState, BannedTokenStoreType, EmailClientType, EmailOutboxType,
+        EmailSuppressionStoreType, TwoFACodeStoreType, UserStoreType,
+    },
     domain::{
-        data_stores::{configure_postgresql, configure_postmark_email_client, configure_redis},
+        data_stores::{
+            configure_http_sms_client, configure_postgresql, configure_postmark_email_client,
+            configure_redis, configure_smtp_email_client, configure_sqlite,
+            HashsetBannedTokenStore,
+        },
+        hashmap_two_fa_code_store::HashMapTwoFACodeStore,
         redis_two_fa_code_store::RedisTwoFACodeStore,
     },
-    services::{data_stores::PostgresUserStore, redis_banned_token_store::RedisBannedTokenStore},
-    utils::{constants::prod, tracing::init_tracing},
+    services::{
+        caching_user_store::CachingUserStore,
+        capturing_email_client::CapturingEmailClient,
+        data_stores::PostgresUserStore,
+        email_outbox_worker::{EmailOutboxWorker, OutboxWorkerSettings},
+        expired_entry_evictor::ExpiredEntryEvictor,
+        expired_row_sweeper::ExpiredRowSweeper,
+        failover_email_client::{CircuitBreakerSettings, FailoverEmailClient},
+        lru_user_cache::LruUserCache,
+        postgres_audit_store::PostgresAuditStore,
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_email_outbox::PostgresEmailOutbox,
+        postgres_email_suppression_store::PostgresEmailSuppressionStore,
+        postgres_organization_store::PostgresOrganizationStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore,
+        redis_banned_token_store::RedisBannedTokenStore,
+        redis_user_cache::RedisUserCache,
+        sqlite_user_store::SqliteUserStore,
+        suppressing_email_client::SuppressingEmailClient,
+    },
+    utils::{
+        basic_auth::BasicCredentials,
+        constants::{
+            prod, EmailClientBackend, Profile, StoreBackend, UserCacheBackend, BANNED_TOKEN_STORE,
+            EMAIL_CLIENTS, IN_MEMORY_FLAG, POSTMARK_WEBHOOK_PASSWORD, POSTMARK_WEBHOOK_USERNAME,
+            PROFILE, SMS_PROVIDER_URL, SQLITE_DATABASE_URL, TWO_FA_CODE_STORE, USER_CACHE,
+            USER_CACHE_CAPACITY, USER_CACHE_TTL,
+        },
+        tracing::init_tracing,
+    },
     Application,
 };
-use tokio::sync::RwLock;
+use tokio::sync::OnceCell;
 
 async fn init() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
-    // Redis and PostgreSQL
-    let redis_connection = Arc::new(RwLock::new(configure_redis()));
+
+    let profile = if env::args().any(|arg| arg == IN_MEMORY_FLAG) {
+        Profile::InMemory
+    } else {
+        *PROFILE
+    };
+    let app_state = match profile {
+        Profile::Production => production_state().await,
+        Profile::InMemory => {
+            tracing::warn!("Running in memory: nothing is persisted and no email is delivered");
+            #[cfg(feature = "dev-mailbox")]
+            tracing::info!("Captured emails are shown at /dev/mailbox");
+            in_memory_state()
+        }
+    };
+
+    let app = Application::build(app_state, prod::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
+
+    app.run().await.expect("Failed to run app");
+}
+
+fn in_memory_state() -> AppState {
+    let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+    let two_fa_code_store = Arc::new(HashMapTwoFACodeStore::default());
+    ExpiredEntryEvictor::new(
+        vec![banned_token_store.clone(), two_fa_code_store.clone()],
+        prod::SWEEP_INTERVAL,
+    )
+    .spawn();
+
+    let mailbox = Arc::new(CapturingEmailClient::default());
+    AppState {
+        banned_token_store,
+        two_fa_code_store,
+        ..AppState::in_memory(mailbox.clone())
+    }
+    .with_mailbox(mailbox)
+}
+
+async fn production_state() -> AppState {
+    // PostgreSQL, and Redis only once a store needs it
     let pool = configure_postgresql().await;
+    let redis_connection = OnceCell::new();
 
     // Store initializations
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pool)));
-    let email_client = Arc::new(RwLock::new(configure_postmark_email_client()));
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-        redis_connection.clone(),
-    )));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-
-    // Old hashmap-based stores
-    // let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::new()));
-    // let two_fa_code_store = Arc::new(RwLock::new(HashMapTwoFACodeStore::default()));
-
-    let app_state: AppState = AppState::new(
+    let user_store: UserStoreType = match SQLITE_DATABASE_URL.as_deref() {
+        Some(url) => Arc::new(SqliteUserStore::new(configure_sqlite(url).await)),
+        None => Arc::new(PostgresUserStore::new(pool.clone())),
+    };
+    let user_store: UserStoreType = match *USER_CACHE {
+        UserCacheBackend::None => user_store,
+        UserCacheBackend::Memory => Arc::new(CachingUserStore::new(
+            user_store,
+            Arc::new(LruUserCache::new(*USER_CACHE_CAPACITY, *USER_CACHE_TTL)),
+        )),
+        UserCacheBackend::Redis => Arc::new(CachingUserStore::new(
+            user_store,
+            Arc::new(RedisUserCache::new(
+                redis_connection.get_or_init(configure_redis).await.clone(),
+                *USER_CACHE_TTL,
+            )),
+        )),
+    };
+    let organization_store = Arc::new(PostgresOrganizationStore::new(pool.clone()));
+    let audit_store = Arc::new(PostgresAuditStore::new(pool.clone()));
+    let (email_client, email_providers): (EmailClientType, _) = match EMAIL_CLIENTS.as_slice() {
+        [backend] => (configure_email_client(*backend), None),
+        backends => {
+            let failover = Arc::new(backends.iter().fold(
+                FailoverEmailClient::new(CircuitBreakerSettings::configured()),
+                |failover, backend| {
+                    failover.with_provider(backend.as_str(), configure_email_client(*backend))
+                },
+            ));
+            (failover.clone(), Some(failover))
+        }
+    };
+    // Requests only write to the outbox, the worker talks to the provider
+    let email_outbox: EmailOutboxType = Arc::new(PostgresEmailOutbox::new(pool.clone()));
+    let outbox_worker = EmailOutboxWorker::new(
+        email_outbox.clone(),
+        email_client,
+        OutboxWorkerSettings::configured(),
+    );
+    let email_client: EmailClientType = Arc::new(outbox_worker.outbox_client());
+    outbox_worker.spawn();
+    // Emails to addresses that bounced or complained are dropped before the outbox
+    let email_suppression_store: EmailSuppressionStoreType =
+        Arc::new(PostgresEmailSuppressionStore::new(pool.clone()));
+    let email_client: EmailClientType = Arc::new(SuppressingEmailClient::new(
+        email_client,
+        email_suppression_store.clone(),
+    ));
+    let banned_token_store: BannedTokenStoreType = match *BANNED_TOKEN_STORE {
+        StoreBackend::Redis => Arc::new(RedisBannedTokenStore::new(
+            redis_connection.get_or_init(configure_redis).await.clone(),
+        )),
+        StoreBackend::Postgres => Arc::new(PostgresBannedTokenStore::new(pool.clone())),
+    };
+    let two_fa_code_store: TwoFACodeStoreType = match *TWO_FA_CODE_STORE {
+        StoreBackend::Redis => Arc::new(RedisTwoFACodeStore::new(
+            redis_connection.get_or_init(configure_redis).await.clone(),
+        )),
+        StoreBackend::Postgres => Arc::new(PostgresTwoFACodeStore::new(pool.clone())),
+    };
+    if [*BANNED_TOKEN_STORE, *TWO_FA_CODE_STORE].contains(&StoreBackend::Postgres) {
+        ExpiredRowSweeper::new(pool.clone(), prod::SWEEP_INTERVAL).spawn();
+    }
+
+    let app_state = AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
         email_client,
+        organization_store,
+        audit_store,
+        email_outbox,
+        email_suppression_store,
     );
+    let app_state = match email_providers {
+        Some(failover) => app_state.with_email_providers(failover),
+        None => app_state,
+    };
+    let app_state = match (
+        POSTMARK_WEBHOOK_USERNAME.as_ref(),
+        POSTMARK_WEBHOOK_PASSWORD.as_ref(),
+    ) {
+        (Some(username), Some(password)) => app_state
+            .with_postmark_webhook(BasicCredentials::new(username.clone(), password.clone())),
+        _ => {
+            tracing::warn!("Postmark webhook credentials are not set, bounces are not handled");
+            app_state
+        }
+    };
+    match SMS_PROVIDER_URL.as_ref() {
+        Some(url) => {
+            app_state.with_message_client(Arc::new(configure_http_sms_client(url.clone())))
+        }
+        None => {
+            tracing::info!("SMS_PROVIDER_URL is not set, 2FA codes are only sent by email");
+            app_state
+        }
+    }
+}
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
-        .await
-        .expect("Failed to build app");
-
-    app.run().await.expect("Failed to run app");
+fn configure_email_client(backend: EmailClientBackend) -> EmailClientType {
+    match backend {
+        EmailClientBackend::Postmark => Arc::new(configure_postmark_email_client()),
+        EmailClientBackend::Smtp => Arc::new(configure_smtp_email_client()),
+    }
 }
 
 #[tokio::main]
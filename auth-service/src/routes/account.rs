@@ -0,0 +1,574 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::{TwoFACode, UserStoreError},
+        email::Email,
+        email_client::RecipientSuppressed,
+        error::AuthAPIError,
+        locale::Locale,
+        organization::OrganizationId,
+        parse::Parseable,
+        password::Password,
+        phone_number::PhoneNumber,
+        user::{AccountStatus, StatusChange, TwoFactorChannel, User},
+    },
+    services::email_templates::{EmailTemplate, SecurityEvent, EMAIL_TEMPLATES},
+    utils::{
+        audit::AuditRecorder,
+        auth::{
+            generate_auth_cookie, generate_phone_verification, generate_security_report_token,
+            validate_phone_verification, validate_security_report_token, Claims, TokenHash,
+        },
+        constants::{
+            JWT_COOKIE_NAME, MAX_PHONE_VERIFICATION_ATTEMPTS, SECURITY_REPORT_URL,
+            TWO_FA_CODE_TTL_SECONDS,
+        },
+        i18n::RequestLocale,
+    },
+};
+
+/// Sets the language the caller's emails are sent in.
+/// `null` makes them follow the language of the caller's requests again.
+#[tracing::instrument(name = "Set locale", skip_all)]
+pub async fn set_locale(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<SetLocaleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::LocaleChanged));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let locale = request
+        .locale
+        .map(|locale| locale.parse::<Locale>())
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+
+    match state.user_store.set_locale(&email, locale).await {
+        Ok(()) => Ok((
+            StatusCode::OK,
+            Json(LocaleResponse {
+                locale: locale.map(|locale| locale.as_str().to_owned()),
+            }),
+        )),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetLocaleRequest {
+    pub locale: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LocaleResponse {
+    pub locale: Option<String>,
+}
+
+/// Replaces the caller's password. The current one must be given again,
+/// so a stolen session alone cannot take over the account.
+/// Every other session ends, and the caller gets a new cookie.
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
+    jar: CookieJar,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::PasswordChanged));
+    let organization = claims.org.and_then(|id| OrganizationId::parse(id).ok());
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let new_password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidInput)?;
+    let user = confirm_password(&state, &email, request.current_password).await?;
+
+    state
+        .user_store
+        .set_password(&email, &new_password)
+        .await
+        .map_err(into_api_error)?;
+    // Revoked a millisecond back, so the caller's new token is not caught too
+    state
+        .banned_token_store
+        .revoke_sessions(&email, Utc::now().timestamp_millis() - 1)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let roles = state
+        .user_store
+        .get_roles(&email)
+        .await
+        .map_err(into_api_error)?;
+    let cookie = generate_auth_cookie(&email, &roles, organization.as_ref())
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    send_security_alert(
+        &state,
+        &email,
+        &user,
+        SecurityEvent::PasswordChanged,
+        client_ip(connect_info),
+        locale.with_preference(user.locale),
+    )
+    .await;
+    Ok((jar.add(cookie), StatusCode::NO_CONTENT))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+/// Turns two-factor authentication on or off for the caller
+#[tracing::instrument(name = "Set two-factor auth", skip_all)]
+pub async fn set_two_factor(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
+    Json(request): Json<SetTwoFactorRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (action, event) = match request.enabled {
+        true => (
+            AuditAction::TwoFactorEnabled,
+            SecurityEvent::TwoFactorEnabled,
+        ),
+        false => (
+            AuditAction::TwoFactorDisabled,
+            SecurityEvent::TwoFactorDisabled,
+        ),
+    };
+    audit.record(AuditEvent::new(&claims.sub, action));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let user = confirm_password(&state, &email, request.password).await?;
+
+    // Repeating the current setting is not worth an email
+    if user.requires_2fa != request.enabled {
+        state
+            .user_store
+            .set_requires_2fa(&email, request.enabled)
+            .await
+            .map_err(into_api_error)?;
+        send_security_alert(
+            &state,
+            &email,
+            &user,
+            event,
+            client_ip(connect_info),
+            locale.with_preference(user.locale),
+        )
+        .await;
+    }
+    Ok((
+        StatusCode::OK,
+        Json(TwoFactorResponse {
+            enabled: request.enabled,
+        }),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct SetTwoFactorRequest {
+    pub enabled: bool,
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TwoFactorResponse {
+    pub enabled: bool,
+}
+
+/// Moves the caller's account to another address. The old address is told,
+/// since it is the only one the real owner may still read. Every session ends,
+/// including the caller's, and the next login uses the new address.
+#[tracing::instrument(name = "Change email", skip_all)]
+pub async fn change_email(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
+    jar: CookieJar,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let new_email = Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidInput)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::EmailChanged).with_target(&new_email));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    if new_email == email {
+        return Err(AuthAPIError::InvalidInput);
+    }
+    let mut user = confirm_password(&state, &email, request.password).await?;
+
+    state
+        .user_store
+        .change_email(&email, &new_email)
+        .await
+        .map_err(into_api_error)?;
+    // Tokens for the old address must not come back to life if it signs up again
+    state
+        .banned_token_store
+        .revoke_sessions(&email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    user.email = new_email;
+    send_security_alert(
+        &state,
+        &email,
+        &user,
+        SecurityEvent::EmailChanged,
+        client_ip(connect_info),
+        locale.with_preference(user.locale),
+    )
+    .await;
+    Ok((jar.remove(JWT_COOKIE_NAME), StatusCode::NO_CONTENT))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: Secret<String>,
+    pub password: Secret<String>,
+}
+
+/// Opts the caller in or out of notices about new logins and 2FA being
+/// turned on. Changes that could lock them out are always reported.
+#[tracing::instrument(name = "Set notifications", skip_all)]
+pub async fn set_notifications(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<NotificationsRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::NotificationsChanged,
+    ));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    state
+        .user_store
+        .set_security_notices(&email, request.security_notices)
+        .await
+        .map_err(into_api_error)?;
+    Ok((StatusCode::OK, Json(request)))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NotificationsRequest {
+    #[serde(rename = "securityNotices")]
+    pub security_notices: bool,
+}
+
+/// Sets or removes the caller's phone number. A new number is texted a code
+/// and only receives 2FA codes once [`verify_phone_number`] confirms it.
+/// Until then, and after removal, 2FA codes go to the caller's email.
+#[tracing::instrument(name = "Set phone number", skip_all)]
+pub async fn set_phone_number(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    locale: RequestLocale,
+    Json(request): Json<SetPhoneNumberRequest>,
+) -> Result<axum::response::Response, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::PhoneNumberChanged,
+    ));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let phone_number = request
+        .phone_number
+        .map(PhoneNumber::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+    let user = confirm_password(&state, &email, request.password).await?;
+
+    let Some(phone_number) = phone_number else {
+        state
+            .user_store
+            .set_phone_number(&email, None)
+            .await
+            .map_err(into_api_error)?;
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    };
+    let message_client = state
+        .message_client
+        .as_ref()
+        .ok_or(AuthAPIError::SmsUnavailable)?;
+    state
+        .user_store
+        .set_phone_number(&email, Some(&phone_number))
+        .await
+        .map_err(into_api_error)?;
+
+    let (verification_id, code) = generate_phone_verification(&email, &phone_number)
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let text = EMAIL_TEMPLATES
+        .render_text_message(
+            &EmailTemplate::TwoFACode {
+                code: code.as_ref().expose_secret().to_owned(),
+                expires_in_minutes: TWO_FA_CODE_TTL_SECONDS / 60,
+            },
+            locale.with_preference(user.locale),
+        )
+        .map_err(AuthAPIError::UnexpectedError)?;
+    message_client
+        .send_message(&phone_number, &text)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(PhoneVerificationResponse { verification_id }),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub struct SetPhoneNumberRequest {
+    #[serde(rename = "phoneNumber")]
+    pub phone_number: Option<Secret<String>>,
+    pub password: Secret<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct PhoneVerificationResponse {
+    #[serde(rename = "verificationId")]
+    pub verification_id: String,
+}
+
+/// Confirms the caller received the code texted by [`set_phone_number`].
+/// After a few wrong codes, a new code has to be texted before trying again.
+#[tracing::instrument(name = "Verify phone number", skip_all)]
+pub async fn verify_phone_number(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<VerifyPhoneNumberRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::PhoneNumberVerified,
+    ));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(into_api_error)?;
+    // The number may have changed since the code was sent
+    let phone_number = user.phone_number.ok_or(AuthAPIError::InvalidInput)?;
+
+    // Counted before the code is checked, so parallel guesses share the limit
+    let attempts = state
+        .user_store
+        .record_phone_verification_attempt(&email)
+        .await
+        .map_err(into_api_error)?;
+    if attempts > MAX_PHONE_VERIFICATION_ATTEMPTS {
+        return Err(AuthAPIError::TooManyAttempts);
+    }
+    if validate_phone_verification(&request.verification_id, &email, &phone_number, &code).is_err()
+    {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    state
+        .user_store
+        .set_phone_verified(&email, true)
+        .await
+        .map_err(into_api_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPhoneNumberRequest {
+    #[serde(rename = "verificationId")]
+    pub verification_id: Secret<String>,
+    pub code: String,
+}
+
+/// Chooses where the caller's 2FA codes are sent. SMS needs a verified
+/// phone number and a configured SMS provider.
+#[tracing::instrument(name = "Set two-factor channel", skip_all)]
+pub async fn set_two_fa_channel(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<TwoFactorChannelRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::TwoFactorChannelChanged,
+    ));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let channel = request
+        .channel
+        .parse::<TwoFactorChannel>()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+
+    if channel == TwoFactorChannel::Sms {
+        if state.message_client.is_none() {
+            return Err(AuthAPIError::SmsUnavailable);
+        }
+        let user = state
+            .user_store
+            .get_user(&email)
+            .await
+            .map_err(into_api_error)?;
+        if !user.phone_verified {
+            return Err(AuthAPIError::PhoneNotVerified);
+        }
+    }
+    state
+        .user_store
+        .set_two_fa_channel(&email, channel)
+        .await
+        .map_err(into_api_error)?;
+    Ok((StatusCode::OK, Json(request)))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TwoFactorChannelRequest {
+    pub channel: String,
+}
+
+/// Target of the "this wasn't me" link in security alerts. Needs no session,
+/// since whoever made the change may hold every one of them.
+/// Locks the account and ends all of its sessions until an admin steps in.
+/// Each link works once, so it cannot lock the account again after an unlock.
+#[tracing::instrument(name = "Report account", skip_all)]
+pub async fn report_account(
+    State(state): State<AppState>,
+    audit: AuditRecorder,
+    Json(request): Json<ReportAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, remaining_lifetime) =
+        validate_security_report_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::AccountReported,
+    ));
+    let token_hash = TokenHash::of(&request.token);
+    match state.banned_token_store.token_exists(&token_hash).await {
+        Ok(false) => {}
+        Ok(true) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let change = StatusChange::new(AccountStatus::Locked, email.as_ref().expose_secret())
+        .with_reason(Some(
+            "Reported by the owner from a security alert".to_owned(),
+        ));
+    state
+        .user_store
+        .set_status(&email, &change)
+        .await
+        .map_err(into_api_error)?;
+    state
+        .banned_token_store
+        .revoke_sessions(&email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
+        .banned_token_store
+        .insert(&token_hash, remaining_lifetime)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct ReportAccountRequest {
+    pub token: Secret<String>,
+}
+
+/// Emails `to` about a change to the account of `user`, with a link that locks
+/// the account in case the user did not make it. Notices the user turned off
+/// are skipped. The change has already happened, so failures are only logged.
+pub(crate) async fn send_security_alert(
+    state: &AppState,
+    to: &Email,
+    user: &User,
+    event: SecurityEvent,
+    ip: Option<String>,
+    locale: Locale,
+) {
+    if !event.is_critical() && !user.security_notices {
+        return;
+    }
+    let result = async {
+        let token = generate_security_report_token(&user.email)?;
+        let message = EMAIL_TEMPLATES.render(
+            &EmailTemplate::SecurityAlert {
+                event,
+                occurred_at: Utc::now(),
+                ip,
+                wasnt_me_link: Some(format!("{}?token={}", *SECURITY_REPORT_URL, token)),
+            },
+            locale,
+        )?;
+        state.email_client.send_email(to, &message).await
+    }
+    .await;
+    match result {
+        Ok(()) => {}
+        Err(e) if RecipientSuppressed::caused(&e) => {
+            tracing::warn!(
+                event = event.as_str(),
+                "Security alert not sent, address suppressed"
+            )
+        }
+        Err(e) => {
+            tracing::error!(error = ?e, event = event.as_str(), "Failed to send security alert")
+        }
+    }
+}
+
+pub(crate) fn client_ip(connect_info: Option<ConnectInfo<SocketAddr>>) -> Option<String> {
+    connect_info.map(|ConnectInfo(address)| address.ip().to_string())
+}
+
+async fn confirm_password(
+    state: &AppState,
+    email: &Email,
+    password: Secret<String>,
+) -> Result<User, AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    state
+        .user_store
+        .validate_user(email, &password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::IncorrectCredentials,
+            e => into_api_error(e),
+        })
+}
+
+fn into_api_error(error: UserStoreError) -> AuthAPIError {
+    match error {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
@@ -0,0 +1,147 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::{EmailSuppressionStoreError, SuppressionQuery},
+        email::Email,
+        email_suppression::EmailSuppression,
+        error::AuthAPIError,
+        organization::TenantScope,
+        parse::Parseable,
+    },
+    utils::{audit::AuditRecorder, auth::Claims},
+};
+
+use super::emails::ensure_global;
+
+/// Addresses that emails are no longer sent to, most recently suppressed first
+#[tracing::instrument(name = "List email suppressions", skip_all)]
+pub async fn list_email_suppressions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Query(params): Query<ListEmailSuppressionsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::EmailSuppressionsListed,
+    ));
+    ensure_global(scope)?;
+    let query = SuppressionQuery::new(params.page, params.per_page);
+    let page = state
+        .email_suppression_store
+        .list(&query)
+        .await
+        .map_err(suppression_api_error)?;
+
+    let response = ListEmailSuppressionsResponse {
+        suppressions: page
+            .suppressions
+            .into_iter()
+            .map(EmailSuppressionResponse::from)
+            .collect(),
+        page: query.page,
+        per_page: query.per_page,
+        total: page.total,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "View email suppression", skip_all)]
+pub async fn get_email_suppression(
+    State(state): State<AppState>,
+    Extension(scope): Extension<TenantScope>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    ensure_global(scope)?;
+    let email = parse_email(email)?;
+    let suppression = state
+        .email_suppression_store
+        .get(&email)
+        .await
+        .map_err(suppression_api_error)?
+        .ok_or(AuthAPIError::SuppressionNotFound)?;
+    Ok((
+        StatusCode::OK,
+        Json(EmailSuppressionResponse::from(suppression)),
+    ))
+}
+
+/// Sends to the address again, e.g. once the user fixed their mailbox
+#[tracing::instrument(name = "Lift email suppression", skip_all)]
+pub async fn lift_email_suppression(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(
+        AuditEvent::new(&claims.sub, AuditAction::EmailSuppressionLifted).with_target(&email),
+    );
+    ensure_global(scope)?;
+    state
+        .email_suppression_store
+        .remove(&email)
+        .await
+        .map_err(suppression_api_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn parse_email(email: String) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(email)).map_err(|_| AuthAPIError::InvalidInput)
+}
+
+fn suppression_api_error(e: EmailSuppressionStoreError) -> AuthAPIError {
+    match e {
+        EmailSuppressionStoreError::SuppressionNotFound => AuthAPIError::SuppressionNotFound,
+        EmailSuppressionStoreError::UnexpectedError(e) => AuthAPIError::UnexpectedError(e),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListEmailSuppressionsParams {
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListEmailSuppressionsResponse {
+    pub suppressions: Vec<EmailSuppressionResponse>,
+    pub page: u32,
+    #[serde(rename = "perPage")]
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EmailSuppressionResponse {
+    pub email: String,
+    pub reason: String,
+    pub description: Option<String>,
+    #[serde(rename = "suppressedAt")]
+    pub suppressed_at: String,
+}
+
+impl From<EmailSuppression> for EmailSuppressionResponse {
+    fn from(suppression: EmailSuppression) -> Self {
+        Self {
+            email: suppression.email.as_ref().expose_secret().to_owned(),
+            reason: suppression.reason.to_string(),
+            description: suppression.description,
+            suppressed_at: suppression.suppressed_at.to_rfc3339(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::{EmailOutboxError, OutboxQuery},
+        email_outbox::{OutboxMessage, OutboxMessageId, OutboxStatus},
+        error::AuthAPIError,
+        organization::TenantScope,
+        parse::Parseable,
+    },
+    services::failover_email_client::ProviderHealth,
+    utils::{audit::AuditRecorder, auth::Claims},
+};
+
+/// Emails in the outbox, newest first, optionally only those with one status.
+/// Bodies are never returned, since they may hold 2FA codes.
+#[tracing::instrument(name = "List emails", skip_all)]
+pub async fn list_emails(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Query(params): Query<ListEmailsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::EmailsListed));
+    ensure_global(scope)?;
+    let status = params
+        .status
+        .map(|status| status.parse::<OutboxStatus>())
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+    let query = OutboxQuery::new(params.page, params.per_page).with_status(status);
+    let page = state
+        .email_outbox
+        .list(&query)
+        .await
+        .map_err(outbox_api_error)?;
+
+    let response = ListEmailsResponse {
+        emails: page
+            .messages
+            .into_iter()
+            .map(OutboxEmailResponse::from)
+            .collect(),
+        page: query.page,
+        per_page: query.per_page,
+        total: page.total,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Gives a dead-lettered email a fresh set of attempts
+#[tracing::instrument(name = "Retry email", skip_all)]
+pub async fn retry_email(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::EmailRetried));
+    ensure_global(scope)?;
+    let id = OutboxMessageId::parse(id).map_err(|_| AuthAPIError::EmailNotFound)?;
+    let email_outbox = &state.email_outbox;
+    email_outbox.requeue(&id).await.map_err(outbox_api_error)?;
+    let message = email_outbox.get(&id).await.map_err(outbox_api_error)?;
+    Ok((StatusCode::OK, Json(OutboxEmailResponse::from(message))))
+}
+
+/// How each email provider is doing, in order of priority, as seen by the
+/// instance that answers. Empty unless emails fail over between providers.
+#[tracing::instrument(name = "List email providers", skip_all)]
+pub async fn list_email_providers(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::EmailProvidersViewed,
+    ));
+    ensure_global(scope)?;
+    let providers = state
+        .email_providers
+        .map(|failover| failover.health())
+        .unwrap_or_default();
+    let response = ListEmailProvidersResponse {
+        providers: providers
+            .into_iter()
+            .map(EmailProviderResponse::from)
+            .collect(),
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Turns away admins scoped to an organization, for data and changes that
+/// reach beyond its members, such as the outbox or the accounts themselves
+pub(super) fn ensure_global(scope: TenantScope) -> Result<(), AuthAPIError> {
+    match scope {
+        TenantScope::Global => Ok(()),
+        TenantScope::Organization(_) => Err(AuthAPIError::Forbidden),
+    }
+}
+
+fn outbox_api_error(e: EmailOutboxError) -> AuthAPIError {
+    match e {
+        EmailOutboxError::MessageNotFound => AuthAPIError::EmailNotFound,
+        EmailOutboxError::NotDeadLettered => AuthAPIError::InvalidInput,
+        EmailOutboxError::UnexpectedError(e) => AuthAPIError::UnexpectedError(e),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListEmailsParams {
+    pub status: Option<String>,
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListEmailsResponse {
+    pub emails: Vec<OutboxEmailResponse>,
+    pub page: u32,
+    #[serde(rename = "perPage")]
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct OutboxEmailResponse {
+    pub id: String,
+    pub recipient: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: u32,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "sentAt")]
+    pub sent_at: Option<String>,
+}
+
+impl From<OutboxMessage> for OutboxEmailResponse {
+    fn from(message: OutboxMessage) -> Self {
+        Self {
+            id: message.id.to_string(),
+            recipient: message.recipient.as_ref().expose_secret().to_owned(),
+            subject: message.message.subject,
+            status: message.status.to_string(),
+            attempts: message.attempts,
+            last_error: message.last_error,
+            next_attempt_at: message.next_attempt_at.to_rfc3339(),
+            created_at: message.created_at.to_rfc3339(),
+            sent_at: message.sent_at.map(|sent_at| sent_at.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListEmailProvidersResponse {
+    pub providers: Vec<EmailProviderResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct EmailProviderResponse {
+    pub name: String,
+    /// `closed` while in use, `open` while skipped, `half_open` while being tried again
+    pub state: String,
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+}
+
+impl From<ProviderHealth> for EmailProviderResponse {
+    fn from(health: ProviderHealth) -> Self {
+        Self {
+            name: health.name,
+            state: health.state.to_string(),
+            consecutive_failures: health.consecutive_failures,
+            successes: health.successes,
+            failures: health.failures,
+            last_error: health.last_error,
+        }
+    }
+}
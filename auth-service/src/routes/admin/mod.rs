@@ -0,0 +1,10 @@
+mod email_suppressions;
+mod emails;
+mod roles;
+mod users;
+
+// re-export items from sub-modules
+pub use email_suppressions::*;
+pub use emails::*;
+pub use roles::*;
+pub use users::*;
@@ -0,0 +1,150 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use secrecy::Secret;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::UserStoreError,
+        email::Email,
+        error::AuthAPIError,
+        organization::{OrganizationId, TenantScope},
+        parse::Parseable,
+        role::Role,
+    },
+    routes::organizations::{ensure_organization_role, organization_api_error},
+    utils::{audit::AuditRecorder, auth::Claims},
+};
+
+use super::users::ensure_in_scope;
+
+/// Grants a global role, or a role inside the organization the request is scoped to
+#[tracing::instrument(name = "Assign role", skip_all)]
+pub async fn assign_role(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Json(request): Json<RoleAssignmentRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, role) = request.parse()?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::RoleAssigned).with_target(&email));
+
+    let roles = match scope {
+        TenantScope::Global => {
+            let user_store = &state.user_store;
+            user_store
+                .assign_role(&email, &role)
+                .await
+                .map_err(into_api_error)?;
+            user_store.get_roles(&email).await.map_err(into_api_error)?
+        }
+        TenantScope::Organization(id) => {
+            ensure_in_scope(&state, scope, &email).await?;
+            ensure_organization_role(&state, &role).await?;
+            let organization_store = &state.organization_store;
+            organization_store
+                .add_member(&id, &email, &[role])
+                .await
+                .map_err(organization_api_error)?;
+            organization_store
+                .get_member_roles(&id, &email)
+                .await
+                .map_err(organization_api_error)?
+        }
+    };
+
+    Ok((StatusCode::OK, Json(RolesResponse::new(roles))))
+}
+
+/// Revokes a global role, or a role inside the organization the request is scoped to
+#[tracing::instrument(name = "Revoke role", skip_all)]
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Json(request): Json<RoleAssignmentRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, role) = request.parse()?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::RoleRevoked).with_target(&email));
+
+    let roles = match scope {
+        TenantScope::Global => {
+            let user_store = &state.user_store;
+            user_store
+                .revoke_role(&email, &role)
+                .await
+                .map_err(into_api_error)?;
+            user_store.get_roles(&email).await.map_err(into_api_error)?
+        }
+        TenantScope::Organization(id) => {
+            ensure_member_and_role_exist(&state, &id, &email, &role).await?;
+            let organization_store = &state.organization_store;
+            organization_store
+                .revoke_member_role(&id, &email, &role)
+                .await
+                .map_err(organization_api_error)?;
+            organization_store
+                .get_member_roles(&id, &email)
+                .await
+                .map_err(organization_api_error)?
+        }
+    };
+
+    Ok((StatusCode::OK, Json(RolesResponse::new(roles))))
+}
+
+async fn ensure_member_and_role_exist(
+    state: &AppState,
+    id: &OrganizationId,
+    email: &Email,
+    role: &Role,
+) -> Result<(), AuthAPIError> {
+    ensure_in_scope(state, TenantScope::Organization(*id), email).await?;
+    let exists = state
+        .user_store
+        .role_exists(role)
+        .await
+        .map_err(into_api_error)?;
+    if !exists {
+        return Err(AuthAPIError::RoleNotFound);
+    }
+    Ok(())
+}
+
+pub(super) fn into_api_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        UserStoreError::RoleNotFound => AuthAPIError::RoleNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RoleAssignmentRequest {
+    pub email: Secret<String>,
+    pub role: String,
+}
+
+impl RoleAssignmentRequest {
+    fn parse(self) -> Result<(Email, Role), AuthAPIError> {
+        let email = Email::parse(self.email).map_err(|_| AuthAPIError::InvalidInput)?;
+        let role = Role::parse(self.role).map_err(|_| AuthAPIError::InvalidInput)?;
+        Ok((email, role))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RolesResponse {
+    pub roles: Vec<String>,
+}
+
+impl RolesResponse {
+    fn new(roles: Vec<Role>) -> Self {
+        Self {
+            roles: roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+        }
+    }
+}
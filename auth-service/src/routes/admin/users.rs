@@ -0,0 +1,343 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::UserQuery,
+        email::Email,
+        error::AuthAPIError,
+        organization::TenantScope,
+        parse::Parseable,
+        user::{AccountStatus, StatusChange, UserSummary},
+    },
+    routes::{
+        organizations::organization_api_error,
+        password_reset::{log_unsent_reset_link, send_password_reset_link},
+    },
+    utils::{audit::AuditRecorder, auth::Claims, i18n::RequestLocale},
+};
+
+use super::{emails::ensure_global, roles::into_api_error};
+
+#[tracing::instrument(name = "List users", skip_all)]
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Query(params): Query<ListUsersParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::UsersListed));
+    let mut query = UserQuery::new(params.search, params.page, params.per_page);
+    if let TenantScope::Organization(id) = scope {
+        let members = state
+            .organization_store
+            .list_members(&id)
+            .await
+            .map_err(organization_api_error)?;
+        query = query.restricted_to(members);
+    }
+    let page = state
+        .user_store
+        .list_users(&query)
+        .await
+        .map_err(into_api_error)?;
+
+    let response = ListUsersResponse {
+        users: page.users.into_iter().map(UserResponse::from).collect(),
+        page: query.page,
+        per_page: query.per_page,
+        total: page.total,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "View user", skip_all)]
+pub async fn get_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::UserViewed).with_target(&email));
+    ensure_in_scope(&state, scope, &email).await?;
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+/// Suspends the account, which also signs the user out everywhere
+#[tracing::instrument(name = "Disable user", skip_all)]
+pub async fn disable_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+    request: Option<Json<StatusReasonRequest>>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::UserDisabled).with_target(&email));
+    ensure_global(scope)?;
+    let reason = request.and_then(|Json(request)| request.reason);
+    let change =
+        StatusChange::new(AccountStatus::Suspended, claims.sub.clone()).with_reason(reason);
+    change_status(&state, &email, &change).await?;
+
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+#[tracing::instrument(name = "Enable user", skip_all)]
+pub async fn enable_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+    request: Option<Json<StatusReasonRequest>>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::UserEnabled).with_target(&email));
+    ensure_global(scope)?;
+    let reason = request.and_then(|Json(request)| request.reason);
+    let change = StatusChange::new(AccountStatus::Active, claims.sub.clone()).with_reason(reason);
+    change_status(&state, &email, &change).await?;
+
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+/// Moves the account to any status, recording the reason and the admin behind it
+#[tracing::instrument(name = "Set user status", skip_all)]
+pub async fn set_user_status(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+    Json(request): Json<SetStatusRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::StatusChanged).with_target(&email));
+    ensure_global(scope)?;
+    let status = request
+        .status
+        .parse::<AccountStatus>()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+    let change = StatusChange::new(status, claims.sub.clone()).with_reason(request.reason);
+    change_status(&state, &email, &change).await?;
+
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+/// Blocks logins until the password is changed and signs the user out everywhere.
+/// The user is emailed a link to choose a new password.
+#[tracing::instrument(name = "Force password reset", skip_all)]
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    locale: RequestLocale,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit
+        .record(AuditEvent::new(&claims.sub, AuditAction::PasswordResetForced).with_target(&email));
+    ensure_global(scope)?;
+    state
+        .user_store
+        .set_password_reset_required(&email, true)
+        .await
+        .map_err(into_api_error)?;
+    revoke_all_sessions(&state, &email).await?;
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(into_api_error)?;
+    // The reset is in place either way, and the user can ask for another link
+    if let Err(e) =
+        send_password_reset_link(&state, &user, locale.with_preference(user.locale)).await
+    {
+        log_unsent_reset_link(e);
+    }
+
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+/// Turns 2FA off and discards any pending 2FA login attempt,
+/// so a user who lost access to their second factor can sign in again.
+#[tracing::instrument(name = "Reset 2FA", skip_all)]
+pub async fn reset_2fa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::TwoFactorReset).with_target(&email));
+    ensure_global(scope)?;
+    state
+        .user_store
+        .set_requires_2fa(&email, false)
+        .await
+        .map_err(into_api_error)?;
+    state
+        .two_fa_code_store
+        .remove_code(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let summary = get_summary(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+#[tracing::instrument(name = "Revoke sessions", skip_all)]
+pub async fn revoke_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(email)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::SessionsRevoked).with_target(&email));
+    ensure_global(scope)?;
+    // Make sure the user exists before recording the revocation
+    let summary = get_summary(&state, &email).await?;
+    revoke_all_sessions(&state, &email).await?;
+    Ok((StatusCode::OK, Json(UserResponse::from(summary))))
+}
+
+/// Admins scoped to an organization only see its members.
+/// Anyone else is reported as missing.
+pub(super) async fn ensure_in_scope(
+    state: &AppState,
+    scope: TenantScope,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    match scope {
+        TenantScope::Global => Ok(()),
+        TenantScope::Organization(id) => state
+            .organization_store
+            .get_member_roles(&id, email)
+            .await
+            .map(|_| ())
+            .map_err(organization_api_error),
+    }
+}
+
+fn parse_email(email: String) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(email)).map_err(|_| AuthAPIError::InvalidInput)
+}
+
+async fn get_summary(state: &AppState, email: &Email) -> Result<UserSummary, AuthAPIError> {
+    state
+        .user_store
+        .get_user_summary(email)
+        .await
+        .map_err(into_api_error)
+}
+
+/// Leaving the active state signs the user out everywhere,
+/// so old tokens stay dead if the account is reactivated later
+async fn change_status(
+    state: &AppState,
+    email: &Email,
+    change: &StatusChange,
+) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .set_status(email, change)
+        .await
+        .map_err(into_api_error)?;
+    if !change.status.is_active() {
+        revoke_all_sessions(state, email).await?;
+    }
+    Ok(())
+}
+
+async fn revoke_all_sessions(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .banned_token_store
+        .revoke_sessions(email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StatusReasonRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SetStatusRequest {
+    pub status: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ListUsersParams {
+    pub search: Option<String>,
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ListUsersResponse {
+    pub users: Vec<UserResponse>,
+    pub page: u32,
+    #[serde(rename = "perPage")]
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct UserResponse {
+    pub email: String,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub status: String,
+    #[serde(rename = "statusReason")]
+    pub status_reason: Option<String>,
+    #[serde(rename = "statusChangedBy")]
+    pub status_changed_by: Option<String>,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+    pub roles: Vec<String>,
+}
+
+impl From<UserSummary> for UserResponse {
+    fn from(summary: UserSummary) -> Self {
+        Self {
+            email: summary.email.as_ref().expose_secret().to_owned(),
+            requires_2fa: summary.requires_2fa,
+            status: summary.status.to_string(),
+            status_reason: summary.status_reason,
+            status_changed_by: summary.status_changed_by,
+            password_reset_required: summary.password_reset_required,
+            roles: summary
+                .roles
+                .iter()
+                .map(|role| role.as_ref().to_owned())
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::{AuditQuery, AuditStoreError},
+        email::Email,
+        error::AuthAPIError,
+        organization::TenantScope,
+        parse::Parseable,
+    },
+    routes::organizations::organization_api_error,
+    utils::{audit::AuditRecorder, auth::Claims},
+};
+
+/// Events the caller performed or was the target of, newest first
+#[tracing::instrument(name = "List own audit events", skip_all)]
+pub async fn list_own_audit_events(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Query(params): Query<AuditEventsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::AuditViewed));
+    let email = Email::parse(Secret::new(claims.sub)).map_err(|_| AuthAPIError::InvalidToken)?;
+    let query = AuditQuery::new(params.page, params.per_page)
+        .with_action(parse_action(params.action)?)
+        .involving(email);
+
+    query_events(&state, query).await
+}
+
+/// The whole audit trail, optionally narrowed down to a single account.
+/// Admins scoped to an organization only see events involving its members.
+#[tracing::instrument(name = "List audit events", skip_all)]
+pub async fn list_audit_events(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    Query(params): Query<AdminAuditEventsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::AuditViewed));
+    let mut query =
+        AuditQuery::new(params.page, params.per_page).with_action(parse_action(params.action)?);
+    if let Some(email) = params.email {
+        let email = Email::parse(email).map_err(|_| AuthAPIError::InvalidInput)?;
+        query = query.involving(email);
+    }
+    if let TenantScope::Organization(id) = scope {
+        let members = state
+            .organization_store
+            .list_members(&id)
+            .await
+            .map_err(organization_api_error)?;
+        query = query.restricted_to(members);
+    }
+
+    query_events(&state, query).await
+}
+
+async fn query_events(
+    state: &AppState,
+    query: AuditQuery,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let page = state
+        .audit_store
+        .query(&query)
+        .await
+        .map_err(|AuditStoreError::UnexpectedError(e)| AuthAPIError::UnexpectedError(e))?;
+
+    let response = AuditEventsResponse {
+        events: page
+            .events
+            .into_iter()
+            .map(AuditEventResponse::from)
+            .collect(),
+        page: query.page,
+        per_page: query.per_page,
+        total: page.total,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+fn parse_action(action: Option<String>) -> Result<Option<AuditAction>, AuthAPIError> {
+    action
+        .map(|action| action.parse::<AuditAction>())
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuditEventsParams {
+    pub action: Option<String>,
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AdminAuditEventsParams {
+    pub email: Option<Secret<String>>,
+    pub action: Option<String>,
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AuditEventsResponse {
+    pub events: Vec<AuditEventResponse>,
+    pub page: u32,
+    #[serde(rename = "perPage")]
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AuditEventResponse {
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: String,
+    pub actor: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub outcome: String,
+    pub ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "requestId")]
+    pub request_id: Option<String>,
+}
+
+impl From<AuditEvent> for AuditEventResponse {
+    fn from(event: AuditEvent) -> Self {
+        Self {
+            occurred_at: event.occurred_at.to_rfc3339(),
+            actor: event.actor,
+            action: event.action.to_string(),
+            target: event
+                .target
+                .map(|target| target.as_ref().expose_secret().to_owned()),
+            outcome: event.outcome.to_string(),
+            ip: event.context.ip,
+            user_agent: event.context.user_agent,
+            request_id: event.context.request_id,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use color_eyre::eyre::eyre;
+use lazy_static::lazy_static;
+use minijinja::{context, Environment};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{email::Email, error::AuthAPIError, parse::Parseable},
+    services::capturing_email_client::{CapturedEmail, CapturingEmailClient},
+};
+
+lazy_static! {
+    static ref MAILBOX_TEMPLATE: Environment<'static> = {
+        let mut env = Environment::new();
+        // The `.html` name turns on escaping, so captured content cannot inject markup
+        env.add_template(
+            "mailbox.html",
+            include_str!("../../templates/dev/mailbox.html"),
+        )
+        .expect("Invalid mailbox template");
+        env
+    };
+}
+
+/// `GET /dev/mailbox`, serving the emails captured by `mailbox`.
+/// Only compiled with the `dev-mailbox` feature, since it shows 2FA codes
+/// and reset links to anyone who asks.
+pub fn dev_mailbox_routes(mailbox: Arc<CapturingEmailClient>) -> Router<AppState> {
+    Router::new()
+        .route("/dev/mailbox", get(get_mailbox))
+        .with_state(mailbox)
+}
+
+/// Captured emails, newest first, optionally only those sent to one address.
+/// Rendered as HTML for browsers, or as JSON with `?format=json` or an
+/// `Accept: application/json` header.
+#[tracing::instrument(name = "Dev mailbox", skip_all)]
+pub async fn get_mailbox(
+    State(mailbox): State<Arc<CapturingEmailClient>>,
+    headers: HeaderMap,
+    Query(params): Query<MailboxParams>,
+) -> Result<Response, AuthAPIError> {
+    let recipient = params
+        .to
+        .as_ref()
+        .map(|to| Email::parse(Secret::new(to.clone())))
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+    let emails: Vec<MailboxEmailResponse> = mailbox
+        .messages()
+        .into_iter()
+        .rev()
+        .filter(|email| recipient.as_ref().is_none_or(|to| &email.recipient == to))
+        .map(MailboxEmailResponse::from)
+        .collect();
+
+    if wants_json(&params, &headers) {
+        return Ok(Json(MailboxResponse { emails }).into_response());
+    }
+    let html = MAILBOX_TEMPLATE
+        .get_template("mailbox.html")
+        .and_then(|template| template.render(context! { emails, to => params.to }))
+        .map_err(|e| AuthAPIError::UnexpectedError(eyre!(e)))?;
+    Ok(Html(html).into_response())
+}
+
+fn wants_json(params: &MailboxParams, headers: &HeaderMap) -> bool {
+    if let Some(format) = &params.format {
+        return format == "json";
+    }
+    headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MailboxParams {
+    pub to: Option<String>,
+    /// `json` or `html`, overriding the `Accept` header
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MailboxResponse {
+    pub emails: Vec<MailboxEmailResponse>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MailboxEmailResponse {
+    pub to: String,
+    pub subject: String,
+    #[serde(rename = "htmlBody")]
+    pub html_body: String,
+    #[serde(rename = "textBody")]
+    pub text_body: String,
+    #[serde(rename = "capturedAt")]
+    pub captured_at: String,
+}
+
+impl From<CapturedEmail> for MailboxEmailResponse {
+    fn from(email: CapturedEmail) -> Self {
+        Self {
+            to: email.recipient.as_ref().expose_secret().to_owned(),
+            subject: email.subject,
+            html_body: email.html_body,
+            text_body: email.text_body,
+            captured_at: email.captured_at.to_rfc3339(),
+        }
+    }
+}
@@ -1,63 +1,125 @@
+use std::net::SocketAddr;
+
 use ::serde::{Deserialize, Serialize};
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
+use color_eyre::eyre::Result;
 use secrecy::{ExposeSecret, Secret};
 
 use crate::{
     app_state::state::AppState,
     domain::{
-        data_stores::{LoginAttemptId, TwoFACode},
+        audit::{AuditAction, AuditEvent},
+        data_stores::{LoginAttemptId, TwoFACode, UserStoreError},
         email::Email,
         error::AuthAPIError,
+        locale::Locale,
+        organization::OrganizationId,
         parse::Parseable,
         password::Password,
+        role::Role,
+        user::{TwoFactorChannel, User},
+    },
+    services::email_templates::{EmailTemplate, SecurityEvent, EMAIL_TEMPLATES},
+    utils::{
+        audit::AuditRecorder,
+        auth::{generate_auth_cookie, generate_device_cookie, TokenHash},
+        constants::{DEVICE_COOKIE_NAME, TWO_FA_CODE_TTL_SECONDS},
+        i18n::{translate, RequestLocale},
     },
-    utils::auth::generate_auth_cookie,
 };
 
+use super::account::{client_ip, send_security_alert};
+
 #[tracing::instrument(name = "Login to the application", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    // Failed attempts are recorded against whichever email was tried,
+    // normalized when it parses so they show up in that user's events
+    audit.record(AuditEvent::new(
+        request.email.expose_secret(),
+        AuditAction::LoggedIn,
+    ));
+
     // TODO: fix. Should be doing validation / error handling here
     let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::LoggedIn,
+    ));
     let password =
         Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
     // Validation
-    let user_store = &state.user_store.read().await;
+    let user_store = &state.user_store;
 
-    // raised if user does not exist
-    user_store
-        .get_user(&email)
-        .await
-        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
-
-    // Raised if username and password info does not match
-    user_store
-        .validate_user(&email, &password)
-        .await
-        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let user = match user_store.validate_user(&email, &password).await {
+        Ok(user) => user,
+        // raised if user does not exist
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::IncorrectCredentials),
+        // Raised if username and password info does not match
+        Err(UserStoreError::InvalidCredentials) => {
+            record_failed_login(&state, &email).await;
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
 
-    let user = user_store
-        .get_user(&email)
-        .await
-        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    // Only reveal the account state to callers who know the password
+    if let Some(error) = AuthAPIError::for_account_status(user.status) {
+        return Err(error);
+    }
+    if user.password_reset_required {
+        return Err(AuthAPIError::PasswordResetRequired);
+    }
 
     // Handle request based on user's 2FA configuration
     // Cases where this can fail:
     let response = match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await?,
-        false => handle_no_2fa(&user.email, jar).await?,
+        true => {
+            audit.record(AuditEvent::new(
+                user.email.as_ref().expose_secret(),
+                AuditAction::TwoFactorRequested,
+            ));
+            handle_2fa(&user, locale, &state, jar).await?
+        }
+        false => {
+            let roles = user_store
+                .get_roles(&user.email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+            let organization = default_organization(&state, &user.email).await?;
+            let (jar, response) =
+                handle_no_2fa(&user.email, &roles, organization.as_ref(), jar).await?;
+            let jar = record_login(
+                &state,
+                &user,
+                connect_info,
+                locale.with_preference(user.locale),
+                jar,
+            )
+            .await;
+            (jar, response)
+        }
     };
     Ok((response.0, response.1))
 }
 
 #[tracing::instrument(name = "Handle Two-factor Auth", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
+    locale: RequestLocale,
     state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
@@ -67,41 +129,68 @@ async fn handle_2fa(
 
     if let Err(e) = state
         .two_fa_code_store
-        .write()
-        .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .add_code(
+            user.email.clone(),
+            login_attempt_id.clone(),
+            two_fa_code.clone(),
+        )
         .await
     {
         return Err(AuthAPIError::UnexpectedError(e.into()));
     }
 
-    // Send 2FA email
-    let email_client = state.email_client.write().await;
-    let subject = "2FA code has been sent!";
-    if let Err(e) = email_client
-        .send_email(&email, subject, two_fa_code.as_ref().expose_secret())
+    let template = EmailTemplate::TwoFACode {
+        code: two_fa_code.as_ref().expose_secret().to_owned(),
+        expires_in_minutes: TWO_FA_CODE_TTL_SECONDS / 60,
+    };
+    let channel = send_two_fa_code(state, user, &template, locale.with_preference(user.locale))
         .await
-    {
-        println!("error: {:?}", e);
-        return Err(AuthAPIError::UnexpectedError(e.into()));
-    }
+        .map_err(AuthAPIError::email_not_sent)?;
 
     // Finally, we need to return the login attempt ID to the client
     let response: Json<LoginResponse> = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
-        message: "2FA required".to_owned(),
+        message: translate(locale.locale(), "login.two_factor_required").to_owned(),
         login_attempt_id: login_attempt_id.as_ref().expose_secret().to_string(),
+        channel: channel.as_str().to_owned(),
     }));
 
     Ok((jar, (StatusCode::PARTIAL_CONTENT, response)))
 }
 
+/// Texts the code to users who chose SMS, as long as their number is verified
+/// and an SMS provider is configured. Everyone else gets it by email.
+/// Returns the channel the code went out on.
+async fn send_two_fa_code(
+    state: &AppState,
+    user: &User,
+    template: &EmailTemplate,
+    locale: Locale,
+) -> Result<TwoFactorChannel> {
+    if let (TwoFactorChannel::Sms, true, Some(phone), Some(message_client)) = (
+        user.two_fa_channel,
+        user.phone_verified,
+        &user.phone_number,
+        &state.message_client,
+    ) {
+        let text = EMAIL_TEMPLATES.render_text_message(template, locale)?;
+        message_client.send_message(phone, &text).await?;
+        return Ok(TwoFactorChannel::Sms);
+    }
+    let message = EMAIL_TEMPLATES.render(template, locale)?;
+    state.email_client.send_email(&user.email, &message).await?;
+    Ok(TwoFactorChannel::Email)
+}
+
 #[tracing::instrument(name = "Handle no two-factor auth", skip_all)]
 pub async fn handle_no_2fa(
     email: &Email,
+    roles: &[Role],
+    organization: Option<&OrganizationId>,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
     // update cookie
-    let auth_cookie = generate_auth_cookie(&email).map_err(|e| AuthAPIError::UnexpectedError(e))?;
+    let auth_cookie =
+        generate_auth_cookie(email, roles, organization).map_err(AuthAPIError::UnexpectedError)?;
     let updated_jar = jar.add(auth_cookie);
     Ok((
         updated_jar,
@@ -109,6 +198,86 @@ pub async fn handle_no_2fa(
     ))
 }
 
+/// Sessions start in the organization the user joined first, if any
+pub(crate) async fn default_organization(
+    state: &AppState,
+    email: &Email,
+) -> Result<Option<OrganizationId>, AuthAPIError> {
+    let memberships = state
+        .organization_store
+        .get_memberships(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    Ok(memberships
+        .first()
+        .map(|membership| membership.organization.id))
+}
+
+/// Logins with 2FA only complete once the code is verified.
+/// `user` is read before the login, so it tells whether there were earlier ones.
+pub(crate) async fn record_login(
+    state: &AppState,
+    user: &User,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: Locale,
+    jar: CookieJar,
+) -> CookieJar {
+    let ip = client_ip(connect_info);
+    let (jar, new_device) = remember_device(state, &user.email, jar).await;
+    // The user already holds a session, losing the bookkeeping must not undo that
+    if let Err(e) = state
+        .user_store
+        .record_login(&user.email, ip.as_deref())
+        .await
+    {
+        tracing::error!(error = ?e, "Failed to record login");
+    }
+    // Every device is new on the first login, which is no news to the user
+    if new_device && user.last_login_at.is_some() {
+        send_security_alert(
+            state,
+            &user.email,
+            user,
+            SecurityEvent::NewLogin,
+            ip,
+            locale,
+        )
+        .await;
+    }
+    jar
+}
+
+/// Browsers without a device cookie get one. Returns whether the user
+/// logged in from the device before.
+async fn remember_device(state: &AppState, email: &Email, jar: CookieJar) -> (CookieJar, bool) {
+    let (jar, device) = match jar.get(DEVICE_COOKIE_NAME) {
+        Some(cookie) => {
+            let device = cookie.value().to_owned();
+            (jar, device)
+        }
+        None => {
+            let cookie = generate_device_cookie();
+            let device = cookie.value().to_owned();
+            (jar.add(cookie), device)
+        }
+    };
+    let device = TokenHash::of(&Secret::new(device));
+    match state.user_store.remember_device(email, &device).await {
+        Ok(new_device) => (jar, new_device),
+        Err(e) => {
+            tracing::error!(error = ?e, "Failed to remember device");
+            (jar, false)
+        }
+    }
+}
+
+/// Counts a wrong password or 2FA code against the account
+pub(crate) async fn record_failed_login(state: &AppState, email: &Email) {
+    if let Err(e) = state.user_store.record_failed_login(email).await {
+        tracing::error!(error = ?e, "Failed to record failed login");
+    }
+}
+
 // The login route can return 2 possible success responses.
 // This enum models each response!
 #[derive(Debug, Serialize)]
@@ -124,6 +293,8 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    /// Where the code was sent, `email` or `sms`
+    pub channel: String,
 }
 
 #[derive(Deserialize)]
@@ -1,17 +1,30 @@
+use std::time::Duration;
+
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use axum_extra::extract::CookieJar;
 use secrecy::Secret;
 
 use crate::{
     app_state::state::AppState,
-    domain::error::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        error::AuthAPIError,
+    },
+    utils::{
+        audit::AuditRecorder,
+        auth::{validate_token, TokenHash, TokenValidationError, TOKEN_TTL_SECONDS},
+        constants::JWT_COOKIE_NAME,
+    },
 };
 
 pub async fn logout(
     State(state): State<AppState>,
+    audit: AuditRecorder,
     jar: CookieJar,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    // Replaced below once the token tells us who is signing out
+    audit.record(AuditEvent::anonymous(AuditAction::LoggedOut));
+
     // Retrieve JWT cookie from the `CookieJar`
     // Return AuthAPIError::MissingToken is the cookie is not found
     let token = match jar.get(JWT_COOKIE_NAME) {
@@ -20,18 +33,27 @@ pub async fn logout(
     };
     let token = Secret::new(token);
 
-    // Return if invalid token
+    // Return if invalid token. Users of inactive accounts can still sign out.
+    // The ban only has to last until the token expires on its own.
     let banned_token_store = &state.banned_token_store;
-    validate_token(&token, banned_token_store.clone())
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let ttl =
+        match validate_token(&token, banned_token_store.clone(), state.user_store.clone()).await {
+            Ok(claims) => {
+                let ttl = claims.remaining_lifetime();
+                audit.record(AuditEvent::new(claims.sub, AuditAction::LoggedOut));
+                ttl
+            }
+            // No claims to read `exp` from, but no token outlives TOKEN_TTL_SECONDS
+            Err(TokenValidationError::InactiveAccount(_)) => {
+                Duration::from_secs(TOKEN_TTL_SECONDS as u64)
+            }
+            Err(e) => return Err(e.into()),
+        };
 
     // Try adding token to ban list
     state
         .banned_token_store
-        .write()
-        .await
-        .insert(token.to_owned())
+        .insert(&TokenHash::of(&token), ttl)
         .await
         .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
     // Remove JWT cookie
@@ -1,18 +1,34 @@
+mod account;
+mod admin;
+mod audit;
+#[cfg(feature = "dev-mailbox")]
+mod dev_mailbox;
 mod login;
 mod logout;
+mod organizations;
+mod password_reset;
 mod signup;
 mod verify_2fa;
 mod verify_token;
+mod webhooks;
 
 use axum::{routing::post, Router};
 use tower_http::services::ServeDir;
 
 // re-export items from sub-modules
+pub use account::*;
+pub use admin::*;
+pub use audit::*;
+#[cfg(feature = "dev-mailbox")]
+pub use dev_mailbox::*;
 pub use login::*;
 pub use logout::*;
+pub use organizations::*;
+pub use password_reset::*;
 pub use signup::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
+pub use webhooks::*;
 
 use crate::app_state::state::AppState;
 
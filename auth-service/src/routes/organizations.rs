@@ -0,0 +1,370 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Extension, Json};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::{OrganizationStoreError, UserStoreError},
+        email::Email,
+        error::AuthAPIError,
+        organization::{
+            Invitation, InvitationToken, Organization, OrganizationId, OrganizationName,
+            TenantScope, INVITATION_TTL_DAYS,
+        },
+        parse::Parseable,
+        role::{Permission, Role},
+    },
+    services::email_templates::{EmailTemplate, EMAIL_TEMPLATES},
+    utils::{
+        audit::AuditRecorder,
+        auth::{generate_auth_cookie, Claims},
+        constants::INVITATION_URL,
+        i18n::RequestLocale,
+    },
+};
+
+/// Creates an organization administered by the caller. They can manage its
+/// members, but only global admins can change the accounts behind them.
+#[tracing::instrument(name = "Create organization", skip_all)]
+pub async fn create_organization(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<CreateOrganizationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::OrganizationCreated,
+    ));
+    let name = OrganizationName::parse(request.name).map_err(|_| AuthAPIError::InvalidInput)?;
+    let email = caller_email(&claims)?;
+    let roles = vec![Role::organization_admin()];
+
+    let organization_store = &state.organization_store;
+    let organization = organization_store
+        .create_organization(name)
+        .await
+        .map_err(organization_api_error)?;
+    organization_store
+        .add_member(&organization.id, &email, &roles)
+        .await
+        .map_err(organization_api_error)?;
+
+    let response = OrganizationResponse::new(organization, &roles);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[tracing::instrument(name = "List organizations", skip_all)]
+pub async fn list_organizations(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::OrganizationsListed,
+    ));
+    let email = caller_email(&claims)?;
+    let memberships = state
+        .organization_store
+        .get_memberships(&email)
+        .await
+        .map_err(organization_api_error)?;
+
+    let response = OrganizationsResponse {
+        organizations: memberships
+            .into_iter()
+            .map(|membership| OrganizationResponse::new(membership.organization, &membership.roles))
+            .collect(),
+        active: claims.org,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Issues a new JWT whose active organization is the requested one,
+/// or no organization at all when `organizationId` is null
+#[tracing::instrument(name = "Switch organization", skip_all)]
+pub async fn switch_organization(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    jar: CookieJar,
+    Json(request): Json<SwitchOrganizationRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::OrganizationSwitched,
+    ));
+    let email = caller_email(&claims)?;
+    let organization = match request.organization_id {
+        Some(id) => {
+            let id = OrganizationId::parse(id).map_err(|_| AuthAPIError::InvalidInput)?;
+            // Organizations the caller does not belong to are reported as missing
+            match state.organization_store.get_member_roles(&id, &email).await {
+                Ok(_) => Some(id),
+                Err(OrganizationStoreError::MemberNotFound) => {
+                    return Err(AuthAPIError::OrganizationNotFound)
+                }
+                Err(e) => return Err(organization_api_error(e)),
+            }
+        }
+        None => None,
+    };
+
+    let roles = state
+        .user_store
+        .get_roles(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    let cookie = generate_auth_cookie(&email, &roles, organization.as_ref())
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = ActiveOrganizationResponse {
+        organization_id: organization.map(|id| id.to_string()),
+    };
+    Ok((jar.add(cookie), (StatusCode::OK, Json(response))))
+}
+
+/// Emails an accept link to the invitee.
+/// Invitations go to the organization the request is scoped to.
+#[tracing::instrument(name = "Invite member", skip_all)]
+pub async fn invite_member(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Extension(scope): Extension<TenantScope>,
+    audit: AuditRecorder,
+    locale: RequestLocale,
+    Json(request): Json<InviteMemberRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidInput)?;
+    audit.record(AuditEvent::new(&claims.sub, AuditAction::MemberInvited).with_target(&email));
+    let organization_id = match scope {
+        TenantScope::Organization(id) => id,
+        // Global admins invite into their active organization
+        TenantScope::Global => claims
+            .org
+            .clone()
+            .and_then(|id| OrganizationId::parse(id).ok())
+            .ok_or(AuthAPIError::InvalidInput)?,
+    };
+    let role = request
+        .role
+        .map(Role::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidInput)?;
+    if let Some(role) = &role {
+        ensure_organization_role(&state, role).await?;
+    }
+
+    let organization_store = &state.organization_store;
+    let organization = organization_store
+        .get_organization(&organization_id)
+        .await
+        .map_err(organization_api_error)?;
+    let invitation = Invitation::new(organization.id, email.clone(), role, claims.sub.clone());
+    organization_store
+        .add_invitation(invitation.clone())
+        .await
+        .map_err(organization_api_error)?;
+
+    // Invitees without an account get the inviter's language
+    let preference = match state.user_store.get_user(&email).await {
+        Ok(user) => user.locale,
+        Err(UserStoreError::UserNotFound) => None,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    let message = EMAIL_TEMPLATES
+        .render(
+            &EmailTemplate::Invitation {
+                inviter: claims.sub.clone(),
+                organization: organization.name.as_ref().to_owned(),
+                link: format!(
+                    "{}?token={}",
+                    *INVITATION_URL,
+                    invitation.token.as_ref().expose_secret()
+                ),
+                expires_in_days: INVITATION_TTL_DAYS,
+            },
+            locale.with_preference(preference),
+        )
+        .map_err(AuthAPIError::UnexpectedError)?;
+    state
+        .email_client
+        .send_email(&email, &message)
+        .await
+        .map_err(AuthAPIError::email_not_sent)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(InvitationResponse::from(invitation)),
+    ))
+}
+
+/// Joins the organization of a pending invitation addressed to the caller
+#[tracing::instrument(name = "Accept invitation", skip_all)]
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    audit: AuditRecorder,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        &claims.sub,
+        AuditAction::InvitationAccepted,
+    ));
+    let token = InvitationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidInput)?;
+    let email = caller_email(&claims)?;
+
+    let organization_store = &state.organization_store;
+    let invitation = organization_store
+        .get_invitation(&token)
+        .await
+        .map_err(organization_api_error)?;
+    if !invitation.is_pending(Utc::now()) {
+        return Err(AuthAPIError::InvitationNotFound);
+    }
+    if invitation.email != email {
+        return Err(AuthAPIError::Forbidden);
+    }
+
+    let roles: Vec<Role> = invitation.role.into_iter().collect();
+    organization_store
+        .add_member(&invitation.organization_id, &email, &roles)
+        .await
+        .map_err(organization_api_error)?;
+    organization_store
+        .mark_invitation_accepted(&token)
+        .await
+        .map_err(organization_api_error)?;
+    let organization = organization_store
+        .get_organization(&invitation.organization_id)
+        .await
+        .map_err(organization_api_error)?;
+    let roles = organization_store
+        .get_member_roles(&organization.id, &email)
+        .await
+        .map_err(organization_api_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(OrganizationResponse::new(organization, &roles)),
+    ))
+}
+
+fn caller_email(claims: &Claims) -> Result<Email, AuthAPIError> {
+    Email::parse(Secret::new(claims.sub.clone())).map_err(|_| AuthAPIError::InvalidToken)
+}
+
+/// Only roles that grant nothing beyond an organization admin's permissions
+/// can be held inside an organization. Others, such as `admin`, would reach
+/// data like the audit trail that organization roles deliberately leave out.
+pub(crate) async fn ensure_organization_role(
+    state: &AppState,
+    role: &Role,
+) -> Result<(), AuthAPIError> {
+    let user_store = &state.user_store;
+    let exists = user_store
+        .role_exists(role)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !exists {
+        return Err(AuthAPIError::RoleNotFound);
+    }
+    let permissions = user_store
+        .get_permissions(std::slice::from_ref(role))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !permissions
+        .iter()
+        .all(|permission| Permission::ORGANIZATION_ADMIN.contains(permission))
+    {
+        return Err(AuthAPIError::Forbidden);
+    }
+    Ok(())
+}
+
+pub(crate) fn organization_api_error(e: OrganizationStoreError) -> AuthAPIError {
+    match e {
+        OrganizationStoreError::OrganizationNotFound => AuthAPIError::OrganizationNotFound,
+        OrganizationStoreError::MemberNotFound => AuthAPIError::UserNotFound,
+        OrganizationStoreError::InvitationNotFound => AuthAPIError::InvitationNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct SwitchOrganizationRequest {
+    #[serde(rename = "organizationId")]
+    pub organization_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct InviteMemberRequest {
+    pub email: Secret<String>,
+    pub role: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl OrganizationResponse {
+    fn new(organization: Organization, roles: &[Role]) -> Self {
+        Self {
+            id: organization.id.to_string(),
+            name: organization.name.as_ref().to_owned(),
+            roles: roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct OrganizationsResponse {
+    pub organizations: Vec<OrganizationResponse>,
+    /// Id of the organization in the caller's JWT
+    pub active: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ActiveOrganizationResponse {
+    #[serde(rename = "organizationId")]
+    pub organization_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct InvitationResponse {
+    #[serde(rename = "organizationId")]
+    pub organization_id: String,
+    pub email: String,
+    pub role: Option<String>,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: String,
+}
+
+impl From<Invitation> for InvitationResponse {
+    fn from(invitation: Invitation) -> Self {
+        Self {
+            organization_id: invitation.organization_id.to_string(),
+            email: invitation.email.as_ref().expose_secret().to_owned(),
+            role: invitation.role.map(|role| role.as_ref().to_owned()),
+            expires_at: invitation.expires_at.to_rfc3339(),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::UserStoreError,
+        email::Email,
+        email_client::RecipientSuppressed,
+        error::AuthAPIError,
+        locale::Locale,
+        parse::Parseable,
+        password::Password,
+        user::User,
+    },
+    services::email_templates::{EmailTemplate, SecurityEvent, EMAIL_TEMPLATES},
+    utils::{
+        audit::AuditRecorder,
+        auth::{generate_password_reset_token, validate_password_reset_token},
+        constants::PASSWORD_RESET_URL,
+        i18n::RequestLocale,
+    },
+};
+
+use super::account::{client_ip, send_security_alert};
+
+/// Emails a link to choose a new password. Needs no session, so users who
+/// forgot their password or had a reset forced on them can sign in again.
+/// Always accepted, so the response does not tell whether the account exists.
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    audit: AuditRecorder,
+    locale: RequestLocale,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        request.email.expose_secret(),
+        AuditAction::PasswordResetRequested,
+    ));
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidInput)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::PasswordResetRequested,
+    ));
+
+    match state.user_store.get_user(&email).await {
+        Ok(user) => {
+            let locale = locale.with_preference(user.locale);
+            if let Err(e) = send_password_reset_link(&state, &user, locale).await {
+                log_unsent_reset_link(e);
+            }
+        }
+        Err(UserStoreError::UserNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: Secret<String>,
+}
+
+/// Target of the password reset link. Replaces the password and ends every
+/// session, since whoever knew the old password may still hold one.
+/// Each link works once: changing the password invalidates it.
+#[tracing::instrument(name = "Reset password", skip_all)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, password_changed_at) =
+        validate_password_reset_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::PasswordReset,
+    ));
+    let new_password =
+        Password::parse(request.new_password).map_err(|_| AuthAPIError::InvalidInput)?;
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+    if user.password_changed_at.timestamp_micros() != password_changed_at {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    state
+        .user_store
+        .set_password(&email, &new_password)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    state
+        .banned_token_store
+        .revoke_sessions(&email, Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    send_security_alert(
+        &state,
+        &email,
+        &user,
+        SecurityEvent::PasswordChanged,
+        client_ip(connect_info),
+        locale.with_preference(user.locale),
+    )
+    .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: Secret<String>,
+    #[serde(rename = "newPassword")]
+    pub new_password: Secret<String>,
+}
+
+/// Logs why a password reset link could not be sent
+pub(crate) fn log_unsent_reset_link(error: Report) {
+    if RecipientSuppressed::caused(&error) {
+        tracing::warn!("Password reset link not sent, address suppressed");
+    } else {
+        tracing::error!(error = ?error, "Failed to send password reset link");
+    }
+}
+
+/// Emails `user` a link to the page at `PASSWORD_RESET_URL`
+pub(crate) async fn send_password_reset_link(
+    state: &AppState,
+    user: &User,
+    locale: Locale,
+) -> color_eyre::eyre::Result<()> {
+    let token = generate_password_reset_token(user)?;
+    let message = EMAIL_TEMPLATES.render(
+        &EmailTemplate::PasswordReset {
+            link: format!("{}?token={}", *PASSWORD_RESET_URL, token),
+        },
+        locale,
+    )?;
+    state.email_client.send_email(&user.email, &message).await
+}
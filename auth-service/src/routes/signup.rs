@@ -1,33 +1,61 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::state::AppState,
-    domain::{email::Email, error::AuthAPIError, parse::Parseable, password::Password, user::User},
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        data_stores::UserStoreError,
+        email::Email,
+        error::AuthAPIError,
+        parse::Parseable,
+        password::Password,
+        user::User,
+    },
+    utils::{
+        audit::AuditRecorder,
+        i18n::{translate, RequestLocale},
+    },
 };
 
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     state: State<AppState>,
+    audit: AuditRecorder,
+    locale: RequestLocale,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
+    audit.record(AuditEvent::new(
+        request.email.expose_secret(),
+        AuditAction::SignedUp,
+    ));
+
     // Invalid values will raise error
     let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::SignedUp,
+    ));
     let password =
         Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
-    let user = User::new(email, request.requires_2fa, password);
+    let mut user = User::new(email, request.requires_2fa, password);
+    // Without a choice, emails follow the language the account was created in
+    user.locale = match request.locale {
+        Some(locale) => Some(locale.parse().map_err(|_| AuthAPIError::InvalidInput)?),
+        None => locale.0,
+    };
 
-    let mut user_store = state.user_store.write().await;
+    let user_store = &state.user_store;
     match user_store.add_user(user).await {
-        // TODO: Have common messaging object to generate messages.
         Ok(_) => {
             let response = Json(SignupResponse {
-                message: "User created successfully!".to_string(),
+                message: translate(locale.locale(), "signup.created").to_owned(),
             });
             Ok((StatusCode::CREATED, response))
         }
-        Err(_) => Err(AuthAPIError::UserAlreadyExists), // Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+        Err(UserStoreError::UserAlreadyExists) => Err(AuthAPIError::UserAlreadyExists),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
     }
 }
 
@@ -37,6 +65,9 @@ pub struct SignupRequest {
     pub password: Secret<String>,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    /// Language for emails, e.g. `de`
+    #[serde(default)]
+    pub locale: Option<String>,
 }
 
 #[derive(Serialize, Debug, PartialEq, Deserialize)]
@@ -1,19 +1,29 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
 use axum_extra::extract::CookieJar;
-use secrecy::Secret;
+use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 
 use crate::{
     app_state::state::AppState,
     domain::{
+        audit::{AuditAction, AuditEvent},
         data_stores::{LoginAttemptId, TwoFACode},
         email::Email,
         error::AuthAPIError,
         parse::Parseable,
     },
-    utils::auth::generate_auth_cookie,
+    utils::{audit::AuditRecorder, auth::generate_auth_cookie, i18n::RequestLocale},
 };
 
+use super::login::{default_organization, record_failed_login, record_login};
+
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct Verify2FARequest {
     pub email: String,
@@ -26,19 +36,31 @@ pub struct Verify2FARequest {
 #[tracing::instrument(name = "Verify two-factor auth", skip_all)]
 pub async fn verify_2fa(
     state: State<AppState>,
+    audit: AuditRecorder,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    locale: RequestLocale,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    audit.record(AuditEvent::new(
+        request.email.as_str(),
+        AuditAction::TwoFactorVerified,
+    ));
+
     // perform input validation
     // TODO: Can we reduce duplicate: |_| AuthAPIError::InvalidCredentials
     let email =
         Email::parse(Secret::new(request.email)).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    audit.record(AuditEvent::new(
+        email.as_ref().expose_secret(),
+        AuditAction::TwoFactorVerified,
+    ));
     let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
         .map_err(|_| AuthAPIError::InvalidCredentials)?;
     let two_fa_code =
         TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::IncorrectCredentials)?;
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+    let two_fa_code_store = &state.two_fa_code_store;
 
     // Call `two_fa_code_store.get_code`. If the call fails
     // return a `AuthAPIError::IncorrectCredentials`.
@@ -50,6 +72,7 @@ pub async fn verify_2fa(
 
     // Validate code
     if stored_login_attempt_id != login_attempt_id || stored_two_fa_code != two_fa_code {
+        record_failed_login(&state, &email).await;
         return Err(AuthAPIError::IncorrectCredentials);
     }
 
@@ -59,11 +82,41 @@ pub async fn verify_2fa(
         return Err(AuthAPIError::UnexpectedError(e.into()));
     }
 
-    let cookie = match generate_auth_cookie(&email) {
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // The account may have been locked or flagged while the code was pending
+    if let Some(error) = AuthAPIError::for_account_status(user.status) {
+        return Err(error);
+    }
+    if user.password_reset_required {
+        return Err(AuthAPIError::PasswordResetRequired);
+    }
+
+    let roles = state
+        .user_store
+        .get_roles(&email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let organization = default_organization(&state, &email).await?;
+
+    let cookie = match generate_auth_cookie(&email, &roles, organization.as_ref()) {
         Ok(cookie) => cookie,
-        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e)),
     };
 
     let updated_jar = jar.add(cookie);
+    let updated_jar = record_login(
+        &state,
+        &user,
+        connect_info,
+        locale.with_preference(user.locale),
+        updated_jar,
+    )
+    .await;
     Ok((updated_jar, StatusCode::OK.into_response()))
 }
@@ -2,7 +2,14 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use secrecy::Secret;
 use serde::Deserialize;
 
-use crate::{app_state::state::AppState, domain::error::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        error::AuthAPIError,
+    },
+    utils::{audit::AuditRecorder, auth::validate_token},
+};
 
 #[derive(Deserialize, Debug)]
 pub struct VerifyTokenRequest {
@@ -12,12 +19,25 @@ pub struct VerifyTokenRequest {
 #[tracing::instrument("Verify authentication token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
+    audit: AuditRecorder,
     Json(request): Json<VerifyTokenRequest>,
 ) -> impl IntoResponse {
     let token = request.token.as_str();
     let banned_token_store = state.banned_token_store;
-    match validate_token(&Secret::new(token.to_string()), banned_token_store).await {
-        Ok(_) => Ok(StatusCode::OK.into_response()),
-        Err(_) => return Err(AuthAPIError::InvalidToken),
+    match validate_token(
+        &Secret::new(token.to_string()),
+        banned_token_store,
+        state.user_store,
+    )
+    .await
+    {
+        Ok(claims) => {
+            audit.record(AuditEvent::new(claims.sub, AuditAction::TokenVerified));
+            Ok(StatusCode::OK.into_response())
+        }
+        Err(e) => {
+            audit.record(AuditEvent::anonymous(AuditAction::TokenVerified));
+            Err(AuthAPIError::from(e))
+        }
     }
 }
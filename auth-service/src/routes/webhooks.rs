@@ -0,0 +1,171 @@
+use axum::{
+    body::Bytes, extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+
+use crate::{
+    app_state::state::AppState,
+    domain::{
+        audit::{AuditAction, AuditEvent},
+        email::Email,
+        email_suppression::{EmailSuppression, SuppressionReason},
+        error::AuthAPIError,
+        parse::Parseable,
+    },
+    utils::audit::AuditRecorder,
+};
+
+// Recorded as the actor of the audit events the webhook causes
+const POSTMARK_ACTOR: &str = "postmark";
+
+/// Receives Postmark's bounce and spam complaint webhooks. Hard bounces and
+/// complaints suppress the address, so nothing is sent to it again until an
+/// admin lifts the suppression. Every other webhook is acknowledged and ignored.
+///
+/// Postmark retries webhooks that fail, so a webhook that cannot be acted on,
+/// e.g. because the address is malformed, is acknowledged all the same.
+#[tracing::instrument(name = "Postmark webhook", skip_all)]
+pub async fn postmark_webhook(
+    State(state): State<AppState>,
+    audit: AuditRecorder,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // Verified before the body is looked at
+    let verified = state
+        .postmark_webhook
+        .as_ref()
+        .is_some_and(|credentials| credentials.verify(&headers));
+    if !verified {
+        tracing::warn!("Rejected a Postmark webhook without valid credentials");
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    let webhook: PostmarkWebhook =
+        serde_json::from_slice(&body).map_err(|_| AuthAPIError::InvalidInput)?;
+
+    let Some(reason) = webhook.suppression_reason() else {
+        tracing::debug!(
+            record_type = webhook.record_type,
+            "Ignoring Postmark webhook"
+        );
+        return Ok(StatusCode::OK);
+    };
+    let Some(email) = webhook
+        .email
+        .clone()
+        .and_then(|email| Email::parse(Secret::new(email)).ok())
+    else {
+        tracing::warn!(%reason, "Postmark webhook without a valid email address");
+        return Ok(StatusCode::OK);
+    };
+
+    let action = match reason {
+        SuppressionReason::HardBounce => AuditAction::EmailBounced,
+        SuppressionReason::SpamComplaint => AuditAction::EmailComplained,
+    };
+    audit.record(AuditEvent::new(POSTMARK_ACTOR, action).with_target(&email));
+    let suppression = EmailSuppression::new(email, reason).with_description(webhook.description());
+    state
+        .email_suppression_store
+        .suppress(&suppression)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    tracing::info!(%reason, "Suppressed emails to an address");
+    Ok(StatusCode::OK)
+}
+
+/// The fields of Postmark's webhooks that matter here
+#[derive(Deserialize, Debug)]
+pub struct PostmarkWebhook {
+    /// `Bounce`, `SpamComplaint`, `Delivery`, ...
+    #[serde(rename = "RecordType")]
+    pub record_type: String,
+    /// Kind of bounce, e.g. `HardBounce` or `SoftBounce`
+    #[serde(rename = "Type")]
+    pub kind: Option<String>,
+    #[serde(rename = "Email")]
+    pub email: Option<String>,
+    #[serde(rename = "Description")]
+    pub summary: Option<String>,
+    /// What the receiving server answered
+    #[serde(rename = "Details")]
+    pub details: Option<String>,
+}
+
+impl PostmarkWebhook {
+    /// Why the webhook suppresses its address, if it does. Soft bounces,
+    /// e.g. a full mailbox, may go away and are left to the outbox retries.
+    pub fn suppression_reason(&self) -> Option<SuppressionReason> {
+        match (self.record_type.as_str(), self.kind.as_deref()) {
+            ("Bounce", Some("HardBounce")) => Some(SuppressionReason::HardBounce),
+            ("SpamComplaint", _) => Some(SuppressionReason::SpamComplaint),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.summary, &self.details]
+            .into_iter()
+            .flatten()
+            .map(|part| part.trim())
+            .filter(|part| !part.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(body: serde_json::Value) -> PostmarkWebhook {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_only_hard_bounces_and_complaints_suppress() {
+        let hard = webhook(serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "TypeCode": 1,
+            "Email": "gone@example.com",
+            "Description": "The server was unable to deliver your message.",
+            "Details": "smtp;550 5.1.1 The email account does not exist.",
+        }));
+        assert_eq!(
+            hard.suppression_reason(),
+            Some(SuppressionReason::HardBounce)
+        );
+        assert_eq!(
+            hard.description().as_deref(),
+            Some(
+                "The server was unable to deliver your message. \
+                 smtp;550 5.1.1 The email account does not exist."
+            )
+        );
+
+        let complaint = webhook(serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "Email": "angry@example.com",
+        }));
+        assert_eq!(
+            complaint.suppression_reason(),
+            Some(SuppressionReason::SpamComplaint)
+        );
+        assert_eq!(complaint.description(), None);
+
+        let soft = webhook(serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "Email": "full@example.com",
+        }));
+        assert_eq!(soft.suppression_reason(), None);
+        let delivery = webhook(serde_json::json!({
+            "RecordType": "Delivery",
+            "Recipient": "fine@example.com",
+        }));
+        assert_eq!(delivery.suppression_reason(), None);
+    }
+}
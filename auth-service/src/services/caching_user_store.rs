@@ -0,0 +1,353 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    app_state::state::UserStoreType,
+    domain::{
+        data_stores::{UserCache, UserPage, UserQuery, UserStore, UserStoreError},
+        email::Email,
+        locale::Locale,
+        password::Password,
+        phone_number::PhoneNumber,
+        role::{Permission, Role},
+        user::{StatusChange, TwoFactorChannel, User, UserSummary},
+    },
+    utils::auth::TokenHash,
+};
+
+/// Serves `get_user` and `get_roles` from a [`UserCache`], which token
+/// validation calls on every authenticated request.
+///
+/// Writes made through this store drop the user's entries. Writes made by
+/// other instances only become visible once the entries expire, so the cache
+/// TTL bounds how long a suspended user can keep using their token there.
+/// A failing cache is logged and bypassed, it never fails a request.
+///
+/// Lookups only cache what they read for the generation seen before reading
+/// the store, so a change that lands meanwhile is never hidden by them.
+pub struct CachingUserStore {
+    inner: UserStoreType,
+    cache: Arc<dyn UserCache>,
+}
+
+impl CachingUserStore {
+    pub fn new(inner: UserStoreType, cache: Arc<dyn UserCache>) -> Self {
+        Self { inner, cache }
+    }
+
+    async fn generation(&self, email: &Email) -> Option<u64> {
+        self.cache
+            .generation(email)
+            .await
+            .inspect_err(|e| tracing::warn!(error = ?e, "Failed to read cache generation"))
+            .ok()
+    }
+
+    async fn cache_user(&self, user: &User, generation: Option<u64>) {
+        let Some(generation) = generation else {
+            return;
+        };
+        if let Err(e) = self.cache.set_user(user, generation).await {
+            tracing::warn!(error = ?e, "Failed to cache user");
+        }
+    }
+
+    async fn invalidate(&self, email: &Email) {
+        if let Err(e) = self.cache.invalidate(email).await {
+            tracing::error!(error = ?e, "Failed to invalidate cached user");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for CachingUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let email = user.email.clone();
+        let result = self.inner.add_user(user).await;
+        self.invalidate(&email).await;
+        result
+    }
+
+    #[tracing::instrument(name = "Retrieving cached user", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let generation = self.generation(email).await;
+        if let Some(generation) = generation {
+            match self.cache.get_user(email, generation).await {
+                Ok(Some(user)) => return Ok(user),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = ?e, "Failed to read cached user"),
+            }
+        }
+        let user = self.inner.get_user(email).await?;
+        self.cache_user(&user, generation).await;
+        Ok(user)
+    }
+
+    // Always checked against the store, since only it can verify the password
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError> {
+        let generation = self.generation(email).await;
+        let user = self.inner.validate_user(email, password).await?;
+        self.cache_user(&user, generation).await;
+        Ok(user)
+    }
+
+    async fn assign_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        let result = self.inner.assign_role(email, role).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn revoke_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        let result = self.inner.revoke_role(email, role).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    #[tracing::instrument(name = "Retrieving cached roles", skip_all)]
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        let Some(generation) = self.generation(email).await else {
+            return self.inner.get_roles(email).await;
+        };
+        match self.cache.get_roles(email, generation).await {
+            Ok(Some(roles)) => return Ok(roles),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = ?e, "Failed to read cached roles"),
+        }
+        let roles = self.inner.get_roles(email).await?;
+        if let Err(e) = self.cache.set_roles(email, &roles, generation).await {
+            tracing::warn!(error = ?e, "Failed to cache roles");
+        }
+        Ok(roles)
+    }
+
+    async fn role_exists(&self, role: &Role) -> Result<bool, UserStoreError> {
+        self.inner.role_exists(role).await
+    }
+
+    async fn get_permissions(&self, roles: &[Role]) -> Result<HashSet<Permission>, UserStoreError> {
+        self.inner.get_permissions(roles).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        self.inner.list_users(query).await
+    }
+
+    async fn get_user_summary(&self, email: &Email) -> Result<UserSummary, UserStoreError> {
+        self.inner.get_user_summary(email).await
+    }
+
+    async fn set_status(&self, email: &Email, change: &StatusChange) -> Result<(), UserStoreError> {
+        let result = self.inner.set_status(email, change).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = self
+            .inner
+            .set_password_reset_required(email, required)
+            .await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_requires_2fa(email, requires_2fa).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_locale(
+        &self,
+        email: &Email,
+        locale: Option<Locale>,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_locale(email, locale).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_security_notices(
+        &self,
+        email: &Email,
+        enabled: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_security_notices(email, enabled).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_phone_number(
+        &self,
+        email: &Email,
+        phone_number: Option<&PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_phone_number(email, phone_number).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_phone_verified(
+        &self,
+        email: &Email,
+        verified: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_phone_verified(email, verified).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    // The attempts are not part of the cached user
+    async fn record_phone_verification_attempt(
+        &self,
+        email: &Email,
+    ) -> Result<u32, UserStoreError> {
+        self.inner.record_phone_verification_attempt(email).await
+    }
+
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFactorChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = self.inner.set_two_fa_channel(email, channel).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn set_password(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
+        let result = self.inner.set_password(email, password).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn change_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        let result = self.inner.change_email(email, new_email).await;
+        // Misses are never cached, so only the old address holds entries
+        self.invalidate(email).await;
+        result
+    }
+
+    // Devices are not part of the cached user
+    async fn remember_device(
+        &self,
+        email: &Email,
+        device: &TokenHash,
+    ) -> Result<bool, UserStoreError> {
+        self.inner.remember_device(email, device).await
+    }
+
+    async fn record_login(&self, email: &Email, ip: Option<&str>) -> Result<(), UserStoreError> {
+        let result = self.inner.record_login(email, ip).await;
+        self.invalidate(email).await;
+        result
+    }
+
+    async fn record_failed_login(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = self.inner.record_failed_login(email).await;
+        self.invalidate(email).await;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::{
+        domain::{parse::Parseable, user::AccountStatus},
+        services::{hashmap_user_store::HashMapUserStore, lru_user_cache::LruUserCache},
+        utils::clock::ManualClock,
+    };
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    async fn setup() -> (
+        Arc<HashMapUserStore>,
+        CachingUserStore,
+        Arc<ManualClock>,
+        Email,
+    ) {
+        let email = Email::parse(Secret::new("alice@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+        let inner = Arc::new(HashMapUserStore::default());
+        inner
+            .add_user(User::new(email.clone(), false, password))
+            .await
+            .unwrap();
+        let clock = Arc::new(ManualClock::default());
+        let cache = Arc::new(LruUserCache::with_clock(100, TTL, clock.clone()));
+        let store = CachingUserStore::new(inner.clone(), cache);
+        (inner, store, clock, email)
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_served_from_the_cache_until_they_expire() {
+        let (inner, store, clock, email) = setup().await;
+        assert!(!store.get_user(&email).await.unwrap().requires_2fa);
+        assert!(store.get_roles(&email).await.unwrap().is_empty());
+
+        // Changes that bypass the cache stay hidden until the TTL runs out
+        inner.set_requires_2fa(&email, true).await.unwrap();
+        inner.assign_role(&email, &Role::admin()).await.unwrap();
+        assert!(!store.get_user(&email).await.unwrap().requires_2fa);
+        assert!(store.get_roles(&email).await.unwrap().is_empty());
+
+        clock.advance(TTL);
+        assert!(store.get_user(&email).await.unwrap().requires_2fa);
+        assert_eq!(store.get_roles(&email).await.unwrap(), vec![Role::admin()]);
+    }
+
+    #[tokio::test]
+    async fn test_writes_invalidate_the_cache() {
+        let (_, store, _, email) = setup().await;
+        assert!(store.get_user(&email).await.unwrap().status.is_active());
+        assert!(store.get_roles(&email).await.unwrap().is_empty());
+
+        store
+            .set_status(
+                &email,
+                &StatusChange::new(AccountStatus::Suspended, "admin@example.com"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user(&email).await.unwrap().status,
+            AccountStatus::Suspended
+        );
+
+        store.assign_role(&email, &Role::admin()).await.unwrap();
+        assert_eq!(store.get_roles(&email).await.unwrap(), vec![Role::admin()]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_users_are_not_cached() {
+        let (inner, store, _, _) = setup().await;
+        let bob = Email::parse(Secret::new("bob@example.com".to_owned())).unwrap();
+        assert_eq!(
+            store.get_user(&bob).await.err(),
+            Some(UserStoreError::UserNotFound)
+        );
+
+        let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+        inner
+            .add_user(User::new(bob.clone(), false, password))
+            .await
+            .unwrap();
+        assert!(store.get_user(&bob).await.is_ok());
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::{PoisonError, RwLock};
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::{
+    email::Email,
+    email_client::{EmailClient, EmailMessage},
+};
+use color_eyre::eyre::Result;
+
+/// An email that was handed to a [`CapturingEmailClient`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedEmail {
+    pub recipient: Email,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub captured_at: DateTime<Utc>,
+}
+
+/// Keeps every email in memory instead of delivering it, so the service can
+/// run without an email provider and the messages can be inspected later.
+#[derive(Default)]
+pub struct CapturingEmailClient {
+    // Oldest first, in the order they were sent
+    messages: RwLock<Vec<CapturedEmail>>,
+}
+
+impl CapturingEmailClient {
+    pub fn messages(&self) -> Vec<CapturedEmail> {
+        self.messages
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// The most recent email sent to `recipient`, if any
+    pub fn last_message_to(&self, recipient: &Email) -> Option<CapturedEmail> {
+        self.messages
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .find(|message| &message.recipient == recipient)
+            .cloned()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for CapturingEmailClient {
+    #[tracing::instrument(name = "Capturing email", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        self.messages
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(CapturedEmail {
+                recipient: recipient.clone(),
+                subject: message.subject.clone(),
+                html_body: message.html_body.clone(),
+                text_body: message.text_body.clone(),
+                captured_at: Utc::now(),
+            });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::parse::Parseable;
+    use secrecy::Secret;
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    fn message(subject: &str, text: &str) -> EmailMessage {
+        EmailMessage {
+            subject: subject.to_owned(),
+            html_body: format!("<p>{}</p>", text),
+            text_body: text.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_captures_messages_in_order() {
+        let client = CapturingEmailClient::default();
+        let alice = email("alice@example.com");
+        let bob = email("bob@example.com");
+        client
+            .send_email(&alice, &message("First", "one"))
+            .await
+            .unwrap();
+        client
+            .send_email(&bob, &message("Second", "two"))
+            .await
+            .unwrap();
+        client
+            .send_email(&alice, &message("Third", "three"))
+            .await
+            .unwrap();
+
+        let subjects: Vec<String> = client
+            .messages()
+            .into_iter()
+            .map(|message| message.subject)
+            .collect();
+        assert_eq!(subjects, vec!["First", "Second", "Third"]);
+        let last = client.last_message_to(&alice).unwrap();
+        assert_eq!(last.text_body, "three");
+        assert_eq!(last.html_body, "<p>three</p>");
+        assert_eq!(client.last_message_to(&email("carol@example.com")), None);
+    }
+}
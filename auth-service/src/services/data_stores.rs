@@ -3,17 +3,27 @@ use argon2::{
     PasswordVerifier, Version,
 };
 
+use std::{collections::HashSet, thread};
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
 
 use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
+    data_stores::{UserPage, UserQuery, UserStore, UserStoreError},
     email::Email,
+    locale::Locale,
     parse::Parseable,
     password::Password,
-    user::User,
+    phone_number::PhoneNumber,
+    role::{Permission, Role},
+    user::{AccountStatus, StatusChange, TwoFactorChannel, User, UserSummary},
 };
-use color_eyre::eyre::{Context, Result};
+use crate::utils::auth::TokenHash;
+use color_eyre::eyre::{eyre, Context, Result};
 
 pub struct PostgresUserStore {
     pool: PgPool,
@@ -28,7 +38,7 @@ impl PostgresUserStore {
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
         // Try hashing password prior to insertion
         let hashed_password = compute_password_hash(user.password.as_ref().to_owned())
             .await
@@ -37,44 +47,76 @@ impl UserStore for PostgresUserStore {
         let result = sqlx::query(
             r#"
             insert into USERS
-            (email, password_hash, requires_2fa)
-            values ($1, $2, $3)
+            (id, email, password_hash, requires_2fa, status,
+             created_at, updated_at, password_changed_at, locale, security_notices,
+             phone_number, phone_verified, two_fa_channel)
+            values ($1::UUID, $2, $3, $4, $5,
+             TO_TIMESTAMP(0) + $6 * INTERVAL '1 microsecond',
+             TO_TIMESTAMP(0) + $7 * INTERVAL '1 microsecond',
+             TO_TIMESTAMP(0) + $8 * INTERVAL '1 microsecond',
+             $9, $10, $11, $12, $13)
             "#,
         )
-        .bind(&user.email.as_ref().expose_secret())
-        .bind(&hashed_password.expose_secret())
-        .bind(&user.requires_2fa)
+        .bind(user.id.to_string())
+        .bind(user.email.as_ref().expose_secret())
+        .bind(hashed_password.expose_secret())
+        .bind(user.requires_2fa)
+        .bind(user.status.as_str())
+        .bind(user.created_at.timestamp_micros())
+        .bind(user.updated_at.timestamp_micros())
+        .bind(user.password_changed_at.timestamp_micros())
+        .bind(user.locale.map(|locale| locale.as_str()))
+        .bind(user.security_notices)
+        .bind(
+            user.phone_number
+                .as_ref()
+                .map(|phone| phone.as_ref().expose_secret().to_owned()),
+        )
+        .bind(user.phone_verified)
+        .bind(user.two_fa_channel.as_str())
         .execute(&self.pool)
         .await;
 
         match result {
             Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
             Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
         }
     }
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        let result: (String, String, bool) = sqlx::query_as(
+        let row: UserRow = sqlx::query_as(
             "
-            SELECT email, password_hash, requires_2fa
+            SELECT
+                id::TEXT AS id,
+                email,
+                password_hash,
+                requires_2fa,
+                status,
+                password_reset_required,
+                (EXTRACT(EPOCH FROM created_at) * 1000000)::BIGINT AS created_at,
+                (EXTRACT(EPOCH FROM updated_at) * 1000000)::BIGINT AS updated_at,
+                (EXTRACT(EPOCH FROM last_login_at) * 1000000)::BIGINT AS last_login_at,
+                last_login_ip,
+                failed_login_count::BIGINT AS failed_login_count,
+                (EXTRACT(EPOCH FROM password_changed_at) * 1000000)::BIGINT AS password_changed_at,
+                locale,
+                security_notices,
+                phone_number,
+                phone_verified,
+                two_fa_channel
             FROM users
             WHERE email = $1",
         )
         .bind(email.as_ref().expose_secret())
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await
-        .map_err(|_| UserStoreError::UserNotFound)?;
-        let user = User::new(
-            Email::parse(Secret::new(result.0))
-                .wrap_err("Cannot parse email")
-                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?,
-            result.2,
-            Password::parse(Secret::new(result.1))
-                .wrap_err("Cannot parse password")
-                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?,
-        );
-        Ok(user)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+        row.into_user()
     }
 
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
@@ -82,31 +124,652 @@ impl UserStore for PostgresUserStore {
         &self,
         email: &Email,
         password: &Password,
-    ) -> Result<(), UserStoreError> {
-        let password_from_db: (String,) = sqlx::query_as(
+    ) -> Result<User, UserStoreError> {
+        // The stored user carries the password hash
+        let user = self.get_user(email).await?;
+
+        // Compare with hashed password
+        if verify_password_hash(
+            user.password.as_ref().expose_secret().to_owned(),
+            password.as_ref().expose_secret().to_string(),
+        )
+        .await
+        .is_ok()
+        {
+            Ok(user)
+        } else {
+            Err(UserStoreError::InvalidCredentials)
+        }
+    }
+
+    #[tracing::instrument(name = "Assigning role in PostgreSQL", skip_all)]
+    async fn assign_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        self.ensure_user_and_role_exist(email, role).await?;
+        sqlx::query(
             "
-            SELECT password_hash
-            FROM users
+            INSERT INTO user_roles (email, role)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(role.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking role in PostgreSQL", skip_all)]
+    async fn revoke_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        self.ensure_user_and_role_exist(email, role).await?;
+        sqlx::query(
+            "
+            DELETE FROM user_roles
+            WHERE email = $1 AND role = $2
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(role.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving roles from PostgreSQL", skip_all)]
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "
+            SELECT role
+            FROM user_roles
             WHERE email = $1
+            ORDER BY role
             ",
         )
         .bind(email.as_ref().expose_secret())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(role,)| Role::parse(role).map_err(UserStoreError::UnexpectedError))
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Checking role in PostgreSQL", skip_all)]
+    async fn role_exists(&self, role: &Role) -> Result<bool, UserStoreError> {
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT EXISTS(SELECT 1 FROM roles WHERE name = $1)")
+                .bind(role.as_ref())
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Retrieving permissions from PostgreSQL", skip_all)]
+    async fn get_permissions(&self, roles: &[Role]) -> Result<HashSet<Permission>, UserStoreError> {
+        let role_names: Vec<&str> = roles.iter().map(|role| role.as_ref()).collect();
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "
+            SELECT DISTINCT permission
+            FROM role_permissions
+            WHERE role = ANY($1)
+            ",
+        )
+        .bind(&role_names)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(permission,)| {
+                permission
+                    .parse::<Permission>()
+                    .map_err(UserStoreError::UnexpectedError)
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Listing users in PostgreSQL", skip_all)]
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let pattern = query
+            .search
+            .as_deref()
+            .map(|search| format!("%{}%", escape_like_pattern(search)));
+        let emails: Option<Vec<&str>> = query.emails.as_ref().map(|emails| {
+            emails
+                .iter()
+                .map(|email| email.as_ref().expose_secret().as_str())
+                .collect()
+        });
+
+        let (total,): (i64,) = sqlx::query_as(
+            "
+            SELECT COUNT(*)
+            FROM users
+            WHERE ($1::TEXT IS NULL OR email ILIKE $1)
+              AND ($2::TEXT[] IS NULL OR email = ANY($2))
+            ",
+        )
+        .bind(&pattern)
+        .bind(&emails)
         .fetch_one(&self.pool)
         .await
-        .map_err(|_| UserStoreError::UserNotFound)?;
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
-        // Compare with hashed password
-        if let Ok(_) = verify_password_hash(
-            password_from_db.0,
-            password.as_ref().expose_secret().to_string(),
+        let rows: Vec<UserSummaryRow> = sqlx::query_as(&format!(
+            "
+            {}
+            WHERE ($1::TEXT IS NULL OR email ILIKE $1)
+              AND ($2::TEXT[] IS NULL OR email = ANY($2))
+            ORDER BY email
+            LIMIT $3 OFFSET $4
+            ",
+            SELECT_USER_SUMMARY
+        ))
+        .bind(&pattern)
+        .bind(&emails)
+        .bind(i64::from(query.per_page))
+        .bind(query.offset() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let users = rows
+            .into_iter()
+            .map(UserSummaryRow::into_summary)
+            .collect::<Result<_, _>>()?;
+        Ok(UserPage {
+            users,
+            total: total as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving user summary from PostgreSQL", skip_all)]
+    async fn get_user_summary(&self, email: &Email) -> Result<UserSummary, UserStoreError> {
+        let row: UserSummaryRow = sqlx::query_as(&format!(
+            "
+            {}
+            WHERE email = $1
+            ",
+            SELECT_USER_SUMMARY
+        ))
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+        row.into_summary()
+    }
+
+    #[tracing::instrument(name = "Updating account status in PostgreSQL", skip_all)]
+    async fn set_status(&self, email: &Email, change: &StatusChange) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET status = $2,
+                status_reason = $3,
+                status_changed_by = $4,
+                status_changed_at = NOW(),
+                updated_at = NOW()
+            WHERE email = $1
+            ",
         )
+        .bind(email.as_ref().expose_secret())
+        .bind(change.status.as_str())
+        .bind(&change.reason)
+        .bind(&change.changed_by)
+        .execute(&self.pool)
         .await
-        {
-            Ok(())
-        } else {
-            Err(UserStoreError::InvalidCredentials)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password reset flag in PostgreSQL", skip_all)]
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("password_reset_required", email, required)
+            .await
+    }
+
+    #[tracing::instrument(name = "Updating 2FA flag in PostgreSQL", skip_all)]
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("requires_2fa", email, requires_2fa).await
+    }
+
+    #[tracing::instrument(name = "Setting locale in PostgreSQL", skip_all)]
+    async fn set_locale(
+        &self,
+        email: &Email,
+        locale: Option<Locale>,
+    ) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET locale = $2, updated_at = NOW() WHERE email = $1")
+                .bind(email.as_ref().expose_secret())
+                .bind(locale.map(|locale| locale.as_str()))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
         }
+        Ok(())
     }
+
+    #[tracing::instrument(name = "Updating security notices flag in PostgreSQL", skip_all)]
+    async fn set_security_notices(
+        &self,
+        email: &Email,
+        enabled: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("security_notices", email, enabled).await
+    }
+
+    #[tracing::instrument(name = "Setting phone number in PostgreSQL", skip_all)]
+    async fn set_phone_number(
+        &self,
+        email: &Email,
+        phone_number: Option<&PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET phone_number = $2,
+                phone_verified = FALSE,
+                two_fa_channel = 'email',
+                phone_verification_attempts = 0,
+                updated_at = NOW()
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(phone_number.map(|phone| phone.as_ref().expose_secret().to_owned()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating phone verification flag in PostgreSQL", skip_all)]
+    async fn set_phone_verified(
+        &self,
+        email: &Email,
+        verified: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("phone_verified", email, verified).await
+    }
+
+    #[tracing::instrument(name = "Counting phone verification attempt in PostgreSQL", skip_all)]
+    async fn record_phone_verification_attempt(
+        &self,
+        email: &Email,
+    ) -> Result<u32, UserStoreError> {
+        let attempts: Option<i32> = sqlx::query_scalar(
+            "
+            UPDATE users
+            SET phone_verification_attempts = phone_verification_attempts + 1
+            WHERE email = $1
+            RETURNING phone_verification_attempts
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let attempts = attempts.ok_or(UserStoreError::UserNotFound)?;
+        attempts
+            .try_into()
+            .map_err(|e: std::num::TryFromIntError| UserStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Setting 2FA channel in PostgreSQL", skip_all)]
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFactorChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET two_fa_channel = $2, updated_at = NOW() WHERE email = $1",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(channel.as_str())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting password in PostgreSQL", skip_all)]
+    async fn set_password(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
+        let hashed_password = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET password_hash = $2,
+                password_reset_required = FALSE,
+                password_changed_at = NOW(),
+                updated_at = NOW()
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(hashed_password.expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Changing email in PostgreSQL", skip_all)]
+    async fn change_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        // Roles, devices and memberships follow through ON UPDATE CASCADE
+        let result =
+            sqlx::query("UPDATE users SET email = $2, updated_at = NOW() WHERE email = $1")
+                .bind(email.as_ref().expose_secret())
+                .bind(new_email.as_ref().expose_secret())
+                .execute(&self.pool)
+                .await;
+
+        match result {
+            Ok(result) if result.rows_affected() == 0 => Err(UserStoreError::UserNotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Remembering device in PostgreSQL", skip_all)]
+    async fn remember_device(
+        &self,
+        email: &Email,
+        device: &TokenHash,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query(
+            "
+            INSERT INTO user_devices (email, device_hash)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(device.as_ref())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(result) => Ok(result.rows_affected() == 1),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(UserStoreError::UserNotFound)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Recording login in PostgreSQL", skip_all)]
+    async fn record_login(&self, email: &Email, ip: Option<&str>) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET last_login_at = NOW(),
+                last_login_ip = $2,
+                failed_login_count = 0
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(ip)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording failed login in PostgreSQL", skip_all)]
+    async fn record_failed_login(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET failed_login_count = failed_login_count + 1 WHERE email = $1",
+        )
+        .bind(email.as_ref().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+}
+
+/// A row of `users` with timestamps as microseconds since the epoch,
+/// as read by both the PostgreSQL and the SQLite store
+#[derive(sqlx::FromRow)]
+pub(crate) struct UserRow {
+    id: String,
+    email: String,
+    password_hash: String,
+    requires_2fa: bool,
+    status: String,
+    password_reset_required: bool,
+    created_at: i64,
+    updated_at: i64,
+    last_login_at: Option<i64>,
+    last_login_ip: Option<String>,
+    failed_login_count: i64,
+    password_changed_at: i64,
+    locale: Option<String>,
+    security_notices: bool,
+    phone_number: Option<String>,
+    phone_verified: bool,
+    two_fa_channel: String,
+}
+
+impl UserRow {
+    pub(crate) fn into_user(self) -> Result<User, UserStoreError> {
+        let mut user = User::new(
+            Email::parse(Secret::new(self.email))
+                .wrap_err("Cannot parse email")
+                .map_err(UserStoreError::UnexpectedError)?,
+            self.requires_2fa,
+            Password::parse(Secret::new(self.password_hash))
+                .wrap_err("Cannot parse password")
+                .map_err(UserStoreError::UnexpectedError)?,
+        );
+        user.id = Uuid::parse_str(&self.id)
+            .wrap_err("Cannot parse user id")
+            .map_err(UserStoreError::UnexpectedError)?;
+        user.status = self
+            .status
+            .parse::<AccountStatus>()
+            .map_err(UserStoreError::UnexpectedError)?;
+        user.password_reset_required = self.password_reset_required;
+        user.created_at = from_micros(self.created_at)?;
+        user.updated_at = from_micros(self.updated_at)?;
+        user.last_login_at = self.last_login_at.map(from_micros).transpose()?;
+        user.last_login_ip = self.last_login_ip;
+        user.failed_login_count = self
+            .failed_login_count
+            .try_into()
+            .wrap_err("Invalid failed login count")
+            .map_err(UserStoreError::UnexpectedError)?;
+        user.password_changed_at = from_micros(self.password_changed_at)?;
+        user.locale = self
+            .locale
+            .map(|locale| locale.parse::<Locale>())
+            .transpose()
+            .map_err(UserStoreError::UnexpectedError)?;
+        user.security_notices = self.security_notices;
+        user.phone_number = self
+            .phone_number
+            .map(|phone| PhoneNumber::parse(Secret::new(phone)))
+            .transpose()
+            .wrap_err("Cannot parse phone number")
+            .map_err(UserStoreError::UnexpectedError)?;
+        user.phone_verified = self.phone_verified;
+        user.two_fa_channel = self
+            .two_fa_channel
+            .parse::<TwoFactorChannel>()
+            .map_err(UserStoreError::UnexpectedError)?;
+        Ok(user)
+    }
+}
+
+fn from_micros(micros: i64) -> Result<DateTime<Utc>, UserStoreError> {
+    DateTime::from_timestamp_micros(micros)
+        .ok_or_else(|| UserStoreError::UnexpectedError(eyre!("Invalid user timestamp")))
+}
+
+// Roles are aggregated into the row so listing a page takes a single query
+const SELECT_USER_SUMMARY: &str = "
+    SELECT
+        email,
+        requires_2fa,
+        status,
+        status_reason,
+        status_changed_by,
+        password_reset_required,
+        ARRAY(SELECT role FROM user_roles WHERE user_roles.email = users.email ORDER BY role) AS roles
+    FROM users";
+
+#[derive(sqlx::FromRow)]
+struct UserSummaryRow {
+    email: String,
+    requires_2fa: bool,
+    status: String,
+    status_reason: Option<String>,
+    status_changed_by: Option<String>,
+    password_reset_required: bool,
+    roles: Vec<String>,
+}
+
+impl UserSummaryRow {
+    fn into_summary(self) -> Result<UserSummary, UserStoreError> {
+        let roles = self
+            .roles
+            .into_iter()
+            .map(Role::parse)
+            .collect::<Result<_, _>>()
+            .map_err(UserStoreError::UnexpectedError)?;
+        Ok(UserSummary {
+            email: Email::parse(Secret::new(self.email))
+                .wrap_err("Cannot parse email")
+                .map_err(UserStoreError::UnexpectedError)?,
+            requires_2fa: self.requires_2fa,
+            status: self
+                .status
+                .parse()
+                .map_err(UserStoreError::UnexpectedError)?,
+            status_reason: self.status_reason,
+            status_changed_by: self.status_changed_by,
+            password_reset_required: self.password_reset_required,
+            roles,
+        })
+    }
+}
+
+// Escape the LIKE wildcards so searches match the input literally
+pub(crate) fn escape_like_pattern(search: &str) -> String {
+    search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+impl PostgresUserStore {
+    /// `column` must be one of the boolean columns of `users`, never user input
+    async fn update_flag(
+        &self,
+        column: &'static str,
+        email: &Email,
+        value: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(&format!(
+            "UPDATE users SET {} = $2, updated_at = NOW() WHERE email = $1",
+            column
+        ))
+        .bind(email.as_ref().expose_secret())
+        .bind(value)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn ensure_user_and_role_exist(
+        &self,
+        email: &Email,
+        role: &Role,
+    ) -> Result<(), UserStoreError> {
+        let (user_exists, role_exists): (bool, bool) = sqlx::query_as(
+            "
+            SELECT
+                EXISTS(SELECT 1 FROM users WHERE email = $1),
+                EXISTS(SELECT 1 FROM roles WHERE name = $2)
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(role.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if !user_exists {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if !role_exists {
+            return Err(UserStoreError::RoleNotFound);
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    // Each Argon2 run takes ~15 MiB and a full core. Running more of them
+    // at once than there are cores only makes every request slower.
+    static ref PASSWORD_HASHING: Semaphore = Semaphore::new(
+        thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1)
+    );
 }
 
 // Helper function to verify if a given password matches an expected hash
@@ -114,10 +777,11 @@ impl UserStore for PostgresUserStore {
 // other async tasks, update this function to perform hashing on a
 // separate thread pool using tokio::task::spawn_blocking. Note that you
 // will need to update the input parameters to be String types instead of &str
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: String,
     password_candidate: String,
 ) -> Result<()> {
+    let _permit = PASSWORD_HASHING.acquire().await?;
     let current_span: tracing::Span = tracing::Span::current();
     let result = tokio::task::spawn_blocking(move || {
         current_span.in_scope(|| {
@@ -140,7 +804,8 @@ async fn verify_password_hash(
 // separate thread pool using tokio::task::spawn_blocking. Note that you
 // will need to update the input parameters to be String types instead of &str
 #[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+pub(crate) async fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+    let _permit = PASSWORD_HASHING.acquire().await?;
     let current_span: tracing::Span = tracing::Span::current();
     let compute_password_hash_task = tokio::task::spawn_blocking(move || {
         current_span.in_scope(|| {
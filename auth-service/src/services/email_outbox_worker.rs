@@ -0,0 +1,413 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{sync::Notify, task::JoinHandle};
+
+use crate::{
+    app_state::state::{EmailClientType, EmailOutboxType},
+    domain::{
+        data_stores::EmailOutboxError,
+        email_outbox::{OutboxMessage, RetryPolicy},
+    },
+    services::outbox_email_client::OutboxEmailClient,
+    utils::constants::{prod, EMAIL_OUTBOX_MAX_ATTEMPTS},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboxWorkerSettings {
+    /// Messages claimed per round
+    pub batch_size: u32,
+    /// How often the outbox is checked when nothing wakes the worker up
+    pub poll_interval: Duration,
+    /// How long a claimed message is hidden from other workers.
+    /// Must be longer than the email client takes to give up on a message.
+    pub lease: Duration,
+    pub retry: RetryPolicy,
+}
+
+impl OutboxWorkerSettings {
+    /// Settings from the environment, with defaults for what is not set
+    pub fn configured() -> Self {
+        Self {
+            batch_size: prod::email_outbox::BATCH_SIZE,
+            poll_interval: prod::email_outbox::POLL_INTERVAL,
+            lease: prod::email_outbox::LEASE,
+            retry: RetryPolicy {
+                max_attempts: *EMAIL_OUTBOX_MAX_ATTEMPTS,
+                base_backoff: prod::email_outbox::BASE_BACKOFF,
+                max_backoff: prod::email_outbox::MAX_BACKOFF,
+            },
+        }
+    }
+}
+
+/// What a round of deliveries did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub sent: usize,
+    /// Failed, and scheduled for another attempt
+    pub retried: usize,
+    /// Failed for the last time
+    pub dead_lettered: usize,
+}
+
+impl DeliveryReport {
+    pub fn claimed(&self) -> usize {
+        self.sent + self.retried + self.dead_lettered
+    }
+}
+
+/// Delivers the messages of an `EmailOutbox` with the real email client,
+/// retrying failures with exponential backoff and dead-lettering messages
+/// that keep failing.
+pub struct EmailOutboxWorker {
+    outbox: EmailOutboxType,
+    email_client: EmailClientType,
+    settings: OutboxWorkerSettings,
+    wakeup: Arc<Notify>,
+}
+
+impl EmailOutboxWorker {
+    pub fn new(
+        outbox: EmailOutboxType,
+        email_client: EmailClientType,
+        settings: OutboxWorkerSettings,
+    ) -> Self {
+        Self {
+            outbox,
+            email_client,
+            settings,
+            wakeup: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Client for the request path: it writes to the outbox and wakes this worker up
+    pub fn outbox_client(&self) -> OutboxEmailClient {
+        OutboxEmailClient::new(self.outbox.clone(), self.wakeup.clone())
+    }
+
+    /// Claim one batch of due messages and try to deliver each of them once.
+    /// An outcome that cannot be recorded does not hold up the rest of the batch;
+    /// that message is due again once its lease runs out.
+    #[tracing::instrument(name = "Delivering outbox emails", skip_all)]
+    pub async fn run_once(&self) -> Result<DeliveryReport, EmailOutboxError> {
+        let messages = self
+            .outbox
+            .claim_due(self.settings.batch_size, self.settings.lease)
+            .await?;
+
+        let mut report = DeliveryReport::default();
+        for message in messages {
+            let id = message.id;
+            if let Err(e) = self.deliver(message, &mut report).await {
+                tracing::error!(id = %id, error = ?e, "Failed to record the outcome of an outbox email");
+            }
+        }
+        Ok(report)
+    }
+
+    async fn deliver(
+        &self,
+        message: OutboxMessage,
+        report: &mut DeliveryReport,
+    ) -> Result<(), EmailOutboxError> {
+        let error = match self
+            .email_client
+            .send_email(&message.recipient, &message.message)
+            .await
+        {
+            Ok(()) => {
+                report.sent += 1;
+                return self.outbox.mark_sent(&message.id).await;
+            }
+            Err(e) => format!("{:#}", e),
+        };
+
+        match self.settings.retry.backoff(message.attempts) {
+            Some(backoff) => {
+                tracing::warn!(
+                    id = %message.id,
+                    attempts = message.attempts,
+                    error,
+                    "Failed to deliver email, retrying in {:?}",
+                    backoff
+                );
+                report.retried += 1;
+                let retry_at = Utc::now()
+                    + chrono::Duration::from_std(backoff)
+                        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+                self.outbox
+                    .mark_failed(&message.id, &error, Some(retry_at))
+                    .await
+            }
+            None => {
+                tracing::error!(
+                    id = %message.id,
+                    attempts = message.attempts,
+                    error,
+                    "Failed to deliver email, giving up"
+                );
+                report.dead_lettered += 1;
+                self.outbox.mark_failed(&message.id, &error, None).await
+            }
+        }
+    }
+
+    /// Deliver whenever a message is enqueued through `outbox_client`,
+    /// and every `poll_interval` for retries and other instances' messages,
+    /// for as long as the runtime is alive
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match self.run_once().await {
+                    // More messages may be due already
+                    Ok(report) if report.claimed() >= self.settings.batch_size as usize => continue,
+                    Ok(report) if report.claimed() > 0 => {
+                        tracing::debug!(?report, "Delivered outbox emails")
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::error!(error = ?e, "Failed to deliver outbox emails"),
+                }
+                tokio::select! {
+                    _ = self.wakeup.notified() => {}
+                    _ = tokio::time::sleep(self.settings.poll_interval) => {}
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{
+            data_stores::{EmailOutbox, OutboxPage, OutboxQuery},
+            email::Email,
+            email_client::{EmailClient, EmailMessage},
+            email_outbox::{OutboxMessageId, OutboxStatus},
+            parse::Parseable,
+        },
+        services::hashmap_email_outbox::HashMapEmailOutbox,
+    };
+    use color_eyre::eyre::{eyre, Result};
+    use secrecy::Secret;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails the first `failures` sends, then succeeds
+    struct FlakyEmailClient {
+        failures: usize,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for FlakyEmailClient {
+        async fn send_email(&self, _recipient: &Email, _message: &EmailMessage) -> Result<()> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(eyre!("Provider unavailable"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn worker(failures: usize, max_attempts: u32) -> (EmailOutboxWorker, Arc<HashMapEmailOutbox>) {
+        let outbox = Arc::new(HashMapEmailOutbox::default());
+        let client = Arc::new(FlakyEmailClient {
+            failures,
+            calls: AtomicUsize::new(0),
+        });
+        let settings = OutboxWorkerSettings {
+            batch_size: 10,
+            poll_interval: Duration::from_secs(60),
+            lease: Duration::from_secs(60),
+            retry: RetryPolicy {
+                max_attempts,
+                base_backoff: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+            },
+        };
+        (
+            EmailOutboxWorker::new(outbox.clone(), client, settings),
+            outbox,
+        )
+    }
+
+    async fn send(worker: &EmailOutboxWorker) {
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+        let message = EmailMessage {
+            subject: "Subject".to_owned(),
+            html_body: "<p>Body</p>".to_owned(),
+            text_body: "Body".to_owned(),
+        };
+        worker
+            .outbox_client()
+            .send_email(&recipient, &message)
+            .await
+            .unwrap();
+    }
+
+    /// Fails to record the first message as sent, like a store that drops a connection
+    #[derive(Default)]
+    struct ForgetfulOutbox {
+        inner: HashMapEmailOutbox,
+        failed: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailOutbox for ForgetfulOutbox {
+        async fn enqueue(
+            &self,
+            recipient: &Email,
+            message: &EmailMessage,
+        ) -> Result<OutboxMessageId, EmailOutboxError> {
+            self.inner.enqueue(recipient, message).await
+        }
+
+        async fn claim_due(
+            &self,
+            limit: u32,
+            lease: Duration,
+        ) -> Result<Vec<OutboxMessage>, EmailOutboxError> {
+            self.inner.claim_due(limit, lease).await
+        }
+
+        async fn mark_sent(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+            if self.failed.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(EmailOutboxError::UnexpectedError(eyre!("Connection reset")));
+            }
+            self.inner.mark_sent(id).await
+        }
+
+        async fn mark_failed(
+            &self,
+            id: &OutboxMessageId,
+            error: &str,
+            retry_at: Option<chrono::DateTime<Utc>>,
+        ) -> Result<(), EmailOutboxError> {
+            self.inner.mark_failed(id, error, retry_at).await
+        }
+
+        async fn get(&self, id: &OutboxMessageId) -> Result<OutboxMessage, EmailOutboxError> {
+            self.inner.get(id).await
+        }
+
+        async fn list(&self, query: &OutboxQuery) -> Result<OutboxPage, EmailOutboxError> {
+            self.inner.list(query).await
+        }
+
+        async fn requeue(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+            self.inner.requeue(id).await
+        }
+    }
+
+    async fn only_message(outbox: &HashMapEmailOutbox) -> OutboxMessage {
+        let page = outbox.list(&Default::default()).await.unwrap();
+        assert_eq!(page.total, 1);
+        page.messages[0].clone()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_and_forgets_the_bodies() {
+        let (worker, outbox) = worker(0, 3);
+        send(&worker).await;
+
+        let report = worker.run_once().await.unwrap();
+        assert_eq!(report.sent, 1);
+        let message = only_message(&outbox).await;
+        assert_eq!(message.status, OutboxStatus::Sent);
+        assert_eq!(message.attempts, 1);
+        assert!(message.sent_at.is_some());
+        assert!(message.message.text_body.is_empty());
+        assert_eq!(message.message.subject, "Subject");
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_delivered() {
+        let (worker, outbox) = worker(2, 3);
+        send(&worker).await;
+
+        assert_eq!(worker.run_once().await.unwrap().retried, 1);
+        let message = only_message(&outbox).await;
+        assert_eq!(message.status, OutboxStatus::Pending);
+        assert_eq!(message.last_error.as_deref(), Some("Provider unavailable"));
+
+        assert_eq!(worker.run_once().await.unwrap().retried, 1);
+        assert_eq!(worker.run_once().await.unwrap().sent, 1);
+        let message = only_message(&outbox).await;
+        assert_eq!(message.status, OutboxStatus::Sent);
+        assert_eq!(message.attempts, 3);
+        assert_eq!(message.last_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_after_the_last_attempt() {
+        let (worker, outbox) = worker(usize::MAX, 2);
+        send(&worker).await;
+
+        assert_eq!(worker.run_once().await.unwrap().retried, 1);
+        assert_eq!(worker.run_once().await.unwrap().dead_lettered, 1);
+        assert_eq!(worker.run_once().await.unwrap(), DeliveryReport::default());
+        let message = only_message(&outbox).await;
+        assert_eq!(message.status, OutboxStatus::Dead);
+        assert_eq!(message.attempts, 2);
+        // Kept, so the message can still be retried
+        assert_eq!(message.message.text_body, "Body");
+    }
+
+    #[tokio::test]
+    async fn test_backoff_delays_the_retry() {
+        let (mut worker, outbox) = worker(1, 3);
+        worker.settings.retry.base_backoff = Duration::from_secs(3600);
+        worker.settings.retry.max_backoff = Duration::from_secs(3600);
+        send(&worker).await;
+
+        assert_eq!(worker.run_once().await.unwrap().retried, 1);
+        assert_eq!(worker.run_once().await.unwrap(), DeliveryReport::default());
+        let message = only_message(&outbox).await;
+        assert!(message.next_attempt_at > Utc::now() + chrono::Duration::minutes(59));
+    }
+
+    #[tokio::test]
+    async fn test_keeps_delivering_the_batch_when_an_outcome_is_not_recorded() {
+        let (worker, _) = worker(0, 3);
+        let outbox = Arc::new(ForgetfulOutbox::default());
+        let worker =
+            EmailOutboxWorker::new(outbox.clone(), worker.email_client.clone(), worker.settings);
+        send(&worker).await;
+        send(&worker).await;
+
+        let report = worker.run_once().await.unwrap();
+        assert_eq!(report.sent, 2);
+        let page = outbox.inner.list(&Default::default()).await.unwrap();
+        let statuses: Vec<OutboxStatus> =
+            page.messages.iter().map(|message| message.status).collect();
+        assert!(statuses.contains(&OutboxStatus::Sent));
+        assert!(statuses.contains(&OutboxStatus::Pending));
+    }
+
+    #[tokio::test]
+    async fn test_spawned_worker_delivers_when_woken_up() {
+        let (worker, outbox) = worker(0, 3);
+        let client = worker.outbox_client();
+        let handle = worker.spawn();
+
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+        let message = EmailMessage {
+            subject: "Subject".to_owned(),
+            html_body: String::new(),
+            text_body: String::new(),
+        };
+        client.send_email(&recipient, &message).await.unwrap();
+
+        // The poll interval is a minute, so only the wakeup can deliver in time
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while only_message(&outbox).await.status != OutboxStatus::Sent {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The worker did not deliver the email");
+        handle.abort();
+    }
+}
@@ -0,0 +1,578 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use lazy_static::lazy_static;
+use minijinja::{
+    context,
+    value::{Kwargs, Value},
+    AutoEscape, Environment, ErrorKind, HtmlEscape, State, UndefinedBehavior,
+};
+use serde::Serialize;
+
+use crate::{
+    domain::{email_client::EmailMessage, locale::Locale},
+    utils::{
+        constants::{BRAND_COLOR, BRAND_NAME, BRAND_SUPPORT_EMAIL, BRAND_URL},
+        i18n,
+    },
+};
+
+lazy_static! {
+    /// Renderer with the configured branding, shared by all routes
+    pub static ref EMAIL_TEMPLATES: EmailTemplates = EmailTemplates::new(Branding::configured());
+}
+
+// Embedded so the binary does not depend on the working directory
+macro_rules! embed_templates {
+    ($($file:literal),* $(,)?) => {
+        &[$((
+            $file,
+            include_str!(concat!("../../templates/email/", $file)),
+        ),)*]
+    };
+}
+
+const SOURCES: &[(&str, &str)] = embed_templates![
+    "layout.html",
+    "layout.txt",
+    "macros.html",
+    "two_fa_code.subject.txt",
+    "two_fa_code.html",
+    "two_fa_code.txt",
+    "two_fa_code.sms.txt",
+    "verification.subject.txt",
+    "verification.html",
+    "verification.txt",
+    "password_reset.subject.txt",
+    "password_reset.html",
+    "password_reset.txt",
+    "security_alert.subject.txt",
+    "security_alert.html",
+    "security_alert.txt",
+    "invitation.subject.txt",
+    "invitation.html",
+    "invitation.txt",
+];
+
+/// Details shown in every email, available to templates as `brand`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Branding {
+    pub name: String,
+    pub url: String,
+    pub support_email: String,
+    /// CSS color of the header and buttons
+    pub color: String,
+}
+
+impl Branding {
+    /// Branding taken from the `BRAND_*` environment variables
+    pub fn configured() -> Self {
+        Self {
+            name: BRAND_NAME.clone(),
+            url: BRAND_URL.clone(),
+            support_email: BRAND_SUPPORT_EMAIL.clone(),
+            color: BRAND_COLOR.clone(),
+        }
+    }
+}
+
+/// Account changes users are warned about by email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityEvent {
+    NewLogin,
+    PasswordChanged,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    EmailChanged,
+}
+
+impl SecurityEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEvent::NewLogin => "new_login",
+            SecurityEvent::PasswordChanged => "password_changed",
+            SecurityEvent::TwoFactorEnabled => "two_factor_enabled",
+            SecurityEvent::TwoFactorDisabled => "two_factor_disabled",
+            SecurityEvent::EmailChanged => "email_changed",
+        }
+    }
+
+    /// Critical events could lock the user out, so they are reported
+    /// even to users who turned security notices off
+    /// ```
+    /// use auth_service::services::email_templates::SecurityEvent;
+    /// assert!(SecurityEvent::PasswordChanged.is_critical());
+    /// assert!(!SecurityEvent::NewLogin.is_critical());
+    /// ```
+    pub fn is_critical(&self) -> bool {
+        match self {
+            SecurityEvent::NewLogin | SecurityEvent::TwoFactorEnabled => false,
+            SecurityEvent::PasswordChanged
+            | SecurityEvent::TwoFactorDisabled
+            | SecurityEvent::EmailChanged => true,
+        }
+    }
+}
+
+/// A transactional email and the values it is rendered with
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmailTemplate {
+    /// Code that completes a login with 2FA
+    TwoFACode {
+        code: String,
+        expires_in_minutes: u64,
+    },
+    /// Link that confirms the user owns the address
+    Verification {
+        link: String,
+    },
+    PasswordReset {
+        link: String,
+    },
+    SecurityAlert {
+        event: SecurityEvent,
+        occurred_at: DateTime<Utc>,
+        ip: Option<String>,
+        /// Lets the user report the change if they did not make it
+        wasnt_me_link: Option<String>,
+    },
+    Invitation {
+        inviter: String,
+        organization: String,
+        link: String,
+        expires_in_days: i64,
+    },
+}
+
+impl EmailTemplate {
+    /// Prefix of the template files
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::TwoFACode { .. } => "two_fa_code",
+            EmailTemplate::Verification { .. } => "verification",
+            EmailTemplate::PasswordReset { .. } => "password_reset",
+            EmailTemplate::SecurityAlert { .. } => "security_alert",
+            EmailTemplate::Invitation { .. } => "invitation",
+        }
+    }
+
+    fn context(&self) -> Value {
+        match self {
+            EmailTemplate::TwoFACode {
+                code,
+                expires_in_minutes,
+            } => context! { code, expires_in_minutes },
+            EmailTemplate::Verification { link } | EmailTemplate::PasswordReset { link } => {
+                context! { link }
+            }
+            EmailTemplate::SecurityAlert {
+                event,
+                occurred_at,
+                ip,
+                wasnt_me_link,
+            } => context! {
+                event => event.as_str(),
+                occurred_at => occurred_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+                ip,
+                wasnt_me_link,
+            },
+            EmailTemplate::Invitation {
+                inviter,
+                organization,
+                link,
+                expires_in_days,
+            } => context! { inviter, organization, link, expires_in_days },
+        }
+    }
+}
+
+/// Renders every [`EmailTemplate`] into a subject, an HTML body and a
+/// plain-text body, in any [`Locale`].
+///
+/// Each template is three files under `templates/email`: `<name>.subject.txt`,
+/// `<name>.html` and `<name>.txt`. The HTML variant is autoescaped, the others
+/// are not. The rendered subject is collapsed onto a single line and is
+/// available to both bodies as `subject`.
+///
+/// Templates that can also go out as a text message, such as 2FA codes, have
+/// a fourth file, `<name>.sms.txt`. It is rendered onto a single line.
+///
+/// The files hold no text of their own. Every sentence comes from the message
+/// catalogs through `t("<key>", name=value, ...)`, which fills `{name}`
+/// placeholders, and `{brand}` with the brand name. Messages missing from a
+/// locale fall back to English, like the messages of API errors do.
+pub struct EmailTemplates {
+    envs: HashMap<Locale, Environment<'static>>,
+}
+
+impl EmailTemplates {
+    pub fn new(branding: Branding) -> Self {
+        let envs = Locale::ALL
+            .iter()
+            .map(|locale| (*locale, Self::environment(&branding, *locale)))
+            .collect();
+        Self { envs }
+    }
+
+    // The templates are compiled once per locale, so `t` and imported macros
+    // never have to be told which locale they render in
+    fn environment(branding: &Branding, locale: Locale) -> Environment<'static> {
+        let mut env = Environment::new();
+        env.set_trim_blocks(true);
+        env.set_lstrip_blocks(true);
+        // A typo in a template should fail loudly instead of leaving a blank
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_global("brand", Value::from_serialize(branding));
+        env.add_global("locale", locale.as_str());
+        let brand_name = branding.name.clone();
+        env.add_function("t", move |state: &State, key: &str, args: Kwargs| {
+            localized(state, locale, &brand_name, key, args)
+        });
+        for (file, source) in SOURCES {
+            env.add_template(file, source)
+                .unwrap_or_else(|e| panic!("Invalid email template {}: {}", file, e));
+        }
+        env
+    }
+
+    pub fn render(&self, template: &EmailTemplate, locale: Locale) -> Result<EmailMessage> {
+        let name = template.name();
+        let context = template.context();
+
+        let subject = self.render_file(locale, &format!("{}.subject.txt", name), &context)?;
+        let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+        let context = context! { subject, ..context };
+
+        Ok(EmailMessage {
+            html_body: self.render_file(locale, &format!("{}.html", name), &context)?,
+            text_body: self.render_file(locale, &format!("{}.txt", name), &context)?,
+            subject,
+        })
+    }
+
+    /// The text message variant of `template`, for a [`MessageClient`](crate::domain::message_client::MessageClient)
+    pub fn render_text_message(&self, template: &EmailTemplate, locale: Locale) -> Result<String> {
+        let file = format!("{}.sms.txt", template.name());
+        let text = self.render_file(locale, &file, &template.context())?;
+        Ok(text.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    fn render_file(&self, locale: Locale, file: &str, context: &Value) -> Result<String> {
+        self.envs[&locale]
+            .get_template(file)
+            .and_then(|template| template.render(context))
+            .wrap_err_with(|| format!("Failed to render email template {}/{}", locale, file))
+    }
+}
+
+/// The `t` function of the templates. In HTML the message and its values are
+/// escaped, except values that are already markup, such as macro output.
+fn localized(
+    state: &State,
+    locale: Locale,
+    brand_name: &str,
+    key: &str,
+    args: Kwargs,
+) -> Result<Value, minijinja::Error> {
+    let message = i18n::message(locale, key).ok_or_else(|| {
+        minijinja::Error::new(ErrorKind::UndefinedError, format!("no message for {}", key))
+    })?;
+    let html = matches!(state.auto_escape(), AutoEscape::Html);
+    let escape = |text: &str| match html {
+        true => HtmlEscape(text).to_string(),
+        false => text.to_owned(),
+    };
+
+    let mut output = String::new();
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        output.push_str(&escape(&rest[..start]));
+        let name = &rest[start + 1..end];
+        match name {
+            "brand" => output.push_str(&escape(brand_name)),
+            _ => {
+                let value: Value = args.get(name)?;
+                match value.is_safe() && html {
+                    true => output.push_str(&value.to_string()),
+                    false => output.push_str(&escape(&value.to_string())),
+                }
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    output.push_str(&escape(rest));
+    // A value the message does not use is most likely a typo
+    args.assert_all_used()?;
+
+    Ok(match html {
+        true => Value::from_safe_string(output),
+        false => Value::from(output),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn branding() -> Branding {
+        Branding {
+            name: "Acme".to_owned(),
+            url: "https://acme.test".to_owned(),
+            support_email: "help@acme.test".to_owned(),
+            color: "#ff6600".to_owned(),
+        }
+    }
+
+    fn templates() -> EmailTemplates {
+        EmailTemplates::new(branding())
+    }
+
+    fn rendered(template: EmailTemplate) -> String {
+        rendered_in(Locale::English, template)
+    }
+
+    // Everything about a rendered email in one snapshot
+    fn rendered_in(locale: Locale, template: EmailTemplate) -> String {
+        let message = templates().render(&template, locale).unwrap();
+        format!(
+            "Subject: {}\n\n----- text -----\n{}\n\n----- html -----\n{}",
+            message.subject, message.text_body, message.html_body
+        )
+    }
+
+    fn security_alert(event: SecurityEvent, wasnt_me_link: Option<&str>) -> EmailTemplate {
+        EmailTemplate::SecurityAlert {
+            event,
+            occurred_at: Utc.with_ymd_and_hms(2024, 10, 28, 9, 30, 0).unwrap(),
+            ip: Some("203.0.113.7".to_owned()),
+            wasnt_me_link: wasnt_me_link.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_two_fa_code() {
+        insta::assert_snapshot!(rendered(EmailTemplate::TwoFACode {
+            code: "123456".to_owned(),
+            expires_in_minutes: 10,
+        }));
+    }
+
+    #[test]
+    fn test_two_fa_code_text_message() {
+        let template = EmailTemplate::TwoFACode {
+            code: "123456".to_owned(),
+            expires_in_minutes: 10,
+        };
+        assert_eq!(
+            templates()
+                .render_text_message(&template, Locale::English)
+                .unwrap(),
+            "123456 is your Acme code. It expires in 10 minutes. Never share it with anyone."
+        );
+        assert!(templates()
+            .render_text_message(&template, Locale::German)
+            .unwrap()
+            .starts_with("123456 ist Ihr Acme-Code."));
+        // Only some templates have a text message
+        assert!(templates()
+            .render_text_message(
+                &EmailTemplate::Verification {
+                    link: "https://acme.test".to_owned()
+                },
+                Locale::English
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_verification() {
+        insta::assert_snapshot!(rendered(EmailTemplate::Verification {
+            link: "https://acme.test/verify?token=abc".to_owned(),
+        }));
+    }
+
+    #[test]
+    fn test_password_reset() {
+        insta::assert_snapshot!(rendered(EmailTemplate::PasswordReset {
+            link: "https://acme.test/reset?token=abc".to_owned(),
+        }));
+    }
+
+    #[test]
+    fn test_security_alert() {
+        insta::assert_snapshot!(rendered(security_alert(
+            SecurityEvent::NewLogin,
+            Some("https://acme.test/wasnt-me?token=abc"),
+        )));
+    }
+
+    #[test]
+    fn test_security_alert_without_link() {
+        insta::assert_snapshot!(rendered(EmailTemplate::SecurityAlert {
+            event: SecurityEvent::PasswordChanged,
+            occurred_at: Utc.with_ymd_and_hms(2024, 10, 28, 9, 30, 0).unwrap(),
+            ip: None,
+            wasnt_me_link: None,
+        }));
+    }
+
+    #[test]
+    fn test_security_alert_subjects() {
+        let subjects: Vec<String> = [
+            SecurityEvent::NewLogin,
+            SecurityEvent::PasswordChanged,
+            SecurityEvent::TwoFactorEnabled,
+            SecurityEvent::TwoFactorDisabled,
+            SecurityEvent::EmailChanged,
+        ]
+        .into_iter()
+        .map(|event| {
+            templates()
+                .render(&security_alert(event, None), Locale::English)
+                .unwrap()
+                .subject
+        })
+        .collect();
+        insta::assert_debug_snapshot!(subjects);
+    }
+
+    #[test]
+    fn test_invitation() {
+        insta::assert_snapshot!(rendered(EmailTemplate::Invitation {
+            inviter: "alice@example.com".to_owned(),
+            organization: "Rustaceans".to_owned(),
+            link: "https://acme.test/invitations/accept?token=abc".to_owned(),
+            expires_in_days: 7,
+        }));
+    }
+
+    #[test]
+    fn test_two_fa_code_in_german() {
+        insta::assert_snapshot!(rendered_in(
+            Locale::German,
+            EmailTemplate::TwoFACode {
+                code: "123456".to_owned(),
+                expires_in_minutes: 10,
+            }
+        ));
+    }
+
+    #[test]
+    fn test_security_alert_in_german() {
+        insta::assert_snapshot!(rendered_in(
+            Locale::German,
+            security_alert(
+                SecurityEvent::NewLogin,
+                Some("https://acme.test/wasnt-me?token=abc"),
+            )
+        ));
+    }
+
+    #[test]
+    fn test_german_subjects() {
+        let link = "https://acme.test/link?token=abc".to_owned();
+        let subjects: Vec<String> = [
+            EmailTemplate::Verification { link: link.clone() },
+            EmailTemplate::PasswordReset { link: link.clone() },
+            security_alert(SecurityEvent::TwoFactorDisabled, None),
+            EmailTemplate::Invitation {
+                inviter: "alice@example.com".to_owned(),
+                organization: "Rustaceans".to_owned(),
+                link,
+                expires_in_days: 7,
+            },
+        ]
+        .iter()
+        .map(|template| {
+            templates()
+                .render(template, Locale::German)
+                .unwrap()
+                .subject
+        })
+        .collect();
+        insta::assert_debug_snapshot!(subjects);
+    }
+
+    #[test]
+    fn test_every_template_renders_in_every_locale() {
+        let link = "https://acme.test/link?token=abc".to_owned();
+        let cases = [
+            EmailTemplate::TwoFACode {
+                code: "123456".to_owned(),
+                expires_in_minutes: 10,
+            },
+            EmailTemplate::Verification { link: link.clone() },
+            EmailTemplate::PasswordReset { link: link.clone() },
+            security_alert(SecurityEvent::EmailChanged, Some(&link)),
+            security_alert(SecurityEvent::TwoFactorEnabled, None),
+            EmailTemplate::Invitation {
+                inviter: "alice@example.com".to_owned(),
+                organization: "Rustaceans".to_owned(),
+                link,
+                expires_in_days: 7,
+            },
+        ];
+        for locale in Locale::ALL {
+            for template in &cases {
+                let message = templates()
+                    .render(template, *locale)
+                    .unwrap_or_else(|e| panic!("{} in {}: {:?}", template.name(), locale, e));
+                // Keys that are not in the catalogs would have failed to render,
+                // so no placeholder may be left unfilled either
+                for text in [&message.subject, &message.text_body, &message.html_body] {
+                    assert!(!text.contains("{brand}"), "{}", text);
+                }
+                assert!(message
+                    .html_body
+                    .contains(&format!("<html lang=\"{}\">", locale)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_escapes_html_but_not_text() {
+        let message = templates()
+            .render(
+                &EmailTemplate::Invitation {
+                    inviter: "alice@example.com".to_owned(),
+                    organization: "<b>Tom & Jerry</b>".to_owned(),
+                    link: "https://acme.test/invitations/accept?token=abc".to_owned(),
+                    expires_in_days: 7,
+                },
+                Locale::English,
+            )
+            .unwrap();
+        assert!(message
+            .html_body
+            .contains("&lt;b&gt;Tom &amp; Jerry&lt;&#x2f;b&gt;"));
+        assert!(!message.html_body.contains("<b>Tom"));
+        assert!(message.text_body.contains("<b>Tom & Jerry</b>"));
+        assert_eq!(
+            message.subject,
+            "You have been invited to join <b>Tom & Jerry</b>"
+        );
+    }
+
+    #[test]
+    fn test_values_are_not_interpolated() {
+        let message = templates()
+            .render(
+                &EmailTemplate::Invitation {
+                    inviter: "{brand}".to_owned(),
+                    organization: "{organization}".to_owned(),
+                    link: "https://acme.test/invitations/accept?token=abc".to_owned(),
+                    expires_in_days: 7,
+                },
+                Locale::German,
+            )
+            .unwrap();
+        assert!(message
+            .text_body
+            .contains("{brand} hat Sie eingeladen, {organization} bei Acme beizutreten."));
+    }
+}
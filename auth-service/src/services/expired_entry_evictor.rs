@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+/// An in-memory store whose entries expire
+pub trait EvictExpired: Send + Sync {
+    /// Free every expired entry, returning how many were removed
+    fn evict_expired(&self) -> usize;
+}
+
+/// Periodically frees expired entries of the in-memory stores.
+/// Reads already hide expired entries, so evicting only bounds memory use.
+pub struct ExpiredEntryEvictor {
+    stores: Vec<Arc<dyn EvictExpired>>,
+    interval: Duration,
+}
+
+impl ExpiredEntryEvictor {
+    pub fn new(stores: Vec<Arc<dyn EvictExpired>>, interval: Duration) -> Self {
+        Self { stores, interval }
+    }
+
+    /// Evict from every store, returning how many entries were removed
+    pub fn evict(&self) -> usize {
+        self.stores.iter().map(|store| store.evict_expired()).sum()
+    }
+
+    /// Evict every `interval` for as long as the runtime is alive
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let evicted = self.evict();
+                tracing::debug!(evicted, "Evicted expired entries");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{
+            data_stores::{
+                BannedTokenStore, HashsetBannedTokenStore, LoginAttemptId, TwoFACode,
+                TwoFACodeStore, TwoFACodeStoreError,
+            },
+            email::Email,
+            hashmap_two_fa_code_store::HashMapTwoFACodeStore,
+            parse::Parseable,
+        },
+        utils::{
+            auth::{TokenHash, TOKEN_TTL_SECONDS},
+            clock::ManualClock,
+            constants::TWO_FA_CODE_TTL_SECONDS,
+        },
+    };
+    use secrecy::Secret;
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_banned_tokens_and_revocations_expire() {
+        let clock = Arc::new(ManualClock::default());
+        let store = Arc::new(HashsetBannedTokenStore::with_clock(clock.clone()));
+        let evictor = ExpiredEntryEvictor::new(vec![store.clone()], Duration::from_secs(60));
+        let token = TokenHash::of(&Secret::new("token".to_owned()));
+        let alice = email("alice@example.com");
+        store
+            .insert(&token, Duration::from_secs(TOKEN_TTL_SECONDS as u64))
+            .await
+            .unwrap();
+        store.revoke_sessions(&alice, 1_000).await.unwrap();
+
+        clock.advance(Duration::from_secs(TOKEN_TTL_SECONDS as u64 - 1));
+        assert!(store.token_exists(&token).await.unwrap());
+        assert_eq!(
+            store.sessions_revoked_at(&alice).await.unwrap(),
+            Some(1_000)
+        );
+        assert_eq!(evictor.evict(), 0);
+
+        clock.advance(Duration::from_secs(1));
+        assert!(!store.token_exists(&token).await.unwrap());
+        assert_eq!(store.sessions_revoked_at(&alice).await.unwrap(), None);
+        assert_eq!(evictor.evict(), 2);
+        assert_eq!(evictor.evict(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_two_fa_codes_expire() {
+        let clock = Arc::new(ManualClock::default());
+        let store = Arc::new(HashMapTwoFACodeStore::with_clock(clock.clone()));
+        let evictor = ExpiredEntryEvictor::new(vec![store.clone()], Duration::from_secs(60));
+        let alice = email("alice@example.com");
+        let bob = email("bob@example.com");
+        store
+            .add_code(
+                alice.clone(),
+                LoginAttemptId::default(),
+                TwoFACode::default(),
+            )
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(TWO_FA_CODE_TTL_SECONDS / 2));
+        // A new code gets a fresh TTL
+        store
+            .add_code(bob.clone(), LoginAttemptId::default(), TwoFACode::default())
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(TWO_FA_CODE_TTL_SECONDS / 2));
+        assert_eq!(
+            store.get_code(&alice).await.err(),
+            Some(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert!(store.get_code(&bob).await.is_ok());
+        assert_eq!(evictor.evict(), 1);
+        assert!(store.get_code(&bob).await.is_ok());
+    }
+}
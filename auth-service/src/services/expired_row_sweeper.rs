@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tokio::{task::JoinHandle, time::MissedTickBehavior};
+
+// Tables of `PostgresBannedTokenStore` and `PostgresTwoFACodeStore`
+const EXPIRING_TABLES: &[&str] = &["banned_tokens", "revoked_sessions", "two_fa_codes"];
+
+/// Deletes expired rows of the Postgres-backed banned token and 2FA code stores.
+/// Reads already skip expired rows, so sweeping only keeps the tables small.
+pub struct ExpiredRowSweeper {
+    pool: PgPool,
+    interval: Duration,
+}
+
+impl ExpiredRowSweeper {
+    pub fn new(pool: PgPool, interval: Duration) -> Self {
+        Self { pool, interval }
+    }
+
+    /// Delete every expired row, returning how many were removed
+    #[tracing::instrument(name = "Sweeping expired rows", skip_all)]
+    pub async fn sweep(&self) -> Result<u64, sqlx::Error> {
+        let mut deleted = 0;
+        for table in EXPIRING_TABLES {
+            deleted += sqlx::query(&format!("DELETE FROM {} WHERE expires_at <= NOW()", table))
+                .execute(&self.pool)
+                .await?
+                .rows_affected();
+        }
+        Ok(deleted)
+    }
+
+    /// Sweep every `interval` for as long as the runtime is alive
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match self.sweep().await {
+                    Ok(deleted) => tracing::debug!(deleted, "Swept expired rows"),
+                    Err(e) => tracing::error!(error = ?e, "Failed to sweep expired rows"),
+                }
+            }
+        })
+    }
+}
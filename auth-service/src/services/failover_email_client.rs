@@ -0,0 +1,327 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{eyre, Result};
+
+use crate::{
+    app_state::state::EmailClientType,
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage},
+    },
+    utils::{
+        clock::{Clock, SystemClock},
+        constants::{EMAIL_CIRCUIT_COOLDOWN, EMAIL_CIRCUIT_FAILURE_THRESHOLD},
+    },
+};
+
+/// When a provider stops being tried, and for how long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long an open circuit skips the provider before it is tried again
+    pub cooldown: Duration,
+}
+
+impl CircuitBreakerSettings {
+    /// Settings from the environment, with defaults for what is not set
+    pub fn configured() -> Self {
+        Self {
+            failure_threshold: *EMAIL_CIRCUIT_FAILURE_THRESHOLD,
+            cooldown: *EMAIL_CIRCUIT_COOLDOWN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The provider is tried for every email
+    Closed,
+    /// The provider failed too often and is skipped until the cooldown ends
+    Open,
+    /// The cooldown ended and a single email is testing the provider
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half_open",
+        }
+    }
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a provider has been doing since the process started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderHealth {
+    pub name: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Circuit {
+    Closed,
+    Open { until: Instant },
+    // A trial that never reports back, e.g. because it was cancelled,
+    // stops blocking the provider after another cooldown
+    HalfOpen { since: Instant },
+}
+
+struct Provider {
+    name: String,
+    client: EmailClientType,
+    health: Mutex<(Circuit, ProviderHealth)>,
+}
+
+impl Provider {
+    /// Whether an email may be sent through the provider now
+    fn try_acquire(&self, now: Instant, settings: &CircuitBreakerSettings) -> bool {
+        let mut guard = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        let (circuit, health) = &mut *guard;
+        match *circuit {
+            Circuit::Closed => true,
+            Circuit::Open { until } if now < until => false,
+            Circuit::HalfOpen { since } if now < since + settings.cooldown => false,
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => {
+                tracing::info!(provider = %self.name, "Trying email provider again");
+                *circuit = Circuit::HalfOpen { since: now };
+                health.state = CircuitState::HalfOpen;
+                true
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut guard = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        let (circuit, health) = &mut *guard;
+        if !matches!(circuit, Circuit::Closed) {
+            tracing::info!(provider = %self.name, "Email provider recovered");
+        }
+        *circuit = Circuit::Closed;
+        health.state = CircuitState::Closed;
+        health.consecutive_failures = 0;
+        health.successes += 1;
+    }
+
+    fn record_failure(&self, now: Instant, settings: &CircuitBreakerSettings, error: String) {
+        let mut guard = self.health.lock().unwrap_or_else(PoisonError::into_inner);
+        let (circuit, health) = &mut *guard;
+        health.consecutive_failures += 1;
+        health.failures += 1;
+        health.last_error = Some(error);
+        let trial_failed = matches!(circuit, Circuit::HalfOpen { .. });
+        if trial_failed || health.consecutive_failures >= settings.failure_threshold {
+            tracing::warn!(
+                provider = %self.name,
+                consecutive_failures = health.consecutive_failures,
+                "Skipping email provider for {:?}",
+                settings.cooldown
+            );
+            *circuit = Circuit::Open {
+                until: now + settings.cooldown,
+            };
+            health.state = CircuitState::Open;
+        }
+    }
+}
+
+/// Sends each email through the first of a prioritized list of providers
+/// that accepts it. Every provider has a circuit breaker, so one that keeps
+/// failing is skipped for a while instead of delaying every email.
+pub struct FailoverEmailClient {
+    providers: Vec<Provider>,
+    settings: CircuitBreakerSettings,
+    clock: Arc<dyn Clock>,
+}
+
+impl FailoverEmailClient {
+    pub fn new(settings: CircuitBreakerSettings) -> Self {
+        Self::with_clock(settings, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(settings: CircuitBreakerSettings, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            providers: Vec::new(),
+            settings,
+            clock,
+        }
+    }
+
+    /// Add a provider, tried after every provider added before it
+    pub fn with_provider(mut self, name: impl Into<String>, client: EmailClientType) -> Self {
+        let name = name.into();
+        let health = ProviderHealth {
+            name: name.clone(),
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            successes: 0,
+            failures: 0,
+            last_error: None,
+        };
+        self.providers.push(Provider {
+            name,
+            client,
+            health: Mutex::new((Circuit::Closed, health)),
+        });
+        self
+    }
+
+    /// Health of every provider, in order of priority
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        self.providers
+            .iter()
+            .map(|provider| {
+                let guard = provider
+                    .health
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner);
+                guard.1.clone()
+            })
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for FailoverEmailClient {
+    #[tracing::instrument(name = "Sending email through failover chain", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let mut errors = Vec::new();
+        for provider in &self.providers {
+            if !provider.try_acquire(self.clock.now(), &self.settings) {
+                continue;
+            }
+            match provider.client.send_email(recipient, message).await {
+                Ok(()) => {
+                    provider.record_success();
+                    return Ok(());
+                }
+                Err(e) => {
+                    let error = format!("{:#}", e);
+                    tracing::warn!(provider = %provider.name, error, "Email provider failed");
+                    provider.record_failure(self.clock.now(), &self.settings, error.clone());
+                    errors.push(format!("{}: {}", provider.name, error));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Err(eyre!("Every email provider is unavailable"))
+        } else {
+            Err(eyre!("Every email provider failed ({})", errors.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{domain::parse::Parseable, utils::clock::ManualClock};
+    use secrecy::Secret;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct SwitchableEmailClient {
+        failing: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for SwitchableEmailClient {
+        async fn send_email(&self, _recipient: &Email, _message: &EmailMessage) -> Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.failing.load(Ordering::SeqCst) {
+                true => Err(eyre!("Service unavailable")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    async fn send(client: &FailoverEmailClient) -> Result<()> {
+        let recipient = Email::parse(Secret::new("user@example.com".to_owned())).unwrap();
+        let message = EmailMessage {
+            subject: "Subject".to_owned(),
+            html_body: String::new(),
+            text_body: String::new(),
+        };
+        client.send_email(&recipient, &message).await
+    }
+
+    #[tokio::test]
+    async fn test_half_open_circuit_lets_a_single_trial_through() {
+        let clock = Arc::new(ManualClock::default());
+        let provider = Arc::new(SwitchableEmailClient::default());
+        provider.failing.store(true, Ordering::SeqCst);
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 1,
+            cooldown: Duration::from_secs(30),
+        };
+        let client = FailoverEmailClient::with_clock(settings, clock.clone())
+            .with_provider("only", provider.clone());
+
+        assert!(send(&client).await.is_err());
+        assert_eq!(client.health()[0].state, CircuitState::Open);
+        // Skipped without being called while open
+        assert!(send(&client).await.is_err());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+
+        clock.advance(Duration::from_secs(30));
+        let now = clock.now();
+        assert!(client.providers[0].try_acquire(now, &settings));
+        assert!(!client.providers[0].try_acquire(now, &settings));
+        // A trial that never reports back does not block the provider forever
+        clock.advance(Duration::from_secs(30));
+        assert!(client.providers[0].try_acquire(clock.now(), &settings));
+    }
+
+    #[tokio::test]
+    async fn test_failed_trial_reopens_the_circuit() {
+        let clock = Arc::new(ManualClock::default());
+        let provider = Arc::new(SwitchableEmailClient::default());
+        provider.failing.store(true, Ordering::SeqCst);
+        let settings = CircuitBreakerSettings {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(30),
+        };
+        let client = FailoverEmailClient::with_clock(settings, clock.clone())
+            .with_provider("only", provider.clone());
+
+        for _ in 0..3 {
+            assert!(send(&client).await.is_err());
+        }
+        assert_eq!(client.health()[0].state, CircuitState::Open);
+
+        // One failed trial is enough to open the circuit again
+        clock.advance(Duration::from_secs(30));
+        assert!(send(&client).await.is_err());
+        let health = &client.health()[0];
+        assert_eq!(health.state, CircuitState::Open);
+        assert_eq!(health.failures, 4);
+        assert_eq!(health.last_error.as_deref(), Some("Service unavailable"));
+    }
+
+    #[tokio::test]
+    async fn test_without_providers_nothing_is_sent() {
+        let client = FailoverEmailClient::new(CircuitBreakerSettings {
+            failure_threshold: 1,
+            cooldown: Duration::from_secs(1),
+        });
+        assert!(send(&client).await.is_err());
+        assert!(client.health().is_empty());
+    }
+}
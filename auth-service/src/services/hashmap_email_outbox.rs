@@ -0,0 +1,170 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{PoisonError, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::{
+    data_stores::{EmailOutbox, EmailOutboxError, OutboxPage, OutboxQuery},
+    email::Email,
+    email_client::EmailMessage,
+    email_outbox::{OutboxMessage, OutboxMessageId, OutboxStatus},
+};
+
+/// Outbox for a single instance. Messages are lost when the process exits.
+#[derive(Default)]
+pub struct HashMapEmailOutbox {
+    messages: RwLock<HashMap<OutboxMessageId, OutboxMessage>>,
+}
+
+impl HashMapEmailOutbox {
+    fn update<T>(
+        &self,
+        id: &OutboxMessageId,
+        f: impl FnOnce(&mut OutboxMessage) -> Result<T, EmailOutboxError>,
+    ) -> Result<T, EmailOutboxError> {
+        let mut messages = self
+            .messages
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let message = messages
+            .get_mut(id)
+            .ok_or(EmailOutboxError::MessageNotFound)?;
+        f(message)
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for HashMapEmailOutbox {
+    async fn enqueue(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxMessageId, EmailOutboxError> {
+        let now = Utc::now();
+        let id = OutboxMessageId::new();
+        let message = OutboxMessage {
+            id,
+            recipient: recipient.clone(),
+            message: message.clone(),
+            status: OutboxStatus::Pending,
+            attempts: 0,
+            last_error: None,
+            next_attempt_at: now,
+            created_at: now,
+            sent_at: None,
+        };
+        self.messages
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, message);
+        Ok(id)
+    }
+
+    async fn claim_due(
+        &self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxMessage>, EmailOutboxError> {
+        let now = Utc::now();
+        let lease = chrono::Duration::from_std(lease)
+            .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        let mut messages = self
+            .messages
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut due: Vec<&mut OutboxMessage> = messages
+            .values_mut()
+            .filter(|message| {
+                message.status == OutboxStatus::Pending && message.next_attempt_at <= now
+            })
+            .collect();
+        due.sort_by_key(|message| message.next_attempt_at);
+
+        let mut claimed: Vec<OutboxMessage> = due
+            .into_iter()
+            .take(limit as usize)
+            .map(|message| {
+                message.attempts += 1;
+                message.next_attempt_at = now + lease;
+                message.clone()
+            })
+            .collect();
+        claimed.sort_by_key(|message| message.created_at);
+        Ok(claimed)
+    }
+
+    async fn mark_sent(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+        self.update(id, |message| {
+            message.status = OutboxStatus::Sent;
+            message.sent_at = Some(Utc::now());
+            message.last_error = None;
+            message.message.html_body.clear();
+            message.message.text_body.clear();
+            Ok(())
+        })
+    }
+
+    async fn mark_failed(
+        &self,
+        id: &OutboxMessageId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxError> {
+        self.update(id, |message| {
+            message.last_error = Some(error.to_owned());
+            match retry_at {
+                Some(retry_at) => {
+                    message.status = OutboxStatus::Pending;
+                    message.next_attempt_at = retry_at;
+                }
+                None => message.status = OutboxStatus::Dead,
+            }
+            Ok(())
+        })
+    }
+
+    async fn get(&self, id: &OutboxMessageId) -> Result<OutboxMessage, EmailOutboxError> {
+        self.messages
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(id)
+            .cloned()
+            .ok_or(EmailOutboxError::MessageNotFound)
+    }
+
+    async fn list(&self, query: &OutboxQuery) -> Result<OutboxPage, EmailOutboxError> {
+        let messages = self.messages.read().unwrap_or_else(PoisonError::into_inner);
+        let mut matching: Vec<&OutboxMessage> = messages
+            .values()
+            .filter(|message| query.status.is_none_or(|status| message.status == status))
+            .collect();
+        matching.sort_by_key(|message| Reverse(message.created_at));
+
+        let page = matching
+            .iter()
+            .skip(query.offset() as usize)
+            .take(query.per_page as usize)
+            .map(|message| (*message).clone())
+            .collect();
+        Ok(OutboxPage {
+            messages: page,
+            total: matching.len() as u64,
+        })
+    }
+
+    async fn requeue(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+        self.update(id, |message| {
+            if message.status != OutboxStatus::Dead {
+                return Err(EmailOutboxError::NotDeadLettered);
+            }
+            message.status = OutboxStatus::Pending;
+            message.attempts = 0;
+            message.next_attempt_at = Utc::now();
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{PoisonError, RwLock},
+};
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{
+        EmailSuppressionStore, EmailSuppressionStoreError, SuppressionPage, SuppressionQuery,
+    },
+    email::Email,
+    email_suppression::EmailSuppression,
+};
+
+#[derive(Default)]
+pub struct HashMapEmailSuppressionStore {
+    // Keyed by address
+    suppressions: RwLock<HashMap<String, EmailSuppression>>,
+}
+
+#[async_trait::async_trait]
+impl EmailSuppressionStore for HashMapEmailSuppressionStore {
+    async fn suppress(
+        &self,
+        suppression: &EmailSuppression,
+    ) -> Result<(), EmailSuppressionStoreError> {
+        self.suppressions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                suppression.email.as_ref().expose_secret().to_owned(),
+                suppression.clone(),
+            );
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        email: &Email,
+    ) -> Result<Option<EmailSuppression>, EmailSuppressionStoreError> {
+        Ok(self
+            .suppressions
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(email.as_ref().expose_secret())
+            .cloned())
+    }
+
+    async fn list(
+        &self,
+        query: &SuppressionQuery,
+    ) -> Result<SuppressionPage, EmailSuppressionStoreError> {
+        let suppressions = self
+            .suppressions
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        let mut all: Vec<&EmailSuppression> = suppressions.values().collect();
+        all.sort_by_key(|suppression| Reverse(suppression.suppressed_at));
+
+        let page = all
+            .iter()
+            .skip(query.offset() as usize)
+            .take(query.per_page as usize)
+            .map(|suppression| (*suppression).clone())
+            .collect();
+        Ok(SuppressionPage {
+            suppressions: page,
+            total: all.len() as u64,
+        })
+    }
+
+    async fn remove(&self, email: &Email) -> Result<(), EmailSuppressionStoreError> {
+        self.suppressions
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(email.as_ref().expose_secret())
+            .map(|_| ())
+            .ok_or(EmailSuppressionStoreError::SuppressionNotFound)
+    }
+}
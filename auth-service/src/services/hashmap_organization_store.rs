@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use secrecy::ExposeSecret;
+
+use crate::domain::{
+    data_stores::{OrganizationStore, OrganizationStoreError},
+    email::Email,
+    organization::{
+        Invitation, InvitationToken, Membership, Organization, OrganizationId, OrganizationName,
+    },
+    role::Role,
+};
+
+#[derive(Default)]
+pub struct HashMapOrganizationStore {
+    tenants: RwLock<Tenants>,
+}
+
+#[derive(Default)]
+struct Tenants {
+    organizations: HashMap<OrganizationId, Organization>,
+    // Memberships in the order they were created, with the roles held in each.
+    // A Vec keeps that order for `get_memberships`.
+    memberships: Vec<(OrganizationId, Email, Vec<Role>)>,
+    // Keyed by the exposed token, since secrets are not hashable
+    invitations: HashMap<String, Invitation>,
+}
+
+impl HashMapOrganizationStore {
+    fn read(&self) -> RwLockReadGuard<'_, Tenants> {
+        self.tenants.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Tenants> {
+        self.tenants.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Tenants {
+    fn ensure_organization_exists(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<(), OrganizationStoreError> {
+        if !self.organizations.contains_key(id) {
+            return Err(OrganizationStoreError::OrganizationNotFound);
+        }
+        Ok(())
+    }
+
+    fn membership(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+    ) -> Option<&(OrganizationId, Email, Vec<Role>)> {
+        self.memberships
+            .iter()
+            .find(|(member_of, member, _)| member_of == id && member == email)
+    }
+
+    fn membership_mut(
+        &mut self,
+        id: &OrganizationId,
+        email: &Email,
+    ) -> Option<&mut (OrganizationId, Email, Vec<Role>)> {
+        self.memberships
+            .iter_mut()
+            .find(|(member_of, member, _)| member_of == id && member == email)
+    }
+}
+
+// Keeps roles unique and ordered by name, like the Postgres store returns them
+fn grant(held: &mut Vec<Role>, roles: &[Role]) {
+    for role in roles {
+        if !held.contains(role) {
+            held.push(role.clone());
+        }
+    }
+    held.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+}
+
+#[async_trait::async_trait]
+impl OrganizationStore for HashMapOrganizationStore {
+    async fn create_organization(
+        &self,
+        name: OrganizationName,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let organization = Organization {
+            id: OrganizationId::new(),
+            name,
+        };
+        self.write()
+            .organizations
+            .insert(organization.id, organization.clone());
+        Ok(organization)
+    }
+
+    async fn get_organization(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<Organization, OrganizationStoreError> {
+        self.read()
+            .organizations
+            .get(id)
+            .cloned()
+            .ok_or(OrganizationStoreError::OrganizationNotFound)
+    }
+
+    async fn add_member(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        roles: &[Role],
+    ) -> Result<(), OrganizationStoreError> {
+        let mut tenants = self.write();
+        tenants.ensure_organization_exists(id)?;
+        match tenants.membership_mut(id, email) {
+            Some((_, _, held)) => grant(held, roles),
+            None => {
+                let mut held = Vec::new();
+                grant(&mut held, roles);
+                tenants.memberships.push((*id, email.clone(), held));
+            }
+        }
+        Ok(())
+    }
+
+    async fn revoke_member_role(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        role: &Role,
+    ) -> Result<(), OrganizationStoreError> {
+        let mut tenants = self.write();
+        let (_, _, held) = tenants
+            .membership_mut(id, email)
+            .ok_or(OrganizationStoreError::MemberNotFound)?;
+        held.retain(|held| held != role);
+        Ok(())
+    }
+
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError> {
+        let tenants = self.read();
+        tenants
+            .memberships
+            .iter()
+            .filter(|(_, member, _)| member == email)
+            .map(|(id, _, roles)| {
+                Ok(Membership {
+                    organization: tenants
+                        .organizations
+                        .get(id)
+                        .cloned()
+                        .ok_or(OrganizationStoreError::OrganizationNotFound)?,
+                    roles: roles.clone(),
+                })
+            })
+            .collect()
+    }
+
+    async fn get_member_roles(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+    ) -> Result<Vec<Role>, OrganizationStoreError> {
+        self.read()
+            .membership(id, email)
+            .map(|(_, _, roles)| roles.clone())
+            .ok_or(OrganizationStoreError::MemberNotFound)
+    }
+
+    async fn list_members(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<Vec<Email>, OrganizationStoreError> {
+        let mut members: Vec<Email> = self
+            .read()
+            .memberships
+            .iter()
+            .filter(|(member_of, _, _)| member_of == id)
+            .map(|(_, member, _)| member.clone())
+            .collect();
+        members.sort_by(|a, b| a.as_ref().expose_secret().cmp(b.as_ref().expose_secret()));
+        Ok(members)
+    }
+
+    async fn add_invitation(&self, invitation: Invitation) -> Result<(), OrganizationStoreError> {
+        let mut tenants = self.write();
+        tenants.ensure_organization_exists(&invitation.organization_id)?;
+        tenants.invitations.insert(
+            invitation.token.as_ref().expose_secret().to_owned(),
+            invitation,
+        );
+        Ok(())
+    }
+
+    async fn get_invitation(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<Invitation, OrganizationStoreError> {
+        self.read()
+            .invitations
+            .get(token.as_ref().expose_secret())
+            .cloned()
+            .ok_or(OrganizationStoreError::InvitationNotFound)
+    }
+
+    async fn mark_invitation_accepted(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<(), OrganizationStoreError> {
+        self.write()
+            .invitations
+            .get_mut(token.as_ref().expose_secret())
+            .ok_or(OrganizationStoreError::InvitationNotFound)?
+            .accepted = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::parse::Parseable;
+    use secrecy::Secret;
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    async fn store_with_organization(name: &str) -> (HashMapOrganizationStore, Organization) {
+        let store = HashMapOrganizationStore::default();
+        let organization = store
+            .create_organization(OrganizationName::parse(name.to_owned()).unwrap())
+            .await
+            .unwrap();
+        (store, organization)
+    }
+
+    #[tokio::test]
+    async fn test_memberships_and_roles() {
+        let (store, acme) = store_with_organization("Acme").await;
+        let globex = store
+            .create_organization(OrganizationName::parse("Globex".to_owned()).unwrap())
+            .await
+            .unwrap();
+        let alice = email("alice@example.com");
+
+        assert_eq!(
+            store.get_member_roles(&acme.id, &alice).await,
+            Err(OrganizationStoreError::MemberNotFound)
+        );
+        store.add_member(&acme.id, &alice, &[]).await.unwrap();
+        store
+            .add_member(&globex.id, &alice, &[Role::admin()])
+            .await
+            .unwrap();
+        // Adding an existing member grants the extra roles only once
+        store
+            .add_member(&acme.id, &alice, &[Role::admin(), Role::admin()])
+            .await
+            .unwrap();
+
+        let memberships = store.get_memberships(&alice).await.unwrap();
+        let names: Vec<&str> = memberships
+            .iter()
+            .map(|membership| membership.organization.name.as_ref())
+            .collect();
+        assert_eq!(names, vec!["Acme", "Globex"]);
+        assert_eq!(
+            store.get_member_roles(&acme.id, &alice).await.unwrap(),
+            vec![Role::admin()]
+        );
+
+        store
+            .revoke_member_role(&acme.id, &alice, &Role::admin())
+            .await
+            .unwrap();
+        assert!(store
+            .get_member_roles(&acme.id, &alice)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.list_members(&acme.id).await.unwrap(), vec![alice]);
+    }
+
+    #[tokio::test]
+    async fn test_add_member_to_unknown_organization() {
+        let store = HashMapOrganizationStore::default();
+        let result = store
+            .add_member(&OrganizationId::new(), &email("bob@example.com"), &[])
+            .await;
+        assert_eq!(result, Err(OrganizationStoreError::OrganizationNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_invitations() {
+        let (store, acme) = store_with_organization("Acme").await;
+        let invitation = Invitation::new(acme.id, email("bob@example.com"), None, "alice");
+        let token = invitation.token.clone();
+        assert_eq!(
+            store.get_invitation(&token).await,
+            Err(OrganizationStoreError::InvitationNotFound)
+        );
+
+        store.add_invitation(invitation.clone()).await.unwrap();
+        assert_eq!(store.get_invitation(&token).await.unwrap(), invitation);
+
+        store.mark_invitation_accepted(&token).await.unwrap();
+        assert!(store.get_invitation(&token).await.unwrap().accepted);
+    }
+}
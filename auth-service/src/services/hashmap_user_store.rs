@@ -1,46 +1,146 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
+use chrono::Utc;
 use secrecy::ExposeSecret;
 
 use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
+    data_stores::{UserPage, UserQuery, UserStore, UserStoreError},
     email::Email,
+    locale::Locale,
     password::Password,
-    user::User,
+    phone_number::PhoneNumber,
+    role::{Permission, Role},
+    user::{StatusChange, TwoFactorChannel, User, UserSummary},
 };
+use crate::utils::auth::TokenHash;
 
-#[derive(Debug, PartialEq, Default)]
+// Every map sits behind one lock, so changes spanning several of them are atomic
+#[derive(Debug, Default)]
 pub struct HashMapUserStore {
+    users: RwLock<Users>,
+}
+
+#[derive(Debug)]
+struct Users {
     user_store: HashMap<Email, User>,
+    user_roles: HashMap<Email, HashSet<Role>>,
+    role_permissions: HashMap<Role, HashSet<Permission>>,
+    // Latest status change per user, for the reason and actor
+    status_changes: HashMap<Email, StatusChange>,
+    devices: HashMap<Email, HashSet<TokenHash>>,
+    phone_verification_attempts: HashMap<Email, u32>,
+}
+
+impl Default for Users {
+    /// Mirrors the migrations by seeding the admin role with every permission,
+    /// and the organization admin role
+    fn default() -> Self {
+        Self {
+            user_store: HashMap::new(),
+            user_roles: HashMap::new(),
+            role_permissions: HashMap::from([
+                (Role::admin(), Permission::ALL.iter().copied().collect()),
+                (
+                    Role::organization_admin(),
+                    Permission::ORGANIZATION_ADMIN.iter().copied().collect(),
+                ),
+            ]),
+            status_changes: HashMap::new(),
+            devices: HashMap::new(),
+            phone_verification_attempts: HashMap::new(),
+        }
+    }
 }
 
 impl HashMapUserStore {
     pub fn count(&self) -> usize {
-        self.user_store.keys().len()
+        self.read().user_store.keys().len()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Users> {
+        self.users.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Users> {
+        self.users.write().unwrap_or_else(PoisonError::into_inner)
     }
+}
 
+impl Users {
     fn user_already_exists(&self, email: &Email) -> Result<(), UserStoreError> {
         if self.user_store.contains_key(email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
         Ok(())
     }
+
+    fn get_user_mut(&mut self, email: &Email) -> Result<&mut User, UserStoreError> {
+        self.user_store
+            .get_mut(email)
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    /// The user about to be changed, with `updated_at` already stamped
+    fn update_user(&mut self, email: &Email) -> Result<&mut User, UserStoreError> {
+        let user = self.get_user_mut(email)?;
+        user.updated_at = Utc::now();
+        Ok(user)
+    }
+
+    fn sorted_roles(&self, email: &Email) -> Vec<Role> {
+        let mut roles: Vec<Role> = self
+            .user_roles
+            .get(email)
+            .map(|roles| roles.iter().cloned().collect())
+            .unwrap_or_default();
+        roles.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        roles
+    }
+
+    fn summarize(&self, user: &User) -> UserSummary {
+        let change = self.status_changes.get(&user.email);
+        UserSummary {
+            email: user.email.clone(),
+            requires_2fa: user.requires_2fa,
+            status: user.status,
+            status_reason: change.and_then(|change| change.reason.clone()),
+            status_changed_by: change.map(|change| change.changed_by.clone()),
+            password_reset_required: user.password_reset_required,
+            roles: self.sorted_roles(&user.email),
+        }
+    }
+
+    fn ensure_user_and_role_exist(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        if !self.user_store.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if !self.role_permissions.contains_key(role) {
+            return Err(UserStoreError::RoleNotFound);
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashMapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let mut users = self.write();
         // Not the prettiest but it will work for now.
         // I assuming that this will be refactored in the future.
-        self.user_already_exists(&user.email)?;
-        self.user_store.insert(user.email.clone(), user);
+        users.user_already_exists(&user.email)?;
+        users.user_store.insert(user.email.clone(), user);
         Ok(())
     }
 
     /// Return the cloned version of the user if it exists.
     /// Otherwise, we return an Error
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        self.user_store
+        let users = self.read();
+        users
+            .user_store
             .get(email)
             .cloned()
             .ok_or_else(|| UserStoreError::UserNotFound)
@@ -50,14 +150,264 @@ impl UserStore for HashMapUserStore {
         &self,
         email: &Email,
         password: &Password,
-    ) -> Result<(), UserStoreError> {
-        let user = self
+    ) -> Result<User, UserStoreError> {
+        let users = self.read();
+        let user = users
             .user_store
             .get(email)
             .ok_or(UserStoreError::UserNotFound)?;
         if user.password.as_ref().expose_secret() != password.as_ref().expose_secret() {
-            return Err(UserStoreError::InvalidPassword);
+            return Err(UserStoreError::InvalidCredentials);
+        }
+        Ok(user.clone())
+    }
+
+    async fn assign_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.ensure_user_and_role_exist(email, role)?;
+        users
+            .user_roles
+            .entry(email.clone())
+            .or_default()
+            .insert(role.clone());
+        Ok(())
+    }
+
+    async fn revoke_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.ensure_user_and_role_exist(email, role)?;
+        if let Some(roles) = users.user_roles.get_mut(email) {
+            roles.remove(role);
+        }
+        Ok(())
+    }
+
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        let users = self.read();
+        Ok(users.sorted_roles(email))
+    }
+
+    async fn role_exists(&self, role: &Role) -> Result<bool, UserStoreError> {
+        let users = self.read();
+        Ok(users.role_permissions.contains_key(role))
+    }
+
+    async fn get_permissions(&self, roles: &[Role]) -> Result<HashSet<Permission>, UserStoreError> {
+        let users = self.read();
+        Ok(roles
+            .iter()
+            .filter_map(|role| users.role_permissions.get(role))
+            .flatten()
+            .copied()
+            .collect())
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let store = self.read();
+        let search = query.search.as_ref().map(|search| search.to_lowercase());
+        let mut users: Vec<&User> = store
+            .user_store
+            .values()
+            .filter(|user| match &query.emails {
+                Some(emails) => emails.contains(&user.email),
+                None => true,
+            })
+            .filter(|user| match &search {
+                Some(search) => user
+                    .email
+                    .as_ref()
+                    .expose_secret()
+                    .to_lowercase()
+                    .contains(search),
+                None => true,
+            })
+            .collect();
+        users.sort_by(|a, b| {
+            a.email
+                .as_ref()
+                .expose_secret()
+                .cmp(b.email.as_ref().expose_secret())
+        });
+
+        let total = users.len() as u64;
+        let users = users
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(query.per_page as usize)
+            .map(|user| store.summarize(user))
+            .collect();
+        Ok(UserPage { users, total })
+    }
+
+    async fn get_user_summary(&self, email: &Email) -> Result<UserSummary, UserStoreError> {
+        let users = self.read();
+        let user = users
+            .user_store
+            .get(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        Ok(users.summarize(user))
+    }
+
+    async fn set_status(&self, email: &Email, change: &StatusChange) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.status = change.status;
+        users.status_changes.insert(email.clone(), change.clone());
+        Ok(())
+    }
+
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.password_reset_required = required;
+        Ok(())
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.requires_2fa = requires_2fa;
+        Ok(())
+    }
+
+    async fn set_locale(
+        &self,
+        email: &Email,
+        locale: Option<Locale>,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.locale = locale;
+        Ok(())
+    }
+
+    async fn set_security_notices(
+        &self,
+        email: &Email,
+        enabled: bool,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.security_notices = enabled;
+        Ok(())
+    }
+
+    async fn set_phone_number(
+        &self,
+        email: &Email,
+        phone_number: Option<&PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        let user = users.update_user(email)?;
+        user.phone_number = phone_number.cloned();
+        user.phone_verified = false;
+        user.two_fa_channel = TwoFactorChannel::Email;
+        users.phone_verification_attempts.remove(email);
+        Ok(())
+    }
+
+    async fn set_phone_verified(
+        &self,
+        email: &Email,
+        verified: bool,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.phone_verified = verified;
+        Ok(())
+    }
+
+    async fn record_phone_verification_attempt(
+        &self,
+        email: &Email,
+    ) -> Result<u32, UserStoreError> {
+        let mut users = self.write();
+        users.get_user_mut(email)?;
+        let attempts = users
+            .phone_verification_attempts
+            .entry(email.clone())
+            .or_default();
+        *attempts = attempts.saturating_add(1);
+        Ok(*attempts)
+    }
+
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFactorChannel,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.update_user(email)?.two_fa_channel = channel;
+        Ok(())
+    }
+
+    async fn set_password(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        let user = users.update_user(email)?;
+        user.password = password.clone();
+        user.password_reset_required = false;
+        user.password_changed_at = user.updated_at;
+        Ok(())
+    }
+
+    async fn change_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        users.user_already_exists(new_email)?;
+        let mut user = users
+            .user_store
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        user.updated_at = Utc::now();
+        users.user_store.insert(new_email.clone(), user);
+        if let Some(roles) = users.user_roles.remove(email) {
+            users.user_roles.insert(new_email.clone(), roles);
         }
+        if let Some(change) = users.status_changes.remove(email) {
+            users.status_changes.insert(new_email.clone(), change);
+        }
+        if let Some(devices) = users.devices.remove(email) {
+            users.devices.insert(new_email.clone(), devices);
+        }
+        if let Some(attempts) = users.phone_verification_attempts.remove(email) {
+            users
+                .phone_verification_attempts
+                .insert(new_email.clone(), attempts);
+        }
+        Ok(())
+    }
+
+    async fn remember_device(
+        &self,
+        email: &Email,
+        device: &TokenHash,
+    ) -> Result<bool, UserStoreError> {
+        let mut users = self.write();
+        if !users.user_store.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(users
+            .devices
+            .entry(email.clone())
+            .or_default()
+            .insert(device.clone()))
+    }
+
+    async fn record_login(&self, email: &Email, ip: Option<&str>) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        let user = users.get_user_mut(email)?;
+        user.last_login_at = Some(Utc::now());
+        user.last_login_ip = ip.map(str::to_owned);
+        user.failed_login_count = 0;
+        Ok(())
+    }
+
+    async fn record_failed_login(&self, email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.write();
+        let user = users.get_user_mut(email)?;
+        user.failed_login_count = user.failed_login_count.saturating_add(1);
         Ok(())
     }
 }
@@ -65,7 +415,12 @@ impl UserStore for HashMapUserStore {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::{parse::Parseable, password::Password, user::User};
+    use crate::domain::{
+        parse::Parseable,
+        password::Password,
+        role::Role,
+        user::{AccountStatus, User},
+    };
     use secrecy::{ExposeSecret, Secret};
     use test_case::test_case;
 
@@ -86,7 +441,7 @@ mod tests {
             requires_2fa,
             Password::parse(Secret::new("captain teemo".to_string())).unwrap(),
         );
-        let mut user_store = empty_hashmap_user_store();
+        let user_store = empty_hashmap_user_store();
         assert_eq!(user_store.count(), 0);
         user_store
             .add_user(user.clone())
@@ -112,7 +467,7 @@ mod tests {
             false,
             Password::parse(Secret::new("a valid password".to_string())).unwrap(),
         );
-        let mut user_store = empty_hashmap_user_store();
+        let user_store = empty_hashmap_user_store();
 
         // Case 1. Should fail if user does not exist
         let error = user_store.get_user(&email).await;
@@ -139,14 +494,14 @@ mod tests {
     #[tokio::test]
     async fn test_validate_user(email: Email, password: Password) {
         let user = User::new(email.clone(), false, password);
-        let mut user_store = empty_hashmap_user_store();
+        let user_store = empty_hashmap_user_store();
         let wrong_password = Password::parse(Secret::new("wrongPassword".to_string())).unwrap();
 
         // Case 1. UserStoreError::UserNotFound
         let error = user_store.validate_user(&email, &wrong_password).await;
         assert_eq!(error, Err(UserStoreError::UserNotFound));
 
-        // Case 2. UserStoreError::InvalidPassword
+        // Case 2. UserStoreError::InvalidCredentials
         user_store.add_user(user.clone()).await.unwrap();
         // Check to see whether password does not equal wrong password
         assert_ne!(
@@ -154,13 +509,121 @@ mod tests {
             wrong_password.as_ref().expose_secret()
         );
         let error = user_store.validate_user(&email, &wrong_password).await;
-        assert_eq!(error, Err(UserStoreError::InvalidPassword));
+        assert_eq!(error, Err(UserStoreError::InvalidCredentials));
 
         // Case 3: Ok
         let is_ok = user_store
             .validate_user(&email, &user.password)
             .await
             .is_ok();
-        assert_eq!(is_ok, true);
+        assert!(is_ok);
+    }
+
+    #[tokio::test]
+    async fn test_assign_and_revoke_role() {
+        let email = Email::parse(Secret::new("admin@gmail.com".to_string())).unwrap();
+        let user = User::new(
+            email.clone(),
+            false,
+            Password::parse(Secret::new("a valid password".to_string())).unwrap(),
+        );
+        let user_store = empty_hashmap_user_store();
+
+        // Case 1. Cannot assign roles to unknown users or unknown roles
+        let error = user_store.assign_role(&email, &Role::admin()).await;
+        assert_eq!(error, Err(UserStoreError::UserNotFound));
+        user_store.add_user(user).await.unwrap();
+        let unknown_role = Role::parse("ghost".to_string()).unwrap();
+        let error = user_store.assign_role(&email, &unknown_role).await;
+        assert_eq!(error, Err(UserStoreError::RoleNotFound));
+
+        // Case 2. Assigning twice is idempotent and grants the admin permissions
+        user_store
+            .assign_role(&email, &Role::admin())
+            .await
+            .unwrap();
+        user_store
+            .assign_role(&email, &Role::admin())
+            .await
+            .unwrap();
+        let roles = user_store.get_roles(&email).await.unwrap();
+        assert_eq!(roles, vec![Role::admin()]);
+        let permissions = user_store.get_permissions(&roles).await.unwrap();
+        assert!(permissions.contains(&Permission::RolesManage));
+
+        // Case 3. Revoking removes the role
+        user_store
+            .revoke_role(&email, &Role::admin())
+            .await
+            .unwrap();
+        assert!(user_store.get_roles(&email).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_users_searches_and_paginates() {
+        let user_store = empty_hashmap_user_store();
+        for name in ["alice", "bob", "carol", "ALINA"] {
+            let email = Email::parse(Secret::new(format!("{}@example.com", name))).unwrap();
+            let password = Password::parse(Secret::new("a valid password".to_string())).unwrap();
+            user_store
+                .add_user(User::new(email, false, password))
+                .await
+                .unwrap();
+        }
+
+        // Search is case-insensitive and results are ordered by email
+        let query = UserQuery::new(Some("ALI".to_string()), None, None);
+        let page = user_store.list_users(&query).await.unwrap();
+        let emails: Vec<&str> = page
+            .users
+            .iter()
+            .map(|user| user.email.as_ref().expose_secret().as_str())
+            .collect();
+        assert_eq!(page.total, 2);
+        assert_eq!(emails, vec!["alice@example.com", "alina@example.com"]);
+
+        // The second page of size 3 holds the last user
+        let query = UserQuery::new(None, Some(2), Some(3));
+        let page = user_store.list_users(&query).await.unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(page.users.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_admin_flags() {
+        let email = Email::parse(Secret::new("flags@example.com".to_string())).unwrap();
+        let user_store = empty_hashmap_user_store();
+        let suspension = StatusChange::new(AccountStatus::Suspended, "admin@example.com")
+            .with_reason(Some("Chargeback".to_owned()));
+        assert_eq!(
+            user_store.set_status(&email, &suspension).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        let password = Password::parse(Secret::new("a valid password".to_string())).unwrap();
+        user_store
+            .add_user(User::new(email.clone(), true, password))
+            .await
+            .unwrap();
+        user_store.set_status(&email, &suspension).await.unwrap();
+        user_store
+            .set_password_reset_required(&email, true)
+            .await
+            .unwrap();
+        user_store.set_requires_2fa(&email, false).await.unwrap();
+
+        let summary = user_store.get_user_summary(&email).await.unwrap();
+        assert_eq!(summary.status, AccountStatus::Suspended);
+        assert_eq!(summary.status_reason.as_deref(), Some("Chargeback"));
+        assert_eq!(
+            summary.status_changed_by.as_deref(),
+            Some("admin@example.com")
+        );
+        assert_eq!(
+            user_store.get_user(&email).await.unwrap().status,
+            AccountStatus::Suspended
+        );
+        assert!(summary.password_reset_required);
+        assert!(!summary.requires_2fa);
     }
 }
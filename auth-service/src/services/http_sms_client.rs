@@ -0,0 +1,151 @@
+use color_eyre::eyre::Result;
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::domain::{message_client::MessageClient, phone_number::PhoneNumber};
+
+/// Sends text messages through an SMS gateway with a plain JSON API:
+/// `POST {base_url}/messages` with a bearer token and `{from, to, text}`.
+/// Most providers offer such an endpoint or can be put behind a small adapter.
+pub struct HttpSmsClient {
+    http_client: Client,
+    base_url: String,
+    // Sender ID or number the message appears to come from
+    sender: String,
+    authorization_token: Secret<String>,
+}
+
+impl HttpSmsClient {
+    pub fn new(
+        base_url: String,
+        sender: String,
+        authorization_token: Secret<String>,
+        http_client: Client,
+    ) -> Self {
+        Self {
+            http_client,
+            base_url,
+            sender,
+            authorization_token,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageClient for HttpSmsClient {
+    #[tracing::instrument(name = "Sending SMS", skip_all)]
+    async fn send_message(&self, recipient: &PhoneNumber, text: &str) -> Result<()> {
+        let base = Url::parse(&self.base_url)?;
+        let url = base.join("/messages")?;
+
+        let request_body = SendMessageRequest {
+            from: &self.sender,
+            to: recipient.as_ref().expose_secret(),
+            text,
+        };
+
+        self.http_client
+            .post(url)
+            .bearer_auth(self.authorization_token.expose_secret())
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SendMessageRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    text: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::parse::Parseable;
+    use crate::utils::constants::test;
+
+    use super::*;
+    use fake::faker::lorem::en::Sentence;
+    use fake::{Fake, Faker};
+    use wiremock::matchers::{any, body_json, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn phone_number() -> PhoneNumber {
+        PhoneNumber::parse(Secret::new("+4915112345678".to_owned())).unwrap()
+    }
+
+    fn sms_client(base_url: String) -> HttpSmsClient {
+        let http_client = Client::builder()
+            .timeout(test::sms_client::TIMEOUT)
+            .build()
+            .unwrap();
+        HttpSmsClient::new(
+            base_url,
+            test::sms_client::SENDER.to_owned(),
+            Secret::new(Faker.fake()),
+            http_client,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_message_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+        let text: String = Sentence(1..5).fake();
+
+        Mock::given(header_exists("Authorization"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/messages"))
+            .and(method("POST"))
+            .and(body_json(serde_json::json!({
+                "from": test::sms_client::SENDER,
+                "to": "+4915112345678",
+                "text": text,
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_message(&phone_number(), &text).await;
+
+        assert!(outcome.is_ok());
+    }
+
+    #[tokio::test]
+    async fn send_message_fails_if_the_server_returns_500() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_message(&phone_number(), "code").await;
+
+        assert!(outcome.is_err());
+    }
+
+    #[tokio::test]
+    async fn send_message_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let sms_client = sms_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = sms_client.send_message(&phone_number(), "code").await;
+
+        assert!(outcome.is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    hash::Hash,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use lru::LruCache;
+
+use crate::{
+    domain::{data_stores::UserCache, email::Email, role::Role, user::User},
+    utils::clock::{Clock, SystemClock},
+};
+
+/// In-process [`UserCache`] holding at most `capacity` users and as many role
+/// lists. The least recently used entries are dropped first.
+pub struct LruUserCache {
+    users: Mutex<LruCache<Email, Entry<User>>>,
+    roles: Mutex<LruCache<Email, Entry<Vec<Role>>>>,
+    generations: Mutex<Generations>,
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+struct Entry<V> {
+    value: V,
+    generation: u64,
+    expires_at: Instant,
+}
+
+// Generations are numbered by invalidation, so they only ever grow.
+// Users whose generation was dropped share the latest one dropped, which
+// is at least as new as any they had.
+struct Generations {
+    latest: LruCache<Email, u64>,
+    invalidations: u64,
+    dropped: u64,
+}
+
+impl Generations {
+    fn of(&mut self, email: &Email) -> u64 {
+        self.latest.get(email).copied().unwrap_or(self.dropped)
+    }
+
+    fn bump(&mut self, email: &Email) {
+        self.invalidations += 1;
+        if let Some((dropped, generation)) = self.latest.push(email.clone(), self.invalidations) {
+            if &dropped != email {
+                self.dropped = self.dropped.max(generation);
+            }
+        }
+    }
+}
+
+impl LruUserCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self::with_clock(capacity, ttl, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(capacity: usize, ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            users: Mutex::new(LruCache::new(capacity)),
+            roles: Mutex::new(LruCache::new(capacity)),
+            generations: Mutex::new(Generations {
+                latest: LruCache::new(capacity),
+                invalidations: 0,
+                dropped: 0,
+            }),
+            ttl,
+            clock,
+        }
+    }
+}
+
+// Expired entries are removed when they are read
+fn get_fresh<K: Hash + Eq, V: Clone>(
+    cache: &Mutex<LruCache<K, Entry<V>>>,
+    key: &K,
+    generation: u64,
+    now: Instant,
+) -> Option<V> {
+    let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+    match cache.get(key) {
+        Some(entry) if entry.expires_at <= now => {
+            cache.pop(key);
+            None
+        }
+        Some(entry) if entry.generation == generation => Some(entry.value.clone()),
+        _ => None,
+    }
+}
+
+#[async_trait::async_trait]
+impl UserCache for LruUserCache {
+    async fn generation(&self, email: &Email) -> Result<u64> {
+        Ok(self
+            .generations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .of(email))
+    }
+
+    async fn get_user(&self, email: &Email, generation: u64) -> Result<Option<User>> {
+        Ok(get_fresh(&self.users, email, generation, self.clock.now()))
+    }
+
+    async fn set_user(&self, user: &User, generation: u64) -> Result<()> {
+        let entry = Entry {
+            value: user.clone(),
+            generation,
+            expires_at: self.clock.now() + self.ttl,
+        };
+        self.users
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(user.email.clone(), entry);
+        Ok(())
+    }
+
+    async fn get_roles(&self, email: &Email, generation: u64) -> Result<Option<Vec<Role>>> {
+        Ok(get_fresh(&self.roles, email, generation, self.clock.now()))
+    }
+
+    async fn set_roles(&self, email: &Email, roles: &[Role], generation: u64) -> Result<()> {
+        let entry = Entry {
+            value: roles.to_vec(),
+            generation,
+            expires_at: self.clock.now() + self.ttl,
+        };
+        self.roles
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(email.clone(), entry);
+        Ok(())
+    }
+
+    async fn invalidate(&self, email: &Email) -> Result<()> {
+        self.generations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .bump(email);
+        self.users
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop(email);
+        self.roles
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{parse::Parseable, password::Password};
+    use secrecy::Secret;
+
+    fn user(address: &str) -> User {
+        User::new(
+            Email::parse(Secret::new(address.to_owned())).unwrap(),
+            false,
+            Password::parse(Secret::new("password123".to_owned())).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_user_is_dropped() {
+        let cache = LruUserCache::new(2, Duration::from_secs(30));
+        let (alice, bob, carol) = (
+            user("alice@example.com"),
+            user("bob@example.com"),
+            user("carol@example.com"),
+        );
+        cache.set_user(&alice, 0).await.unwrap();
+        cache.set_user(&bob, 0).await.unwrap();
+        // Reading alice makes bob the least recently used
+        assert!(cache.get_user(&alice.email, 0).await.unwrap().is_some());
+        cache.set_user(&carol, 0).await.unwrap();
+
+        assert!(cache.get_user(&alice.email, 0).await.unwrap().is_some());
+        assert!(cache.get_user(&bob.email, 0).await.unwrap().is_none());
+        assert!(cache.get_user(&carol.email, 0).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_drops_user_and_roles() {
+        let cache = LruUserCache::new(10, Duration::from_secs(30));
+        let alice = user("alice@example.com");
+        cache.set_user(&alice, 0).await.unwrap();
+        cache
+            .set_roles(&alice.email, &[Role::admin()], 0)
+            .await
+            .unwrap();
+
+        cache.invalidate(&alice.email).await.unwrap();
+        assert!(cache.get_user(&alice.email, 0).await.unwrap().is_none());
+        assert!(cache.get_roles(&alice.email, 0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_users_read_before_an_invalidation_are_never_served() {
+        let cache = LruUserCache::new(1, Duration::from_secs(30));
+        let (alice, bob) = (user("alice@example.com"), user("bob@example.com"));
+        let generation = cache.generation(&alice.email).await.unwrap();
+        cache.invalidate(&alice.email).await.unwrap();
+        cache.set_user(&alice, generation).await.unwrap();
+        let current = cache.generation(&alice.email).await.unwrap();
+        assert!(cache
+            .get_user(&alice.email, current)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Still the case once alice's generation is no longer tracked
+        cache.invalidate(&bob.email).await.unwrap();
+        let current = cache.generation(&alice.email).await.unwrap();
+        assert!(cache
+            .get_user(&alice.email, current)
+            .await
+            .unwrap()
+            .is_none());
+        cache.set_user(&alice, current).await.unwrap();
+        assert!(cache
+            .get_user(&alice.email, current)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -1,4 +1,7 @@
-use crate::domain::{email::Email, email_client::EmailClient};
+use crate::domain::{
+    email::Email,
+    email_client::{EmailClient, EmailMessage},
+};
 use color_eyre::eyre::Result;
 
 #[derive(Default)]
@@ -6,13 +9,13 @@ pub struct MockEmailClient;
 
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
         // Our mock email client will simply log the recipient, subject, and content to standard output
         tracing::info!(
             "Sending email to {:?} with subject: {:?} and content: {:?}",
             recipient.as_ref(),
-            subject,
-            content
+            message.subject,
+            message.text_body
         );
 
         Ok(())
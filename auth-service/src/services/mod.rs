@@ -1,5 +1,29 @@
+pub mod caching_user_store;
+pub mod capturing_email_client;
 pub mod data_stores;
+pub mod email_outbox_worker;
+pub mod email_templates;
+pub mod expired_entry_evictor;
+pub mod expired_row_sweeper;
+pub mod failover_email_client;
+pub mod hashmap_email_outbox;
+pub mod hashmap_email_suppression_store;
+pub mod hashmap_organization_store;
 pub mod hashmap_user_store;
+pub mod http_sms_client;
+pub mod lru_user_cache;
 pub mod mock_email_client;
+pub mod outbox_email_client;
+pub mod postgres_audit_store;
+pub mod postgres_banned_token_store;
+pub mod postgres_email_outbox;
+pub mod postgres_email_suppression_store;
+pub mod postgres_organization_store;
+pub mod postgres_two_fa_code_store;
 pub mod postmark_email_client;
 pub mod redis_banned_token_store;
+pub mod redis_user_cache;
+pub mod smtp_email_client;
+pub mod sqlite_user_store;
+pub mod suppressing_email_client;
+pub mod vec_audit_store;
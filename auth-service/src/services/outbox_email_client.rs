@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::Result;
+use tokio::sync::Notify;
+
+use crate::{
+    app_state::state::EmailOutboxType,
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage},
+    },
+};
+
+/// Email client that only writes messages to the outbox and leaves delivery
+/// to `EmailOutboxWorker`. Sending succeeds as soon as the message is stored,
+/// whatever state the email provider is in.
+pub struct OutboxEmailClient {
+    outbox: EmailOutboxType,
+    wakeup: Arc<Notify>,
+}
+
+impl OutboxEmailClient {
+    /// `wakeup` is notified after every write, so the worker delivers
+    /// right away instead of at its next poll
+    pub fn new(outbox: EmailOutboxType, wakeup: Arc<Notify>) -> Self {
+        Self { outbox, wakeup }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for OutboxEmailClient {
+    #[tracing::instrument(name = "Enqueuing email", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let id = self.outbox.enqueue(recipient, message).await?;
+        tracing::debug!(%id, "Enqueued email");
+        self.wakeup.notify_one();
+        Ok(())
+    }
+}
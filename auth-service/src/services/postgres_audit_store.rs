@@ -0,0 +1,164 @@
+use chrono::DateTime;
+use color_eyre::eyre::{eyre, Context};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    audit::{AuditAction, AuditContext, AuditEvent, AuditOutcome},
+    data_stores::{AuditPage, AuditQuery, AuditStore, AuditStoreError},
+    email::Email,
+    parse::Parseable,
+};
+
+// Shared filter of the count and page queries.
+// $1 is the account the events involve, $2 the accounts they are restricted to
+// and $3 the action.
+const AUDIT_FILTER: &str = "
+    WHERE ($1::TEXT IS NULL OR actor = $1 OR target = $1)
+      AND ($2::TEXT[] IS NULL OR actor = ANY($2) OR target = ANY($2))
+      AND ($3::TEXT IS NULL OR action = $3)
+";
+
+pub struct PostgresAuditStore {
+    pool: PgPool,
+}
+
+impl PostgresAuditStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditStore for PostgresAuditStore {
+    #[tracing::instrument(name = "Appending audit event to PostgreSQL", skip_all)]
+    async fn append(&self, event: AuditEvent) -> Result<(), AuditStoreError> {
+        sqlx::query(
+            "
+            INSERT INTO audit_events
+            (occurred_at, actor, action, target, outcome, ip, user_agent, request_id)
+            VALUES (TO_TIMESTAMP($1::DOUBLE PRECISION / 1000000), $2, $3, $4, $5, $6, $7, $8)
+            ",
+        )
+        .bind(event.occurred_at.timestamp_micros())
+        .bind(&event.actor)
+        .bind(event.action.as_str())
+        .bind(
+            event
+                .target
+                .as_ref()
+                .map(|target| target.as_ref().expose_secret()),
+        )
+        .bind(event.outcome.as_str())
+        .bind(&event.context.ip)
+        .bind(&event.context.user_agent)
+        .bind(&event.context.request_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| AuditStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Querying audit events in PostgreSQL", skip_all)]
+    async fn query(&self, query: &AuditQuery) -> Result<AuditPage, AuditStoreError> {
+        let involving = query
+            .involving
+            .as_ref()
+            .map(|email| email.as_ref().expose_secret().as_str());
+        let emails: Option<Vec<&str>> = query.emails.as_ref().map(|emails| {
+            emails
+                .iter()
+                .map(|email| email.as_ref().expose_secret().as_str())
+                .collect()
+        });
+        let action = query.action.map(|action| action.as_str());
+
+        let (total,): (i64,) = sqlx::query_as(&format!(
+            "SELECT COUNT(*) FROM audit_events {}",
+            AUDIT_FILTER
+        ))
+        .bind(involving)
+        .bind(&emails)
+        .bind(action)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| AuditStoreError::UnexpectedError(e.into()))?;
+
+        let rows: Vec<AuditEventRow> = sqlx::query_as(&format!(
+            "
+            SELECT
+                (EXTRACT(EPOCH FROM occurred_at) * 1000000)::BIGINT AS occurred_at,
+                actor,
+                action,
+                target,
+                outcome,
+                ip,
+                user_agent,
+                request_id
+            FROM audit_events
+            {}
+            ORDER BY id DESC
+            LIMIT $4 OFFSET $5
+            ",
+            AUDIT_FILTER
+        ))
+        .bind(involving)
+        .bind(&emails)
+        .bind(action)
+        .bind(i64::from(query.per_page))
+        .bind(query.offset() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AuditStoreError::UnexpectedError(e.into()))?;
+
+        let events = rows
+            .into_iter()
+            .map(AuditEventRow::into_event)
+            .collect::<Result<_, _>>()?;
+        Ok(AuditPage {
+            events,
+            total: total as u64,
+        })
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditEventRow {
+    occurred_at: i64,
+    actor: Option<String>,
+    action: String,
+    target: Option<String>,
+    outcome: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+}
+
+impl AuditEventRow {
+    fn into_event(self) -> Result<AuditEvent, AuditStoreError> {
+        Ok(AuditEvent {
+            occurred_at: DateTime::from_timestamp_micros(self.occurred_at).ok_or_else(|| {
+                AuditStoreError::UnexpectedError(eyre!("Invalid audit event timestamp"))
+            })?,
+            actor: self.actor,
+            action: self
+                .action
+                .parse::<AuditAction>()
+                .map_err(AuditStoreError::UnexpectedError)?,
+            target: self
+                .target
+                .map(|target| Email::parse(Secret::new(target)).wrap_err("Cannot parse email"))
+                .transpose()
+                .map_err(AuditStoreError::UnexpectedError)?,
+            outcome: self
+                .outcome
+                .parse::<AuditOutcome>()
+                .map_err(AuditStoreError::UnexpectedError)?,
+            context: AuditContext {
+                ip: self.ip,
+                user_agent: self.user_agent,
+                request_id: self.request_id,
+            },
+        })
+    }
+}
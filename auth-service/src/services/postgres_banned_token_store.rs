@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        email::Email,
+    },
+    utils::auth::{TokenHash, TOKEN_TTL_SECONDS},
+};
+
+/// Banned tokens and session revocations kept in PostgreSQL for deployments
+/// without Redis. Rows carry an expiry like the Redis keys do: reads ignore
+/// expired rows, and `ExpiredRowSweeper` deletes them in the background.
+pub struct PostgresBannedTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresBannedTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for PostgresBannedTokenStore {
+    #[tracing::instrument(name = "Banning token in PostgreSQL", skip_all)]
+    async fn insert(&self, token: &TokenHash, ttl: Duration) -> Result<(), BannedTokenStoreError> {
+        // Banning a token again replaces its expiry, like SETEX in Redis
+        sqlx::query(
+            "
+            INSERT INTO banned_tokens (token_hash, expires_at)
+            VALUES ($1, NOW() + make_interval(secs => $2::DOUBLE PRECISION))
+            ON CONFLICT (token_hash) DO UPDATE SET expires_at = EXCLUDED.expires_at
+            ",
+        )
+        .bind(token.as_ref())
+        .bind(ttl.as_secs_f64())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking banned token in PostgreSQL", skip_all)]
+    async fn token_exists(&self, token: &TokenHash) -> Result<bool, BannedTokenStoreError> {
+        let (exists,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM banned_tokens WHERE token_hash = $1 AND expires_at > NOW())",
+        )
+        .bind(token.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Revoking sessions in PostgreSQL", skip_all)]
+    async fn revoke_sessions(
+        &self,
+        email: &Email,
+        revoked_at: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Tokens issued before the revocation expire within TOKEN_TTL_SECONDS,
+        // so the row does not need to outlive them.
+        sqlx::query(
+            "
+            INSERT INTO revoked_sessions (email, revoked_at, expires_at)
+            VALUES ($1, $2, NOW() + make_interval(secs => $3::DOUBLE PRECISION))
+            ON CONFLICT (email) DO UPDATE
+            SET revoked_at = EXCLUDED.revoked_at, expires_at = EXCLUDED.expires_at
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(revoked_at)
+        .bind(TOKEN_TTL_SECONDS)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Checking session revocation in PostgreSQL", skip_all)]
+    async fn sessions_revoked_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        let row: Option<(i64,)> = sqlx::query_as(
+            "SELECT revoked_at FROM revoked_sessions WHERE email = $1 AND expires_at > NOW()",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+        Ok(row.map(|(revoked_at,)| revoked_at))
+    }
+}
@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{EmailOutbox, EmailOutboxError, OutboxPage, OutboxQuery},
+    email::Email,
+    email_client::EmailMessage,
+    email_outbox::{OutboxMessage, OutboxMessageId, OutboxStatus},
+    parse::Parseable,
+};
+
+// Columns of `OutboxMessageRow`
+const OUTBOX_COLUMNS: &str = "
+    id::TEXT,
+    recipient,
+    subject,
+    html_body,
+    text_body,
+    status,
+    attempts,
+    last_error,
+    (EXTRACT(EPOCH FROM next_attempt_at) * 1000000)::BIGINT AS next_attempt_at,
+    (EXTRACT(EPOCH FROM created_at) * 1000000)::BIGINT AS created_at,
+    (EXTRACT(EPOCH FROM sent_at) * 1000000)::BIGINT AS sent_at
+";
+
+/// Outbox shared by every instance. Workers on different instances never
+/// claim the same message, since claims skip rows locked by another claim.
+pub struct PostgresEmailOutbox {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutbox {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for PostgresEmailOutbox {
+    #[tracing::instrument(name = "Enqueuing email in PostgreSQL", skip_all)]
+    async fn enqueue(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<OutboxMessageId, EmailOutboxError> {
+        let id = OutboxMessageId::new();
+        sqlx::query(
+            "
+            INSERT INTO email_outbox (id, recipient, subject, html_body, text_body)
+            VALUES ($1::UUID, $2, $3, $4, $5)
+            ",
+        )
+        .bind(id.to_string())
+        .bind(recipient.as_ref().expose_secret())
+        .bind(&message.subject)
+        .bind(&message.html_body)
+        .bind(&message.text_body)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(name = "Claiming due emails in PostgreSQL", skip_all)]
+    async fn claim_due(
+        &self,
+        limit: u32,
+        lease: Duration,
+    ) -> Result<Vec<OutboxMessage>, EmailOutboxError> {
+        let rows: Vec<OutboxMessageRow> = sqlx::query_as(&format!(
+            "
+            UPDATE email_outbox
+            SET attempts = attempts + 1,
+                next_attempt_at = NOW() + $2 * INTERVAL '1 microsecond'
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING {}
+            ",
+            OUTBOX_COLUMNS
+        ))
+        .bind(i64::from(limit))
+        .bind(lease.as_micros() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        let mut messages = rows
+            .into_iter()
+            .map(OutboxMessageRow::into_message)
+            .collect::<Result<Vec<_>, _>>()?;
+        // RETURNING does not keep the order of the subquery
+        messages.sort_by_key(|message| message.created_at);
+        Ok(messages)
+    }
+
+    #[tracing::instrument(name = "Marking email sent in PostgreSQL", skip_all)]
+    async fn mark_sent(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+        let result = sqlx::query(
+            "
+            UPDATE email_outbox
+            SET status = 'sent', sent_at = NOW(), last_error = NULL,
+                html_body = '', text_body = ''
+            WHERE id = $1::UUID
+            ",
+        )
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxError::MessageNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking email failed in PostgreSQL", skip_all)]
+    async fn mark_failed(
+        &self,
+        id: &OutboxMessageId,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), EmailOutboxError> {
+        let result = sqlx::query(
+            "
+            UPDATE email_outbox
+            SET status = CASE WHEN $3::BIGINT IS NULL THEN 'dead' ELSE 'pending' END,
+                last_error = $2,
+                next_attempt_at = COALESCE(
+                    TO_TIMESTAMP(0) + $3 * INTERVAL '1 microsecond',
+                    next_attempt_at
+                )
+            WHERE id = $1::UUID
+            ",
+        )
+        .bind(id.to_string())
+        .bind(error)
+        .bind(retry_at.map(|retry_at| retry_at.timestamp_micros()))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxError::MessageNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving email from PostgreSQL", skip_all)]
+    async fn get(&self, id: &OutboxMessageId) -> Result<OutboxMessage, EmailOutboxError> {
+        let row: OutboxMessageRow = sqlx::query_as(&format!(
+            "SELECT {} FROM email_outbox WHERE id = $1::UUID",
+            OUTBOX_COLUMNS
+        ))
+        .bind(id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?
+        .ok_or(EmailOutboxError::MessageNotFound)?;
+        row.into_message()
+    }
+
+    #[tracing::instrument(name = "Listing emails in PostgreSQL", skip_all)]
+    async fn list(&self, query: &OutboxQuery) -> Result<OutboxPage, EmailOutboxError> {
+        let status = query.status.map(|status| status.as_str());
+
+        let (total,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM email_outbox WHERE ($1::TEXT IS NULL OR status = $1)",
+        )
+        .bind(status)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        let rows: Vec<OutboxMessageRow> = sqlx::query_as(&format!(
+            "
+            SELECT {}
+            FROM email_outbox
+            WHERE ($1::TEXT IS NULL OR status = $1)
+            ORDER BY created_at DESC, id
+            LIMIT $2 OFFSET $3
+            ",
+            OUTBOX_COLUMNS
+        ))
+        .bind(status)
+        .bind(i64::from(query.per_page))
+        .bind(query.offset() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        let messages = rows
+            .into_iter()
+            .map(OutboxMessageRow::into_message)
+            .collect::<Result<_, _>>()?;
+        Ok(OutboxPage {
+            messages,
+            total: total as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "Requeuing email in PostgreSQL", skip_all)]
+    async fn requeue(&self, id: &OutboxMessageId) -> Result<(), EmailOutboxError> {
+        let result = sqlx::query(
+            "
+            UPDATE email_outbox
+            SET status = 'pending', attempts = 0, next_attempt_at = NOW()
+            WHERE id = $1::UUID AND status = 'dead'
+            ",
+        )
+        .bind(id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        if result.rows_affected() == 0 {
+            // Tell a missing message from one that is not dead-lettered
+            self.get(id).await?;
+            return Err(EmailOutboxError::NotDeadLettered);
+        }
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OutboxMessageRow {
+    id: String,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    status: String,
+    attempts: i32,
+    last_error: Option<String>,
+    next_attempt_at: i64,
+    created_at: i64,
+    sent_at: Option<i64>,
+}
+
+impl OutboxMessageRow {
+    fn into_message(self) -> Result<OutboxMessage, EmailOutboxError> {
+        let timestamp = |micros: i64| {
+            DateTime::from_timestamp_micros(micros)
+                .ok_or_else(|| EmailOutboxError::UnexpectedError(eyre!("Invalid outbox timestamp")))
+        };
+        Ok(OutboxMessage {
+            id: OutboxMessageId::parse(self.id).map_err(EmailOutboxError::UnexpectedError)?,
+            recipient: Email::parse(Secret::new(self.recipient))
+                .wrap_err("Cannot parse email")
+                .map_err(EmailOutboxError::UnexpectedError)?,
+            message: EmailMessage {
+                subject: self.subject,
+                html_body: self.html_body,
+                text_body: self.text_body,
+            },
+            status: self
+                .status
+                .parse::<OutboxStatus>()
+                .map_err(EmailOutboxError::UnexpectedError)?,
+            attempts: self.attempts.max(0) as u32,
+            last_error: self.last_error,
+            next_attempt_at: timestamp(self.next_attempt_at)?,
+            created_at: timestamp(self.created_at)?,
+            sent_at: self.sent_at.map(timestamp).transpose()?,
+        })
+    }
+}
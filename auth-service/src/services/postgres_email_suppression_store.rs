@@ -0,0 +1,153 @@
+use chrono::DateTime;
+use color_eyre::eyre::{eyre, Context};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{
+        EmailSuppressionStore, EmailSuppressionStoreError, SuppressionPage, SuppressionQuery,
+    },
+    email::Email,
+    email_suppression::{EmailSuppression, SuppressionReason},
+    parse::Parseable,
+};
+
+// Columns of `SuppressionRow`
+const SUPPRESSION_COLUMNS: &str = "
+    email,
+    reason,
+    description,
+    (EXTRACT(EPOCH FROM suppressed_at) * 1000000)::BIGINT AS suppressed_at
+";
+
+pub struct PostgresEmailSuppressionStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailSuppressionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSuppressionStore for PostgresEmailSuppressionStore {
+    #[tracing::instrument(name = "Suppressing email in PostgreSQL", skip_all)]
+    async fn suppress(
+        &self,
+        suppression: &EmailSuppression,
+    ) -> Result<(), EmailSuppressionStoreError> {
+        sqlx::query(
+            "
+            INSERT INTO email_suppressions (email, reason, description, suppressed_at)
+            VALUES ($1, $2, $3, TO_TIMESTAMP(0) + $4 * INTERVAL '1 microsecond')
+            ON CONFLICT (email) DO UPDATE
+            SET reason = EXCLUDED.reason,
+                description = EXCLUDED.description,
+                suppressed_at = EXCLUDED.suppressed_at
+            ",
+        )
+        .bind(suppression.email.as_ref().expose_secret())
+        .bind(suppression.reason.as_str())
+        .bind(&suppression.description)
+        .bind(suppression.suppressed_at.timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailSuppressionStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving email suppression from PostgreSQL", skip_all)]
+    async fn get(
+        &self,
+        email: &Email,
+    ) -> Result<Option<EmailSuppression>, EmailSuppressionStoreError> {
+        let row: Option<SuppressionRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM email_suppressions WHERE email = $1",
+            SUPPRESSION_COLUMNS
+        ))
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| EmailSuppressionStoreError::UnexpectedError(e.into()))?;
+        row.map(SuppressionRow::into_suppression).transpose()
+    }
+
+    #[tracing::instrument(name = "Listing email suppressions in PostgreSQL", skip_all)]
+    async fn list(
+        &self,
+        query: &SuppressionQuery,
+    ) -> Result<SuppressionPage, EmailSuppressionStoreError> {
+        let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM email_suppressions")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| EmailSuppressionStoreError::UnexpectedError(e.into()))?;
+
+        let rows: Vec<SuppressionRow> = sqlx::query_as(&format!(
+            "
+            SELECT {}
+            FROM email_suppressions
+            ORDER BY suppressed_at DESC, email
+            LIMIT $1 OFFSET $2
+            ",
+            SUPPRESSION_COLUMNS
+        ))
+        .bind(i64::from(query.per_page))
+        .bind(query.offset() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailSuppressionStoreError::UnexpectedError(e.into()))?;
+
+        let suppressions = rows
+            .into_iter()
+            .map(SuppressionRow::into_suppression)
+            .collect::<Result<_, _>>()?;
+        Ok(SuppressionPage {
+            suppressions,
+            total: total as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "Removing email suppression from PostgreSQL", skip_all)]
+    async fn remove(&self, email: &Email) -> Result<(), EmailSuppressionStoreError> {
+        let result = sqlx::query("DELETE FROM email_suppressions WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| EmailSuppressionStoreError::UnexpectedError(e.into()))?;
+        if result.rows_affected() == 0 {
+            return Err(EmailSuppressionStoreError::SuppressionNotFound);
+        }
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SuppressionRow {
+    email: String,
+    reason: String,
+    description: Option<String>,
+    suppressed_at: i64,
+}
+
+impl SuppressionRow {
+    fn into_suppression(self) -> Result<EmailSuppression, EmailSuppressionStoreError> {
+        Ok(EmailSuppression {
+            email: Email::parse(Secret::new(self.email))
+                .wrap_err("Cannot parse email")
+                .map_err(EmailSuppressionStoreError::UnexpectedError)?,
+            reason: self
+                .reason
+                .parse::<SuppressionReason>()
+                .map_err(EmailSuppressionStoreError::UnexpectedError)?,
+            description: self.description,
+            suppressed_at: DateTime::from_timestamp_micros(self.suppressed_at).ok_or_else(
+                || {
+                    EmailSuppressionStoreError::UnexpectedError(eyre!(
+                        "Invalid suppression timestamp"
+                    ))
+                },
+            )?,
+        })
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::DateTime;
+use color_eyre::eyre::{eyre, Context};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{OrganizationStore, OrganizationStoreError},
+    email::Email,
+    organization::{
+        Invitation, InvitationToken, Membership, Organization, OrganizationId, OrganizationName,
+    },
+    parse::Parseable,
+    role::Role,
+};
+
+pub struct PostgresOrganizationStore {
+    pool: PgPool,
+}
+
+impl PostgresOrganizationStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OrganizationStore for PostgresOrganizationStore {
+    #[tracing::instrument(name = "Creating organization in PostgreSQL", skip_all)]
+    async fn create_organization(
+        &self,
+        name: OrganizationName,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let organization = Organization {
+            id: OrganizationId::new(),
+            name,
+        };
+        sqlx::query("INSERT INTO organizations (id, name) VALUES ($1::UUID, $2)")
+            .bind(organization.id.to_string())
+            .bind(organization.name.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        Ok(organization)
+    }
+
+    #[tracing::instrument(name = "Retrieving organization from PostgreSQL", skip_all)]
+    async fn get_organization(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<Organization, OrganizationStoreError> {
+        let (id, name): (String, String) =
+            sqlx::query_as("SELECT id::TEXT, name FROM organizations WHERE id = $1::UUID")
+                .bind(id.to_string())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?
+                .ok_or(OrganizationStoreError::OrganizationNotFound)?;
+        parse_organization(id, name)
+    }
+
+    #[tracing::instrument(name = "Adding member in PostgreSQL", skip_all)]
+    async fn add_member(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        roles: &[Role],
+    ) -> Result<(), OrganizationStoreError> {
+        self.get_organization(id).await?;
+        let role_names: Vec<&str> = roles.iter().map(|role| role.as_ref()).collect();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        sqlx::query(
+            "
+            INSERT INTO memberships (organization_id, email)
+            VALUES ($1::UUID, $2)
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(id.to_string())
+        .bind(email.as_ref().expose_secret())
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        sqlx::query(
+            "
+            INSERT INTO membership_roles (organization_id, email, role)
+            SELECT $1::UUID, $2, role FROM UNNEST($3::TEXT[]) AS role
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(id.to_string())
+        .bind(email.as_ref().expose_secret())
+        .bind(&role_names)
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        transaction
+            .commit()
+            .await
+            .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Revoking member role in PostgreSQL", skip_all)]
+    async fn revoke_member_role(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+        role: &Role,
+    ) -> Result<(), OrganizationStoreError> {
+        self.get_member_roles(id, email).await?;
+        sqlx::query(
+            "
+            DELETE FROM membership_roles
+            WHERE organization_id = $1::UUID AND email = $2 AND role = $3
+            ",
+        )
+        .bind(id.to_string())
+        .bind(email.as_ref().expose_secret())
+        .bind(role.as_ref())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving memberships from PostgreSQL", skip_all)]
+    async fn get_memberships(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<Membership>, OrganizationStoreError> {
+        let rows: Vec<(String, String, Vec<String>)> = sqlx::query_as(
+            "
+            SELECT
+                organizations.id::TEXT,
+                organizations.name,
+                ARRAY(
+                    SELECT role FROM membership_roles
+                    WHERE membership_roles.organization_id = memberships.organization_id
+                      AND membership_roles.email = memberships.email
+                    ORDER BY role
+                )
+            FROM memberships
+            JOIN organizations ON organizations.id = memberships.organization_id
+            WHERE memberships.email = $1
+            ORDER BY memberships.created_at, organizations.name
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(id, name, roles)| {
+                Ok(Membership {
+                    organization: parse_organization(id, name)?,
+                    roles: parse_roles(roles)?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Retrieving member roles from PostgreSQL", skip_all)]
+    async fn get_member_roles(
+        &self,
+        id: &OrganizationId,
+        email: &Email,
+    ) -> Result<Vec<Role>, OrganizationStoreError> {
+        let (roles,): (Vec<String>,) = sqlx::query_as(
+            "
+            SELECT ARRAY(
+                SELECT role FROM membership_roles
+                WHERE organization_id = $1::UUID AND email = $2
+                ORDER BY role
+            )
+            FROM memberships
+            WHERE organization_id = $1::UUID AND email = $2
+            ",
+        )
+        .bind(id.to_string())
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?
+        .ok_or(OrganizationStoreError::MemberNotFound)?;
+        parse_roles(roles)
+    }
+
+    #[tracing::instrument(name = "Listing members in PostgreSQL", skip_all)]
+    async fn list_members(
+        &self,
+        id: &OrganizationId,
+    ) -> Result<Vec<Email>, OrganizationStoreError> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "
+            SELECT email
+            FROM memberships
+            WHERE organization_id = $1::UUID
+            ORDER BY email
+            ",
+        )
+        .bind(id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(email,)| parse_email(email))
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Adding invitation to PostgreSQL", skip_all)]
+    async fn add_invitation(&self, invitation: Invitation) -> Result<(), OrganizationStoreError> {
+        self.get_organization(&invitation.organization_id).await?;
+        sqlx::query(
+            "
+            INSERT INTO invitations (token, organization_id, email, role, invited_by, expires_at)
+            VALUES ($1, $2::UUID, $3, $4, $5, TO_TIMESTAMP($6))
+            ",
+        )
+        .bind(invitation.token.as_ref().expose_secret())
+        .bind(invitation.organization_id.to_string())
+        .bind(invitation.email.as_ref().expose_secret())
+        .bind(invitation.role.as_ref().map(|role| role.as_ref()))
+        .bind(&invitation.invited_by)
+        .bind(invitation.expires_at.timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving invitation from PostgreSQL", skip_all)]
+    async fn get_invitation(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<Invitation, OrganizationStoreError> {
+        let row: InvitationRow = sqlx::query_as(
+            "
+            SELECT
+                organization_id::TEXT,
+                email,
+                role,
+                invited_by,
+                EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at,
+                accepted_at IS NOT NULL AS accepted
+            FROM invitations
+            WHERE token = $1
+            ",
+        )
+        .bind(token.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?
+        .ok_or(OrganizationStoreError::InvitationNotFound)?;
+        row.into_invitation(token.clone())
+    }
+
+    #[tracing::instrument(name = "Accepting invitation in PostgreSQL", skip_all)]
+    async fn mark_invitation_accepted(
+        &self,
+        token: &InvitationToken,
+    ) -> Result<(), OrganizationStoreError> {
+        let result = sqlx::query("UPDATE invitations SET accepted_at = NOW() WHERE token = $1")
+            .bind(token.as_ref().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| OrganizationStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OrganizationStoreError::InvitationNotFound);
+        }
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct InvitationRow {
+    organization_id: String,
+    email: String,
+    role: Option<String>,
+    invited_by: String,
+    expires_at: i64,
+    accepted: bool,
+}
+
+impl InvitationRow {
+    fn into_invitation(self, token: InvitationToken) -> Result<Invitation, OrganizationStoreError> {
+        Ok(Invitation {
+            token,
+            organization_id: OrganizationId::parse(self.organization_id)
+                .map_err(OrganizationStoreError::UnexpectedError)?,
+            email: parse_email(self.email)?,
+            role: self
+                .role
+                .map(Role::parse)
+                .transpose()
+                .map_err(OrganizationStoreError::UnexpectedError)?,
+            invited_by: self.invited_by,
+            expires_at: DateTime::from_timestamp(self.expires_at, 0)
+                .ok_or_else(|| OrganizationStoreError::UnexpectedError(eyre!("Invalid expiry")))?,
+            accepted: self.accepted,
+        })
+    }
+}
+
+fn parse_organization(id: String, name: String) -> Result<Organization, OrganizationStoreError> {
+    Ok(Organization {
+        id: OrganizationId::parse(id).map_err(OrganizationStoreError::UnexpectedError)?,
+        name: OrganizationName::parse(name).map_err(OrganizationStoreError::UnexpectedError)?,
+    })
+}
+
+fn parse_email(email: String) -> Result<Email, OrganizationStoreError> {
+    Email::parse(Secret::new(email))
+        .wrap_err("Cannot parse email")
+        .map_err(OrganizationStoreError::UnexpectedError)
+}
+
+fn parse_roles(roles: Vec<String>) -> Result<Vec<Role>, OrganizationStoreError> {
+    roles
+        .into_iter()
+        .map(Role::parse)
+        .collect::<Result<_, _>>()
+        .map_err(OrganizationStoreError::UnexpectedError)
+}
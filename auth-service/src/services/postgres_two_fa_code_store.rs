@@ -0,0 +1,88 @@
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+        email::Email,
+    },
+    utils::constants::TWO_FA_CODE_TTL_SECONDS,
+};
+
+/// Pending 2FA codes kept in PostgreSQL for deployments without Redis,
+/// expiring like `PostgresBannedTokenStore` rows do
+pub struct PostgresTwoFACodeStore {
+    pool: PgPool,
+}
+
+impl PostgresTwoFACodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for PostgresTwoFACodeStore {
+    #[tracing::instrument(name = "Adding 2FA code to PostgreSQL", skip_all)]
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+    ) -> Result<(), TwoFACodeStoreError> {
+        // A new login replaces the pending code, like SETEX in Redis
+        sqlx::query(
+            "
+            INSERT INTO two_fa_codes (email, login_attempt_id, code, expires_at)
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4::DOUBLE PRECISION))
+            ON CONFLICT (email) DO UPDATE
+            SET login_attempt_id = EXCLUDED.login_attempt_id,
+                code = EXCLUDED.code,
+                expires_at = EXCLUDED.expires_at
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(login_attempt_id.as_ref().expose_secret())
+        .bind(code.as_ref().expose_secret())
+        .bind(TWO_FA_CODE_TTL_SECONDS as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing 2FA code from PostgreSQL", skip_all)]
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        sqlx::query("DELETE FROM two_fa_codes WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving 2FA code from PostgreSQL", skip_all)]
+    async fn get_code(
+        &self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        let (login_attempt_id, code): (String, String) = sqlx::query_as(
+            "
+            SELECT login_attempt_id, code
+            FROM two_fa_codes
+            WHERE email = $1 AND expires_at > NOW()
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TwoFACodeStoreError::UnexpectedError(e.into()))?
+        .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+
+        Ok((
+            LoginAttemptId::parse(login_attempt_id)
+                .map_err(TwoFACodeStoreError::UnexpectedError)?,
+            TwoFACode::parse(code).map_err(TwoFACodeStoreError::UnexpectedError)?,
+        ))
+    }
+}
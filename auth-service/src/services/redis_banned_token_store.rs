@@ -1,71 +1,124 @@
-use std::sync::Arc;
+use std::time::Duration;
 
 use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
-use secrecy::{ExposeSecret, Secret};
-use tokio::sync::RwLock;
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::ExposeSecret;
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        email::Email,
+    },
+    utils::auth::{TokenHash, TOKEN_TTL_SECONDS},
 };
 
+// The connection is multiplexed, so each command runs on a cheap clone of it
+// and concurrent requests never wait on one another.
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
-const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+// Keys hold the token hash, never the token itself.
+const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token_hash:";
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+// Revocation times are in milliseconds. Keys with the old prefix hold seconds,
+// and are still read until they expire, TOKEN_TTL_SECONDS after an upgrade.
+const REVOKED_SESSIONS_KEY_PREFIX: &str = "revoked_sessions_ms:";
+const LEGACY_REVOKED_SESSIONS_KEY_PREFIX: &str = "revoked_sessions:";
+
+fn get_key(token: &TokenHash) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token.as_ref())
+}
+
+fn get_revoked_sessions_key(prefix: &str, email: &Email) -> String {
+    format!("{}{}", prefix, email.as_ref().expose_secret())
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
     #[tracing::instrument("Insert banned token", skip_all)]
-    async fn insert(&mut self, token: Secret<String>) -> Result<(), BannedTokenStoreError> {
-        // 1. Create a new key using the get_key helper function.
-        let key = get_key(token.expose_secret());
-        // 2. Call the set_ex command on the Redis connection to set a new key/value pair with an expiration time (TTL).
-        // The value should simply be a `true` (boolean value).
-        // The expiration time should be set to TOKEN_TTL_SECONDS.
-        // NOTE: The TTL is expected to be a u64 so you will have to cast TOKEN_TTL_SECONDS to a u64.
-        // Return BannedTokenStoreError::UnexpectedError if casting fails or the call to set_ex fails.
-        let value = true;
-
-        // Convert to u64
-        let time_to_live: u64 = TOKEN_TTL_SECONDS
-            .try_into()
-            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
-            .map_err(|e| BannedTokenStoreError::UnexpectedError(e.into()))?;
+    async fn insert(&self, token: &TokenHash, ttl: Duration) -> Result<(), BannedTokenStoreError> {
+        // The key expires together with the token. SETEX rejects a TTL of zero,
+        // and a token without time left needs no ban.
+        let time_to_live = ttl.as_secs();
+        if time_to_live == 0 {
+            return Ok(());
+        }
 
         let _: () = self
             .conn
-            .write()
+            .clone()
+            .set_ex(get_key(token), true, time_to_live)
             .await
-            .set_ex(key, value, time_to_live)
-            .map_err(|_| BannedTokenStoreError::TokenAlreadyExists)?;
+            .wrap_err("failed to store banned token in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
         Ok(())
     }
 
     #[tracing::instrument("Check if banned token exists", skip_all)]
-    async fn token_exists(&self, token: &Secret<String>) -> Result<bool, BannedTokenStoreError> {
-        // Check if the token exists by calling the exists method on the Redis connection
-        let key = get_key(&token.expose_secret());
+    async fn token_exists(&self, token: &TokenHash) -> Result<bool, BannedTokenStoreError> {
         let is_banned: bool = self
             .conn
-            .write()
+            .clone()
+            .exists(get_key(token))
             .await
-            .exists(&key)
             .wrap_err("failed to check if token exists in Redis")
-            .map_err(|_| BannedTokenStoreError::TokenNotFound)?;
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
         Ok(is_banned)
     }
+
+    #[tracing::instrument("Revoke all sessions", skip_all)]
+    async fn revoke_sessions(
+        &self,
+        email: &Email,
+        revoked_at: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        let key = get_revoked_sessions_key(REVOKED_SESSIONS_KEY_PREFIX, email);
+        // Tokens issued before the revocation expire within TOKEN_TTL_SECONDS,
+        // so the marker does not need to outlive them.
+        let time_to_live: u64 = TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(key, revoked_at, time_to_live)
+            .await
+            .wrap_err("failed to store session revocation in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    #[tracing::instrument("Check when sessions were revoked", skip_all)]
+    async fn sessions_revoked_at(
+        &self,
+        email: &Email,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        let mut conn = self.conn.clone();
+        let revoked_at: Option<i64> = conn
+            .get(get_revoked_sessions_key(REVOKED_SESSIONS_KEY_PREFIX, email))
+            .await
+            .wrap_err("failed to read session revocation from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        let legacy_revoked_at: Option<i64> = conn
+            .get(get_revoked_sessions_key(
+                LEGACY_REVOKED_SESSIONS_KEY_PREFIX,
+                email,
+            ))
+            .await
+            .wrap_err("failed to read session revocation from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+        // A revocation in seconds covers every token issued within that second
+        let legacy_revoked_at = legacy_revoked_at.map(|seconds| seconds * 1000 + 999);
+        Ok(revoked_at.max(legacy_revoked_at))
+    }
 }
@@ -0,0 +1,244 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context, Result};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::UserCache,
+    email::Email,
+    parse::Parseable,
+    password::Password,
+    phone_number::PhoneNumber,
+    role::Role,
+    user::{AccountStatus, User},
+};
+
+/// [`UserCache`] shared by every instance through Redis. Invalidations made by
+/// one instance are seen by all of them. Cached users leave out the password
+/// hash, since only `validate_user` needs it and that always asks the store.
+///
+/// Entries are kept under their generation, so entries of an older one are
+/// never read again and simply expire. Invalidating picks a random generation
+/// that is kept for twice the TTL, well beyond any entry written for the one
+/// it replaced.
+pub struct RedisUserCache {
+    conn: ConnectionManager,
+    ttl: Duration,
+}
+
+impl RedisUserCache {
+    pub fn new(conn: ConnectionManager, ttl: Duration) -> Self {
+        Self { conn, ttl }
+    }
+
+    async fn get_json<T: for<'de> Deserialize<'de>>(&self, key: String) -> Result<Option<T>> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(key)
+            .await
+            .wrap_err("failed to read cached user from Redis")?;
+        value
+            .map(|value| serde_json::from_str(&value).wrap_err("failed to deserialize cached user"))
+            .transpose()
+    }
+
+    async fn set_json<T: Serialize>(&self, key: String, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value).wrap_err("failed to serialize cached user")?;
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(key, value, self.ttl_secs())
+            .await
+            .wrap_err("failed to cache user in Redis")?;
+        Ok(())
+    }
+
+    fn ttl_secs(&self) -> u64 {
+        self.ttl.as_secs().max(1)
+    }
+}
+
+const GENERATION_KEY_PREFIX: &str = "user_cache:generation:";
+const USER_KEY_PREFIX: &str = "user_cache:user:";
+const ROLES_KEY_PREFIX: &str = "user_cache:roles:";
+
+fn get_key(prefix: &str, email: &Email) -> String {
+    format!("{}{}", prefix, email.as_ref().expose_secret())
+}
+
+fn get_entry_key(prefix: &str, email: &Email, generation: u64) -> String {
+    format!(
+        "{}{}:{}",
+        prefix,
+        generation,
+        email.as_ref().expose_secret()
+    )
+}
+
+// Stands in for the password hash, which is not cached
+const UNCACHED_PASSWORD: &str = "not-cached";
+
+// Timestamps are kept as microseconds since the epoch
+#[derive(Serialize, Deserialize)]
+struct CachedUser {
+    id: Uuid,
+    email: String,
+    requires_2fa: bool,
+    status: String,
+    password_reset_required: bool,
+    created_at: i64,
+    updated_at: i64,
+    last_login_at: Option<i64>,
+    last_login_ip: Option<String>,
+    failed_login_count: u32,
+    password_changed_at: i64,
+    // Missing from users cached before locales were stored
+    #[serde(default)]
+    locale: Option<String>,
+    // Missing from users cached before notices could be turned off
+    #[serde(default = "notices_enabled")]
+    security_notices: bool,
+    // Missing from users cached before phone numbers were stored
+    #[serde(default)]
+    phone_number: Option<String>,
+    #[serde(default)]
+    phone_verified: bool,
+    #[serde(default)]
+    two_fa_channel: Option<String>,
+}
+
+fn notices_enabled() -> bool {
+    true
+}
+
+impl From<&User> for CachedUser {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email.as_ref().expose_secret().to_owned(),
+            requires_2fa: user.requires_2fa,
+            status: user.status.as_str().to_owned(),
+            password_reset_required: user.password_reset_required,
+            created_at: user.created_at.timestamp_micros(),
+            updated_at: user.updated_at.timestamp_micros(),
+            last_login_at: user.last_login_at.map(|at| at.timestamp_micros()),
+            last_login_ip: user.last_login_ip.clone(),
+            failed_login_count: user.failed_login_count,
+            password_changed_at: user.password_changed_at.timestamp_micros(),
+            locale: user.locale.map(|locale| locale.as_str().to_owned()),
+            security_notices: user.security_notices,
+            phone_number: user
+                .phone_number
+                .as_ref()
+                .map(|phone| phone.as_ref().expose_secret().to_owned()),
+            phone_verified: user.phone_verified,
+            two_fa_channel: Some(user.two_fa_channel.as_str().to_owned()),
+        }
+    }
+}
+
+impl TryFrom<CachedUser> for User {
+    type Error = color_eyre::eyre::Report;
+
+    fn try_from(cached: CachedUser) -> Result<Self> {
+        let mut user = User::new(
+            Email::parse(Secret::new(cached.email))?,
+            cached.requires_2fa,
+            Password::parse(Secret::new(UNCACHED_PASSWORD.to_owned()))?,
+        );
+        user.status = cached.status.parse::<AccountStatus>()?;
+        user.id = cached.id;
+        user.password_reset_required = cached.password_reset_required;
+        user.created_at = from_micros(cached.created_at)?;
+        user.updated_at = from_micros(cached.updated_at)?;
+        user.last_login_at = cached.last_login_at.map(from_micros).transpose()?;
+        user.last_login_ip = cached.last_login_ip;
+        user.failed_login_count = cached.failed_login_count;
+        user.password_changed_at = from_micros(cached.password_changed_at)?;
+        user.locale = cached.locale.map(|locale| locale.parse()).transpose()?;
+        user.security_notices = cached.security_notices;
+        user.phone_number = cached
+            .phone_number
+            .map(|phone| PhoneNumber::parse(Secret::new(phone)))
+            .transpose()?;
+        user.phone_verified = cached.phone_verified;
+        user.two_fa_channel = cached
+            .two_fa_channel
+            .map(|channel| channel.parse())
+            .transpose()?
+            .unwrap_or_default();
+        Ok(user)
+    }
+}
+
+fn from_micros(micros: i64) -> Result<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(micros).ok_or_else(|| eyre!("Invalid cached timestamp"))
+}
+
+#[async_trait::async_trait]
+impl UserCache for RedisUserCache {
+    // Users who were not invalidated lately are in generation 0
+    #[tracing::instrument("Read cache generation from Redis", skip_all)]
+    async fn generation(&self, email: &Email) -> Result<u64> {
+        let generation: Option<u64> = self
+            .conn
+            .clone()
+            .get(get_key(GENERATION_KEY_PREFIX, email))
+            .await
+            .wrap_err("failed to read cache generation from Redis")?;
+        Ok(generation.unwrap_or_default())
+    }
+
+    #[tracing::instrument("Read cached user from Redis", skip_all)]
+    async fn get_user(&self, email: &Email, generation: u64) -> Result<Option<User>> {
+        self.get_json::<CachedUser>(get_entry_key(USER_KEY_PREFIX, email, generation))
+            .await?
+            .map(User::try_from)
+            .transpose()
+    }
+
+    #[tracing::instrument("Cache user in Redis", skip_all)]
+    async fn set_user(&self, user: &User, generation: u64) -> Result<()> {
+        self.set_json(
+            get_entry_key(USER_KEY_PREFIX, &user.email, generation),
+            &CachedUser::from(user),
+        )
+        .await
+    }
+
+    #[tracing::instrument("Read cached roles from Redis", skip_all)]
+    async fn get_roles(&self, email: &Email, generation: u64) -> Result<Option<Vec<Role>>> {
+        self.get_json::<Vec<String>>(get_entry_key(ROLES_KEY_PREFIX, email, generation))
+            .await?
+            .map(|roles| roles.into_iter().map(Role::parse).collect())
+            .transpose()
+    }
+
+    #[tracing::instrument("Cache roles in Redis", skip_all)]
+    async fn set_roles(&self, email: &Email, roles: &[Role], generation: u64) -> Result<()> {
+        let roles: Vec<&str> = roles.iter().map(|role| role.as_ref()).collect();
+        self.set_json(get_entry_key(ROLES_KEY_PREFIX, email, generation), &roles)
+            .await
+    }
+
+    #[tracing::instrument("Invalidate cached user in Redis", skip_all)]
+    async fn invalidate(&self, email: &Email) -> Result<()> {
+        let generation = rand::random::<u64>().max(1);
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(
+                get_key(GENERATION_KEY_PREFIX, email),
+                generation,
+                2 * self.ttl_secs(),
+            )
+            .await
+            .wrap_err("failed to invalidate cached user in Redis")?;
+        Ok(())
+    }
+}
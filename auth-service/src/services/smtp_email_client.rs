@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{Context, Result};
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Certificate, Tls, TlsParameters},
+    },
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use crate::{
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage},
+    },
+    utils::constants::SmtpTls,
+};
+
+/// Where the SMTP server is and how to log in to it
+#[derive(Debug)]
+pub struct SmtpSettings {
+    pub host: String,
+    /// Defaults to the usual port of the TLS mode
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    /// Username and password, if the server requires authentication
+    pub credentials: Option<(String, Secret<String>)>,
+    /// PEM certificate trusted on top of the bundled roots,
+    /// e.g. of a relay signed by a private CA
+    pub root_certificate: Option<Vec<u8>>,
+    pub timeout: Duration,
+}
+
+/// Sends emails through any SMTP server.
+///
+/// Connections are pooled: one that is still open from an earlier email is
+/// reused instead of going through the handshake and login again.
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: SmtpSettings, sender: Email) -> Result<Self> {
+        let tls = match settings.tls {
+            SmtpTls::None => Tls::None,
+            SmtpTls::StartTls => Tls::Required(tls_parameters(&settings)?),
+            SmtpTls::Implicit => Tls::Wrapper(tls_parameters(&settings)?),
+        };
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            .port(settings.port.unwrap_or(settings.tls.default_port()))
+            .tls(tls)
+            .timeout(Some(settings.timeout));
+        if let Some((username, password)) = settings.credentials {
+            transport = transport.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+
+        let sender = sender
+            .as_ref()
+            .expose_secret()
+            .parse()
+            .wrap_err("Cannot use the sender as a mailbox")?;
+        Ok(Self {
+            transport: transport.build(),
+            sender,
+        })
+    }
+}
+
+fn tls_parameters(settings: &SmtpSettings) -> Result<TlsParameters> {
+    let mut parameters = TlsParameters::builder(settings.host.clone());
+    if let Some(pem) = &settings.root_certificate {
+        parameters = parameters.add_root_certificate(
+            Certificate::from_pem(pem).wrap_err("Invalid SMTP root certificate")?,
+        );
+    }
+    parameters.build().wrap_err("Failed to set up TLS for SMTP")
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(recipient
+                .as_ref()
+                .expose_secret()
+                .parse()
+                .wrap_err("Cannot use the recipient as a mailbox")?)
+            .subject(&message.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                message.text_body.clone(),
+                message.html_body.clone(),
+            ))
+            .wrap_err("Failed to build email")?;
+
+        self.transport
+            .send(message)
+            .await
+            .wrap_err("SMTP server did not accept the email")?;
+        Ok(())
+    }
+}
@@ -0,0 +1,826 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::SqlitePool;
+
+use crate::{
+    domain::{
+        data_stores::{UserPage, UserQuery, UserStore, UserStoreError},
+        email::Email,
+        locale::Locale,
+        parse::Parseable,
+        password::Password,
+        phone_number::PhoneNumber,
+        role::{Permission, Role},
+        user::{StatusChange, TwoFactorChannel, User, UserSummary},
+    },
+    services::data_stores::{
+        compute_password_hash, escape_like_pattern, verify_password_hash, UserRow,
+    },
+    utils::auth::TokenHash,
+};
+
+/// User store backed by a single SQLite file, for local development and
+/// small deployments. The schema lives in `migrations_sqlite`.
+///
+/// SQLite has no arrays, so lists are passed to and read from queries as
+/// JSON and expanded with `json_each`.
+pub struct SqliteUserStore {
+    pool: SqlitePool,
+}
+
+impl SqliteUserStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for SqliteUserStore {
+    #[tracing::instrument(name = "Adding user to SQLite", skip_all)]
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let hashed_password = compute_password_hash(user.password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        let result = sqlx::query(
+            "
+            INSERT INTO users
+            (id, email, password_hash, requires_2fa, status,
+             created_at, updated_at, password_changed_at, locale, security_notices,
+             phone_number, phone_verified, two_fa_channel)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            ",
+        )
+        .bind(user.id.to_string())
+        .bind(user.email.as_ref().expose_secret())
+        .bind(hashed_password.expose_secret())
+        .bind(user.requires_2fa)
+        .bind(user.status.as_str())
+        .bind(user.created_at.timestamp_micros())
+        .bind(user.updated_at.timestamp_micros())
+        .bind(user.password_changed_at.timestamp_micros())
+        .bind(user.locale.map(|locale| locale.as_str()))
+        .bind(user.security_notices)
+        .bind(
+            user.phone_number
+                .as_ref()
+                .map(|phone| phone.as_ref().expose_secret().to_owned()),
+        )
+        .bind(user.phone_verified)
+        .bind(user.two_fa_channel.as_str())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving user from SQLite", skip_all)]
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        let row: UserRow = sqlx::query_as(
+            "
+            SELECT
+                id,
+                email,
+                password_hash,
+                requires_2fa,
+                status,
+                password_reset_required,
+                created_at,
+                updated_at,
+                last_login_at,
+                last_login_ip,
+                failed_login_count,
+                password_changed_at,
+                locale,
+                security_notices,
+                phone_number,
+                phone_verified,
+                two_fa_channel
+            FROM users
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+        row.into_user()
+    }
+
+    #[tracing::instrument(name = "Validating user credentials in SQLite", skip_all)]
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError> {
+        // The stored user carries the password hash
+        let user = self.get_user(email).await?;
+
+        verify_password_hash(
+            user.password.as_ref().expose_secret().to_owned(),
+            password.as_ref().expose_secret().to_owned(),
+        )
+        .await
+        .map_err(|_| UserStoreError::InvalidCredentials)?;
+        Ok(user)
+    }
+
+    #[tracing::instrument(name = "Assigning role in SQLite", skip_all)]
+    async fn assign_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        self.ensure_user_and_role_exist(email, role).await?;
+        sqlx::query("INSERT OR IGNORE INTO user_roles (email, role) VALUES ($1, $2)")
+            .bind(email.as_ref().expose_secret())
+            .bind(role.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking role in SQLite", skip_all)]
+    async fn revoke_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        self.ensure_user_and_role_exist(email, role).await?;
+        sqlx::query("DELETE FROM user_roles WHERE email = $1 AND role = $2")
+            .bind(email.as_ref().expose_secret())
+            .bind(role.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving roles from SQLite", skip_all)]
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT role FROM user_roles WHERE email = $1 ORDER BY role")
+                .bind(email.as_ref().expose_secret())
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(role,)| Role::parse(role).map_err(UserStoreError::UnexpectedError))
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Checking role in SQLite", skip_all)]
+    async fn role_exists(&self, role: &Role) -> Result<bool, UserStoreError> {
+        let (exists,): (bool,) =
+            sqlx::query_as("SELECT EXISTS(SELECT 1 FROM roles WHERE name = $1)")
+                .bind(role.as_ref())
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Retrieving permissions from SQLite", skip_all)]
+    async fn get_permissions(&self, roles: &[Role]) -> Result<HashSet<Permission>, UserStoreError> {
+        let role_names: Vec<&str> = roles.iter().map(|role| role.as_ref()).collect();
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "
+            SELECT DISTINCT permission
+            FROM role_permissions
+            WHERE role IN (SELECT value FROM json_each($1))
+            ",
+        )
+        .bind(to_json(&role_names)?)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|(permission,)| {
+                permission
+                    .parse::<Permission>()
+                    .map_err(UserStoreError::UnexpectedError)
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Listing users in SQLite", skip_all)]
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        // LIKE is case-insensitive for ASCII in SQLite, like ILIKE in PostgreSQL
+        let pattern = query
+            .search
+            .as_deref()
+            .map(|search| format!("%{}%", escape_like_pattern(search)));
+        let emails = query
+            .emails
+            .as_ref()
+            .map(|emails| {
+                let emails: Vec<&str> = emails
+                    .iter()
+                    .map(|email| email.as_ref().expose_secret().as_str())
+                    .collect();
+                to_json(&emails)
+            })
+            .transpose()?;
+
+        let (total,): (i64,) =
+            sqlx::query_as(&format!("SELECT COUNT(*) FROM users {}", USER_LIST_FILTER))
+                .bind(&pattern)
+                .bind(&emails)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let rows: Vec<UserSummaryRow> = sqlx::query_as(&format!(
+            "
+            {}
+            {}
+            ORDER BY email
+            LIMIT $3 OFFSET $4
+            ",
+            SELECT_USER_SUMMARY, USER_LIST_FILTER
+        ))
+        .bind(&pattern)
+        .bind(&emails)
+        .bind(i64::from(query.per_page))
+        .bind(query.offset() as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let users = rows
+            .into_iter()
+            .map(UserSummaryRow::into_summary)
+            .collect::<Result<_, _>>()?;
+        Ok(UserPage {
+            users,
+            total: total as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving user summary from SQLite", skip_all)]
+    async fn get_user_summary(&self, email: &Email) -> Result<UserSummary, UserStoreError> {
+        let row: UserSummaryRow =
+            sqlx::query_as(&format!("{} WHERE email = $1", SELECT_USER_SUMMARY))
+                .bind(email.as_ref().expose_secret())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+                .ok_or(UserStoreError::UserNotFound)?;
+        row.into_summary()
+    }
+
+    #[tracing::instrument(name = "Updating account status in SQLite", skip_all)]
+    async fn set_status(&self, email: &Email, change: &StatusChange) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET status = $2,
+                status_reason = $3,
+                status_changed_by = $4,
+                status_changed_at = datetime('now'),
+                updated_at = $5
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(change.status.as_str())
+        .bind(&change.reason)
+        .bind(&change.changed_by)
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password reset flag in SQLite", skip_all)]
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("password_reset_required", email, required)
+            .await
+    }
+
+    #[tracing::instrument(name = "Updating 2FA flag in SQLite", skip_all)]
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("requires_2fa", email, requires_2fa).await
+    }
+
+    #[tracing::instrument(name = "Setting locale in SQLite", skip_all)]
+    async fn set_locale(
+        &self,
+        email: &Email,
+        locale: Option<Locale>,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query("UPDATE users SET locale = $2, updated_at = $3 WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .bind(locale.map(|locale| locale.as_str()))
+            .bind(Utc::now().timestamp_micros())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating security notices flag in SQLite", skip_all)]
+    async fn set_security_notices(
+        &self,
+        email: &Email,
+        enabled: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("security_notices", email, enabled).await
+    }
+
+    #[tracing::instrument(name = "Setting phone number in SQLite", skip_all)]
+    async fn set_phone_number(
+        &self,
+        email: &Email,
+        phone_number: Option<&PhoneNumber>,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET phone_number = $2,
+                phone_verified = FALSE,
+                two_fa_channel = 'email',
+                phone_verification_attempts = 0,
+                updated_at = $3
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(phone_number.map(|phone| phone.as_ref().expose_secret().to_owned()))
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating phone verification flag in SQLite", skip_all)]
+    async fn set_phone_verified(
+        &self,
+        email: &Email,
+        verified: bool,
+    ) -> Result<(), UserStoreError> {
+        self.update_flag("phone_verified", email, verified).await
+    }
+
+    #[tracing::instrument(name = "Counting phone verification attempt in SQLite", skip_all)]
+    async fn record_phone_verification_attempt(
+        &self,
+        email: &Email,
+    ) -> Result<u32, UserStoreError> {
+        let attempts: Option<i64> = sqlx::query_scalar(
+            "
+            UPDATE users
+            SET phone_verification_attempts = phone_verification_attempts + 1
+            WHERE email = $1
+            RETURNING phone_verification_attempts
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let attempts = attempts.ok_or(UserStoreError::UserNotFound)?;
+        attempts
+            .try_into()
+            .map_err(|e: std::num::TryFromIntError| UserStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Setting 2FA channel in SQLite", skip_all)]
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFactorChannel,
+    ) -> Result<(), UserStoreError> {
+        let result =
+            sqlx::query("UPDATE users SET two_fa_channel = $2, updated_at = $3 WHERE email = $1")
+                .bind(email.as_ref().expose_secret())
+                .bind(channel.as_str())
+                .bind(Utc::now().timestamp_micros())
+                .execute(&self.pool)
+                .await
+                .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting password in SQLite", skip_all)]
+    async fn set_password(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
+        let hashed_password = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+        let now = Utc::now().timestamp_micros();
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET password_hash = $2,
+                password_reset_required = FALSE,
+                password_changed_at = $3,
+                updated_at = $3
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(hashed_password.expose_secret())
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Changing email in SQLite", skip_all)]
+    async fn change_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        // Roles and devices follow through ON UPDATE CASCADE
+        let result = sqlx::query("UPDATE users SET email = $2, updated_at = $3 WHERE email = $1")
+            .bind(email.as_ref().expose_secret())
+            .bind(new_email.as_ref().expose_secret())
+            .bind(Utc::now().timestamp_micros())
+            .execute(&self.pool)
+            .await;
+
+        match result {
+            Ok(result) if result.rows_affected() == 0 => Err(UserStoreError::UserNotFound),
+            Ok(_) => Ok(()),
+            Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                Err(UserStoreError::UserAlreadyExists)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Remembering device in SQLite", skip_all)]
+    async fn remember_device(
+        &self,
+        email: &Email,
+        device: &TokenHash,
+    ) -> Result<bool, UserStoreError> {
+        let result = sqlx::query(
+            "
+            INSERT INTO user_devices (email, device_hash, first_seen_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(device.as_ref())
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await;
+
+        match result {
+            Ok(result) => Ok(result.rows_affected() == 1),
+            Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+                Err(UserStoreError::UserNotFound)
+            }
+            Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
+        }
+    }
+
+    #[tracing::instrument(name = "Recording login in SQLite", skip_all)]
+    async fn record_login(&self, email: &Email, ip: Option<&str>) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "
+            UPDATE users
+            SET last_login_at = $2,
+                last_login_ip = $3,
+                failed_login_count = 0
+            WHERE email = $1
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(Utc::now().timestamp_micros())
+        .bind(ip)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording failed login in SQLite", skip_all)]
+    async fn record_failed_login(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query(
+            "UPDATE users SET failed_login_count = failed_login_count + 1 WHERE email = $1",
+        )
+        .bind(email.as_ref().expose_secret())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+}
+
+// Roles are aggregated into a JSON array so listing a page takes a single query
+const SELECT_USER_SUMMARY: &str = "
+    SELECT
+        email,
+        requires_2fa,
+        status,
+        status_reason,
+        status_changed_by,
+        password_reset_required,
+        (SELECT json_group_array(role) FROM user_roles WHERE user_roles.email = users.email) AS roles
+    FROM users";
+
+const USER_LIST_FILTER: &str = r"
+    WHERE ($1 IS NULL OR email LIKE $1 ESCAPE '\')
+      AND ($2 IS NULL OR email IN (SELECT value FROM json_each($2)))";
+
+#[derive(sqlx::FromRow)]
+struct UserSummaryRow {
+    email: String,
+    requires_2fa: bool,
+    status: String,
+    status_reason: Option<String>,
+    status_changed_by: Option<String>,
+    password_reset_required: bool,
+    roles: String,
+}
+
+impl UserSummaryRow {
+    fn into_summary(self) -> Result<UserSummary, UserStoreError> {
+        let mut roles: Vec<String> = serde_json::from_str(&self.roles)
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        // json_group_array does not guarantee any order
+        roles.sort();
+        let roles = roles
+            .into_iter()
+            .map(Role::parse)
+            .collect::<Result<_, _>>()
+            .map_err(UserStoreError::UnexpectedError)?;
+        Ok(UserSummary {
+            email: Email::parse(Secret::new(self.email))
+                .wrap_err("Cannot parse email")
+                .map_err(UserStoreError::UnexpectedError)?,
+            requires_2fa: self.requires_2fa,
+            status: self
+                .status
+                .parse()
+                .map_err(UserStoreError::UnexpectedError)?,
+            status_reason: self.status_reason,
+            status_changed_by: self.status_changed_by,
+            password_reset_required: self.password_reset_required,
+            roles,
+        })
+    }
+}
+
+fn to_json(values: &[&str]) -> Result<String, UserStoreError> {
+    serde_json::to_string(values)
+        .map_err(|e| UserStoreError::UnexpectedError(eyre!("Cannot encode list: {}", e)))
+}
+
+impl SqliteUserStore {
+    /// `column` must be one of the boolean columns of `users`, never user input
+    async fn update_flag(
+        &self,
+        column: &'static str,
+        email: &Email,
+        value: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query(&format!(
+            "UPDATE users SET {} = $2, updated_at = $3 WHERE email = $1",
+            column
+        ))
+        .bind(email.as_ref().expose_secret())
+        .bind(value)
+        .bind(Utc::now().timestamp_micros())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    async fn ensure_user_and_role_exist(
+        &self,
+        email: &Email,
+        role: &Role,
+    ) -> Result<(), UserStoreError> {
+        let (user_exists, role_exists): (bool, bool) = sqlx::query_as(
+            "
+            SELECT
+                EXISTS(SELECT 1 FROM users WHERE email = $1),
+                EXISTS(SELECT 1 FROM roles WHERE name = $2)
+            ",
+        )
+        .bind(email.as_ref().expose_secret())
+        .bind(role.as_ref())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if !user_exists {
+            return Err(UserStoreError::UserNotFound);
+        }
+        if !role_exists {
+            return Err(UserStoreError::RoleNotFound);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{data_stores::configure_sqlite, user::AccountStatus};
+
+    // Every test gets its own private in-memory database
+    async fn empty_sqlite_user_store() -> SqliteUserStore {
+        SqliteUserStore::new(configure_sqlite("sqlite::memory:").await)
+    }
+
+    fn user(email: &str, requires_2fa: bool) -> User {
+        User::new(
+            Email::parse(Secret::new(email.to_owned())).unwrap(),
+            requires_2fa,
+            Password::parse(Secret::new("a valid password".to_owned())).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_get_and_validate_user() {
+        let user_store = empty_sqlite_user_store().await;
+        let user = user("teemo@gmail.com", true);
+
+        assert_eq!(
+            user_store.get_user(&user.email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        user_store.add_user(user.clone()).await.unwrap();
+        assert_eq!(
+            user_store.add_user(user.clone()).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        // Only the hash of the password is stored
+        let stored = user_store.get_user(&user.email).await.unwrap();
+        assert_eq!(stored.email, user.email);
+        assert!(stored.requires_2fa);
+        assert_eq!(stored.status, AccountStatus::Active);
+        assert_ne!(
+            stored.password.as_ref().expose_secret(),
+            user.password.as_ref().expose_secret()
+        );
+
+        assert!(user_store
+            .validate_user(&user.email, &user.password)
+            .await
+            .is_ok());
+        let wrong_password = Password::parse(Secret::new("wrongPassword".to_owned())).unwrap();
+        assert_eq!(
+            user_store.validate_user(&user.email, &wrong_password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_roles_and_permissions() {
+        let user_store = empty_sqlite_user_store().await;
+        let user = user("admin@gmail.com", false);
+
+        assert_eq!(
+            user_store.assign_role(&user.email, &Role::admin()).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        user_store.add_user(user.clone()).await.unwrap();
+        let unknown = Role::parse("auditor".to_owned()).unwrap();
+        assert_eq!(
+            user_store.assign_role(&user.email, &unknown).await,
+            Err(UserStoreError::RoleNotFound)
+        );
+
+        user_store
+            .assign_role(&user.email, &Role::admin())
+            .await
+            .unwrap();
+        assert_eq!(
+            user_store.get_roles(&user.email).await.unwrap(),
+            vec![Role::admin()]
+        );
+        let permissions = user_store.get_permissions(&[Role::admin()]).await.unwrap();
+        assert_eq!(permissions, Permission::ALL.iter().copied().collect());
+
+        user_store
+            .revoke_role(&user.email, &Role::admin())
+            .await
+            .unwrap();
+        assert!(user_store.get_roles(&user.email).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_users_filters_and_paginates() {
+        let user_store = empty_sqlite_user_store().await;
+        for email in ["carol@example.com", "alice@example.com", "bob@test.com"] {
+            user_store.add_user(user(email, false)).await.unwrap();
+        }
+        let alice = Email::parse(Secret::new("alice@example.com".to_owned())).unwrap();
+        user_store
+            .assign_role(&alice, &Role::admin())
+            .await
+            .unwrap();
+
+        let page = user_store
+            .list_users(&UserQuery::new(
+                Some("EXAMPLE".to_owned()),
+                Some(1),
+                Some(1),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.users.len(), 1);
+        assert_eq!(page.users[0].email, alice);
+        assert_eq!(page.users[0].roles, vec![Role::admin()]);
+
+        // Wildcards in the search are matched literally
+        let page = user_store
+            .list_users(&UserQuery::new(Some("%".to_owned()), None, None))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 0);
+
+        let page = user_store
+            .list_users(&UserQuery::default().restricted_to(vec![alice.clone()]))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_status_and_flags() {
+        let user_store = empty_sqlite_user_store().await;
+        let user = user("suspended@gmail.com", true);
+        let change = StatusChange::new(AccountStatus::Suspended, "admin@gmail.com")
+            .with_reason(Some("Chargeback".to_owned()));
+        assert_eq!(
+            user_store.set_status(&user.email, &change).await,
+            Err(UserStoreError::UserNotFound)
+        );
+
+        user_store.add_user(user.clone()).await.unwrap();
+        user_store.set_status(&user.email, &change).await.unwrap();
+        user_store
+            .set_password_reset_required(&user.email, true)
+            .await
+            .unwrap();
+        user_store
+            .set_requires_2fa(&user.email, false)
+            .await
+            .unwrap();
+
+        let summary = user_store.get_user_summary(&user.email).await.unwrap();
+        assert_eq!(summary.status, AccountStatus::Suspended);
+        assert_eq!(summary.status_reason.as_deref(), Some("Chargeback"));
+        assert_eq!(
+            summary.status_changed_by.as_deref(),
+            Some("admin@gmail.com")
+        );
+        assert!(summary.password_reset_required);
+        assert!(!summary.requires_2fa);
+    }
+}
@@ -0,0 +1,110 @@
+use color_eyre::eyre::Result;
+
+use crate::{
+    app_state::state::{EmailClientType, EmailSuppressionStoreType},
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage, RecipientSuppressed},
+    },
+};
+
+/// Refuses emails to addresses that bounced or complained with
+/// [`RecipientSuppressed`], and hands every other email to `inner`.
+pub struct SuppressingEmailClient {
+    inner: EmailClientType,
+    suppressions: EmailSuppressionStoreType,
+}
+
+impl SuppressingEmailClient {
+    pub fn new(inner: EmailClientType, suppressions: EmailSuppressionStoreType) -> Self {
+        Self {
+            inner,
+            suppressions,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SuppressingEmailClient {
+    #[tracing::instrument(name = "Checking email suppressions", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        match self.suppressions.get(recipient).await {
+            Ok(Some(suppression)) => {
+                tracing::warn!(
+                    reason = %suppression.reason,
+                    "Not sending email to a suppressed address"
+                );
+                return Err(RecipientSuppressed {
+                    reason: suppression.reason,
+                }
+                .into());
+            }
+            Ok(None) => {}
+            // Better to risk another bounce than to lose a 2FA code
+            Err(e) => {
+                tracing::error!(error = ?e, "Cannot check email suppressions, sending anyway")
+            }
+        }
+        self.inner.send_email(recipient, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::{
+        domain::{
+            data_stores::EmailSuppressionStore,
+            email_suppression::{EmailSuppression, SuppressionReason},
+            parse::Parseable,
+        },
+        services::{
+            capturing_email_client::CapturingEmailClient,
+            hashmap_email_suppression_store::HashMapEmailSuppressionStore,
+        },
+    };
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refuses_suppressed_addresses() {
+        let inner = Arc::new(CapturingEmailClient::default());
+        let store = Arc::new(HashMapEmailSuppressionStore::default());
+        let client = SuppressingEmailClient::new(inner.clone(), store.clone());
+        let bounced = email("bounced@example.com");
+        let fine = email("fine@example.com");
+        store
+            .suppress(&EmailSuppression::new(
+                bounced.clone(),
+                SuppressionReason::HardBounce,
+            ))
+            .await
+            .unwrap();
+
+        let message = EmailMessage {
+            subject: "Subject".to_owned(),
+            html_body: String::new(),
+            text_body: String::new(),
+        };
+        let error = client.send_email(&bounced, &message).await.unwrap_err();
+        assert!(RecipientSuppressed::caused(&error));
+        client.send_email(&fine, &message).await.unwrap();
+        let recipients: Vec<Email> = inner
+            .messages()
+            .into_iter()
+            .map(|message| message.recipient)
+            .collect();
+        assert_eq!(recipients, vec![fine.clone()]);
+
+        // Lifting the suppression sends to the address again
+        store.remove(&bounced).await.unwrap();
+        client.send_email(&bounced, &message).await.unwrap();
+        assert_eq!(inner.messages().len(), 2);
+    }
+}
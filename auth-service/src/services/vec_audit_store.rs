@@ -0,0 +1,100 @@
+use std::sync::{PoisonError, RwLock};
+
+use crate::domain::{
+    audit::AuditEvent,
+    data_stores::{AuditPage, AuditQuery, AuditStore, AuditStoreError},
+};
+
+#[derive(Default)]
+pub struct VecAuditStore {
+    // Oldest first, in the order they were appended
+    events: RwLock<Vec<AuditEvent>>,
+}
+
+#[async_trait::async_trait]
+impl AuditStore for VecAuditStore {
+    async fn append(&self, event: AuditEvent) -> Result<(), AuditStoreError> {
+        self.events
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(event);
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<AuditPage, AuditStoreError> {
+        let events = self.events.read().unwrap_or_else(PoisonError::into_inner);
+        let matching: Vec<&AuditEvent> = events
+            .iter()
+            .rev()
+            .filter(|event| query.matches(event))
+            .collect();
+
+        let events = matching
+            .iter()
+            .skip(query.offset() as usize)
+            .take(query.per_page as usize)
+            .map(|event| (*event).clone())
+            .collect();
+        Ok(AuditPage {
+            events,
+            total: matching.len() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{audit::AuditAction, email::Email, parse::Parseable};
+    use secrecy::Secret;
+
+    fn email(address: &str) -> Email {
+        Email::parse(Secret::new(address.to_owned())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_and_paginates_newest_first() {
+        let store = VecAuditStore::default();
+        let alice = email("alice@example.com");
+        let bob = email("bob@example.com");
+        store
+            .append(AuditEvent::new("alice@example.com", AuditAction::LoggedIn))
+            .await
+            .unwrap();
+        store
+            .append(
+                AuditEvent::new("admin@example.com", AuditAction::UserDisabled).with_target(&alice),
+            )
+            .await
+            .unwrap();
+        store
+            .append(AuditEvent::new("bob@example.com", AuditAction::LoggedIn))
+            .await
+            .unwrap();
+
+        let page = store
+            .query(&AuditQuery::default().involving(alice.clone()))
+            .await
+            .unwrap();
+        let actions: Vec<AuditAction> = page.events.iter().map(|event| event.action).collect();
+        assert_eq!(page.total, 2);
+        assert_eq!(
+            actions,
+            vec![AuditAction::UserDisabled, AuditAction::LoggedIn]
+        );
+
+        let page = store
+            .query(&AuditQuery::new(Some(2), Some(1)).with_action(Some(AuditAction::LoggedIn)))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].actor.as_deref(), Some("alice@example.com"));
+
+        let page = store
+            .query(&AuditQuery::default().restricted_to(vec![bob]))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+    }
+}
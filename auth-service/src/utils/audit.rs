@@ -0,0 +1,113 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header::USER_AGENT, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{
+    app_state::state::AppState,
+    domain::audit::{AuditContext, AuditEvent, AuditOutcome},
+};
+
+use super::tracing::REQUEST_ID_HEADER;
+
+const MAXIMUM_USER_AGENT_LEN: usize = 512;
+
+/// Slot for the audit event describing the current request.
+///
+/// Handlers take it as an extractor and [`record`](Self::record) the event as
+/// soon as they know who is acting and on what, before anything can fail.
+/// Once the response is ready, [`record_audit_events`] fills in the outcome and
+/// where the request came from, then appends the event to the audit store.
+///
+/// ## Example
+///
+/// ```ignore
+/// pub async fn logout(audit: AuditRecorder, ...) -> Result<impl IntoResponse, AuthAPIError> {
+///     audit.record(AuditEvent::new(claims.sub, AuditAction::LoggedOut));
+///     ...
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct AuditRecorder(Arc<Mutex<Option<AuditEvent>>>);
+
+impl AuditRecorder {
+    /// Recording again replaces the previous event,
+    /// e.g. once a handler learns more about the request
+    pub fn record(&self, event: AuditEvent) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(event);
+    }
+
+    fn take(&self) -> Option<AuditEvent> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+// Outside of `record_audit_events` events go to a detached slot and are dropped
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuditRecorder {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<AuditRecorder>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// Middleware that persists the event a handler recorded for the request.
+/// Any response outside the 2xx range is recorded as a failure.
+pub async fn record_audit_events(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let context = request_context(&request);
+    let recorder = AuditRecorder::default();
+    request.extensions_mut().insert(recorder.clone());
+
+    let response = next.run(request).await;
+
+    if let Some(event) = recorder.take() {
+        let outcome = match response.status().is_success() {
+            true => AuditOutcome::Success,
+            false => AuditOutcome::Failure,
+        };
+        let event = event.with_outcome(outcome).with_context(context);
+        event.emit();
+        // Losing an audit event must not turn a completed request into an error
+        if let Err(e) = state.audit_store.append(event).await {
+            tracing::error!(error = ?e, "Failed to append audit event");
+        }
+    }
+    response
+}
+
+fn request_context(request: &Request) -> AuditContext {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    AuditContext {
+        ip: request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string()),
+        user_agent: header(USER_AGENT.as_str())
+            .map(|agent| agent.chars().take(MAXIMUM_USER_AGENT_LEN).collect()),
+        request_id: header(REQUEST_ID_HEADER),
+    }
+}
@@ -1,18 +1,44 @@
+use std::time::Duration;
+
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use thiserror::Error;
+use uuid::Uuid;
 
-use crate::{app_state::state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::state::{BannedTokenStoreType, UserStoreType},
+    domain::{
+        data_stores::TwoFACode,
+        email::Email,
+        error::AuthAPIError,
+        organization::OrganizationId,
+        parse::Parseable,
+        phone_number::PhoneNumber,
+        role::Role,
+        user::{AccountStatus, User},
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::constants::{
+    DEVICE_COOKIE_NAME, JWT_COOKIE_NAME, JWT_SECRET, PASSWORD_RESET_TTL_MINUTES,
+    SECURITY_REPORT_TTL_DAYS, TWO_FA_CODE_TTL_SECONDS,
+};
 
 // Create cookie with a new JWT auth token
 #[tracing::instrument("Generate authentication cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(
+    email: &Email,
+    roles: &[Role],
+    organization: Option<&OrganizationId>,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(email, roles, organization)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -28,6 +54,17 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Create a cookie that identifies the browser for as long as it keeps the cookie
+#[tracing::instrument("Generate device cookie", skip_all)]
+pub fn generate_device_cookie() -> Cookie<'static> {
+    Cookie::build((DEVICE_COOKIE_NAME, Uuid::new_v4().to_string()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .permanent()
+        .build()
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -37,14 +74,40 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+/// Keyed SHA-256 of a JWT or device token, hex encoded. Stores only ever see
+/// this, so read access to a store does not reveal tokens that could be replayed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenHash(String);
+
+impl TokenHash {
+    pub fn of(token: &Secret<String>) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(JWT_SECRET.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(token.expose_secret().as_bytes());
+        Self(hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+impl AsRef<str> for TokenHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
 // Create JWT auth token
 #[tracing::instrument("Generate authentication token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String> {
+fn generate_auth_token(
+    email: &Email,
+    roles: &[Role],
+    organization: Option<&OrganizationId>,
+) -> Result<String> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .wrap_err("failed to create 10 minute time delta")?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(eyre!("failed to add 10 minutes to current time"))?
         .timestamp();
@@ -57,39 +120,87 @@ fn generate_auth_token(email: &Email) -> Result<String> {
 
     let sub = email.as_ref().expose_secret().to_owned();
 
-    let claims = Claims { sub, exp };
+    let roles = roles.iter().map(|role| role.as_ref().to_owned()).collect();
+
+    let claims = Claims {
+        sub,
+        exp,
+        iat: now.timestamp(),
+        iat_ms: now.timestamp_millis(),
+        roles,
+        org: organization.map(|id| id.to_string()),
+    };
 
     create_token(&claims)
 }
 
+/// Why a token was rejected.
+/// Tokens of accounts that are not active are reported separately,
+/// so callers can tell the user what happened to their account.
+#[derive(Debug, Error)]
+pub enum TokenValidationError {
+    #[error("Invalid token")]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("Account is {0}")]
+    InactiveAccount(AccountStatus),
+}
+
+impl From<TokenValidationError> for AuthAPIError {
+    fn from(error: TokenValidationError) -> Self {
+        match error {
+            TokenValidationError::InvalidToken(_) => AuthAPIError::InvalidToken,
+            TokenValidationError::InactiveAccount(status) => {
+                AuthAPIError::for_account_status(status).unwrap_or(AuthAPIError::InvalidToken)
+            }
+        }
+    }
+}
+
+fn invalid_token() -> TokenValidationError {
+    jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken).into()
+}
+
 // Check if JWT auth token is valid by decoding it using the JWT secret
 #[tracing::instrument("Validate authentication token", skip_all)]
 pub async fn validate_token(
     token: &Secret<String>,
     banned_token_store: BannedTokenStoreType,
-) -> Result<Claims, jsonwebtoken::errors::Error> {
+    user_store: UserStoreType,
+) -> Result<Claims, TokenValidationError> {
     // If exists in banned token store
-    match banned_token_store.read().await.token_exists(token).await {
-        Ok(value) => {
-            if value {
-                return Err(jsonwebtoken::errors::Error::from(
-                    jsonwebtoken::errors::ErrorKind::InvalidToken,
-                ));
-            }
-        }
-        Err(_) => {
-            return Err(jsonwebtoken::errors::Error::from(
-                jsonwebtoken::errors::ErrorKind::InvalidToken,
-            ));
-        }
+    match banned_token_store.token_exists(&TokenHash::of(token)).await {
+        Ok(false) => {}
+        Ok(true) | Err(_) => return Err(invalid_token()),
     }
 
-    decode::<Claims>(
+    let claims = decode::<Claims>(
         token.expose_secret(),
         &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
         &Validation::default(),
     )
-    .map(|data| data.claims)
+    .map(|data| data.claims)?;
+
+    // Reject tokens issued before the user's sessions were revoked. Milliseconds
+    // tell a login right after the revocation apart from the sessions it ended.
+    let email = Email::parse(Secret::new(claims.sub.clone())).map_err(|_| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSubject)
+    })?;
+    match banned_token_store.sessions_revoked_at(&email).await {
+        Ok(Some(revoked_at)) if claims.iat_ms <= revoked_at => return Err(invalid_token()),
+        Ok(_) => {}
+        Err(_) => return Err(invalid_token()),
+    }
+
+    // Tokens stop working as soon as the account is no longer active,
+    // and tokens of deleted users are never valid
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| invalid_token())?;
+    if !user.status.is_active() {
+        return Err(TokenValidationError::InactiveAccount(user.status));
+    }
+    Ok(claims)
 }
 
 // Create JWT auth token by encoding claims using the JWT secret
@@ -102,10 +213,206 @@ fn create_token(claims: &Claims) -> Result<String> {
     .wrap_err("Failed to create token")
 }
 
+/// Creates the token of the "this wasn't me" link in security alerts.
+/// It is signed with its own key, so it never passes as a session token.
+#[tracing::instrument("Generate security report token", skip_all)]
+pub fn generate_security_report_token(email: &Email) -> Result<String> {
+    let exp = Utc::now()
+        .checked_add_signed(chrono::Duration::days(SECURITY_REPORT_TTL_DAYS))
+        .ok_or(eyre!("failed to compute security report expiry"))?
+        .timestamp();
+    let claims = SecurityReportClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        exp: exp
+            .try_into()
+            .wrap_err("failed to cast security report expiry to usize")?,
+    };
+    encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(&derived_key(b"security-report")),
+    )
+    .wrap_err("Failed to create security report token")
+}
+
+/// Returns the account a "this wasn't me" link was sent for,
+/// and the time left until the link expires
+#[tracing::instrument("Validate security report token", skip_all)]
+pub fn validate_security_report_token(token: &Secret<String>) -> Result<(Email, Duration)> {
+    let claims = decode::<SecurityReportClaims>(
+        token.expose_secret(),
+        &DecodingKey::from_secret(&derived_key(b"security-report")),
+        &Validation::default(),
+    )
+    .wrap_err("Invalid security report token")?
+    .claims;
+    let remaining = claims.exp as i64 - Utc::now().timestamp();
+    let remaining_lifetime = Duration::from_secs(remaining.max(0) as u64);
+    Ok((Email::parse(Secret::new(claims.sub))?, remaining_lifetime))
+}
+
+/// Creates the token of a password reset link for `user`. It carries the time
+/// the password was last changed, so it stops working once it has been used.
+#[tracing::instrument("Generate password reset token", skip_all)]
+pub fn generate_password_reset_token(user: &User) -> Result<String> {
+    let exp = Utc::now()
+        .checked_add_signed(chrono::Duration::minutes(PASSWORD_RESET_TTL_MINUTES))
+        .ok_or(eyre!("failed to compute password reset expiry"))?
+        .timestamp();
+    let claims = PasswordResetClaims {
+        sub: user.email.as_ref().expose_secret().to_owned(),
+        pwd: user.password_changed_at.timestamp_micros(),
+        exp: exp
+            .try_into()
+            .wrap_err("failed to cast password reset expiry to usize")?,
+    };
+    encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(&derived_key(b"password-reset")),
+    )
+    .wrap_err("Failed to create password reset token")
+}
+
+/// Returns the account a password reset link was sent for, and the time its
+/// password had last been changed then, in unix microseconds. The link is only
+/// good while that time still matches the account.
+#[tracing::instrument("Validate password reset token", skip_all)]
+pub fn validate_password_reset_token(token: &Secret<String>) -> Result<(Email, i64)> {
+    let claims = decode::<PasswordResetClaims>(
+        token.expose_secret(),
+        &DecodingKey::from_secret(&derived_key(b"password-reset")),
+        &Validation::default(),
+    )
+    .wrap_err("Invalid password reset token")?
+    .claims;
+    Ok((Email::parse(Secret::new(claims.sub))?, claims.pwd))
+}
+
+/// Starts verifying that `email` owns `phone`. Returns the verification id
+/// handed to the caller and the code to send to the phone.
+///
+/// The code is derived from the id with a server-side key, so nothing is
+/// stored and the id alone does not reveal it. Unlike codes in the 2FA code
+/// store, it can never complete a login.
+pub fn generate_phone_verification(
+    email: &Email,
+    phone: &PhoneNumber,
+) -> Result<(String, TwoFACode)> {
+    let exp = Utc::now()
+        .checked_add_signed(chrono::Duration::seconds(TWO_FA_CODE_TTL_SECONDS as i64))
+        .ok_or(eyre!("failed to compute phone verification expiry"))?
+        .timestamp();
+    let claims = PhoneVerificationClaims {
+        sub: email.as_ref().expose_secret().to_owned(),
+        phone: phone.as_ref().expose_secret().to_owned(),
+        nonce: Uuid::new_v4().to_string(),
+        exp: exp
+            .try_into()
+            .wrap_err("failed to cast phone verification expiry to usize")?,
+    };
+    let token = encode(
+        &jsonwebtoken::Header::default(),
+        &claims,
+        &EncodingKey::from_secret(&derived_key(b"phone-verification")),
+    )
+    .wrap_err("Failed to create phone verification id")?;
+    Ok((token, claims.code()?))
+}
+
+/// Checks that `code` was sent to `phone` for `email` with the verification `id`
+#[tracing::instrument("Validate phone verification", skip_all)]
+pub fn validate_phone_verification(
+    id: &Secret<String>,
+    email: &Email,
+    phone: &PhoneNumber,
+    code: &TwoFACode,
+) -> Result<()> {
+    let claims = decode::<PhoneVerificationClaims>(
+        id.expose_secret(),
+        &DecodingKey::from_secret(&derived_key(b"phone-verification")),
+        &Validation::default(),
+    )
+    .wrap_err("Invalid phone verification id")?
+    .claims;
+    if &claims.sub != email.as_ref().expose_secret()
+        || &claims.phone != phone.as_ref().expose_secret()
+    {
+        return Err(eyre!("Phone verification is for another number"));
+    }
+    if claims.code()? != *code {
+        return Err(eyre!("Incorrect phone verification code"));
+    }
+    Ok(())
+}
+
+// Derived from the JWT secret, so there is no other secret to configure.
+// Each `purpose` gets its own key, so tokens of one kind are never accepted as another.
+fn derived_key(purpose: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(JWT_SECRET.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(purpose);
+    mac.finalize().into_bytes().to_vec()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+struct SecurityReportClaims {
+    sub: String,
+    exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PasswordResetClaims {
+    sub: String,
+    /// `password_changed_at` of the account in unix microseconds
+    pwd: i64,
+    exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PhoneVerificationClaims {
+    sub: String,
+    phone: String,
+    nonce: String,
+    exp: usize,
+}
+
+impl PhoneVerificationClaims {
+    fn code(&self) -> Result<TwoFACode> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&derived_key(b"phone-verification-code"))
+            .expect("HMAC accepts keys of any length");
+        mac.update(self.nonce.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        let number = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+        TwoFACode::parse((100_000 + number % 900_000).to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    /// Issued-at time in unix seconds
+    #[serde(default)]
+    pub iat: i64,
+    /// Issued-at time in unix milliseconds, used to honour session revocation.
+    /// Tokens issued before it existed count as issued at the epoch.
+    #[serde(default)]
+    pub iat_ms: i64,
+    // Tokens issued before roles existed carry no roles
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Id of the organization the user is currently acting in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+}
+
+impl Claims {
+    /// Time left until `exp`, zero once the token has expired
+    pub fn remaining_lifetime(&self) -> Duration {
+        let remaining = self.exp as i64 - Utc::now().timestamp();
+        Duration::from_secs(remaining.max(0) as u64)
+    }
 }
 
 #[cfg(test)]
@@ -113,16 +420,32 @@ mod tests {
     use std::sync::Arc;
 
     use secrecy::Secret;
-    use tokio::sync::RwLock;
 
-    use crate::domain::{data_stores::HashsetBannedTokenStore, parse::Parseable};
+    use crate::{
+        domain::{
+            data_stores::{BannedTokenStore, HashsetBannedTokenStore, UserStore},
+            password::Password,
+            user::{StatusChange, User},
+        },
+        services::hashmap_user_store::HashMapUserStore,
+    };
 
     use super::*;
 
+    async fn user_store_with(email: &Email) -> Arc<HashMapUserStore> {
+        let user_store = HashMapUserStore::default();
+        let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+        user_store
+            .add_user(User::new(email.clone(), false, password))
+            .await
+            .unwrap();
+        Arc::new(user_store)
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, &[], None).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -144,19 +467,109 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, &[], None).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
+    #[test]
+    fn test_token_hash_is_keyed_and_stable() {
+        let token = Secret::new("header.payload.signature".to_owned());
+        let hash = TokenHash::of(&token);
+        assert_eq!(hash, TokenHash::of(&token));
+        assert_ne!(hash, TokenHash::of(&Secret::new("other".to_owned())));
+        assert_eq!(hash.as_ref().len(), 64);
+        assert!(!hash.as_ref().contains(token.expose_secret().as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_security_report_tokens_are_not_session_tokens() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let report = Secret::new(generate_security_report_token(&email).unwrap());
+        let (reported, remaining_lifetime) = validate_security_report_token(&report).unwrap();
+        assert_eq!(reported, email);
+        assert!(remaining_lifetime.as_secs() > 0);
+
+        let result = validate_token(
+            &report,
+            Arc::new(HashsetBannedTokenStore::new()),
+            user_store_with(&email).await,
+        )
+        .await;
+        assert!(matches!(result, Err(TokenValidationError::InvalidToken(_))));
+
+        let session = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        assert!(validate_security_report_token(&session).is_err());
+    }
+
+    #[test]
+    fn test_password_reset_tokens_carry_the_last_password_change() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+        let user = User::new(email.clone(), false, password);
+        let token = Secret::new(generate_password_reset_token(&user).unwrap());
+        assert_eq!(
+            validate_password_reset_token(&token).unwrap(),
+            (email.clone(), user.password_changed_at.timestamp_micros())
+        );
+
+        let report = Secret::new(generate_security_report_token(&email).unwrap());
+        assert!(validate_password_reset_token(&report).is_err());
+        let session = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        assert!(validate_password_reset_token(&session).is_err());
+    }
+
+    #[test]
+    fn test_phone_verification_only_accepts_its_own_code_and_number() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let phone = PhoneNumber::parse(Secret::new("+4915112345678".to_owned())).unwrap();
+        let (id, code) = generate_phone_verification(&email, &phone).unwrap();
+        let id = Secret::new(id);
+        assert!(validate_phone_verification(&id, &email, &phone, &code).is_ok());
+
+        let other_code = match code.as_ref().expose_secret().as_str() {
+            "123456" => TwoFACode::parse("654321".to_owned()),
+            _ => TwoFACode::parse("123456".to_owned()),
+        }
+        .unwrap();
+        assert!(validate_phone_verification(&id, &email, &phone, &other_code).is_err());
+        let other_phone = PhoneNumber::parse(Secret::new("+14155550100".to_owned())).unwrap();
+        assert!(validate_phone_verification(&id, &email, &other_phone, &code).is_err());
+        let other_email = Email::parse(Secret::new("other@example.com".to_owned())).unwrap();
+        assert!(validate_phone_verification(&id, &other_email, &phone, &code).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remaining_lifetime_counts_down_to_exp() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        let mut claims = validate_token(
+            &token,
+            Arc::new(HashsetBannedTokenStore::new()),
+            user_store_with(&email).await,
+        )
+        .await
+        .unwrap();
+        let remaining = claims.remaining_lifetime().as_secs() as i64;
+        assert!((TOKEN_TTL_SECONDS - 5..=TOKEN_TTL_SECONDS).contains(&remaining));
+
+        claims.exp = (Utc::now().timestamp() - 60) as usize;
+        assert_eq!(claims.remaining_lifetime(), Duration::ZERO);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
-        let token = Secret::new(generate_auth_token(&email).unwrap());
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::new()));
+        let token = Secret::new(generate_auth_token(&email, &[Role::admin()], None).unwrap());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+        let user_store = user_store_with(&email).await;
 
         // Validate
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let result = validate_token(&token, banned_token_store, user_store)
+            .await
+            .unwrap();
         assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.roles, vec!["admin".to_owned()]);
+        assert_eq!(result.org, None);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -166,12 +579,110 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_carries_active_organization() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let organization = OrganizationId::new();
+        let token = Secret::new(generate_auth_token(&email, &[], Some(&organization)).unwrap());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+        let user_store = user_store_with(&email).await;
+
+        let result = validate_token(&token, banned_token_store, user_store)
+            .await
+            .unwrap();
+        assert_eq!(result.org, Some(organization.to_string()));
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = Secret::new("invalid_token".to_owned());
-        let banned_token_store: Arc<RwLock<HashsetBannedTokenStore>> =
-            Arc::new(RwLock::new(HashsetBannedTokenStore::new()));
-        let result = validate_token(&token, banned_token_store).await;
-        assert!(result.is_err());
+        let banned_token_store: Arc<HashsetBannedTokenStore> =
+            Arc::new(HashsetBannedTokenStore::new());
+        let user_store = Arc::new(HashMapUserStore::default());
+        let result = validate_token(&token, banned_token_store, user_store).await;
+        assert!(matches!(result, Err(TokenValidationError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_after_sessions_revoked() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+        let user_store = user_store_with(&email).await;
+
+        // Revocations that predate the token do not affect it
+        let issued_at = validate_token(&token, banned_token_store.clone(), user_store.clone())
+            .await
+            .unwrap()
+            .iat_ms;
+        banned_token_store
+            .revoke_sessions(&email, issued_at - 1)
+            .await
+            .unwrap();
+        assert!(
+            validate_token(&token, banned_token_store.clone(), user_store.clone())
+                .await
+                .is_ok()
+        );
+
+        banned_token_store
+            .revoke_sessions(&email, Utc::now().timestamp_millis())
+            .await
+            .unwrap();
+        assert!(
+            validate_token(&token, banned_token_store.clone(), user_store.clone())
+                .await
+                .is_err()
+        );
+
+        // Logging in again right away works, even within the same second
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        let token = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        assert!(validate_token(&token, banned_token_store, user_store)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_inactive_account() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+        let user_store = user_store_with(&email).await;
+
+        user_store
+            .set_status(
+                &email,
+                &StatusChange::new(AccountStatus::Locked, "admin@example.com"),
+            )
+            .await
+            .unwrap();
+        let result = validate_token(&token, banned_token_store.clone(), user_store.clone()).await;
+        assert!(matches!(
+            result,
+            Err(TokenValidationError::InactiveAccount(AccountStatus::Locked))
+        ));
+
+        // Reactivating the account makes the token usable again
+        user_store
+            .set_status(
+                &email,
+                &StatusChange::new(AccountStatus::Active, "admin@example.com"),
+            )
+            .await
+            .unwrap();
+        assert!(validate_token(&token, banned_token_store, user_store)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_deleted_user() {
+        let email = Email::parse(Secret::new("test@example.com".to_owned())).unwrap();
+        let token = Secret::new(generate_auth_token(&email, &[], None).unwrap());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::new());
+        let user_store = Arc::new(HashMapUserStore::default());
+        let result = validate_token(&token, banned_token_store, user_store).await;
+        assert!(matches!(result, Err(TokenValidationError::InvalidToken(_))));
     }
 }
@@ -0,0 +1,54 @@
+use std::{
+    sync::{PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Source of the current time for anything that expires, so tests can move
+/// time forward instead of sleeping.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The real, monotonic clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to
+/// ```
+/// use std::time::Duration;
+/// use auth_service::utils::clock::{Clock, ManualClock};
+/// let clock = ManualClock::default();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.now() - start, Duration::from_secs(60));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    now: RwLock<Instant>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self {
+            now: RwLock::new(Instant::now()),
+        }
+    }
+}
+
+impl ManualClock {
+    pub fn advance(&self, by: Duration) {
+        *self.now.write().unwrap_or_else(PoisonError::into_inner) += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -1,7 +1,7 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use secrecy::Secret;
-use std::env as std_env;
+use std::{env as std_env, fmt::Display, str::FromStr, time::Duration};
 
 // Define a lazily evaluated static.
 // lazy_static is needed because std_env::var is not a const function.
@@ -10,13 +10,69 @@ lazy_static! {
     pub static ref DATABASE_URL: String = get_database_url();
     pub static ref REDIS_HOST_NAME: String = get_redis_host();
     pub static ref POSTMARK_AUTH_TOKEN: Secret<String> = get_postmark_auth_token(); // New!
+    pub static ref INVITATION_URL: String = get_invitation_url();
+    pub static ref SECURITY_REPORT_URL: String = get_optional(env::SECURITY_REPORT_URL_ENV_VAR)
+        .unwrap_or(DEFAULT_SECURITY_REPORT_URL.to_owned());
+    pub static ref PASSWORD_RESET_URL: String = get_optional(env::PASSWORD_RESET_URL_ENV_VAR)
+        .unwrap_or(DEFAULT_PASSWORD_RESET_URL.to_owned());
+    pub static ref SQLITE_DATABASE_URL: Option<String> = get_sqlite_database_url();
+    pub static ref BANNED_TOKEN_STORE: StoreBackend =
+        get_store_backend(env::BANNED_TOKEN_STORE_ENV_VAR);
+    pub static ref TWO_FA_CODE_STORE: StoreBackend =
+        get_store_backend(env::TWO_FA_CODE_STORE_ENV_VAR);
+    pub static ref PROFILE: Profile = get_profile();
+    pub static ref USER_CACHE: UserCacheBackend = get_user_cache_backend();
+    pub static ref USER_CACHE_TTL: Duration = Duration::from_secs(get_number(
+        env::USER_CACHE_TTL_SECONDS_ENV_VAR,
+        DEFAULT_USER_CACHE_TTL_SECONDS
+    ));
+    pub static ref USER_CACHE_CAPACITY: usize =
+        get_number(env::USER_CACHE_CAPACITY_ENV_VAR, DEFAULT_USER_CACHE_CAPACITY);
+    pub static ref EMAIL_CLIENTS: Vec<EmailClientBackend> = get_email_client_backends();
+    pub static ref EMAIL_CIRCUIT_FAILURE_THRESHOLD: u32 = get_number(
+        env::EMAIL_CIRCUIT_FAILURE_THRESHOLD_ENV_VAR,
+        DEFAULT_EMAIL_CIRCUIT_FAILURE_THRESHOLD
+    );
+    pub static ref EMAIL_CIRCUIT_COOLDOWN: Duration = Duration::from_secs(get_number(
+        env::EMAIL_CIRCUIT_COOLDOWN_SECONDS_ENV_VAR,
+        DEFAULT_EMAIL_CIRCUIT_COOLDOWN_SECONDS
+    ));
+    pub static ref SMTP_HOST: String = retrieve_dot_env_variable(String::from(env::SMTP_HOST_ENV_VAR));
+    pub static ref SMTP_PORT: Option<u16> = get_optional(env::SMTP_PORT_ENV_VAR);
+    pub static ref SMTP_TLS: SmtpTls = get_optional(env::SMTP_TLS_ENV_VAR).unwrap_or_default();
+    pub static ref SMTP_USERNAME: Option<String> = get_optional(env::SMTP_USERNAME_ENV_VAR);
+    pub static ref SMTP_PASSWORD: Secret<String> =
+        Secret::new(get_optional(env::SMTP_PASSWORD_ENV_VAR).unwrap_or_default());
+    pub static ref SMTP_CA_CERTIFICATE: Option<String> =
+        get_optional(env::SMTP_CA_CERTIFICATE_ENV_VAR);
+    pub static ref BRAND_NAME: String =
+        get_optional(env::BRAND_NAME_ENV_VAR).unwrap_or(DEFAULT_BRAND_NAME.to_owned());
+    pub static ref BRAND_URL: String =
+        get_optional(env::BRAND_URL_ENV_VAR).unwrap_or(DEFAULT_BRAND_URL.to_owned());
+    pub static ref BRAND_SUPPORT_EMAIL: String = get_optional(env::BRAND_SUPPORT_EMAIL_ENV_VAR)
+        .unwrap_or(DEFAULT_BRAND_SUPPORT_EMAIL.to_owned());
+    pub static ref BRAND_COLOR: String =
+        get_optional(env::BRAND_COLOR_ENV_VAR).unwrap_or(DEFAULT_BRAND_COLOR.to_owned());
+    pub static ref EMAIL_OUTBOX_MAX_ATTEMPTS: u32 = get_number(
+        env::EMAIL_OUTBOX_MAX_ATTEMPTS_ENV_VAR,
+        DEFAULT_EMAIL_OUTBOX_MAX_ATTEMPTS
+    );
+    pub static ref POSTMARK_WEBHOOK_USERNAME: Option<String> =
+        get_optional(env::POSTMARK_WEBHOOK_USERNAME_ENV_VAR);
+    pub static ref POSTMARK_WEBHOOK_PASSWORD: Option<Secret<String>> =
+        get_optional(env::POSTMARK_WEBHOOK_PASSWORD_ENV_VAR).map(Secret::new);
+    pub static ref SMS_PROVIDER_URL: Option<String> = get_optional(env::SMS_PROVIDER_URL_ENV_VAR);
+    pub static ref SMS_PROVIDER_TOKEN: Secret<String> =
+        Secret::new(get_optional(env::SMS_PROVIDER_TOKEN_ENV_VAR).unwrap_or_default());
+    pub static ref SMS_SENDER: String =
+        get_optional(env::SMS_SENDER_ENV_VAR).unwrap_or(DEFAULT_SMS_SENDER.to_owned());
 }
 
 /// Add a variable key
 fn retrieve_dot_env_variable(variable_key: String) -> String {
     dotenv().ok();
     let value =
-        std_env::var(&variable_key).expect(format!("{} must be set", variable_key).as_str());
+        std_env::var(&variable_key).unwrap_or_else(|_| panic!("{} must be set", variable_key));
     if value.is_empty() {
         panic!("{}", format!("{} must not be empty", variable_key).as_str())
     }
@@ -28,6 +84,243 @@ fn get_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn get_invitation_url() -> String {
+    dotenv().ok();
+    std_env::var(env::INVITATION_URL_ENV_VAR).unwrap_or(DEFAULT_INVITATION_URL.to_owned())
+}
+
+// Users are kept in PostgreSQL unless a SQLite database is configured
+fn get_sqlite_database_url() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::SQLITE_DATABASE_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+/// Where the banned token and 2FA code stores keep their entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoreBackend {
+    Redis,
+    Postgres,
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "redis" => Ok(StoreBackend::Redis),
+            "postgres" => Ok(StoreBackend::Postgres),
+            _ => Err(format!("Unknown store backend: {}", s)),
+        }
+    }
+}
+
+// Stores default to Redis when nothing is configured
+fn get_store_backend(variable_key: &str) -> StoreBackend {
+    dotenv().ok();
+    match std_env::var(variable_key) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .unwrap_or_else(|e| panic!("{} is invalid: {}", variable_key, e)),
+        _ => StoreBackend::Redis,
+    }
+}
+
+/// Which set of services the binary wires up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// PostgreSQL, Redis and Postmark, as configured by the environment
+    #[default]
+    Production,
+    /// Every store and the email client live in memory, so nothing external
+    /// is needed. State is lost when the process exits.
+    InMemory,
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "production" => Ok(Profile::Production),
+            "in-memory" => Ok(Profile::InMemory),
+            _ => Err(format!("Unknown profile: {}", s)),
+        }
+    }
+}
+
+fn get_profile() -> Profile {
+    dotenv().ok();
+    match std_env::var(env::PROFILE_ENV_VAR) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .unwrap_or_else(|e| panic!("{} is invalid: {}", env::PROFILE_ENV_VAR, e)),
+        _ => Profile::default(),
+    }
+}
+
+/// Where `CachingUserStore` keeps users, if anywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UserCacheBackend {
+    #[default]
+    None,
+    /// Per instance, invalidated only by writes made on that instance
+    Memory,
+    /// Shared by every instance
+    Redis,
+}
+
+impl FromStr for UserCacheBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(UserCacheBackend::None),
+            "memory" => Ok(UserCacheBackend::Memory),
+            "redis" => Ok(UserCacheBackend::Redis),
+            _ => Err(format!("Unknown user cache: {}", s)),
+        }
+    }
+}
+
+fn get_user_cache_backend() -> UserCacheBackend {
+    dotenv().ok();
+    match std_env::var(env::USER_CACHE_ENV_VAR) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .unwrap_or_else(|e| panic!("{} is invalid: {}", env::USER_CACHE_ENV_VAR, e)),
+        _ => UserCacheBackend::default(),
+    }
+}
+
+fn get_number<T>(variable_key: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    get_optional(variable_key).unwrap_or(default)
+}
+
+fn get_optional<T>(variable_key: &str) -> Option<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    dotenv().ok();
+    match std_env::var(variable_key) {
+        Ok(value) if !value.is_empty() => Some(
+            value
+                .parse()
+                .unwrap_or_else(|e| panic!("{} is invalid: {}", variable_key, e)),
+        ),
+        _ => None,
+    }
+}
+
+/// Which service delivers emails in production
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmailClientBackend {
+    #[default]
+    Postmark,
+    /// Any SMTP server, configured by the `SMTP_*` variables
+    Smtp,
+}
+
+impl EmailClientBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailClientBackend::Postmark => "postmark",
+            EmailClientBackend::Smtp => "smtp",
+        }
+    }
+
+    /// Parses a comma-separated list of providers, most preferred first
+    /// ```
+    /// use auth_service::utils::constants::EmailClientBackend;
+    /// assert_eq!(
+    ///     EmailClientBackend::parse_list("smtp, postmark").unwrap(),
+    ///     vec![EmailClientBackend::Smtp, EmailClientBackend::Postmark]
+    /// );
+    /// assert!(EmailClientBackend::parse_list("smtp,smtp").is_err());
+    /// assert!(EmailClientBackend::parse_list("smtp,").is_err());
+    /// ```
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        let mut backends = Vec::new();
+        for backend in s.split(',') {
+            let backend: EmailClientBackend = backend.trim().parse()?;
+            if backends.contains(&backend) {
+                return Err(format!("{} is listed twice", backend.as_str()));
+            }
+            backends.push(backend);
+        }
+        Ok(backends)
+    }
+}
+
+impl FromStr for EmailClientBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "postmark" => Ok(EmailClientBackend::Postmark),
+            "smtp" => Ok(EmailClientBackend::Smtp),
+            _ => Err(format!("Unknown email client: {}", s)),
+        }
+    }
+}
+
+// Postmark alone when nothing is configured
+fn get_email_client_backends() -> Vec<EmailClientBackend> {
+    dotenv().ok();
+    match std_env::var(env::EMAIL_CLIENT_ENV_VAR) {
+        Ok(value) if !value.is_empty() => EmailClientBackend::parse_list(&value)
+            .unwrap_or_else(|e| panic!("{} is invalid: {}", env::EMAIL_CLIENT_ENV_VAR, e)),
+        _ => vec![EmailClientBackend::default()],
+    }
+}
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SmtpTls {
+    /// Plaintext, only for relays on a trusted network
+    None,
+    /// Plaintext upgraded with STARTTLS, which the server must offer
+    #[default]
+    StartTls,
+    /// TLS from the first byte, also known as SMTPS
+    Implicit,
+}
+
+impl SmtpTls {
+    /// Port the server usually listens on for this mode
+    /// ```
+    /// use auth_service::utils::constants::SmtpTls;
+    /// assert_eq!(SmtpTls::default().default_port(), 587);
+    /// assert_eq!("tls".parse::<SmtpTls>().unwrap().default_port(), 465);
+    /// ```
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpTls::None => 25,
+            SmtpTls::StartTls => 587,
+            SmtpTls::Implicit => 465,
+        }
+    }
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(SmtpTls::None),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Implicit),
+            _ => Err(format!("Unknown SMTP TLS mode: {}", s)),
+        }
+    }
+}
+
 fn get_postmark_auth_token() -> Secret<String> {
     Secret::new(retrieve_dot_env_variable(String::from(
         env::POSTMARK_AUTH_TOKEN,
@@ -47,14 +340,82 @@ pub mod env {
     pub const DATABASE_URL: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
     pub const POSTMARK_AUTH_TOKEN: &str = "POSTMARK_AUTH_TOKEN";
+    pub const INVITATION_URL_ENV_VAR: &str = "INVITATION_URL";
+    pub const SECURITY_REPORT_URL_ENV_VAR: &str = "SECURITY_REPORT_URL";
+    pub const PASSWORD_RESET_URL_ENV_VAR: &str = "PASSWORD_RESET_URL";
+    pub const SQLITE_DATABASE_URL_ENV_VAR: &str = "SQLITE_DATABASE_URL";
+    pub const BANNED_TOKEN_STORE_ENV_VAR: &str = "BANNED_TOKEN_STORE";
+    pub const TWO_FA_CODE_STORE_ENV_VAR: &str = "TWO_FA_CODE_STORE";
+    pub const PROFILE_ENV_VAR: &str = "APP_PROFILE";
+    pub const USER_CACHE_ENV_VAR: &str = "USER_CACHE";
+    pub const USER_CACHE_TTL_SECONDS_ENV_VAR: &str = "USER_CACHE_TTL_SECONDS";
+    pub const USER_CACHE_CAPACITY_ENV_VAR: &str = "USER_CACHE_CAPACITY";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_CA_CERTIFICATE_ENV_VAR: &str = "SMTP_CA_CERTIFICATE";
+    pub const BRAND_NAME_ENV_VAR: &str = "BRAND_NAME";
+    pub const BRAND_URL_ENV_VAR: &str = "BRAND_URL";
+    pub const BRAND_SUPPORT_EMAIL_ENV_VAR: &str = "BRAND_SUPPORT_EMAIL";
+    pub const BRAND_COLOR_ENV_VAR: &str = "BRAND_COLOR";
+    pub const EMAIL_OUTBOX_MAX_ATTEMPTS_ENV_VAR: &str = "EMAIL_OUTBOX_MAX_ATTEMPTS";
+    pub const EMAIL_CIRCUIT_FAILURE_THRESHOLD_ENV_VAR: &str = "EMAIL_CIRCUIT_FAILURE_THRESHOLD";
+    pub const EMAIL_CIRCUIT_COOLDOWN_SECONDS_ENV_VAR: &str = "EMAIL_CIRCUIT_COOLDOWN_SECONDS";
+    pub const POSTMARK_WEBHOOK_USERNAME_ENV_VAR: &str = "POSTMARK_WEBHOOK_USERNAME";
+    pub const POSTMARK_WEBHOOK_PASSWORD_ENV_VAR: &str = "POSTMARK_WEBHOOK_PASSWORD";
+    pub const SMS_PROVIDER_URL_ENV_VAR: &str = "SMS_PROVIDER_URL";
+    pub const SMS_PROVIDER_TOKEN_ENV_VAR: &str = "SMS_PROVIDER_TOKEN";
+    pub const SMS_SENDER_ENV_VAR: &str = "SMS_SENDER";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+// Identifies the browser across logins, so logins from new devices can be reported
+pub const DEVICE_COOKIE_NAME: &str = "device";
+// Pending 2FA codes expire after 10 minutes
+pub const TWO_FA_CODE_TTL_SECONDS: u64 = 600;
+// Wrong codes allowed per texted phone verification code, out of 900000 possible codes
+pub const MAX_PHONE_VERIFICATION_ATTEMPTS: u32 = 5;
 // Needed to get it working in production
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+// Page of the app service that accepts invitations. The token is appended as a query parameter.
+pub const DEFAULT_INVITATION_URL: &str = "http://localhost:8000/invitations/accept";
+// Page of the app service behind the "this wasn't me" button of security alerts.
+// It posts the token appended as a query parameter to `/account/wasnt-me`.
+pub const DEFAULT_SECURITY_REPORT_URL: &str = "http://localhost:8000/account/wasnt-me";
+pub const SECURITY_REPORT_TTL_DAYS: i64 = 7;
+// Page of the app service where users choose a new password.
+// It posts the token appended as a query parameter to `/password-reset/confirm`.
+pub const DEFAULT_PASSWORD_RESET_URL: &str = "http://localhost:8000/password-reset";
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+
+// Short, since cached users are not invalidated by writes made on other instances
+pub const DEFAULT_USER_CACHE_TTL_SECONDS: u64 = 30;
+pub const DEFAULT_USER_CACHE_CAPACITY: usize = 10_000;
+
+// Branding shown in every email
+pub const DEFAULT_BRAND_NAME: &str = "Auth Service";
+pub const DEFAULT_BRAND_URL: &str = "http://localhost:8000";
+pub const DEFAULT_BRAND_SUPPORT_EMAIL: &str = "support@example.com";
+pub const DEFAULT_BRAND_COLOR: &str = "#2563eb";
+// With the default backoff, about two hours pass before an email is given up on
+pub const DEFAULT_EMAIL_OUTBOX_MAX_ATTEMPTS: u32 = 10;
+// An email provider is skipped for a minute after three failures in a row
+pub const DEFAULT_EMAIL_CIRCUIT_FAILURE_THRESHOLD: u32 = 3;
+pub const DEFAULT_EMAIL_CIRCUIT_COOLDOWN_SECONDS: u64 = 60;
+// Alphanumeric sender ID shown instead of a number by most carriers
+pub const DEFAULT_SMS_SENDER: &str = "AuthService";
+// Command line flag that selects `Profile::InMemory` regardless of `APP_PROFILE`
+pub const IN_MEMORY_FLAG: &str = "--in-memory";
 
 pub mod prod {
+    use std::time::Duration;
+
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
+    // How often expired entries of the Postgres and in-memory token stores are deleted
+    pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
     pub mod email_client {
         use std::time::Duration;
 
@@ -63,6 +424,21 @@ pub mod prod {
         pub const SENDER: &str = "bogdan@codeiron.io";
         pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
     }
+    pub mod sms_client {
+        use std::time::Duration;
+
+        pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
+    }
+    pub mod email_outbox {
+        use std::time::Duration;
+
+        pub const BATCH_SIZE: u32 = 20;
+        pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+        // Well above the email client timeout, so a message is never sent twice at once
+        pub const LEASE: Duration = Duration::from_secs(60);
+        pub const BASE_BACKOFF: Duration = Duration::from_secs(15);
+        pub const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+    }
 }
 
 pub mod test {
@@ -73,4 +449,10 @@ pub mod test {
         pub const SENDER: &str = "test@email.com";
         pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
     }
+    pub mod sms_client {
+        use std::time::Duration;
+
+        pub const SENDER: &str = "TestSender";
+        pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
+    }
 }
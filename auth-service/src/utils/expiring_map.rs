@@ -0,0 +1,80 @@
+use std::{collections::HashMap, hash::Hash, time::Instant};
+
+/// A map whose entries disappear once their deadline has passed, like keys
+/// set with a TTL in Redis. Expired entries are hidden from reads right away
+/// and only freed by [`ExpiringMap::evict_expired`].
+#[derive(Debug)]
+pub struct ExpiringMap<K, V> {
+    entries: HashMap<K, (V, Instant)>,
+}
+
+impl<K, V> Default for ExpiringMap<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> ExpiringMap<K, V> {
+    /// Insert or replace `key`, which expires at `expires_at`
+    pub fn insert(&mut self, key: K, value: V, expires_at: Instant) {
+        self.entries.insert(key, (value, expires_at));
+    }
+
+    pub fn get(&self, key: &K, now: Instant) -> Option<&V> {
+        self.entries
+            .get(key)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(value, _)| value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key).map(|(value, _)| value)
+    }
+
+    /// Drop every expired entry, returning how many were removed
+    pub fn evict_expired(&mut self, now: Instant) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, (_, expires_at)| *expires_at > now);
+        before - self.entries.len()
+    }
+
+    /// Number of entries, including expired ones not evicted yet
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_entries_expire_and_are_evicted() {
+        let now = Instant::now();
+        let mut map = ExpiringMap::default();
+        map.insert("short", 1, now + Duration::from_secs(10));
+        map.insert("long", 2, now + Duration::from_secs(60));
+        assert_eq!(map.get(&"short", now), Some(&1));
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(map.get(&"short", later), None);
+        assert_eq!(map.get(&"long", later), Some(&2));
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.evict_expired(later), 1);
+        assert_eq!(map.evict_expired(later), 0);
+        assert_eq!(map.len(), 1);
+
+        map.insert("long", 3, later + Duration::from_secs(5));
+        assert_eq!(map.get(&"long", later), Some(&3));
+        assert_eq!(map.remove(&"long"), Some(3));
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, convert::Infallible};
+
+use axum::{
+    async_trait,
+    body::Body,
+    extract::{FromRequestParts, Request},
+    http::{
+        header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_LENGTH},
+        request::Parts,
+        HeaderMap, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use lazy_static::lazy_static;
+
+use crate::{domain::locale::Locale, ErrorResponse};
+
+lazy_static! {
+    static ref CATALOGS: Catalogs = Locale::ALL
+        .iter()
+        .map(|locale| {
+            let catalog = serde_json::from_str(catalog_source(*locale))
+                .unwrap_or_else(|e| panic!("Invalid message catalog {}: {}", locale, e));
+            (*locale, catalog)
+        })
+        .collect();
+}
+
+// Embedded so the binary does not depend on the working directory
+fn catalog_source(locale: Locale) -> &'static str {
+    match locale {
+        Locale::English => include_str!("../../locales/en.json"),
+        Locale::German => include_str!("../../locales/de.json"),
+    }
+}
+
+type Catalogs = HashMap<Locale, HashMap<String, String>>;
+
+/// The message for `key` in the first locale of the fallback chain that has
+/// it, or `None` if not even English has it.
+///
+/// ```
+/// use auth_service::{domain::locale::Locale, utils::i18n::message};
+/// assert_eq!(message(Locale::German, "error.user_not_found"), Some("Benutzer nicht gefunden"));
+/// assert_eq!(message(Locale::German, "no.such.key"), None);
+/// ```
+pub fn message(locale: Locale, key: &str) -> Option<&'static str> {
+    find_message(&CATALOGS, locale, key)
+}
+
+fn find_message<'a>(catalogs: &'a Catalogs, locale: Locale, key: &str) -> Option<&'a str> {
+    locale
+        .fallbacks()
+        .iter()
+        .find_map(|locale| catalogs.get(locale)?.get(key))
+        .map(String::as_str)
+}
+
+/// Like [`message`], but unknown keys are returned as they are.
+///
+/// ```
+/// use auth_service::{domain::locale::Locale, utils::i18n::translate};
+/// assert_eq!(translate(Locale::German, "error.user_not_found"), "Benutzer nicht gefunden");
+/// assert_eq!(translate(Locale::English, "error.user_not_found"), "User not found");
+/// ```
+pub fn translate(locale: Locale, key: &str) -> &str {
+    message(locale, key).unwrap_or_else(|| {
+        tracing::warn!("No message for {}", key);
+        key
+    })
+}
+
+/// The message of an API error, from its [`code`](crate::domain::error::AuthAPIError::code)
+pub fn error_message(locale: Locale, code: &str) -> String {
+    translate(locale, &format!("error.{}", code)).to_owned()
+}
+
+/// The locale a request asked for with `Accept-Language`,
+/// or `None` if it names no supported language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RequestLocale(pub Option<Locale>);
+
+impl RequestLocale {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self(
+            headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Locale::negotiate),
+        )
+    }
+
+    /// The requested locale, or the default one
+    pub fn locale(&self) -> Locale {
+        self.0.unwrap_or_default()
+    }
+
+    /// Locale to email a user in: the one they chose, or else the requested one
+    pub fn with_preference(&self, preference: Option<Locale>) -> Locale {
+        preference.or(self.0).unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestLocale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers(&parts.headers))
+    }
+}
+
+/// Set on error responses by [`AuthAPIError`](crate::domain::error::AuthAPIError),
+/// so [`localize_errors`] can translate them
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorCode(pub &'static str);
+
+/// Middleware that translates error responses into the locale the request
+/// asked for. Errors are rendered in English until they get here.
+pub async fn localize_errors(request: Request, next: Next) -> Response {
+    let locale = RequestLocale::from_headers(request.headers()).locale();
+    let response = next.run(request).await;
+    let Some(ErrorCode(code)) = response.extensions().get::<ErrorCode>().copied() else {
+        return response;
+    };
+
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .insert(CONTENT_LANGUAGE, HeaderValue::from_static(locale.as_str()));
+    if locale == Locale::default() {
+        return Response::from_parts(parts, body);
+    }
+    let body = serde_json::to_vec(&ErrorResponse {
+        error: error_message(locale, code),
+        code: code.to_owned(),
+    })
+    .expect("ErrorResponse is always serializable");
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::error::AuthAPIError;
+    use color_eyre::eyre::eyre;
+
+    #[test]
+    fn test_every_error_has_an_english_message() {
+        let errors = [
+            AuthAPIError::UserAlreadyExists,
+            AuthAPIError::InvalidCredentials,
+            AuthAPIError::IncorrectCredentials,
+            AuthAPIError::MissingToken,
+            AuthAPIError::InvalidToken,
+            AuthAPIError::InvalidInput,
+            AuthAPIError::UserNotFound,
+            AuthAPIError::RoleNotFound,
+            AuthAPIError::OrganizationNotFound,
+            AuthAPIError::InvitationNotFound,
+            AuthAPIError::EmailNotFound,
+            AuthAPIError::SuppressionNotFound,
+            AuthAPIError::Forbidden,
+            AuthAPIError::AccountSuspended,
+            AuthAPIError::AccountLocked,
+            AuthAPIError::AccountPendingVerification,
+            AuthAPIError::PasswordResetRequired,
+            AuthAPIError::PhoneNotVerified,
+            AuthAPIError::SmsUnavailable,
+            AuthAPIError::TooManyAttempts,
+            AuthAPIError::EmailSuppressed,
+            AuthAPIError::UnexpectedError(eyre!("boom")),
+        ];
+        for error in errors {
+            let key = format!("error.{}", error.code());
+            assert!(
+                CATALOGS[&Locale::English].contains_key(&key),
+                "{} is not in the English catalog",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_catalogs_only_use_english_keys() {
+        let english = &CATALOGS[&Locale::English];
+        for locale in Locale::ALL {
+            for key in CATALOGS[locale].keys() {
+                assert!(
+                    english.contains_key(key),
+                    "{} is not in the English catalog",
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_messages_fall_back_to_english() {
+        let catalogs: Catalogs = [
+            (
+                Locale::English,
+                HashMap::from([
+                    ("greeting".to_owned(), "Hello".to_owned()),
+                    ("farewell".to_owned(), "Goodbye".to_owned()),
+                ]),
+            ),
+            (
+                Locale::German,
+                HashMap::from([("greeting".to_owned(), "Hallo".to_owned())]),
+            ),
+        ]
+        .into();
+        assert_eq!(
+            find_message(&catalogs, Locale::German, "greeting"),
+            Some("Hallo")
+        );
+        assert_eq!(
+            find_message(&catalogs, Locale::German, "farewell"),
+            Some("Goodbye")
+        );
+        assert_eq!(find_message(&catalogs, Locale::German, "no.such.key"), None);
+    }
+
+    #[test]
+    fn test_translate_returns_unknown_keys() {
+        assert_eq!(translate(Locale::German, "no.such.key"), "no.such.key");
+        assert_eq!(translate(Locale::English, "no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_request_locale_from_accept_language() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            RequestLocale::from_headers(&headers).locale(),
+            Locale::English
+        );
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("de-DE,de;q=0.9"));
+        assert_eq!(
+            RequestLocale::from_headers(&headers),
+            RequestLocale(Some(Locale::German))
+        );
+        assert_eq!(
+            RequestLocale::from_headers(&headers).with_preference(Some(Locale::English)),
+            Locale::English
+        );
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static("fr"));
+        assert_eq!(RequestLocale::from_headers(&headers), RequestLocale(None));
+    }
+}
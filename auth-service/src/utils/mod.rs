@@ -1,3 +1,9 @@
+pub mod audit;
 pub mod auth;
+pub mod basic_auth;
+pub mod clock;
 pub mod constants;
+pub mod expiring_map;
+pub mod guard;
+pub mod i18n;
 pub mod tracing;
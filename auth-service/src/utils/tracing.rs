@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use axum::{body::Body, extract::Request, response::Response};
+use axum::{body::Body, extract::Request, http::HeaderValue, middleware::Next, response::Response};
 use color_eyre::eyre::Result;
 use tracing::{Level, Span};
 use tracing_error::ErrorLayer;
@@ -28,10 +28,40 @@ pub fn init_tracing() -> Result<()> {
 
 //...
 
-// Creates a new tracing span with a unique request ID for each incoming request.
+/// Header carrying the id that correlates logs and audit events of a request
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const MAXIMUM_REQUEST_ID_LEN: usize = 64;
+
+// Tags each request with an ID and echoes it back in the response.
+// An ID set by a proxy in front of the service is kept, so its logs line up with ours.
+pub async fn propagate_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .filter(|id| !id.is_empty() && id.len() <= MAXIMUM_REQUEST_ID_LEN && id.to_str().is_ok())
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("UUIDs are valid header values")
+        });
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, request_id.clone());
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+    response
+}
+
+// Creates a new tracing span with the request ID of each incoming request.
 // This helps in tracking and correlating logs for individual requests.
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
     tracing::span!(
         Level::INFO,
         "[REQUEST]",
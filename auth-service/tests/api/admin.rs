@@ -0,0 +1,363 @@
+use auth_service::{
+    domain::{email::Email, parse::Parseable, role::Role},
+    routes::{ListUsersResponse, RolesResponse, UserResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use reqwest::StatusCode;
+use secrecy::Secret;
+use test_case::test_case;
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let body = serde_json::json!({ "email": get_random_email(), "role": "admin" });
+    let response = app.post_admin("roles/assign", &body).await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_user_is_not_admin() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    // Users cannot promote themselves
+    let body = serde_json::json!({ "email": email, "role": "admin" });
+    let response = app.post_admin("roles/assign", &body).await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Insufficient permissions".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_assign_and_revoke_role_if_admin() {
+    let mut app = TestApp::new().await;
+    // Create the target user first, then log in as the admin
+    let target = app.signup_and_login_with_roles(&[]).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let body = serde_json::json!({ "email": target, "role": "admin" });
+    let response = app.post_admin("roles/assign", &body).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let roles = response.json::<RolesResponse>().await.unwrap();
+    assert_eq!(roles.roles, vec!["admin".to_owned()]);
+
+    let response = app.post_admin("roles/revoke", &body).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let roles = response.json::<RolesResponse>().await.unwrap();
+    assert!(roles.roles.is_empty());
+
+    let target = Email::parse(Secret::new(target)).unwrap();
+    let stored_roles = app.user_store.get_roles(&target).await.unwrap();
+    assert!(stored_roles.is_empty());
+    app.clean_up().await;
+}
+
+#[test_case(serde_json::json!({ "email": get_random_email(), "role": "admin" }), StatusCode::NOT_FOUND)]
+#[test_case(serde_json::json!({ "email": "not-an-email", "role": "admin" }), StatusCode::BAD_REQUEST)]
+#[test_case(serde_json::json!({ "email": get_random_email(), "role": "Not A Role" }), StatusCode::BAD_REQUEST)]
+#[tokio::test]
+async fn should_reject_invalid_role_assignments(body: serde_json::Value, expected: StatusCode) {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app.post_admin("roles/assign", &body).await;
+    _assert_eq_status_code(&response, expected);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_if_role_not_found() {
+    let mut app = TestApp::new().await;
+    let target = app.signup_and_login_with_roles(&[]).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let body = serde_json::json!({ "email": target, "role": "ghost" });
+    let response = app.post_admin("roles/assign", &body).await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    app.clean_up().await;
+}
+
+/// Sign up a user and log them in, returning their email and JWT.
+/// The cookie jar is overwritten by the next login.
+async fn signup_and_capture_token(app: &TestApp, requires_2fa: bool) -> (String, String) {
+    let email = get_random_email();
+    let credentials = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app
+        .signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    if requires_2fa {
+        return (email, String::new());
+    }
+
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    (email, token)
+}
+
+#[tokio::test]
+async fn should_return_403_listing_users_without_permission() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app.get_admin("users").await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_and_search_users_with_pagination() {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, false).await;
+    let admin = app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let response = app.get_admin("users?perPage=1&page=2").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let page = response.json::<ListUsersResponse>().await.unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.page, 2);
+    assert_eq!(page.per_page, 1);
+    assert_eq!(page.users.len(), 1);
+
+    // Search matches a case-insensitive substring of the email
+    let search = &target[..8].to_uppercase();
+    let response = app.get_admin(&format!("users?search={}", search)).await;
+    let page = response.json::<ListUsersResponse>().await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.users[0].email, target);
+
+    let response = app.get_admin(&format!("users/{}", admin)).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let user = response.json::<UserResponse>().await.unwrap();
+    assert_eq!(user.roles, vec!["admin".to_owned()]);
+    assert_eq!(user.status, "active");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_viewing_unknown_user() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app
+        .get_admin(&format!("users/{}", get_random_email()))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_while_disabled() {
+    let mut app = TestApp::new().await;
+    let (target, token) = signup_and_capture_token(&app, false).await;
+    let admin = app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let credentials = serde_json::json!({ "email": target, "password": "password123" });
+
+    let response = app
+        .post_admin(
+            &format!("users/{}/disable", target),
+            &serde_json::json!({ "reason": "Chargeback" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let user = response.json::<UserResponse>().await.unwrap();
+    assert_eq!(user.status, "suspended");
+    assert_eq!(user.status_reason.as_deref(), Some("Chargeback"));
+    assert_eq!(user.status_changed_by.as_deref(), Some(admin.as_str()));
+
+    // Existing sessions are revoked and new logins are refused
+    let response = app
+        .verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Account suspended".to_owned()
+    );
+
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app
+        .post_admin(&format!("users/{}/enable", target), &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_after_forced_password_reset() {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, false).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let response = app
+        .post_admin(
+            &format!("users/{}/force-password-reset", target),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert!(
+        response
+            .json::<UserResponse>()
+            .await
+            .unwrap()
+            .password_reset_required
+    );
+
+    let credentials = serde_json::json!({ "email": target, "password": "password123" });
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Password reset required".to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_turn_off_2fa_on_reset() {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, true).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let response = app
+        .post_admin(
+            &format!("users/{}/reset-2fa", target),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert!(!response.json::<UserResponse>().await.unwrap().requires_2fa);
+
+    // The user now logs in with their password alone
+    let credentials = serde_json::json!({ "email": target, "password": "password123" });
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_existing_tokens_on_session_revocation() {
+    let mut app = TestApp::new().await;
+    let (target, token) = signup_and_capture_token(&app, false).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let response = app
+        .verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .post_admin(
+            &format!("users/{}/revoke-sessions", target),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+
+    // Logging in again right away, within the same second, gives a working session
+    let credentials = serde_json::json!({ "email": target, "password": "password123" });
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let response = app
+        .verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_modifying_users_with_read_only_access() {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, false).await;
+    // Users without any role are rejected before the handler runs
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app
+        .post_admin(&format!("users/{}/disable", target), &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    app.clean_up().await;
+}
+
+#[test_case("locked", StatusCode::LOCKED, "Account locked")]
+#[test_case(
+    "pending_verification",
+    StatusCode::FORBIDDEN,
+    "Account pending verification"
+)]
+#[test_case("suspended", StatusCode::FORBIDDEN, "Account suspended")]
+#[tokio::test]
+async fn should_report_account_status_at_login(status: &str, code: StatusCode, message: &str) {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, false).await;
+    let admin = app.signup_and_login_with_roles(&[Role::admin()]).await;
+
+    let response = app
+        .post_admin(
+            &format!("users/{}/status", target),
+            &serde_json::json!({ "status": status, "reason": "Investigating" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let user = response.json::<UserResponse>().await.unwrap();
+    assert_eq!(user.status, status);
+    assert_eq!(user.status_reason.as_deref(), Some("Investigating"));
+    assert_eq!(user.status_changed_by.as_deref(), Some(admin.as_str()));
+
+    let credentials = serde_json::json!({ "email": target, "password": "password123" });
+    let response = app.login(&credentials).await;
+    _assert_eq_status_code(&response, code);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        message.to_owned()
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_unknown_status() {
+    let mut app = TestApp::new().await;
+    let (target, _) = signup_and_capture_token(&app, false).await;
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app
+        .post_admin(
+            &format!("users/{}/status", target),
+            &serde_json::json!({ "status": "deleted" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
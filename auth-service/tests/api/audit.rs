@@ -0,0 +1,215 @@
+use auth_service::{
+    domain::{
+        audit::AuditAction, data_stores::AuditQuery, email::Email, parse::Parseable, role::Role,
+    },
+    routes::AuditEventsResponse,
+    utils::tracing::REQUEST_ID_HEADER,
+};
+use reqwest::StatusCode;
+use secrecy::Secret;
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+async fn audit_events(app: &TestApp, admin: bool, query: &str) -> AuditEventsResponse {
+    let response = app.get_audit_events(admin, query).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+    let response = app.get_audit_events(false, "").await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_own_events_newest_first() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    // Someone else's events stay out of the list
+    let other = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": other,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+
+    let page = audit_events(&app, false, "").await;
+    let actions: Vec<&str> = page
+        .events
+        .iter()
+        .map(|event| event.action.as_str())
+        .collect();
+    assert_eq!(actions, vec!["logged_in", "signed_up"]);
+    assert_eq!(page.total, 2);
+    for event in &page.events {
+        assert_eq!(event.actor.as_deref(), Some(email.as_str()));
+        assert_eq!(event.outcome, "success");
+        assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+        assert!(event.request_id.is_some());
+    }
+
+    // Reading the trail is itself audited
+    let page = audit_events(&app, false, "?action=audit_viewed").await;
+    assert_eq!(page.total, 1);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_failed_logins_against_the_account() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "wrong-password" }))
+        .await;
+    assert!(!response.status().is_success());
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let page = audit_events(&app, false, "?action=logged_in&perPage=1&page=2").await;
+    assert_eq!(page.total, 2);
+    assert_eq!(page.events.len(), 1);
+    assert_eq!(page.events[0].outcome, "failure");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_attempts_with_differently_cased_emails_against_the_account() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email.to_uppercase(),
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+    let mixed_case = email
+        .char_indices()
+        .map(|(i, c)| {
+            if i % 2 == 0 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    let response = app
+        .login(&serde_json::json!({ "email": mixed_case, "password": "wrong-password" }))
+        .await;
+    assert!(!response.status().is_success());
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let page = audit_events(&app, false, "").await;
+    let events: Vec<(&str, &str)> = page
+        .events
+        .iter()
+        .map(|event| (event.action.as_str(), event.outcome.as_str()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("logged_in", "success"),
+            ("logged_in", "failure"),
+            ("signed_up", "success")
+        ]
+    );
+    for event in &page.events {
+        assert_eq!(event.actor.as_deref(), Some(email.as_str()));
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_user_agent_and_request_id() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    }))
+    .await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("user-agent", "audit-test/1.0")
+        .header(REQUEST_ID_HEADER, "request-from-proxy")
+        .json(&serde_json::json!({ "email": email, "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to handle login");
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "request-from-proxy"
+    );
+
+    let page = audit_events(&app, false, "?action=logged_in").await;
+    assert_eq!(page.events[0].user_agent.as_deref(), Some("audit-test/1.0"));
+    assert_eq!(
+        page.events[0].request_id.as_deref(),
+        Some("request-from-proxy")
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_unknown_action() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app
+        .get_audit_events(false, "?action=launched_rockets")
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_denied_access_and_let_admins_query_it() {
+    let mut app = TestApp::new().await;
+    let user = app.signup_and_login_with_roles(&[]).await;
+    let response = app.get_audit_events(true, "").await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+
+    let admin = app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app.get_admin(&format!("users/{}", user)).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let page = audit_events(&app, true, &format!("?email={}&action=access_denied", user)).await;
+    assert_eq!(page.total, 1);
+    assert_eq!(page.events[0].actor.as_deref(), Some(user.as_str()));
+    assert_eq!(page.events[0].outcome, "failure");
+
+    // Admin actions show up in the trail of the account they targeted
+    let target = Email::parse(Secret::new(user)).unwrap();
+    let page = app
+        .audit_store
+        .query(&AuditQuery::default().involving(target))
+        .await
+        .unwrap();
+    let viewed = page
+        .events
+        .iter()
+        .find(|event| event.action == AuditAction::UserViewed)
+        .expect("Viewing the user should be audited");
+    assert_eq!(viewed.actor.as_deref(), Some(admin.as_str()));
+    app.clean_up().await;
+}
@@ -0,0 +1,106 @@
+use auth_service::routes::{MailboxResponse, TwoFactorAuthResponse};
+use reqwest::{header, StatusCode};
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+/// Sign up `email` with 2FA and log in, which emails them a code
+async fn login_with_2fa(app: &TestApp, email: &str) -> TwoFactorAuthResponse {
+    let response = app
+        .signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let response = app
+        .login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+    response.json::<TwoFactorAuthResponse>().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_complete_2fa_with_the_code_from_the_mailbox() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = get_random_email();
+    let login = login_with_2fa(&app, &email).await;
+
+    let code = app.two_fa_code_from_mailbox(&email).await;
+    let response = app
+        .verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    // Nothing reached the email provider
+    let requests = app.email_server.received_requests().await.unwrap();
+    assert!(requests.is_empty());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_captured_emails_newest_first() {
+    let mut app = TestApp::with_mailbox().await;
+    let alice = get_random_email();
+    let bob = get_random_email();
+    login_with_2fa(&app, &alice).await;
+    login_with_2fa(&app, &bob).await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/dev/mailbox", &app.address))
+        .header(header::ACCEPT, "application/json")
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let mailbox = response.json::<MailboxResponse>().await.unwrap();
+    let recipients: Vec<&str> = mailbox
+        .emails
+        .iter()
+        .map(|email| email.to.as_str())
+        .collect();
+    assert_eq!(recipients, vec![bob.as_str(), alice.as_str()]);
+
+    let response = app
+        .get_dev_mailbox(&format!("to={}&format=json", alice))
+        .await;
+    let mailbox = response.json::<MailboxResponse>().await.unwrap();
+    assert_eq!(mailbox.emails.len(), 1);
+    assert_eq!(mailbox.emails[0].to, alice);
+
+    let response = app.get_dev_mailbox("to=not-an-email").await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_render_the_mailbox_as_escaped_html() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = get_random_email();
+    login_with_2fa(&app, &email).await;
+
+    let response = app.get_dev_mailbox("").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+    assert!(content_type.starts_with("text/html"));
+    let page = response.text().await.unwrap();
+    assert!(page.contains(&email));
+    // The email's own markup only appears escaped, inside the sandboxed frame
+    assert!(page.contains("srcdoc=\"&lt;"));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_serve_the_mailbox_when_emails_are_sent() {
+    let mut app = TestApp::new().await;
+    let response = app.get_dev_mailbox("format=json").await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    app.clean_up().await;
+}
@@ -0,0 +1,142 @@
+use auth_service::domain::{email::Email, parse::Parseable, role::Role};
+use reqwest::StatusCode;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::migrate::Migrator;
+use uuid::Uuid;
+
+use crate::helpers::{_assert_eq_status_code, TestApp};
+
+// Version of the migration that lowercases stored emails
+const NORMALIZE_EMAILS_MIGRATION: i64 = 20241103090000;
+
+#[tokio::test]
+async fn should_treat_spellings_of_an_email_as_one_account() {
+    let mut app = TestApp::new().await;
+    let local_part = Uuid::new_v4();
+    let typed = format!("  {}@Example.COM ", local_part.to_string().to_uppercase());
+
+    let response = app
+        .signup(&serde_json::json!({
+            "email": typed,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let response = app
+        .signup(&serde_json::json!({
+            "email": format!("{}@example.com", local_part),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CONFLICT);
+
+    let response = app
+        .login(&serde_json::json!({
+            "email": format!("{}@EXAMPLE.com", local_part),
+            "password": "password123"
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+/// Migrator for the test database, rolled back to before emails were normalized
+async fn undo_normalize_emails_migration(app: &TestApp) -> Migrator {
+    let mut migrator = sqlx::migrate!();
+    // A failed run keeps the advisory lock on its pooled connection, and would
+    // block the next one. Nothing else migrates the test database anyway.
+    migrator.set_locking(false);
+    migrator
+        .undo(&app.pg_pool, NORMALIZE_EMAILS_MIGRATION - 1)
+        .await
+        .unwrap();
+    migrator
+}
+
+#[tokio::test]
+async fn should_refuse_to_migrate_emails_that_only_differ_in_case() {
+    let mut app = TestApp::new().await;
+    let migrator = undo_normalize_emails_migration(&app).await;
+
+    // Written before emails were normalized
+    let local_part = Uuid::new_v4();
+    let spellings = [
+        format!("{}@Example.com", local_part),
+        format!("{}@example.com", local_part),
+    ];
+    for email in &spellings {
+        sqlx::query(
+            "INSERT INTO users (id, email, password_hash, requires_2fa)
+             VALUES (gen_random_uuid(), $1, 'hash', FALSE)",
+        )
+        .bind(email)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query("INSERT INTO user_roles (email, role) VALUES ($1, 'admin')")
+        .bind(&spellings[0])
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let error = migrator.run(&app.pg_pool).await.unwrap_err();
+    assert!(error.to_string().contains(&spellings[0]));
+
+    // Once merged by hand, the remaining spelling is lowercased
+    sqlx::query("DELETE FROM users WHERE email = $1")
+        .bind(&spellings[1])
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    migrator.run(&app.pg_pool).await.unwrap();
+    let email = Email::parse(Secret::new(spellings[0].clone())).unwrap();
+    assert_eq!(
+        app.user_store.get_roles(&email).await.unwrap(),
+        vec![Role::admin()]
+    );
+
+    // The index guards the database itself
+    let result = sqlx::query(
+        "INSERT INTO users (id, email, password_hash, requires_2fa)
+         VALUES (gen_random_uuid(), $1, 'hash', FALSE)",
+    )
+    .bind(&spellings[0])
+    .execute(&app.pg_pool)
+    .await;
+    assert!(result.is_err());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_refuse_to_migrate_emails_that_need_converting() {
+    let mut app = TestApp::new().await;
+    let migrator = undo_normalize_emails_migration(&app).await;
+
+    // SQL cannot convert the domain to punycode like the service does
+    let typed = format!("{}@Bücher.example", Uuid::new_v4());
+    sqlx::query(
+        "INSERT INTO users (id, email, password_hash, requires_2fa)
+         VALUES (gen_random_uuid(), $1, 'hash', FALSE)",
+    )
+    .bind(&typed)
+    .execute(&app.pg_pool)
+    .await
+    .unwrap();
+    let error = migrator.run(&app.pg_pool).await.unwrap_err();
+    assert!(error.to_string().contains(&typed));
+
+    // Converted by hand, the address is the one the service looks up
+    let email = Email::parse(Secret::new(typed.clone())).unwrap();
+    sqlx::query("UPDATE users SET email = $1 WHERE email = $2")
+        .bind(email.as_ref().expose_secret())
+        .bind(&typed)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    migrator.run(&app.pg_pool).await.unwrap();
+    assert!(app.user_store.get_user_summary(&email).await.is_ok());
+    app.clean_up().await;
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{
+        data_stores::OutboxQuery,
+        email_outbox::{OutboxMessage, OutboxStatus, RetryPolicy},
+        role::Role,
+    },
+    routes::{ListEmailsResponse, OutboxEmailResponse},
+    services::email_outbox_worker::OutboxWorkerSettings,
+};
+use reqwest::StatusCode;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+fn settings(max_attempts: u32) -> OutboxWorkerSettings {
+    OutboxWorkerSettings {
+        batch_size: 10,
+        poll_interval: Duration::from_millis(50),
+        lease: Duration::from_secs(60),
+        retry: RetryPolicy {
+            max_attempts,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+        },
+    }
+}
+
+async fn mock_postmark(app: &TestApp, status: u16) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&app.email_server)
+        .await;
+}
+
+/// Sign up a user with 2FA and log in, which emails them a code
+async fn login_with_2fa(app: &TestApp) -> reqwest::Response {
+    let email = get_random_email();
+    let response = app
+        .signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+/// Wait for the worker to move the only message in the outbox to `status`
+async fn wait_for_status(app: &TestApp, status: OutboxStatus) -> OutboxMessage {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let page = app
+                .email_outbox
+                .list(&OutboxQuery::default().with_status(Some(status)))
+                .await
+                .unwrap();
+            if let Some(message) = page.messages.into_iter().next() {
+                return message;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("No email became {}", status))
+}
+
+#[tokio::test]
+async fn should_deliver_emails_written_by_requests() {
+    let mut app = TestApp::with_email_outbox(settings(3)).await;
+    mock_postmark(&app, 200).await;
+
+    let response = login_with_2fa(&app).await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+
+    let message = wait_for_status(&app, OutboxStatus::Sent).await;
+    assert_eq!(message.attempts, 1);
+    assert!(message.message.text_body.is_empty());
+    let requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_log_in_while_the_email_provider_fails() {
+    let mut app = TestApp::with_email_outbox(settings(2)).await;
+    mock_postmark(&app, 500).await;
+
+    let response = login_with_2fa(&app).await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+
+    // Retried once, then dead-lettered
+    let message = wait_for_status(&app, OutboxStatus::Dead).await;
+    assert_eq!(message.attempts, 2);
+    assert!(message.last_error.is_some());
+    assert!(!message.message.text_body.is_empty());
+    let requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_let_admins_list_and_retry_dead_emails() {
+    let mut app = TestApp::with_email_outbox(settings(1)).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .mount(&app.email_server)
+        .await;
+    mock_postmark(&app, 200).await;
+
+    let response = login_with_2fa(&app).await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+    let dead = wait_for_status(&app, OutboxStatus::Dead).await;
+
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app.get_admin("emails?status=dead").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let page = response.json::<ListEmailsResponse>().await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.emails[0].id, dead.id.to_string());
+    assert_eq!(page.emails[0].status, "dead");
+
+    let response = app
+        .post_admin(&format!("emails/{}/retry", dead.id), &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let retried = response.json::<OutboxEmailResponse>().await.unwrap();
+    assert_eq!(retried.attempts, 0);
+
+    let sent = wait_for_status(&app, OutboxStatus::Sent).await;
+    assert_eq!(sent.id, dead.id);
+
+    // Only dead-lettered emails can be retried
+    let response = app
+        .post_admin(&format!("emails/{}/retry", dead.id), &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    let response = app
+        .post_admin("emails/not-an-id/retry", &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    let response = app.get_admin("emails?status=lost").await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_permission_to_list_emails() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+
+    let response = app.get_admin("emails").await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    app.clean_up().await;
+}
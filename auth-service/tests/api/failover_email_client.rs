@@ -0,0 +1,248 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    app_state::state::AppState,
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage},
+        parse::Parseable,
+        password::Password,
+        role::Role,
+        user::User,
+    },
+    routes::ListEmailProvidersResponse,
+    services::failover_email_client::{CircuitBreakerSettings, CircuitState, FailoverEmailClient},
+    utils::{clock::ManualClock, constants::test},
+    Application,
+};
+use reqwest::{cookie::Jar, StatusCode};
+use secrecy::{ExposeSecret, Secret};
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::helpers::{configure_postmark_email_client, get_random_email};
+
+const COOLDOWN: Duration = Duration::from_secs(60);
+
+/// A provider stand-in that answers every email with `status`
+async fn provider(status: u16) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Primary and secondary providers, with circuits opening after two failures
+fn failover(
+    primary: &MockServer,
+    secondary: &MockServer,
+    clock: Arc<ManualClock>,
+) -> FailoverEmailClient {
+    let settings = CircuitBreakerSettings {
+        failure_threshold: 2,
+        cooldown: COOLDOWN,
+    };
+    FailoverEmailClient::with_clock(settings, clock)
+        .with_provider(
+            "primary",
+            Arc::new(configure_postmark_email_client(primary.uri())),
+        )
+        .with_provider(
+            "secondary",
+            Arc::new(configure_postmark_email_client(secondary.uri())),
+        )
+}
+
+async fn send(client: &FailoverEmailClient) -> color_eyre::eyre::Result<()> {
+    let recipient = Email::parse(Secret::new(get_random_email())).unwrap();
+    let message = EmailMessage {
+        subject: "Your code".to_owned(),
+        html_body: "<p>123456</p>".to_owned(),
+        text_body: "123456".to_owned(),
+    };
+    client.send_email(&recipient, &message).await
+}
+
+async fn requests(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap().len()
+}
+
+#[tokio::test]
+async fn should_only_use_the_primary_while_it_is_healthy() {
+    let (primary, secondary) = (provider(200).await, provider(200).await);
+    let client = failover(&primary, &secondary, Arc::new(ManualClock::default()));
+
+    for _ in 0..3 {
+        send(&client).await.unwrap();
+    }
+    assert_eq!(requests(&primary).await, 3);
+    assert_eq!(requests(&secondary).await, 0);
+    let health = client.health();
+    assert_eq!(health[0].successes, 3);
+    assert_eq!(health[1].successes, 0);
+}
+
+#[tokio::test]
+async fn should_fail_over_and_skip_a_failing_provider() {
+    let (primary, secondary) = (provider(500).await, provider(200).await);
+    let client = failover(&primary, &secondary, Arc::new(ManualClock::default()));
+
+    // Every email still goes out, first through both providers...
+    send(&client).await.unwrap();
+    send(&client).await.unwrap();
+    assert_eq!(requests(&primary).await, 2);
+    assert_eq!(requests(&secondary).await, 2);
+
+    // ...then straight through the secondary once the primary's circuit opens
+    send(&client).await.unwrap();
+    assert_eq!(requests(&primary).await, 2);
+    assert_eq!(requests(&secondary).await, 3);
+
+    let health = client.health();
+    assert_eq!(health[0].name, "primary");
+    assert_eq!(health[0].state, CircuitState::Open);
+    assert_eq!(health[0].consecutive_failures, 2);
+    assert!(health[0].last_error.is_some());
+    assert_eq!(health[1].state, CircuitState::Closed);
+}
+
+#[tokio::test]
+async fn should_fail_over_when_a_provider_times_out() {
+    let primary = MockServer::start().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+        .mount(&primary)
+        .await;
+    let secondary = provider(200).await;
+    let client = failover(&primary, &secondary, Arc::new(ManualClock::default()));
+
+    send(&client).await.unwrap();
+    assert_eq!(requests(&secondary).await, 1);
+    assert_eq!(client.health()[0].failures, 1);
+}
+
+#[tokio::test]
+async fn should_return_to_the_primary_once_it_recovers() {
+    let primary = MockServer::start().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .mount(&primary)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&primary)
+        .await;
+    let secondary = provider(200).await;
+    let clock = Arc::new(ManualClock::default());
+    let client = failover(&primary, &secondary, clock.clone());
+
+    send(&client).await.unwrap();
+    send(&client).await.unwrap();
+    assert_eq!(client.health()[0].state, CircuitState::Open);
+
+    // Still skipped until the cooldown ends
+    clock.advance(COOLDOWN - Duration::from_secs(1));
+    send(&client).await.unwrap();
+    assert_eq!(requests(&primary).await, 2);
+
+    // The trial email succeeds, which closes the circuit
+    clock.advance(Duration::from_secs(1));
+    send(&client).await.unwrap();
+    send(&client).await.unwrap();
+    assert_eq!(requests(&primary).await, 4);
+    assert_eq!(requests(&secondary).await, 3);
+    let health = client.health();
+    assert_eq!(health[0].state, CircuitState::Closed);
+    assert_eq!(health[0].consecutive_failures, 0);
+}
+
+#[tokio::test]
+async fn should_report_every_failure_when_no_provider_accepts() {
+    let (primary, secondary) = (provider(500).await, provider(422).await);
+    let client = failover(&primary, &secondary, Arc::new(ManualClock::default()));
+
+    let error = send(&client).await.unwrap_err().to_string();
+    assert!(error.contains("primary"), "{}", error);
+    assert!(error.contains("secondary"), "{}", error);
+
+    // Once both circuits are open, nothing is tried at all
+    assert!(send(&client).await.is_err());
+    let error = send(&client).await.unwrap_err().to_string();
+    assert!(error.contains("unavailable"), "{}", error);
+    assert_eq!(requests(&primary).await, 2);
+    assert_eq!(requests(&secondary).await, 2);
+}
+
+#[tokio::test]
+async fn should_show_admins_how_each_provider_is_doing() {
+    let (primary, secondary) = (provider(500).await, provider(200).await);
+    let client = Arc::new(failover(
+        &primary,
+        &secondary,
+        Arc::new(ManualClock::default()),
+    ));
+    let state = AppState::in_memory(client.clone()).with_email_providers(client.clone());
+    let admin = Email::parse(Secret::new(get_random_email())).unwrap();
+    let password = Password::parse(Secret::new("password123".to_owned())).unwrap();
+    state
+        .user_store
+        .add_user(User::new(admin.clone(), false, password))
+        .await
+        .unwrap();
+    state
+        .user_store
+        .assign_role(&admin, &Role::admin())
+        .await
+        .unwrap();
+    let app = Application::build(state, test::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
+    let address = format!("http://{}", app.address);
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+    let http_client = reqwest::Client::builder()
+        .cookie_provider(Arc::new(Jar::default()))
+        .build()
+        .unwrap();
+    let response = http_client
+        .post(format!("{}/login", address))
+        .json(&serde_json::json!({
+            "email": admin.as_ref().expose_secret(),
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    send(&client).await.unwrap();
+    send(&client).await.unwrap();
+    let response = http_client
+        .get(format!("{}/admin/emails/providers", address))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let providers = response
+        .json::<ListEmailProvidersResponse>()
+        .await
+        .unwrap()
+        .providers;
+    assert_eq!(providers.len(), 2);
+    assert_eq!(providers[0].name, "primary");
+    assert_eq!(providers[0].state, "open");
+    assert_eq!(providers[0].consecutive_failures, 2);
+    assert!(providers[0].last_error.is_some());
+    assert_eq!(providers[1].name, "secondary");
+    assert_eq!(providers[1].state, "closed");
+    assert_eq!(providers[1].successes, 2);
+}
@@ -1,17 +1,33 @@
 use std::{str::FromStr, sync::Arc};
 
 use auth_service::{
-    app_state::state::{AppState, BannedTokenStoreType, EmailClientType, TwoFACodeStoreType},
+    app_state::state::{
+        AppState, AuditStoreType, BannedTokenStoreType, EmailClientType, EmailOutboxType,
+        EmailSuppressionStoreType, OrganizationStoreType, TwoFACodeStoreType, UserStoreType,
+    },
     domain::{
         data_stores::configure_redis, email::Email, parse::Parseable,
-        redis_two_fa_code_store::RedisTwoFACodeStore,
+        redis_two_fa_code_store::RedisTwoFACodeStore, role::Role,
     },
     get_postgres_pool,
+    routes::{MailboxEmailResponse, MailboxResponse},
     services::{
-        data_stores::PostgresUserStore, postmark_email_client::PostmarkEmailClient,
+        capturing_email_client::CapturingEmailClient,
+        data_stores::PostgresUserStore,
+        email_outbox_worker::{EmailOutboxWorker, OutboxWorkerSettings},
+        http_sms_client::HttpSmsClient,
+        postgres_audit_store::PostgresAuditStore,
+        postgres_email_outbox::PostgresEmailOutbox,
+        postgres_email_suppression_store::PostgresEmailSuppressionStore,
+        postgres_organization_store::PostgresOrganizationStore,
+        postmark_email_client::PostmarkEmailClient,
         redis_banned_token_store::RedisBannedTokenStore,
+        suppressing_email_client::SuppressingEmailClient,
+    },
+    utils::{
+        basic_auth::BasicCredentials,
+        constants::{test, DATABASE_URL},
     },
-    utils::constants::{test, DATABASE_URL},
     Application,
 };
 use reqwest::{cookie::Jar, Client, StatusCode};
@@ -20,9 +36,8 @@ use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
 };
-use tokio::sync::RwLock;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
 
 pub struct TestApp {
     pub address: String,
@@ -31,11 +46,24 @@ pub struct TestApp {
     pub cookie_jar: Arc<Jar>,
     pub http_client: reqwest::Client,
     pub email_server: MockServer,
+    /// Accepts every text message sent through the SMS provider API
+    pub sms_server: MockServer,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    #[allow(dead_code)]
     pub email_client: EmailClientType,
+    pub organization_store: OrganizationStoreType,
+    pub audit_store: AuditStoreType,
+    pub email_outbox: EmailOutboxType,
+    pub email_suppression_store: EmailSuppressionStoreType,
+    pub pg_pool: PgPool,
 }
 
+/// Credentials the test apps expect on Postmark webhooks
+pub const POSTMARK_WEBHOOK_USERNAME: &str = "postmark";
+pub const POSTMARK_WEBHOOK_PASSWORD: &str = "webhook-password";
+
 /// Check whether a status code is expected value
 pub fn _assert_eq_status_code(response: &reqwest::Response, http_response_code: StatusCode) {
     assert_eq!(response.status().as_u16(), http_response_code.as_u16());
@@ -52,7 +80,7 @@ pub fn _assert_eq_response(response: &reqwest::Response, key: &str, expected_val
 }
 
 // New!
-fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
+pub fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
     let postmark_auth_token = Secret::new("auth_token".to_owned());
 
     let sender = Email::parse(Secret::new(test::email_client::SENDER.to_owned())).unwrap();
@@ -65,23 +93,36 @@ fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
     PostmarkEmailClient::new(base_url, sender, postmark_auth_token, http_client)
 }
 
+pub fn configure_http_sms_client(base_url: String) -> HttpSmsClient {
+    let http_client = Client::builder()
+        .timeout(test::sms_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    HttpSmsClient::new(
+        base_url,
+        test::sms_client::SENDER.to_owned(),
+        Secret::new("auth_token".to_owned()),
+        http_client,
+    )
+}
+
 async fn configure_postgresql(db_name: &str) -> PgPool {
     let postgresql_conn_url = DATABASE_URL.to_owned();
 
-    configure_database(&postgresql_conn_url, &db_name).await;
+    configure_database(&postgresql_conn_url, db_name).await;
 
     let postgresql_conn_url_with_db = format!("{}/{}", postgresql_conn_url, db_name);
 
     // Create a new connection pool and return it
     get_postgres_pool(&postgresql_conn_url_with_db)
         .await
-        .expect(
-            format!(
+        .unwrap_or_else(|_| {
+            panic!(
                 "Failed to create Postgres connection pool! Current url: {}",
                 postgresql_conn_url_with_db
             )
-            .as_str(),
-        )
+        })
 }
 
 async fn configure_database(db_conn_string: &str, db_name: &str) {
@@ -146,30 +187,96 @@ async fn delete_database(db_name: &str) {
         .expect("Failed to drop the database.");
 }
 
+/// Where the emails sent by a `TestApp` end up
+enum EmailDelivery {
+    /// Straight to the Postmark mock at `email_server`
+    Postmark,
+    /// Through the outbox, delivered to `email_server` by a worker
+    Outbox(OutboxWorkerSettings),
+    /// Captured, and shown at `/dev/mailbox`
+    Mailbox,
+}
+
 impl TestApp {
     pub async fn new() -> Self {
+        Self::build(EmailDelivery::Postmark).await
+    }
+
+    /// Like `new`, but requests only write emails to the outbox,
+    /// and a worker with `settings` delivers them to `email_server`
+    pub async fn with_email_outbox(settings: OutboxWorkerSettings) -> Self {
+        Self::build(EmailDelivery::Outbox(settings)).await
+    }
+
+    /// Like `new`, but emails are captured instead of sent,
+    /// so tests can read them back from `/dev/mailbox`
+    pub async fn with_mailbox() -> Self {
+        Self::build(EmailDelivery::Mailbox).await
+    }
+
+    async fn build(delivery: EmailDelivery) -> Self {
         let db_name = Uuid::new_v4().to_string();
-        let redis_connection = Arc::new(RwLock::new(configure_redis()));
+        let redis_connection = configure_redis().await;
         let pg_pool = configure_postgresql(&db_name).await;
         // Set up a mock email server
         let email_server = MockServer::start().await; // New!
         let base_url = email_server.uri(); // New!
-        let email_client = Arc::new(RwLock::new(configure_postmark_email_client(base_url))); // Updated!
+        let email_client = Arc::new(configure_postmark_email_client(base_url)); // Updated!
+        let sms_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&sms_server)
+            .await;
 
         // Required stores
-        let store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-        let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-            redis_connection.clone(),
-        )));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-        // let email_client = Arc::new(RwLock::new(MockEmailClient::default()));
-
-        let app_state: AppState = AppState::new(
-            store,
+        let user_store = Arc::new(PostgresUserStore::new(pg_pool.clone()));
+        let organization_store = Arc::new(PostgresOrganizationStore::new(pg_pool.clone()));
+        let audit_store = Arc::new(PostgresAuditStore::new(pg_pool.clone()));
+        let banned_token_store = Arc::new(RedisBannedTokenStore::new(redis_connection.clone()));
+        let two_fa_code_store = Arc::new(RedisTwoFACodeStore::new(redis_connection));
+        // let email_client = Arc::new(MockEmailClient::default());
+        let email_outbox: EmailOutboxType = Arc::new(PostgresEmailOutbox::new(pg_pool.clone()));
+        let mut mailbox = None;
+        let email_client: EmailClientType = match delivery {
+            EmailDelivery::Postmark => email_client,
+            EmailDelivery::Outbox(settings) => {
+                let worker = EmailOutboxWorker::new(email_outbox.clone(), email_client, settings);
+                let outbox_client = Arc::new(worker.outbox_client());
+                worker.spawn();
+                outbox_client
+            }
+            EmailDelivery::Mailbox => {
+                let capturing = Arc::new(CapturingEmailClient::default());
+                mailbox = Some(capturing.clone());
+                capturing
+            }
+        };
+
+        let email_suppression_store: EmailSuppressionStoreType =
+            Arc::new(PostgresEmailSuppressionStore::new(pg_pool.clone()));
+        let email_client: EmailClientType = Arc::new(SuppressingEmailClient::new(
+            email_client,
+            email_suppression_store.clone(),
+        ));
+
+        let mut app_state: AppState = AppState::new(
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
             email_client.clone(),
-        );
+            organization_store.clone(),
+            audit_store.clone(),
+            email_outbox.clone(),
+            email_suppression_store.clone(),
+        )
+        .with_postmark_webhook(BasicCredentials::new(
+            POSTMARK_WEBHOOK_USERNAME,
+            Secret::new(POSTMARK_WEBHOOK_PASSWORD.to_owned()),
+        ))
+        .with_message_client(Arc::new(configure_http_sms_client(sms_server.uri())));
+        if let Some(mailbox) = mailbox {
+            app_state = app_state.with_mailbox(mailbox);
+        }
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
@@ -196,9 +303,16 @@ impl TestApp {
             cookie_jar,
             http_client,
             email_server,
+            sms_server,
+            user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            organization_store,
+            audit_store,
+            email_outbox,
+            email_suppression_store,
+            pg_pool,
         }
     }
 
@@ -207,9 +321,42 @@ impl TestApp {
         self.clean_up_called = true;
     }
 
+    /// Sign up a user without 2FA, grant them `roles` and log in,
+    /// so the cookie jar holds a JWT carrying those roles.
+    /// Returns the email of the new user.
+    pub async fn signup_and_login_with_roles(&self, roles: &[Role]) -> String {
+        let email = get_random_email();
+        let password = "password123";
+        let response = self
+            .signup(&serde_json::json!({
+                "email": email,
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        _assert_eq_status_code(&response, StatusCode::CREATED);
+
+        let parsed_email = Email::parse(Secret::new(email.clone())).unwrap();
+        for role in roles {
+            self.user_store
+                .assign_role(&parsed_email, role)
+                .await
+                .expect("Failed to assign role");
+        }
+
+        let response = self
+            .login(&serde_json::json!({
+                "email": email,
+                "password": password,
+            }))
+            .await;
+        _assert_eq_status_code(&response, StatusCode::OK);
+        email
+    }
+
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -222,7 +369,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -235,7 +382,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -244,7 +391,7 @@ impl TestApp {
 
     pub async fn logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
             .send()
             .await
             .expect("Failed to handle logout")
@@ -255,13 +402,165 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
             .json(body)
             .send()
             .await
             .expect("Failed to verify two factor authentication")
     }
 
+    pub async fn get_organizations(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/organizations", &self.address))
+            .send()
+            .await
+            .expect("Failed to list organizations.")
+    }
+
+    /// Post to `/organizations` or one of its sub-routes, e.g. `switch`
+    pub async fn post_organizations<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let url = match path {
+            "" => format!("{}/organizations", &self.address),
+            path => format!("{}/organizations/{}", &self.address, path),
+        };
+        self.http_client
+            .post(url)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute organization request.")
+    }
+
+    pub async fn get_admin(&self, path: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute admin request.")
+    }
+
+    /// Deliver a Postmark webhook with the configured credentials
+    pub async fn post_postmark_webhook(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webhooks/postmark", &self.address))
+            .basic_auth(POSTMARK_WEBHOOK_USERNAME, Some(POSTMARK_WEBHOOK_PASSWORD))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// `query` is appended to `/dev/mailbox?`
+    pub async fn get_dev_mailbox(&self, query: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/dev/mailbox?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The code in the latest 2FA email captured for `email`,
+    /// for apps built `with_mailbox`
+    pub async fn two_fa_code_from_mailbox(&self, email: &str) -> String {
+        let response = self
+            .get_dev_mailbox(&format!("to={}&format=json", email))
+            .await;
+        _assert_eq_status_code(&response, StatusCode::OK);
+        let mailbox = response.json::<MailboxResponse>().await.unwrap();
+        let latest = mailbox
+            .emails
+            .first()
+            .expect("A 2FA email should have been captured");
+        latest
+            .text_body
+            .split_whitespace()
+            .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+            .expect("The email should contain the code")
+            .to_owned()
+    }
+
+    /// The security alerts captured for `email`, newest first,
+    /// for apps built `with_mailbox`
+    pub async fn security_alerts_in_mailbox(&self, email: &str) -> Vec<MailboxEmailResponse> {
+        let response = self
+            .get_dev_mailbox(&format!("to={}&format=json", email))
+            .await;
+        _assert_eq_status_code(&response, StatusCode::OK);
+        let mailbox = response.json::<MailboxResponse>().await.unwrap();
+        mailbox
+            .emails
+            .into_iter()
+            .filter(|email| email.text_body.contains("This happened on"))
+            .collect()
+    }
+
+    /// The texts sent to `phone_number` through `sms_server`, oldest first
+    pub async fn texts_sent_to(&self, phone_number: &str) -> Vec<String> {
+        self.sms_server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|request| request.body_json::<serde_json::Value>().ok())
+            .filter(|body| body["to"] == phone_number)
+            .map(|body| body["text"].as_str().unwrap_or_default().to_owned())
+            .collect()
+    }
+
+    /// Post to `/account/` followed by `path`, e.g. `password`
+    pub async fn post_account<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/{}", &self.address, path))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute account request.")
+    }
+
+    pub async fn post_admin<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/{}", &self.address, path))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute admin request.")
+    }
+
+    /// Get `/audit/events`, or `/admin/audit/events` when `admin` is set
+    pub async fn get_audit_events(&self, admin: bool, query: &str) -> reqwest::Response {
+        let path = match admin {
+            true => "admin/audit/events",
+            false => "audit/events",
+        };
+        self.http_client
+            .get(format!("{}/{}{}", &self.address, path, query))
+            .send()
+            .await
+            .expect("Failed to list audit events.")
+    }
+
+    /// Post to `/password-reset` followed by `path`, e.g. `/confirm`
+    pub async fn post_password_reset<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset{}", &self.address, path))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute password reset request.")
+    }
+
     pub async fn verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
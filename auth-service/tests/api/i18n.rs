@@ -0,0 +1,212 @@
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+use auth_service::{
+    domain::{email::Email, locale::Locale, parse::Parseable},
+    routes::{LocaleResponse, SignupResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use reqwest::{header::CONTENT_LANGUAGE, StatusCode};
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+async fn signup(
+    app: &TestApp,
+    email: &str,
+    requires_2fa: bool,
+    locale: Option<&str>,
+    accept_language: Option<&str>,
+) -> reqwest::Response {
+    let mut request = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa,
+            "locale": locale,
+        }));
+    if let Some(accept_language) = accept_language {
+        request = request.header("Accept-Language", accept_language);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+async fn stored_locale(app: &TestApp, email: &str) -> Option<Locale> {
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    app.user_store.get_user(&email).await.unwrap().locale
+}
+
+#[tokio::test]
+async fn should_translate_errors_into_the_requested_language() {
+    let mut app = TestApp::new().await;
+    let body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "password123",
+    });
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("Accept-Language", "fr-CH, de;q=0.8, en;q=0.5")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()[CONTENT_LANGUAGE], "de");
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Falsche Anmeldedaten");
+    assert_eq!(error.code, "incorrect_credentials");
+
+    // Unsupported languages get English
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("Accept-Language", "fr")
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[CONTENT_LANGUAGE], "en");
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Incorrect credentials");
+    assert_eq!(error.code, "incorrect_credentials");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_translate_errors_raised_by_guards() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/users", &app.address))
+        .header("Accept-Language", "de")
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Authentifizierungstoken fehlt");
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_store_the_locale_of_the_signup_request() {
+    let mut app = TestApp::new().await;
+
+    let german = get_random_email();
+    let response = signup(&app, &german, false, None, Some("de-DE,de;q=0.9")).await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    assert_eq!(
+        response.json::<SignupResponse>().await.unwrap().message,
+        "Benutzer erfolgreich erstellt!"
+    );
+    assert_eq!(stored_locale(&app, &german).await, Some(Locale::German));
+
+    // An explicit choice wins over the header
+    let english = get_random_email();
+    let response = signup(&app, &english, false, Some("en"), Some("de")).await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    assert_eq!(stored_locale(&app, &english).await, Some(Locale::English));
+
+    let undecided = get_random_email();
+    let response = signup(&app, &undecided, false, None, Some("fr")).await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    assert_eq!(stored_locale(&app, &undecided).await, None);
+
+    let response = signup(&app, &get_random_email(), false, Some("fr"), None).await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_email_the_2fa_code_in_the_stored_locale() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let response = signup(&app, &email, true, Some("de"), None).await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // The API answers in the language of the request, the email follows the account
+    let response = app
+        .login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .unwrap()
+            .message,
+        "2FA required"
+    );
+
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    assert!(body["Subject"].as_str().unwrap().contains("Anmeldecode"));
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("Mit diesem Code"));
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains(r#"<html lang="de">"#));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_the_stored_locale() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    let set_locale = |locale: serde_json::Value| {
+        app.http_client
+            .post(format!("{}/account/locale", &app.address))
+            .json(&serde_json::json!({ "locale": locale }))
+            .send()
+    };
+
+    let response = set_locale(serde_json::json!("de-AT")).await.unwrap();
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(
+        response.json::<LocaleResponse>().await.unwrap().locale,
+        Some("de".to_owned())
+    );
+    assert_eq!(stored_locale(&app, &email).await, Some(Locale::German));
+
+    let response = set_locale(serde_json::json!("tlh")).await.unwrap();
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    assert_eq!(stored_locale(&app, &email).await, Some(Locale::German));
+
+    let response = set_locale(serde_json::Value::Null).await.unwrap();
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(stored_locale(&app, &email).await, None);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_login_to_change_the_locale() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/account/locale", &app.address))
+        .json(&serde_json::json!({ "locale": "de" }))
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use auth_service::{
+    app_state::state::AppState,
+    domain::{email::Email, parse::Parseable},
+    routes::TwoFactorAuthResponse,
+    services::capturing_email_client::CapturingEmailClient,
+    utils::constants::{test, JWT_COOKIE_NAME},
+    Application,
+};
+use reqwest::{cookie::Jar, StatusCode};
+use secrecy::Secret;
+
+use crate::helpers::get_random_email;
+
+// Boots the whole service without PostgreSQL, Redis or Postmark
+#[tokio::test]
+async fn in_memory_app_signs_up_and_logs_in_with_2fa() {
+    let email_client = Arc::new(CapturingEmailClient::default());
+    let app = Application::build(AppState::in_memory(email_client.clone()), test::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
+    let address = format!("http://{}", app.address);
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+    let http_client = reqwest::Client::builder()
+        .cookie_provider(Arc::new(Jar::default()))
+        .build()
+        .unwrap();
+
+    let email = get_random_email();
+    let response = http_client
+        .post(format!("{}/signup", address))
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = http_client
+        .post(format!("{}/login", address))
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    // The code never leaves the process
+    let text_body = email_client
+        .last_message_to(&Email::parse(Secret::new(email.clone())).unwrap())
+        .expect("A 2FA email should have been captured")
+        .text_body;
+    let code = text_body
+        .split_whitespace()
+        .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+        .expect("The email should contain the code");
+
+    let response = http_client
+        .post(format!("{}/verify-2fa", address))
+        .json(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty()));
+}
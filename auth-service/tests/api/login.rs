@@ -14,9 +14,10 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::{TestApp, _assert_eq_status_code};
+use crate::helpers::{_assert_eq_status_code, TestApp};
 
 #[derive(Debug)]
+#[allow(clippy::upper_case_acronyms)]
 enum LoginKeys {
     EMAIL,
     PASSWORD,
@@ -148,7 +149,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert_eq!(json_body.message, "2FA required".to_owned());
     app.clean_up().await;
     // TODO: assert that `json_body.login_attempt_id` is stored inside `app.two_fa_code_store`
-    let two_fa_store = app.two_fa_code_store.write().await;
+    let two_fa_store = &app.two_fa_code_store;
 
     let mut email_string = login_body
         .as_object()
@@ -170,3 +171,29 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         panic!("{}", error_msg);
     }
 }
+
+#[tokio::test]
+async fn should_record_login_metadata() {
+    let (mut app, login_body) = prepare_login(false, false).await;
+    let email = Email::parse(Secret::new(
+        login_body["email"].as_str().unwrap().to_owned(),
+    ))
+    .unwrap();
+
+    let wrong_password = get_test_case(login_body["email"].as_str().unwrap(), "wrongPassword");
+    for _ in 0..2 {
+        let response = app.login(&wrong_password).await;
+        _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    }
+    let user = app.user_store.get_user(&email).await.unwrap();
+    assert_eq!(user.failed_login_count, 2);
+    assert_eq!(user.last_login_at, None);
+
+    let response = app.login(&login_body).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let user = app.user_store.get_user(&email).await.unwrap();
+    assert_eq!(user.failed_login_count, 0);
+    assert!(user.last_login_at.is_some());
+    assert_eq!(user.last_login_ip.as_deref(), Some("127.0.0.1"));
+    app.clean_up().await;
+}
@@ -1,9 +1,9 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::{auth::TokenHash, constants::JWT_COOKIE_NAME};
 use reqwest::{StatusCode, Url};
 use secrecy::Secret;
 use test_case::test_case;
 
-use crate::helpers::{get_random_email, TestApp, _assert_eq_status_code};
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
 use crate::signup::get_test_case;
 
 #[tokio::test]
@@ -36,14 +36,14 @@ async fn should_return_401_if_invalid_token() {
 #[tokio::test]
 async fn should_return_200_if_valid_jwt_cookie(password: &str, email: String, enable_2fa: bool) {
     let mut app = TestApp::new().await;
-    test_logout_once(&app, &password, &email, enable_2fa).await;
+    test_logout_once(&app, password, &email, enable_2fa).await;
     app.clean_up().await;
 }
 
 async fn test_logout_once(app: &TestApp, password: &str, email: &str, enable_2fa: bool) {
     // Need to signup new usper
     let response = app
-        .signup(&get_test_case(password, &email, enable_2fa))
+        .signup(&get_test_case(password, email, enable_2fa))
         .await;
     _assert_eq_status_code(&response, StatusCode::CREATED);
 
@@ -70,9 +70,9 @@ async fn test_logout_once(app: &TestApp, password: &str, email: &str, enable_2fa
     _assert_eq_status_code(&response, StatusCode::OK);
 
     // Check whether token was added to banned token store
-    let banned_token_store = app.banned_token_store.read().await;
+    let banned_token_store = &app.banned_token_store;
     assert!(banned_token_store
-        .token_exists(&Secret::new(token.to_string()))
+        .token_exists(&TokenHash::of(&Secret::new(token.to_string())))
         .await
         .expect("Token should have been added to banned store"));
 }
@@ -85,7 +85,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row(
     enable_2fa: bool,
 ) {
     let mut app = TestApp::new().await;
-    test_logout_once(&app, &password, &email, enable_2fa).await;
+    test_logout_once(&app, password, &email, enable_2fa).await;
     // This should fail since we logged out already
     let response = app.logout().await;
     _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
@@ -1,7 +1,24 @@
+mod admin;
+mod audit;
+mod dev_mailbox;
+mod email_normalization;
+mod email_outbox;
+mod failover_email_client;
 mod helpers;
+mod i18n;
+mod in_memory;
 mod login;
 mod logout;
+mod organizations;
+mod password_reset;
+mod postgres_token_stores;
+mod postmark_webhook;
 mod root;
+mod security_alerts;
 mod signup;
+mod sms_two_factor;
+mod smtp_email_client;
+mod smtp_sink;
+mod store_conformance;
 mod verify_2fa;
 mod verify_token;
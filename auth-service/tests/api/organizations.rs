@@ -0,0 +1,320 @@
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+use auth_service::{
+    domain::{
+        email::Email,
+        organization::{OrganizationId, OrganizationName},
+        parse::Parseable,
+    },
+    routes::{
+        ActiveOrganizationResponse, InvitationResponse, ListUsersResponse, OrganizationResponse,
+        OrganizationsResponse, RolesResponse,
+    },
+    ErrorResponse,
+};
+use reqwest::StatusCode;
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+/// Create an organization as the signed-in user and make it their active one
+async fn create_and_switch(app: &TestApp, name: &str) -> String {
+    let response = app
+        .post_organizations("", &serde_json::json!({ "name": name }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let organization = response.json::<OrganizationResponse>().await.unwrap();
+    assert_eq!(organization.roles, vec!["org-admin".to_owned()]);
+
+    let response = app
+        .post_organizations(
+            "switch",
+            &serde_json::json!({ "organizationId": organization.id }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    organization.id
+}
+
+async fn add_member(app: &TestApp, organization_id: &str, email: &str) {
+    let id = OrganizationId::parse(organization_id.to_owned()).unwrap();
+    let email = Email::parse(Secret::new(email.to_owned())).unwrap();
+    app.organization_store
+        .add_member(&id, &email, &[])
+        .await
+        .unwrap();
+}
+
+/// Pull the invitation token out of the accept link in the last email sent
+async fn invitation_token(app: &TestApp) -> String {
+    let requests = app.email_server.received_requests().await.unwrap();
+    let body: serde_json::Value = requests.last().unwrap().body_json().unwrap();
+    let text = body["TextBody"].as_str().unwrap();
+    text.split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .unwrap()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_return_400_without_jwt() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_organizations("", &serde_json::json!({ "name": "Acme" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_blank_name() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app
+        .post_organizations("", &serde_json::json!({ "name": "   " }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_create_list_and_switch_organizations() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    let response = app.get_organizations().await;
+    let organizations = response.json::<OrganizationsResponse>().await.unwrap();
+    assert!(organizations.organizations.is_empty());
+    assert_eq!(organizations.active, None);
+
+    let id = create_and_switch(&app, "Acme").await;
+    let organizations = app
+        .get_organizations()
+        .await
+        .json::<OrganizationsResponse>()
+        .await
+        .unwrap();
+    assert_eq!(organizations.organizations.len(), 1);
+    assert_eq!(organizations.organizations[0].name, "Acme");
+    assert_eq!(organizations.active, Some(id.clone()));
+
+    // Switching to no organization clears the claim
+    let response = app
+        .post_organizations("switch", &serde_json::json!({ "organizationId": null }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let active = response.json::<ActiveOrganizationResponse>().await.unwrap();
+    assert_eq!(active.organization_id, None);
+
+    // A fresh login starts in the first organization the user joined
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let organizations = app
+        .get_organizations()
+        .await
+        .json::<OrganizationsResponse>()
+        .await
+        .unwrap();
+    assert_eq!(organizations.active, Some(id));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_switching_to_foreign_organization() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let other = app
+        .organization_store
+        .create_organization(OrganizationName::parse("Globex".to_owned()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_organizations(
+            "switch",
+            &serde_json::json!({ "organizationId": other.id.to_string() }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Organization not found".to_owned()
+    );
+
+    let response = app
+        .post_organizations("switch", &serde_json::json!({ "organizationId": "acme" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invite_and_accept_member() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let id = create_and_switch(&app, "Acme").await;
+    let invitee = get_random_email();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_organizations(
+            "invitations",
+            &serde_json::json!({ "email": invitee, "role": "org-admin" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let invitation = response.json::<InvitationResponse>().await.unwrap();
+    assert_eq!(invitation.organization_id, id);
+    assert_eq!(invitation.email, invitee);
+    let token = invitation_token(&app).await;
+
+    // Only the invitee can accept
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app
+        .post_organizations("invitations/accept", &serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+
+    let response = app
+        .signup(&serde_json::json!({
+            "email": invitee,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let response = app
+        .login(&serde_json::json!({ "email": invitee, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .post_organizations("invitations/accept", &serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let organization = response.json::<OrganizationResponse>().await.unwrap();
+    assert_eq!(organization.id, id);
+    assert_eq!(organization.roles, vec!["org-admin".to_owned()]);
+
+    // Invitations are single use
+    let response = app
+        .post_organizations("invitations/accept", &serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_inviting_with_a_role_beyond_organization_admin() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    create_and_switch(&app, "Acme").await;
+
+    let response = app
+        .post_organizations(
+            "invitations",
+            &serde_json::json!({ "email": get_random_email(), "role": "admin" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_inviting_without_active_organization() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+    let response = app
+        .post_organizations(
+            "invitations",
+            &serde_json::json!({ "email": get_random_email() }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_scope_admin_apis_to_active_organization() {
+    let mut app = TestApp::new().await;
+    let member = app.signup_and_login_with_roles(&[]).await;
+    let outsider = app.signup_and_login_with_roles(&[]).await;
+    let owner = app.signup_and_login_with_roles(&[]).await;
+    let id = create_and_switch(&app, "Acme").await;
+    add_member(&app, &id, &member).await;
+
+    let response = app.get_admin("users").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let page = response.json::<ListUsersResponse>().await.unwrap();
+    let mut emails: Vec<String> = page.users.into_iter().map(|user| user.email).collect();
+    emails.sort();
+    let mut expected = vec![member.clone(), owner];
+    expected.sort();
+    assert_eq!(emails, expected);
+
+    // Users outside the organization look missing
+    let response = app.get_admin(&format!("users/{}", outsider)).await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+
+    // Accounts are shared between organizations, so only global admins change them
+    for action in [
+        "disable",
+        "enable",
+        "force-password-reset",
+        "reset-2fa",
+        "revoke-sessions",
+    ] {
+        let response = app
+            .post_admin(
+                &format!("users/{}/{}", member, action),
+                &serde_json::json!({}),
+            )
+            .await;
+        _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    }
+    let response = app
+        .post_admin(
+            &format!("users/{}/status", member),
+            &serde_json::json!({ "status": "locked" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+
+    // Roles that grant more than an organization admin has stay global
+    let response = app
+        .post_admin(
+            "roles/assign",
+            &serde_json::json!({ "email": member, "role": "admin" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+
+    // Role assignments only apply inside the organization
+    let response = app
+        .post_admin(
+            "roles/assign",
+            &serde_json::json!({ "email": member, "role": "org-admin" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let roles = response.json::<RolesResponse>().await.unwrap();
+    assert_eq!(roles.roles, vec!["org-admin".to_owned()]);
+    let member = Email::parse(Secret::new(member)).unwrap();
+    assert!(app.user_store.get_roles(&member).await.unwrap().is_empty());
+    app.clean_up().await;
+}
@@ -0,0 +1,126 @@
+use auth_service::{domain::role::Role, routes::MailboxResponse};
+use reqwest::StatusCode;
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+/// The token of the latest password reset link captured for `email`
+async fn reset_token_from_mailbox(app: &TestApp, email: &str) -> String {
+    let response = app
+        .get_dev_mailbox(&format!("to={}&format=json", email))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let mailbox = response.json::<MailboxResponse>().await.unwrap();
+    mailbox
+        .emails
+        .iter()
+        .flat_map(|email| email.text_body.split_whitespace())
+        .find_map(|word| word.split_once("/password-reset?token="))
+        .map(|(_, token)| token.to_owned())
+        .expect("A password reset link should have been captured")
+}
+
+#[tokio::test]
+async fn should_let_users_with_a_forced_reset_choose_a_new_password() {
+    let mut app = TestApp::with_mailbox().await;
+    let target = get_random_email();
+    let response = app
+        .signup(&serde_json::json!({
+            "email": target,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app
+        .post_admin(
+            &format!("users/{}/force-password-reset", target),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let token = reset_token_from_mailbox(&app, &target).await;
+
+    let response = app
+        .post_password_reset(
+            "/confirm",
+            &serde_json::json!({ "token": "not-a-token", "newPassword": "new-password" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .post_password_reset(
+            "/confirm",
+            &serde_json::json!({ "token": token, "newPassword": "new-password" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+
+    let response = app
+        .login(&serde_json::json!({ "email": target, "password": "password123" }))
+        .await;
+    assert_ne!(response.status(), StatusCode::OK);
+    let response = app
+        .login(&serde_json::json!({ "email": target, "password": "new-password" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    // Each link works once
+    let response = app
+        .post_password_reset(
+            "/confirm",
+            &serde_json::json!({ "token": token, "newPassword": "other-password" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_end_every_session_when_the_password_is_reset() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    let response = app
+        .post_password_reset("", &serde_json::json!({ "email": email }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let token = reset_token_from_mailbox(&app, &email).await;
+    let response = app
+        .post_password_reset(
+            "/confirm",
+            &serde_json::json!({ "token": token, "newPassword": "new-password" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": true }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_tell_whether_an_account_exists() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_password_reset("", &serde_json::json!({ "email": email }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let response = app
+        .get_dev_mailbox(&format!("to={}&format=json", email))
+        .await;
+    assert!(response
+        .json::<MailboxResponse>()
+        .await
+        .unwrap()
+        .emails
+        .is_empty());
+    app.clean_up().await;
+}
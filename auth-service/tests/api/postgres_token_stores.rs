@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{
+        data_stores::{
+            BannedTokenStore, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError,
+        },
+        email::Email,
+        parse::Parseable,
+    },
+    services::{
+        expired_row_sweeper::ExpiredRowSweeper,
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore,
+    },
+    utils::auth::TokenHash,
+};
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const TTL: Duration = Duration::from_secs(60);
+
+fn random_email() -> Email {
+    Email::parse(Secret::new(get_random_email())).unwrap()
+}
+
+// Moves every row of `table` into the past, as if its TTL had run out
+async fn expire_rows(pool: &PgPool, table: &str) {
+    sqlx::query(&format!(
+        "UPDATE {} SET expires_at = NOW() - INTERVAL '1 second'",
+        table
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn banned_tokens_expire() {
+    let mut app = TestApp::new().await;
+    let store = PostgresBannedTokenStore::new(app.pg_pool.clone());
+    let token = TokenHash::of(&Secret::new("token".to_owned()));
+
+    assert!(!store.token_exists(&token).await.unwrap());
+    store.insert(&token, TTL).await.unwrap();
+    // Banning twice is not an error
+    store.insert(&token, TTL).await.unwrap();
+    assert!(store.token_exists(&token).await.unwrap());
+
+    expire_rows(&app.pg_pool, "banned_tokens").await;
+    assert!(!store.token_exists(&token).await.unwrap());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn banned_tokens_are_stored_hashed_until_they_expire() {
+    let mut app = TestApp::new().await;
+    let store = PostgresBannedTokenStore::new(app.pg_pool.clone());
+    let token = Secret::new("header.payload.signature".to_owned());
+    store
+        .insert(&TokenHash::of(&token), Duration::from_secs(90))
+        .await
+        .unwrap();
+
+    let (token_hash, remaining): (String, f64) = sqlx::query_as(
+        "SELECT token_hash, EXTRACT(EPOCH FROM expires_at - NOW())::DOUBLE PRECISION FROM banned_tokens",
+    )
+    .fetch_one(&app.pg_pool)
+    .await
+    .unwrap();
+    assert_ne!(token_hash, *token.expose_secret());
+    assert_eq!(token_hash, TokenHash::of(&token).as_ref());
+    assert!((80.0..=90.0).contains(&remaining));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn session_revocations_expire() {
+    let mut app = TestApp::new().await;
+    let store = PostgresBannedTokenStore::new(app.pg_pool.clone());
+    let email = random_email();
+
+    assert_eq!(store.sessions_revoked_at(&email).await.unwrap(), None);
+    store.revoke_sessions(&email, 1_000).await.unwrap();
+    store.revoke_sessions(&email, 2_000).await.unwrap();
+    assert_eq!(
+        store.sessions_revoked_at(&email).await.unwrap(),
+        Some(2_000)
+    );
+
+    expire_rows(&app.pg_pool, "revoked_sessions").await;
+    assert_eq!(store.sessions_revoked_at(&email).await.unwrap(), None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn two_fa_codes_are_replaced_removed_and_expire() {
+    let mut app = TestApp::new().await;
+    let store = PostgresTwoFACodeStore::new(app.pg_pool.clone());
+    let email = random_email();
+
+    assert!(matches!(
+        store.get_code(&email).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    ));
+
+    store
+        .add_code(
+            email.clone(),
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+    // A new login replaces the pending code
+    let (login_attempt_id, code) = (LoginAttemptId::default(), TwoFACode::default());
+    store
+        .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+        .await
+        .unwrap();
+    let (stored_login_attempt_id, stored_code) = store.get_code(&email).await.unwrap();
+    assert_eq!(
+        stored_login_attempt_id.as_ref().expose_secret(),
+        login_attempt_id.as_ref().expose_secret()
+    );
+    assert_eq!(
+        stored_code.as_ref().expose_secret(),
+        code.as_ref().expose_secret()
+    );
+
+    store.remove_code(&email).await.unwrap();
+    assert!(matches!(
+        store.get_code(&email).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    ));
+
+    store
+        .add_code(
+            email.clone(),
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+    expire_rows(&app.pg_pool, "two_fa_codes").await;
+    assert!(matches!(
+        store.get_code(&email).await,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    ));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn sweeper_deletes_only_expired_rows() {
+    let mut app = TestApp::new().await;
+    let banned_token_store = PostgresBannedTokenStore::new(app.pg_pool.clone());
+    let two_fa_code_store = PostgresTwoFACodeStore::new(app.pg_pool.clone());
+    let sweeper = ExpiredRowSweeper::new(app.pg_pool.clone(), Duration::from_secs(60));
+
+    banned_token_store
+        .insert(&TokenHash::of(&Secret::new("expired".to_owned())), TTL)
+        .await
+        .unwrap();
+    banned_token_store
+        .revoke_sessions(&random_email(), 1_000)
+        .await
+        .unwrap();
+    two_fa_code_store
+        .add_code(
+            random_email(),
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+    for table in ["banned_tokens", "revoked_sessions", "two_fa_codes"] {
+        expire_rows(&app.pg_pool, table).await;
+    }
+
+    let live = TokenHash::of(&Secret::new("live".to_owned()));
+    banned_token_store.insert(&live, TTL).await.unwrap();
+
+    assert_eq!(sweeper.sweep().await.unwrap(), 3);
+    assert_eq!(sweeper.sweep().await.unwrap(), 0);
+    assert!(banned_token_store.token_exists(&live).await.unwrap());
+
+    app.clean_up().await;
+}
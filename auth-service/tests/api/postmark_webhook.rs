@@ -0,0 +1,244 @@
+use auth_service::{
+    domain::{
+        audit::AuditAction, data_stores::AuditQuery, email::Email,
+        email_suppression::SuppressionReason, parse::Parseable, role::Role,
+    },
+    routes::{EmailSuppressionResponse, ListEmailSuppressionsResponse},
+    ErrorResponse,
+};
+use reqwest::StatusCode;
+use secrecy::Secret;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{
+    _assert_eq_status_code, get_random_email, TestApp, POSTMARK_WEBHOOK_USERNAME,
+};
+
+fn hard_bounce(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": 4323372036854775807_u64,
+        "Type": "HardBounce",
+        "TypeCode": 1,
+        "Name": "Hard bounce",
+        "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+        "Description": "The server was unable to deliver your message (ex: unknown user, mailbox not found).",
+        "Details": "smtp;550 5.1.1 The email account that you tried to reach does not exist.",
+        "Email": email,
+        "BouncedAt": "2024-10-31T09:00:00Z",
+        "Inactive": true,
+    })
+}
+
+fn spam_complaint(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Type": "SpamComplaint",
+        "TypeCode": 512,
+        "Email": email,
+        "BouncedAt": "2024-10-31T09:00:00Z",
+    })
+}
+
+fn parse(email: &str) -> Email {
+    Email::parse(Secret::new(email.to_owned())).unwrap()
+}
+
+/// Sign up `email` with 2FA and log in, which emails them a code
+async fn login_with_2fa(app: &TestApp, email: &str) {
+    let response = app
+        .signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+    let response = app
+        .login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+}
+
+#[tokio::test]
+async fn should_stop_emailing_addresses_that_hard_bounced() {
+    let mut app = TestApp::new().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let email = get_random_email();
+    login_with_2fa(&app, &email).await;
+
+    let response = app.post_postmark_webhook(&hard_bounce(&email)).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let suppression = app
+        .email_suppression_store
+        .get(&parse(&email))
+        .await
+        .unwrap()
+        .expect("The address should be suppressed");
+    assert_eq!(suppression.reason, SuppressionReason::HardBounce);
+    assert!(suppression.description.unwrap().contains("550 5.1.1"));
+
+    // No second code goes out, and the user is told why
+    let response = app
+        .login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CONFLICT);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().code,
+        "email_suppressed"
+    );
+    let requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+
+    // Recorded against the user
+    let page = app
+        .audit_store
+        .query(&AuditQuery::default().involving(parse(&email)))
+        .await
+        .unwrap();
+    assert!(page
+        .events
+        .iter()
+        .any(|event| event.action == AuditAction::EmailBounced
+            && event.actor.as_deref() == Some("postmark")));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_spam_complaints_and_ignore_other_webhooks() {
+    let mut app = TestApp::new().await;
+    let complained = get_random_email();
+    let soft_bounced = get_random_email();
+
+    let response = app
+        .post_postmark_webhook(&spam_complaint(&complained))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let mut soft_bounce = hard_bounce(&soft_bounced);
+    soft_bounce["Type"] = "SoftBounce".into();
+    let response = app.post_postmark_webhook(&soft_bounce).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Delivery",
+            "Recipient": soft_bounced,
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    // Acknowledged, so Postmark does not retry it forever
+    let response = app
+        .post_postmark_webhook(&hard_bounce("not-an-email"))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let store = &app.email_suppression_store;
+    let suppression = store.get(&parse(&complained)).await.unwrap().unwrap();
+    assert_eq!(suppression.reason, SuppressionReason::SpamComplaint);
+    assert_eq!(store.get(&parse(&soft_bounced)).await.unwrap(), None);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_webhooks_without_valid_credentials() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let url = format!("{}/webhooks/postmark", &app.address);
+
+    let response = app
+        .http_client
+        .post(&url)
+        .json(&hard_bounce(&email))
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .http_client
+        .post(&url)
+        .basic_auth(POSTMARK_WEBHOOK_USERNAME, Some("wrong"))
+        .json(&hard_bounce(&email))
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(
+        app.email_suppression_store
+            .get(&parse(&email))
+            .await
+            .unwrap(),
+        None
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_let_admins_list_and_lift_suppressions() {
+    let mut app = TestApp::new().await;
+    let bounced = get_random_email();
+    let complained = get_random_email();
+    app.post_postmark_webhook(&hard_bounce(&bounced)).await;
+    app.post_postmark_webhook(&spam_complaint(&complained))
+        .await;
+
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app.get_admin("email-suppressions").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let page = response
+        .json::<ListEmailSuppressionsResponse>()
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.suppressions[0].email, complained);
+    assert_eq!(page.suppressions[0].reason, "spam_complaint");
+
+    let response = app
+        .get_admin(&format!("email-suppressions/{}", bounced))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let suppression = response.json::<EmailSuppressionResponse>().await.unwrap();
+    assert_eq!(suppression.reason, "hard_bounce");
+
+    let response = app
+        .post_admin(
+            &format!("email-suppressions/{}/lift", bounced),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+    let response = app
+        .get_admin(&format!("email-suppressions/{}", bounced))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    let response = app
+        .post_admin(
+            &format!("email-suppressions/{}/lift", bounced),
+            &serde_json::json!({}),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NOT_FOUND);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_permission_to_list_suppressions() {
+    let mut app = TestApp::new().await;
+    app.signup_and_login_with_roles(&[]).await;
+
+    let response = app.get_admin("email-suppressions").await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    app.clean_up().await;
+}
@@ -1,6 +1,6 @@
 use reqwest::StatusCode;
 
-use crate::helpers::{TestApp, _assert_eq_response, _assert_eq_status_code};
+use crate::helpers::{_assert_eq_response, _assert_eq_status_code, TestApp};
 
 // Tokio's test macro is use to run the test in an async environment
 #[tokio::test]
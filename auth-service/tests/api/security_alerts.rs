@@ -0,0 +1,276 @@
+use auth_service::{
+    domain::role::Role,
+    routes::{NotificationsRequest, TwoFactorResponse},
+};
+use reqwest::StatusCode;
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+/// Log in as `email` from a browser that has never been used before
+async fn login_from_new_device(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap()
+        .post(format!("{}/login", &app.address))
+        .json(&serde_json::json!({ "email": email, "password": password }))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// The token of the "this wasn't me" link in an alert
+fn report_token(text_body: &str) -> String {
+    text_body
+        .split_whitespace()
+        .find_map(|word| word.split_once("/account/wasnt-me?token="))
+        .map(|(_, token)| token.to_owned())
+        .expect("The alert should link to /account/wasnt-me")
+}
+
+#[tokio::test]
+async fn should_alert_on_logins_from_new_devices_only() {
+    let mut app = TestApp::with_mailbox().await;
+    // The first login is not reported
+    let email = app.signup_and_login_with_roles(&[]).await;
+    assert!(app.security_alerts_in_mailbox(&email).await.is_empty());
+
+    let response = login_from_new_device(&app, &email, "password123").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let alerts = app.security_alerts_in_mailbox(&email).await;
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].subject.starts_with("New login"));
+    assert!(alerts[0].text_body.contains("127.0.0.1"));
+
+    // The browser of the first login is remembered
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(app.security_alerts_in_mailbox(&email).await.len(), 1);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_skip_non_critical_alerts_of_users_who_opted_out() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": false }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(
+        response.json::<NotificationsRequest>().await.unwrap(),
+        NotificationsRequest {
+            security_notices: false
+        }
+    );
+
+    let response = login_from_new_device(&app, &email, "password123").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let response = app
+        .post_account(
+            "2fa",
+            &serde_json::json!({ "enabled": true, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert!(app.security_alerts_in_mailbox(&email).await.is_empty());
+
+    // Turning 2FA off is always reported
+    let response = app
+        .post_account(
+            "2fa",
+            &serde_json::json!({ "enabled": false, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(
+        response.json::<TwoFactorResponse>().await.unwrap(),
+        TwoFactorResponse { enabled: false }
+    );
+    let alerts = app.security_alerts_in_mailbox(&email).await;
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].subject.contains("turned off"));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_password_and_alert() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    // Logins are not reported, while password changes always are
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": false }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let other_session = reqwest::Client::builder()
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    let response = other_session
+        .post(format!("{}/login", &app.address))
+        .json(&serde_json::json!({ "email": email, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .post_account(
+            "password",
+            &serde_json::json!({ "currentPassword": "wrongPassword", "newPassword": "password456" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .post_account(
+            "password",
+            &serde_json::json!({ "currentPassword": "password123", "newPassword": "short" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    assert!(app.security_alerts_in_mailbox(&email).await.is_empty());
+
+    let response = app
+        .post_account(
+            "password",
+            &serde_json::json!({ "currentPassword": "password123", "newPassword": "password456" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+    let alerts = app.security_alerts_in_mailbox(&email).await;
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].subject.contains("password was changed"));
+
+    // Other sessions end, while the caller stays signed in
+    let response = other_session
+        .post(format!("{}/account/notifications", &app.address))
+        .json(&serde_json::json!({ "securityNotices": true }))
+        .send()
+        .await
+        .unwrap();
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": true }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password456" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_email_and_alert_the_old_address() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    let taken = get_random_email();
+    let response = app
+        .signup(&serde_json::json!({
+            "email": taken,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CREATED);
+
+    let response = app
+        .post_account(
+            "email",
+            &serde_json::json!({ "newEmail": taken, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CONFLICT);
+
+    let new_email = get_random_email();
+    let response = app
+        .post_account(
+            "email",
+            &serde_json::json!({ "newEmail": new_email, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+    let alerts = app.security_alerts_in_mailbox(&email).await;
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].subject.contains("email address"));
+
+    // The caller's session ends and they sign in with the new address
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": true }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .login(&serde_json::json!({ "email": new_email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_lock_the_account_from_the_wasnt_me_link() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    let response = login_from_new_device(&app, &email, "password123").await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let alerts = app.security_alerts_in_mailbox(&email).await;
+    let token = report_token(&alerts[0].text_body);
+
+    let response = app
+        .post_account("wasnt-me", &serde_json::json!({ "token": "not-a-token" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .post_account("wasnt-me", &serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+
+    // Existing sessions end and nobody can log in any more
+    let response = app
+        .post_account(
+            "notifications",
+            &serde_json::json!({ "securityNotices": true }),
+        )
+        .await;
+    assert_ne!(response.status(), StatusCode::OK);
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::LOCKED);
+
+    // Once an admin unlocks the account, the same link cannot lock it again
+    app.signup_and_login_with_roles(&[Role::admin()]).await;
+    let response = app
+        .post_admin(&format!("users/{}/enable", email), &serde_json::json!({}))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let response = app
+        .post_account("wasnt-me", &serde_json::json!({ "token": token }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
@@ -8,10 +8,11 @@ use test_case::test_case;
 use crate::helpers::TestApp;
 
 async fn get_test_app() -> TestApp {
-    return TestApp::new().await;
+    TestApp::new().await
 }
 
 // TODO: Create a general enum that can do to_string effectively
+#[allow(clippy::upper_case_acronyms)]
 pub enum SignUpKeys {
     PASSWORD,
     EMAIL,
@@ -27,7 +28,7 @@ impl fmt::Display for SignUpKeys {
             SignUpKeys::PASSWORD => "password",
             SignUpKeys::REQUIRES2FA => "requires2FA",
         };
-        f.write_str(&string_repr)
+        f.write_str(string_repr)
         // Normally, we would do this, but we have
         // mix of upper and lower-case characters in our API
         // write!(f, "{:?}", self)
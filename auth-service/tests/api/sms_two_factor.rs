@@ -0,0 +1,313 @@
+use std::sync::Arc;
+
+use auth_service::{
+    app_state::state::AppState,
+    domain::{email::Email, parse::Parseable, user::TwoFactorChannel},
+    routes::{PhoneVerificationResponse, TwoFactorAuthResponse, TwoFactorChannelRequest},
+    services::capturing_email_client::CapturingEmailClient,
+    utils::constants::{test, MAX_PHONE_VERIFICATION_ATTEMPTS},
+    Application,
+};
+use reqwest::{cookie::Jar, StatusCode};
+use secrecy::Secret;
+
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+
+const PHONE_NUMBER: &str = "+4915112345678";
+
+/// The code in the latest text sent to `phone_number`
+async fn code_texted_to(app: &TestApp, phone_number: &str) -> String {
+    let texts = app.texts_sent_to(phone_number).await;
+    let latest = texts.last().expect("A text should have been sent");
+    latest
+        .split_whitespace()
+        .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+        .expect("The text should contain the code")
+        .to_owned()
+}
+
+/// Add `phone_number` to the signed-in user and enter the texted code
+async fn add_verified_phone_number(app: &TestApp, phone_number: &str) {
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": phone_number, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let verification = response.json::<PhoneVerificationResponse>().await.unwrap();
+    let code = code_texted_to(app, phone_number).await;
+    let response = app
+        .post_account(
+            "phone/verify",
+            &serde_json::json!({
+                "verificationId": verification.verification_id,
+                "code": code,
+            }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn should_verify_phone_numbers_with_a_texted_code() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": PHONE_NUMBER, "password": "wrongPassword" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": "0151 12345678", "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    assert!(app.texts_sent_to(PHONE_NUMBER).await.is_empty());
+
+    // Numbers are stored in their international form
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": "+49 151 1234 5678", "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let verification = response.json::<PhoneVerificationResponse>().await.unwrap();
+    let code = code_texted_to(&app, PHONE_NUMBER).await;
+
+    let wrong_code = if code == "123456" { "654321" } else { "123456" };
+    let response = app
+        .post_account(
+            "phone/verify",
+            &serde_json::json!({
+                "verificationId": verification.verification_id,
+                "code": wrong_code,
+            }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let response = app
+        .post_account(
+            "phone/verify",
+            &serde_json::json!({
+                "verificationId": verification.verification_id,
+                "code": code,
+            }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(Secret::new(email)).unwrap())
+        .await
+        .unwrap();
+    assert!(user.phone_verified);
+    assert_eq!(user.two_fa_channel, TwoFactorChannel::Email);
+
+    // Removing the number needs no verification
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": null, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+    let response = app
+        .post_account(
+            "phone/verify",
+            &serde_json::json!({
+                "verificationId": verification.verification_id,
+                "code": code,
+            }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_stop_accepting_codes_after_too_many_wrong_ones() {
+    let mut app = TestApp::new().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+    let set_phone_number =
+        serde_json::json!({ "phoneNumber": PHONE_NUMBER, "password": "password123" });
+
+    let response = app.post_account("phone", &set_phone_number).await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let verification = response.json::<PhoneVerificationResponse>().await.unwrap();
+    let code = code_texted_to(&app, PHONE_NUMBER).await;
+    let wrong_code = if code == "123456" { "654321" } else { "123456" };
+    let verify = |code: &str| {
+        serde_json::json!({
+            "verificationId": verification.verification_id,
+            "code": code,
+        })
+    };
+    for _ in 0..MAX_PHONE_VERIFICATION_ATTEMPTS {
+        let response = app.post_account("phone/verify", &verify(wrong_code)).await;
+        _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    }
+
+    // Even the right code is turned away now
+    let response = app.post_account("phone/verify", &verify(&code)).await;
+    _assert_eq_status_code(&response, StatusCode::TOO_MANY_REQUESTS);
+    // Wrong codes do not count as failed logins, which could lock the account
+    let user = app
+        .user_store
+        .get_user(&Email::parse(Secret::new(email)).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.failed_login_count, 0);
+
+    // A new code gets a fresh set of attempts
+    let response = app.post_account("phone", &set_phone_number).await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let verification = response.json::<PhoneVerificationResponse>().await.unwrap();
+    let code = code_texted_to(&app, PHONE_NUMBER).await;
+    let response = app
+        .post_account(
+            "phone/verify",
+            &serde_json::json!({
+                "verificationId": verification.verification_id,
+                "code": code,
+            }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::NO_CONTENT);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_send_2fa_codes_by_sms_once_chosen() {
+    let mut app = TestApp::with_mailbox().await;
+    let email = app.signup_and_login_with_roles(&[]).await;
+
+    let response = app
+        .post_account("2fa/channel", &serde_json::json!({ "channel": "sms" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::CONFLICT);
+    let response = app
+        .post_account("2fa/channel", &serde_json::json!({ "channel": "pigeon" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::BAD_REQUEST);
+
+    add_verified_phone_number(&app, PHONE_NUMBER).await;
+    let response = app
+        .post_account("2fa/channel", &serde_json::json!({ "channel": "sms" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    assert_eq!(
+        response.json::<TwoFactorChannelRequest>().await.unwrap(),
+        TwoFactorChannelRequest {
+            channel: "sms".to_owned()
+        }
+    );
+    let response = app
+        .post_account(
+            "2fa",
+            &serde_json::json!({ "enabled": true, "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+    let body = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    assert_eq!(body.channel, "sms");
+    let response = app
+        .verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code_texted_to(&app, PHONE_NUMBER).await,
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+
+    // A new number has to be verified first, meanwhile codes go by email
+    let response = app
+        .post_account(
+            "phone",
+            &serde_json::json!({ "phoneNumber": "+14155550100", "password": "password123" }),
+        )
+        .await;
+    _assert_eq_status_code(&response, StatusCode::ACCEPTED);
+    let response = app
+        .login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::PARTIAL_CONTENT);
+    let body = response.json::<TwoFactorAuthResponse>().await.unwrap();
+    assert_eq!(body.channel, "email");
+    let response = app
+        .verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": app.two_fa_code_from_mailbox(&email).await,
+        }))
+        .await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    app.clean_up().await;
+}
+
+// Without an SMS provider, codes can only go out by email
+#[tokio::test]
+async fn should_reject_sms_without_a_provider() {
+    let email_client = Arc::new(CapturingEmailClient::default());
+    let app = Application::build(AppState::in_memory(email_client), test::APP_ADDRESS)
+        .await
+        .expect("Failed to build app");
+    let address = format!("http://{}", app.address);
+    #[allow(clippy::let_underscore_future)]
+    let _ = tokio::spawn(app.run());
+    let http_client = reqwest::Client::builder()
+        .cookie_provider(Arc::new(Jar::default()))
+        .build()
+        .unwrap();
+
+    let email = get_random_email();
+    for (path, body) in [
+        (
+            "signup",
+            serde_json::json!({ "email": email, "password": "password123", "requires2FA": false }),
+        ),
+        (
+            "login",
+            serde_json::json!({ "email": email, "password": "password123" }),
+        ),
+    ] {
+        let response = http_client
+            .post(format!("{}/{}", address, path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+    }
+
+    for (path, body) in [
+        (
+            "account/phone",
+            serde_json::json!({ "phoneNumber": PHONE_NUMBER, "password": "password123" }),
+        ),
+        (
+            "account/2fa/channel",
+            serde_json::json!({ "channel": "sms" }),
+        ),
+    ] {
+        let response = http_client
+            .post(format!("{}/{}", address, path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+    }
+}
@@ -0,0 +1,179 @@
+use std::time::Duration;
+
+use auth_service::{
+    domain::{
+        email::Email,
+        email_client::{EmailClient, EmailMessage},
+        parse::Parseable,
+    },
+    services::smtp_email_client::{SmtpEmailClient, SmtpSettings},
+    utils::constants::SmtpTls,
+};
+use secrecy::Secret;
+
+use crate::{
+    helpers::get_random_email,
+    smtp_sink::{SinkTls, SmtpSink},
+};
+
+const SENDER: &str = "sender@example.com";
+const USERNAME: &str = "mailer";
+const PASSWORD: &str = "correct horse";
+
+fn email(address: &str) -> Email {
+    Email::parse(Secret::new(address.to_owned())).unwrap()
+}
+
+fn message(subject: &str, text: &str) -> EmailMessage {
+    EmailMessage {
+        subject: subject.to_owned(),
+        html_body: format!("<p>{}</p>", text),
+        text_body: text.to_owned(),
+    }
+}
+
+fn settings(sink: &SmtpSink, tls: SmtpTls) -> SmtpSettings {
+    SmtpSettings {
+        host: "localhost".to_owned(),
+        port: Some(sink.port),
+        tls,
+        credentials: None,
+        root_certificate: Some(sink.certificate_pem.clone().into_bytes()),
+        timeout: Duration::from_secs(5),
+    }
+}
+
+fn with_credentials(settings: SmtpSettings, password: &str) -> SmtpSettings {
+    SmtpSettings {
+        credentials: Some((USERNAME.to_owned(), Secret::new(password.to_owned()))),
+        ..settings
+    }
+}
+
+fn client(settings: SmtpSettings) -> SmtpEmailClient {
+    SmtpEmailClient::new(settings, email(SENDER)).unwrap()
+}
+
+#[tokio::test]
+async fn should_deliver_over_starttls_after_logging_in() {
+    let sink = SmtpSink::start(SinkTls::StartTls, Some((USERNAME, PASSWORD))).await;
+    let client = client(with_credentials(
+        settings(&sink, SmtpTls::StartTls),
+        PASSWORD,
+    ));
+    let recipient = get_random_email();
+
+    client
+        .send_email(&email(&recipient), &message("Your code", "123456"))
+        .await
+        .unwrap();
+
+    let emails = sink.emails();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].mail_from, SENDER);
+    assert_eq!(emails[0].rcpt_to, vec![recipient]);
+    assert!(emails[0].data.contains("Subject: Your code"));
+    assert!(emails[0].data.contains("123456"));
+    assert!(emails[0].data.contains("<p>123456</p>"));
+    assert_eq!(emails[0].username.as_deref(), Some(USERNAME));
+    assert!(emails[0].encrypted);
+}
+
+#[tokio::test]
+async fn should_deliver_over_implicit_tls() {
+    let sink = SmtpSink::start(SinkTls::Implicit, None).await;
+    let client = client(settings(&sink, SmtpTls::Implicit));
+
+    client
+        .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+        .await
+        .unwrap();
+
+    let emails = sink.emails();
+    assert_eq!(emails.len(), 1);
+    assert!(emails[0].encrypted);
+}
+
+#[tokio::test]
+async fn should_deliver_in_plaintext_when_tls_is_off() {
+    let sink = SmtpSink::start(SinkTls::None, None).await;
+    let client = client(settings(&sink, SmtpTls::None));
+
+    client
+        .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+        .await
+        .unwrap();
+
+    let emails = sink.emails();
+    assert_eq!(emails.len(), 1);
+    assert!(!emails[0].encrypted);
+}
+
+#[tokio::test]
+async fn should_reuse_the_connection_for_later_emails() {
+    let sink = SmtpSink::start(SinkTls::StartTls, Some((USERNAME, PASSWORD))).await;
+    let client = client(with_credentials(
+        settings(&sink, SmtpTls::StartTls),
+        PASSWORD,
+    ));
+
+    for _ in 0..3 {
+        client
+            .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+            .await
+            .unwrap();
+        // Connections go back to the pool in the background
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(sink.emails().len(), 3);
+    assert_eq!(sink.connections(), 1);
+}
+
+#[tokio::test]
+async fn should_fail_if_the_credentials_are_rejected() {
+    let sink = SmtpSink::start(SinkTls::StartTls, Some((USERNAME, PASSWORD))).await;
+    let client = client(with_credentials(
+        settings(&sink, SmtpTls::StartTls),
+        "wrong password",
+    ));
+
+    let outcome = client
+        .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+        .await;
+
+    assert!(outcome.is_err());
+    assert!(sink.emails().is_empty());
+}
+
+#[tokio::test]
+async fn should_not_fall_back_to_plaintext_without_starttls() {
+    let sink = SmtpSink::start(SinkTls::None, Some((USERNAME, PASSWORD))).await;
+    let client = client(with_credentials(
+        settings(&sink, SmtpTls::StartTls),
+        PASSWORD,
+    ));
+
+    let outcome = client
+        .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+        .await;
+
+    assert!(outcome.is_err());
+    assert!(sink.emails().is_empty());
+}
+
+#[tokio::test]
+async fn should_not_trust_an_unknown_certificate() {
+    let sink = SmtpSink::start(SinkTls::Implicit, None).await;
+    let client = client(SmtpSettings {
+        root_certificate: None,
+        ..settings(&sink, SmtpTls::Implicit)
+    });
+
+    let outcome = client
+        .send_email(&email(&get_random_email()), &message("Hello", "Hi there"))
+        .await;
+
+    assert!(outcome.is_err());
+    assert!(sink.emails().is_empty());
+}
@@ -0,0 +1,287 @@
+//! Minimal SMTP server that accepts every email and keeps it in memory,
+//! so the SMTP email client can be tested without a real mail server.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+use tokio_rustls::{
+    rustls::{
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+/// How the sink expects clients to secure the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkTls {
+    None,
+    StartTls,
+    Implicit,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceivedEmail {
+    pub mail_from: String,
+    pub rcpt_to: Vec<String>,
+    pub data: String,
+    /// Username the session logged in with
+    pub username: Option<String>,
+    pub encrypted: bool,
+}
+
+#[derive(Default)]
+struct Received {
+    emails: Vec<ReceivedEmail>,
+    connections: usize,
+}
+
+pub struct SmtpSink {
+    pub port: u16,
+    /// PEM of the self-signed certificate for `localhost`
+    pub certificate_pem: String,
+    received: Arc<Mutex<Received>>,
+}
+
+impl SmtpSink {
+    /// Start listening on a random port. With `credentials`, emails are only
+    /// accepted after logging in with them.
+    pub async fn start(tls: SinkTls, credentials: Option<(&str, &str)>) -> Self {
+        let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()])
+            .expect("Failed to generate certificate");
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(certificate.cert.der().to_vec())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(
+                    certificate.signing_key.serialize_der(),
+                )),
+            )
+            .expect("Failed to configure TLS");
+        let session = Session {
+            tls,
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            credentials: credentials
+                .map(|(username, password)| (username.to_owned(), password.to_owned())),
+            received: Arc::new(Mutex::new(Received::default())),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = session.received.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let session = session.clone();
+                tokio::spawn(async move { session.serve(stream).await });
+            }
+        });
+
+        Self {
+            port,
+            certificate_pem: certificate.cert.pem(),
+            received,
+        }
+    }
+
+    pub fn emails(&self) -> Vec<ReceivedEmail> {
+        self.lock().emails.clone()
+    }
+
+    /// Number of TCP connections clients opened so far
+    pub fn connections(&self) -> usize {
+        self.lock().connections
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Received> {
+        self.received.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Clone)]
+struct Session {
+    tls: SinkTls,
+    acceptor: TlsAcceptor,
+    credentials: Option<(String, String)>,
+    received: Arc<Mutex<Received>>,
+}
+
+/// Why a conversation ended
+enum Ended {
+    Closed,
+    StartTls,
+}
+
+/// State of one connection
+#[derive(Default)]
+struct Conversation {
+    encrypted: bool,
+    username: Option<String>,
+    mail_from: Option<String>,
+    rcpt_to: Vec<String>,
+}
+
+impl Session {
+    async fn serve(self, stream: tokio::net::TcpStream) {
+        self.received
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .connections += 1;
+
+        match self.tls {
+            SinkTls::Implicit => {
+                let Ok(stream) = self.acceptor.accept(stream).await else {
+                    return;
+                };
+                self.converse(stream, true, true).await;
+            }
+            SinkTls::StartTls => {
+                let mut stream = stream;
+                if let Some(Ended::StartTls) = self.converse(&mut stream, false, true).await {
+                    let Ok(stream) = self.acceptor.accept(stream).await else {
+                        return;
+                    };
+                    self.converse(stream, true, false).await;
+                }
+            }
+            SinkTls::None => {
+                self.converse(stream, false, true).await;
+            }
+        }
+    }
+
+    async fn converse<S>(&self, stream: S, encrypted: bool, greet: bool) -> Option<Ended>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufReader::new(stream);
+        let mut conversation = Conversation {
+            encrypted,
+            ..Conversation::default()
+        };
+        if greet {
+            reply(&mut stream, "220 localhost ESMTP sink").await?;
+        }
+
+        loop {
+            let line = read_line(&mut stream).await?;
+            let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+            match command.to_uppercase().as_str() {
+                "EHLO" | "HELO" => {
+                    let mut lines = vec!["250-localhost".to_owned()];
+                    if self.tls == SinkTls::StartTls && !encrypted {
+                        lines.push("250-STARTTLS".to_owned());
+                    }
+                    if self.credentials.is_some() {
+                        lines.push("250-AUTH PLAIN".to_owned());
+                    }
+                    lines.push("250 OK".to_owned());
+                    reply(&mut stream, &lines.join("\r\n")).await?;
+                }
+                "STARTTLS" if self.tls == SinkTls::StartTls && !encrypted => {
+                    reply(&mut stream, "220 Ready to start TLS").await?;
+                    return Some(Ended::StartTls);
+                }
+                "AUTH" => {
+                    let username = argument
+                        .strip_prefix("PLAIN ")
+                        .and_then(|response| self.authenticate(response));
+                    match username {
+                        Some(username) => {
+                            conversation.username = Some(username);
+                            reply(&mut stream, "235 Authentication successful").await?;
+                        }
+                        None => reply(&mut stream, "535 Authentication failed").await?,
+                    }
+                }
+                "MAIL" if self.credentials.is_some() && conversation.username.is_none() => {
+                    reply(&mut stream, "530 Authentication required").await?;
+                }
+                "MAIL" => {
+                    conversation.mail_from = Some(address(argument));
+                    reply(&mut stream, "250 OK").await?;
+                }
+                "RCPT" => {
+                    conversation.rcpt_to.push(address(argument));
+                    reply(&mut stream, "250 OK").await?;
+                }
+                "DATA" => {
+                    reply(&mut stream, "354 End data with <CR><LF>.<CR><LF>").await?;
+                    let mut data = String::new();
+                    loop {
+                        let line = read_line(&mut stream).await?;
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                        data.push_str("\r\n");
+                    }
+                    self.received
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .emails
+                        .push(ReceivedEmail {
+                            mail_from: conversation.mail_from.take().unwrap_or_default(),
+                            rcpt_to: std::mem::take(&mut conversation.rcpt_to),
+                            data,
+                            username: conversation.username.clone(),
+                            encrypted: conversation.encrypted,
+                        });
+                    reply(&mut stream, "250 Queued").await?;
+                }
+                "RSET" => {
+                    conversation.mail_from = None;
+                    conversation.rcpt_to.clear();
+                    reply(&mut stream, "250 OK").await?;
+                }
+                "NOOP" => reply(&mut stream, "250 OK").await?,
+                "QUIT" => {
+                    reply(&mut stream, "221 Bye").await?;
+                    return Some(Ended::Closed);
+                }
+                _ => reply(&mut stream, "502 Command not implemented").await?,
+            }
+        }
+    }
+
+    /// The username, if the AUTH PLAIN response holds the expected credentials
+    fn authenticate(&self, response: &str) -> Option<String> {
+        let decoded = String::from_utf8(STANDARD.decode(response).ok()?).ok()?;
+        let mut parts = decoded.split('\0').skip(1);
+        let (username, password) = (parts.next()?, parts.next()?);
+        let (expected_username, expected_password) = self.credentials.as_ref()?;
+        (username == expected_username && password == expected_password)
+            .then(|| username.to_owned())
+    }
+}
+
+fn address(argument: &str) -> String {
+    argument
+        .split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_owned())
+        .unwrap_or_default()
+}
+
+async fn read_line<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut BufReader<S>) -> Option<String> {
+    let mut line = String::new();
+    match stream.read_line(&mut line).await {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_owned()),
+    }
+}
+
+async fn reply<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut BufReader<S>,
+    response: &str,
+) -> Option<()> {
+    let stream = stream.get_mut();
+    stream
+        .write_all(format!("{}\r\n", response).as_bytes())
+        .await
+        .ok()?;
+    stream.flush().await.ok()
+}
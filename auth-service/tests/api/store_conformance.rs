@@ -0,0 +1,825 @@
+//! Behaviour every store implementation must share. Each `check_*` function
+//! exercises one trait through a trait object, and the tests below run it
+//! against every implementation.
+
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{
+        data_stores::{
+            configure_redis, configure_sqlite, BannedTokenStore, EmailOutbox, EmailOutboxError,
+            EmailSuppressionStore, EmailSuppressionStoreError, HashsetBannedTokenStore,
+            LoginAttemptId, OutboxQuery, SuppressionQuery, TwoFACode, TwoFACodeStore,
+            TwoFACodeStoreError, UserCache, UserQuery, UserStore, UserStoreError,
+        },
+        email::Email,
+        email_client::EmailMessage,
+        email_outbox::{OutboxMessageId, OutboxStatus},
+        email_suppression::{EmailSuppression, SuppressionReason},
+        hashmap_two_fa_code_store::HashMapTwoFACodeStore,
+        locale::Locale,
+        parse::Parseable,
+        password::Password,
+        phone_number::PhoneNumber,
+        role::{Permission, Role},
+        user::{AccountStatus, StatusChange, TwoFactorChannel, User},
+    },
+    services::{
+        caching_user_store::CachingUserStore, data_stores::PostgresUserStore,
+        hashmap_email_outbox::HashMapEmailOutbox,
+        hashmap_email_suppression_store::HashMapEmailSuppressionStore,
+        hashmap_user_store::HashMapUserStore, lru_user_cache::LruUserCache,
+        postgres_banned_token_store::PostgresBannedTokenStore,
+        postgres_email_outbox::PostgresEmailOutbox,
+        postgres_email_suppression_store::PostgresEmailSuppressionStore,
+        postgres_two_fa_code_store::PostgresTwoFACodeStore, redis_user_cache::RedisUserCache,
+        sqlite_user_store::SqliteUserStore,
+    },
+    utils::auth::TokenHash,
+};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn random_email() -> Email {
+    Email::parse(Secret::new(get_random_email())).unwrap()
+}
+
+fn password(password: &str) -> Password {
+    Password::parse(Secret::new(password.to_owned())).unwrap()
+}
+
+async fn check_user_store(store: &dyn UserStore) {
+    let email = random_email();
+    let unknown = random_email();
+
+    // Adding
+    let new_user = User::new(email.clone(), true, password("password123"));
+    store.add_user(new_user.clone()).await.unwrap();
+    assert_eq!(
+        store
+            .add_user(User::new(email.clone(), false, password("password456")))
+            .await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+
+    // Reading
+    let user = store.get_user(&email).await.unwrap();
+    assert_eq!(user.email, email);
+    assert!(user.requires_2fa);
+    assert_eq!(user.status, AccountStatus::Active);
+    assert!(!user.password_reset_required);
+    // Timestamps are kept to the microsecond
+    assert_eq!(user.id, new_user.id);
+    assert_eq!(
+        user.created_at.timestamp_micros(),
+        new_user.created_at.timestamp_micros()
+    );
+    assert_eq!(
+        user.password_changed_at.timestamp_micros(),
+        new_user.password_changed_at.timestamp_micros()
+    );
+    assert_eq!(user.last_login_at, None);
+    assert_eq!(user.failed_login_count, 0);
+    assert_eq!(
+        store.get_user(&unknown).await.err(),
+        Some(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.get_user_summary(&unknown).await.err(),
+        Some(UserStoreError::UserNotFound)
+    );
+
+    // Credentials
+    // Validating returns the user, so login needs a single lookup
+    let validated = store
+        .validate_user(&email, &password("password123"))
+        .await
+        .unwrap();
+    assert_eq!(validated.email, email);
+    assert!(validated.requires_2fa);
+    assert_eq!(
+        store.validate_user(&email, &password("password456")).await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    assert_eq!(
+        store
+            .validate_user(&unknown, &password("password123"))
+            .await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Login metadata
+    store.record_failed_login(&email).await.unwrap();
+    store.record_failed_login(&email).await.unwrap();
+    assert_eq!(store.get_user(&email).await.unwrap().failed_login_count, 2);
+    store
+        .record_login(&email, Some("203.0.113.7"))
+        .await
+        .unwrap();
+    let user = store.get_user(&email).await.unwrap();
+    assert_eq!(user.failed_login_count, 0);
+    assert_eq!(user.last_login_ip.as_deref(), Some("203.0.113.7"));
+    assert!(user.last_login_at.unwrap() >= user.created_at);
+    // Logins are not changes to the account
+    assert_eq!(
+        user.updated_at.timestamp_micros(),
+        new_user.updated_at.timestamp_micros()
+    );
+    assert_eq!(
+        store.record_login(&unknown, None).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.record_failed_login(&unknown).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Roles
+    let admin = Role::admin();
+    let missing_role = Role::parse("no_such_role".to_owned()).unwrap();
+    assert!(store.role_exists(&admin).await.unwrap());
+    assert!(!store.role_exists(&missing_role).await.unwrap());
+    assert_eq!(
+        store
+            .get_permissions(std::slice::from_ref(&admin))
+            .await
+            .unwrap(),
+        Permission::ALL.iter().copied().collect::<HashSet<_>>()
+    );
+    assert!(store
+        .get_permissions(std::slice::from_ref(&missing_role))
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(store.get_roles(&email).await.unwrap().is_empty());
+    assert!(store.get_roles(&unknown).await.unwrap().is_empty());
+
+    store.assign_role(&email, &admin).await.unwrap();
+    store.assign_role(&email, &admin).await.unwrap();
+    assert_eq!(store.get_roles(&email).await.unwrap(), vec![admin.clone()]);
+    assert_eq!(
+        store.assign_role(&unknown, &admin).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.assign_role(&email, &missing_role).await,
+        Err(UserStoreError::RoleNotFound)
+    );
+
+    store.revoke_role(&email, &admin).await.unwrap();
+    store.revoke_role(&email, &admin).await.unwrap();
+    assert!(store.get_roles(&email).await.unwrap().is_empty());
+    assert_eq!(
+        store.revoke_role(&unknown, &admin).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Account state
+    let change = StatusChange::new(AccountStatus::Suspended, "admin@example.com")
+        .with_reason(Some("Chargeback".to_owned()));
+    store.set_status(&email, &change).await.unwrap();
+    store
+        .set_password_reset_required(&email, true)
+        .await
+        .unwrap();
+    store.set_requires_2fa(&email, false).await.unwrap();
+
+    let summary = store.get_user_summary(&email).await.unwrap();
+    assert_eq!(summary.email, email);
+    assert_eq!(summary.status, AccountStatus::Suspended);
+    assert_eq!(summary.status_reason.as_deref(), Some("Chargeback"));
+    assert_eq!(
+        summary.status_changed_by.as_deref(),
+        Some("admin@example.com")
+    );
+    assert!(summary.password_reset_required);
+    assert!(!summary.requires_2fa);
+    let user = store.get_user(&email).await.unwrap();
+    assert_eq!(user.status, AccountStatus::Suspended);
+    assert!(user.updated_at > new_user.updated_at);
+    assert!(user.password_reset_required);
+    assert!(!user.requires_2fa);
+
+    assert_eq!(
+        store.set_status(&unknown, &change).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.set_password_reset_required(&unknown, true).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.set_requires_2fa(&unknown, true).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Locale preference
+    assert_eq!(user.locale, None);
+    store
+        .set_locale(&email, Some(Locale::German))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_user(&email).await.unwrap().locale,
+        Some(Locale::German)
+    );
+    store.set_locale(&email, None).await.unwrap();
+    assert_eq!(store.get_user(&email).await.unwrap().locale, None);
+    assert_eq!(
+        store.set_locale(&unknown, None).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    let german = random_email();
+    let mut german_user = User::new(german.clone(), false, password("password123"));
+    german_user.locale = Some(Locale::German);
+    store.add_user(german_user).await.unwrap();
+    assert_eq!(
+        store.get_user(&german).await.unwrap().locale,
+        Some(Locale::German)
+    );
+
+    // Listing
+    let other = random_email();
+    store
+        .add_user(User::new(other.clone(), false, password("password123")))
+        .await
+        .unwrap();
+    let mut expected = vec![email.clone(), other.clone()];
+    expected.sort_by(|a, b| a.as_ref().expose_secret().cmp(b.as_ref().expose_secret()));
+
+    let page = store
+        .list_users(&UserQuery::new(None, None, None).restricted_to(expected.clone()))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    let emails: Vec<Email> = page.users.into_iter().map(|user| user.email).collect();
+    assert_eq!(emails, expected);
+
+    let page = store
+        .list_users(&UserQuery::new(None, Some(2), Some(1)).restricted_to(expected.clone()))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.users.len(), 1);
+    assert_eq!(page.users[0].email, expected[1]);
+
+    // Search is a case-insensitive substring match
+    let local_part = email.as_ref().expose_secret().split('@').next().unwrap();
+    let page = store
+        .list_users(&UserQuery::new(Some(local_part.to_uppercase()), None, None))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.users[0].email, email);
+
+    // Security settings
+    assert!(store.get_user(&email).await.unwrap().security_notices);
+    store.set_security_notices(&email, false).await.unwrap();
+    assert!(!store.get_user(&email).await.unwrap().security_notices);
+    assert_eq!(
+        store.set_security_notices(&unknown, false).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    store
+        .set_password_reset_required(&email, true)
+        .await
+        .unwrap();
+    store
+        .set_password(&email, &password("password456"))
+        .await
+        .unwrap();
+    let user = store
+        .validate_user(&email, &password("password456"))
+        .await
+        .unwrap();
+    assert!(!user.password_reset_required);
+    assert!(user.password_changed_at >= user.created_at);
+    assert_eq!(
+        store.validate_user(&email, &password("password123")).await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    assert_eq!(
+        store.set_password(&unknown, &password("password456")).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Phone numbers
+    let phone = PhoneNumber::parse(Secret::new("+4915112345678".to_owned())).unwrap();
+    store.set_phone_number(&email, Some(&phone)).await.unwrap();
+    store.set_phone_verified(&email, true).await.unwrap();
+    store
+        .set_two_fa_channel(&email, TwoFactorChannel::Sms)
+        .await
+        .unwrap();
+    let user = store.get_user(&email).await.unwrap();
+    assert_eq!(user.phone_number, Some(phone.clone()));
+    assert!(user.phone_verified);
+    assert_eq!(user.two_fa_channel, TwoFactorChannel::Sms);
+    // A new number must be verified again and codes go back to email
+    store.set_phone_number(&email, Some(&phone)).await.unwrap();
+    let user = store.get_user(&email).await.unwrap();
+    assert!(!user.phone_verified);
+    assert_eq!(user.two_fa_channel, TwoFactorChannel::Email);
+    store.set_phone_number(&email, None).await.unwrap();
+    assert_eq!(store.get_user(&email).await.unwrap().phone_number, None);
+    assert_eq!(
+        store.set_phone_number(&unknown, Some(&phone)).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.set_phone_verified(&unknown, true).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store
+            .set_two_fa_channel(&unknown, TwoFactorChannel::Sms)
+            .await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Devices
+    let device = TokenHash::of(&Secret::new(Uuid::new_v4().to_string()));
+    assert!(store.remember_device(&email, &device).await.unwrap());
+    assert!(!store.remember_device(&email, &device).await.unwrap());
+    assert_eq!(
+        store.remember_device(&unknown, &device).await,
+        Err(UserStoreError::UserNotFound)
+    );
+
+    // Changing the email keeps roles and devices
+    store.assign_role(&email, &Role::admin()).await.unwrap();
+    assert_eq!(
+        store.change_email(&email, &other).await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+    assert_eq!(
+        store.change_email(&unknown, &random_email()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    let new_email = random_email();
+    store.change_email(&email, &new_email).await.unwrap();
+    assert_eq!(
+        store.get_user(&email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    let moved = store.get_user(&new_email).await.unwrap();
+    assert_eq!(moved.id, user.id);
+    assert_eq!(moved.email, new_email);
+    assert_eq!(
+        store.get_roles(&new_email).await.unwrap(),
+        vec![Role::admin()]
+    );
+    assert!(!store.remember_device(&new_email, &device).await.unwrap());
+}
+
+async fn check_user_cache(cache: &dyn UserCache) {
+    let user = User::new(random_email(), false, password("password123"));
+    let generation = cache.generation(&user.email).await.unwrap();
+    cache.set_user(&user, generation).await.unwrap();
+    cache
+        .set_roles(&user.email, &[Role::admin()], generation)
+        .await
+        .unwrap();
+    let cached = cache
+        .get_user(&user.email, generation)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cached.id, user.id);
+    assert_eq!(
+        cache.get_roles(&user.email, generation).await.unwrap(),
+        Some(vec![Role::admin()])
+    );
+
+    // A lookup that read the store before an invalidation caches nothing
+    let stale = cache.generation(&user.email).await.unwrap();
+    cache.invalidate(&user.email).await.unwrap();
+    cache.set_user(&user, stale).await.unwrap();
+    cache
+        .set_roles(&user.email, &[Role::admin()], stale)
+        .await
+        .unwrap();
+    let generation = cache.generation(&user.email).await.unwrap();
+    assert_ne!(generation, stale);
+    assert!(cache
+        .get_user(&user.email, generation)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        cache.get_roles(&user.email, generation).await.unwrap(),
+        None
+    );
+}
+
+async fn check_banned_token_store(store: &dyn BannedTokenStore) {
+    let token = TokenHash::of(&Secret::new(Uuid::new_v4().to_string()));
+    let other_token = TokenHash::of(&Secret::new(Uuid::new_v4().to_string()));
+    let ttl = Duration::from_secs(60);
+
+    assert!(!store.token_exists(&token).await.unwrap());
+    store.insert(&token, ttl).await.unwrap();
+    store.insert(&token, ttl).await.unwrap();
+    assert!(store.token_exists(&token).await.unwrap());
+    assert!(!store.token_exists(&other_token).await.unwrap());
+
+    // A token without time left needs no ban
+    store.insert(&other_token, Duration::ZERO).await.unwrap();
+    assert!(!store.token_exists(&other_token).await.unwrap());
+
+    let email = random_email();
+    assert_eq!(store.sessions_revoked_at(&email).await.unwrap(), None);
+    store.revoke_sessions(&email, 1_000).await.unwrap();
+    store.revoke_sessions(&email, 2_000).await.unwrap();
+    assert_eq!(
+        store.sessions_revoked_at(&email).await.unwrap(),
+        Some(2_000)
+    );
+    assert_eq!(
+        store.sessions_revoked_at(&random_email()).await.unwrap(),
+        None
+    );
+}
+
+async fn check_two_fa_code_store(store: &dyn TwoFACodeStore) {
+    let email = random_email();
+
+    assert_eq!(
+        store.get_code(&email).await.err(),
+        Some(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    store.remove_code(&email).await.unwrap();
+
+    store
+        .add_code(
+            email.clone(),
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+    // A new login attempt replaces the pending code
+    let (login_attempt_id, code) = (LoginAttemptId::default(), TwoFACode::default());
+    store
+        .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_code(&email).await.unwrap(),
+        (login_attempt_id, code)
+    );
+    assert_eq!(
+        store.get_code(&random_email()).await.err(),
+        Some(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+
+    store.remove_code(&email).await.unwrap();
+    assert_eq!(
+        store.get_code(&email).await.err(),
+        Some(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    );
+    store.remove_code(&email).await.unwrap();
+}
+
+async fn check_email_outbox(outbox: &dyn EmailOutbox) {
+    let lease = Duration::from_secs(60);
+    let message = EmailMessage {
+        subject: "Your code".to_owned(),
+        html_body: "<p>123456</p>".to_owned(),
+        text_body: "123456".to_owned(),
+    };
+    let (first_recipient, second_recipient) = (random_email(), random_email());
+    let first = outbox.enqueue(&first_recipient, &message).await.unwrap();
+    let second = outbox.enqueue(&second_recipient, &message).await.unwrap();
+
+    let pending = outbox.get(&first).await.unwrap();
+    assert_eq!(pending.recipient, first_recipient);
+    assert_eq!(pending.message, message);
+    assert_eq!(pending.status, OutboxStatus::Pending);
+    assert_eq!(pending.attempts, 0);
+    assert_eq!(pending.sent_at, None);
+
+    // Claimed messages are leased, so they are not handed out twice
+    let claimed = outbox.claim_due(1, lease).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].id, first);
+    assert_eq!(claimed[0].attempts, 1);
+    let claimed = outbox.claim_due(10, lease).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].id, second);
+    assert!(outbox.claim_due(10, lease).await.unwrap().is_empty());
+
+    // Bodies are dropped once sent
+    outbox.mark_sent(&first).await.unwrap();
+    let sent = outbox.get(&first).await.unwrap();
+    assert_eq!(sent.status, OutboxStatus::Sent);
+    assert!(sent.sent_at.is_some());
+    assert_eq!(sent.message.subject, message.subject);
+    assert!(sent.message.html_body.is_empty() && sent.message.text_body.is_empty());
+
+    let retry_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+    outbox
+        .mark_failed(&second, "Connection refused", Some(retry_at))
+        .await
+        .unwrap();
+    let failed = outbox.get(&second).await.unwrap();
+    assert_eq!(failed.status, OutboxStatus::Pending);
+    assert_eq!(failed.last_error.as_deref(), Some("Connection refused"));
+    let claimed = outbox.claim_due(10, lease).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].attempts, 2);
+
+    outbox
+        .mark_failed(&second, "Mailbox full", None)
+        .await
+        .unwrap();
+    let dead = outbox.get(&second).await.unwrap();
+    assert_eq!(dead.status, OutboxStatus::Dead);
+    assert_eq!(dead.last_error.as_deref(), Some("Mailbox full"));
+    assert_eq!(dead.message, message);
+    assert!(outbox.claim_due(10, lease).await.unwrap().is_empty());
+
+    let page = outbox.list(&OutboxQuery::default()).await.unwrap();
+    assert_eq!(page.total, 2);
+    let ids: Vec<OutboxMessageId> = page.messages.iter().map(|message| message.id).collect();
+    assert_eq!(ids, vec![second, first]);
+    let page = outbox
+        .list(&OutboxQuery::new(Some(2), Some(1)))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.messages[0].id, first);
+    let page = outbox
+        .list(&OutboxQuery::default().with_status(Some(OutboxStatus::Dead)))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(page.messages[0].id, second);
+
+    // Only dead-lettered messages can be requeued
+    let unknown = OutboxMessageId::new();
+    assert_eq!(
+        outbox.requeue(&first).await.err(),
+        Some(EmailOutboxError::NotDeadLettered)
+    );
+    assert_eq!(
+        outbox.requeue(&unknown).await.err(),
+        Some(EmailOutboxError::MessageNotFound)
+    );
+    outbox.requeue(&second).await.unwrap();
+    let requeued = outbox.get(&second).await.unwrap();
+    assert_eq!(requeued.status, OutboxStatus::Pending);
+    assert_eq!(requeued.attempts, 0);
+    assert_eq!(outbox.claim_due(10, lease).await.unwrap().len(), 1);
+
+    assert_eq!(
+        outbox.get(&unknown).await.err(),
+        Some(EmailOutboxError::MessageNotFound)
+    );
+    assert_eq!(
+        outbox.mark_sent(&unknown).await.err(),
+        Some(EmailOutboxError::MessageNotFound)
+    );
+    assert_eq!(
+        outbox.mark_failed(&unknown, "Lost", None).await.err(),
+        Some(EmailOutboxError::MessageNotFound)
+    );
+}
+
+async fn check_email_suppression_store(store: &dyn EmailSuppressionStore) {
+    let bounced = random_email();
+    let complained = random_email();
+    assert_eq!(store.get(&bounced).await.unwrap(), None);
+
+    let suppressed_at = Utc::now() - chrono::Duration::minutes(5);
+    let suppression = EmailSuppression {
+        suppressed_at,
+        ..EmailSuppression::new(bounced.clone(), SuppressionReason::SpamComplaint)
+    };
+    store.suppress(&suppression).await.unwrap();
+    // Suppressing again replaces the reason
+    let suppression = EmailSuppression {
+        suppressed_at,
+        ..EmailSuppression::new(bounced.clone(), SuppressionReason::HardBounce)
+            .with_description(Some("550 5.1.1 Unknown user".to_owned()))
+    };
+    store.suppress(&suppression).await.unwrap();
+    let stored = store.get(&bounced).await.unwrap().unwrap();
+    assert_eq!(stored.reason, SuppressionReason::HardBounce);
+    assert_eq!(
+        stored.description.as_deref(),
+        Some("550 5.1.1 Unknown user")
+    );
+    assert_eq!(
+        stored.suppressed_at.timestamp_micros(),
+        suppressed_at.timestamp_micros()
+    );
+    store
+        .suppress(&EmailSuppression::new(
+            complained.clone(),
+            SuppressionReason::SpamComplaint,
+        ))
+        .await
+        .unwrap();
+
+    // Most recent first
+    let page = store.list(&SuppressionQuery::default()).await.unwrap();
+    assert_eq!(page.total, 2);
+    let emails: Vec<Email> = page
+        .suppressions
+        .into_iter()
+        .map(|suppression| suppression.email)
+        .collect();
+    assert_eq!(emails, vec![complained.clone(), bounced.clone()]);
+    let page = store
+        .list(&SuppressionQuery::new(Some(2), Some(1)))
+        .await
+        .unwrap();
+    assert_eq!(page.total, 2);
+    assert_eq!(page.suppressions[0].email, bounced);
+
+    store.remove(&bounced).await.unwrap();
+    assert_eq!(store.get(&bounced).await.unwrap(), None);
+    assert_eq!(
+        store.remove(&bounced).await.err(),
+        Some(EmailSuppressionStoreError::SuppressionNotFound)
+    );
+    assert!(store.get(&complained).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn hashmap_user_store_conforms() {
+    check_user_store(&HashMapUserStore::default()).await;
+}
+
+#[tokio::test]
+async fn postgres_user_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_user_store(&PostgresUserStore::new(app.pg_pool.clone())).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn lru_cached_user_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_user_store(&CachingUserStore::new(
+        app.user_store.clone(),
+        Arc::new(LruUserCache::new(100, Duration::from_secs(30))),
+    ))
+    .await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn redis_cached_user_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_user_store(&CachingUserStore::new(
+        app.user_store.clone(),
+        Arc::new(RedisUserCache::new(
+            configure_redis().await,
+            Duration::from_secs(30),
+        )),
+    ))
+    .await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn lru_user_cache_conforms() {
+    check_user_cache(&LruUserCache::new(100, Duration::from_secs(30))).await;
+}
+
+#[tokio::test]
+async fn redis_user_cache_conforms() {
+    check_user_cache(&RedisUserCache::new(
+        configure_redis().await,
+        Duration::from_secs(30),
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn redis_user_cache_leaves_out_password_hashes() {
+    let mut conn = configure_redis().await;
+    let cache = RedisUserCache::new(conn.clone(), Duration::from_secs(30));
+    let user = User::new(random_email(), false, password("password123"));
+    cache.set_user(&user, 0).await.unwrap();
+
+    let cached: String = redis::AsyncCommands::get(
+        &mut conn,
+        format!("user_cache:user:0:{}", user.email.as_ref().expose_secret()),
+    )
+    .await
+    .unwrap();
+    assert!(!cached.contains("password123"));
+    assert!(cache.get_user(&user.email, 0).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn sqlite_user_store_conforms() {
+    check_user_store(&SqliteUserStore::new(
+        configure_sqlite("sqlite::memory:").await,
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn hashset_banned_token_store_conforms() {
+    check_banned_token_store(&HashsetBannedTokenStore::new()).await;
+}
+
+#[tokio::test]
+async fn redis_banned_token_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_banned_token_store(app.banned_token_store.as_ref()).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn redis_banned_token_store_reads_revocations_in_seconds() {
+    let mut app = TestApp::new().await;
+    let email = random_email();
+    // Written before revocation times had milliseconds
+    let _: () = redis::AsyncCommands::set_ex(
+        &mut configure_redis().await,
+        format!("revoked_sessions:{}", email.as_ref().expose_secret()),
+        1_000,
+        60,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        app.banned_token_store
+            .sessions_revoked_at(&email)
+            .await
+            .unwrap(),
+        Some(1_000_999)
+    );
+
+    app.banned_token_store
+        .revoke_sessions(&email, 2_000_000)
+        .await
+        .unwrap();
+    assert_eq!(
+        app.banned_token_store
+            .sessions_revoked_at(&email)
+            .await
+            .unwrap(),
+        Some(2_000_000)
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn postgres_banned_token_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_banned_token_store(&PostgresBannedTokenStore::new(app.pg_pool.clone())).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn hashmap_two_fa_code_store_conforms() {
+    check_two_fa_code_store(&HashMapTwoFACodeStore::default()).await;
+}
+
+#[tokio::test]
+async fn redis_two_fa_code_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_two_fa_code_store(app.two_fa_code_store.as_ref()).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn postgres_two_fa_code_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_two_fa_code_store(&PostgresTwoFACodeStore::new(app.pg_pool.clone())).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn hashmap_email_outbox_conforms() {
+    check_email_outbox(&HashMapEmailOutbox::default()).await;
+}
+
+#[tokio::test]
+async fn postgres_email_outbox_conforms() {
+    let mut app = TestApp::new().await;
+    check_email_outbox(&PostgresEmailOutbox::new(app.pg_pool.clone())).await;
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn hashmap_email_suppression_store_conforms() {
+    check_email_suppression_store(&HashMapEmailSuppressionStore::default()).await;
+}
+
+#[tokio::test]
+async fn postgres_email_suppression_store_conforms() {
+    let mut app = TestApp::new().await;
+    check_email_suppression_store(&PostgresEmailSuppressionStore::new(app.pg_pool.clone())).await;
+    app.clean_up().await;
+}
@@ -1,9 +1,14 @@
 use crate::{
-    helpers::{TestApp, _assert_eq_status_code},
+    helpers::{_assert_eq_status_code, TestApp},
     login::prepare_login,
 };
 use auth_service::{
-    domain::{data_stores::LoginAttemptId, email::Email, parse::Parseable},
+    domain::{
+        data_stores::LoginAttemptId,
+        email::Email,
+        parse::Parseable,
+        user::{AccountStatus, StatusChange},
+    },
     routes::TwoFactorAuthResponse,
     utils::constants::JWT_COOKIE_NAME,
 };
@@ -45,8 +50,6 @@ async fn prepare_200_case(requires_mock_email_server: bool) -> (TestApp, Value,
     // Get two factor auth code
     let two_fa_code = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&email_object)
         .await
         .expect("Two fractor authentication Code should have been registered");
@@ -88,7 +91,7 @@ async fn should_return_200_if_correct_code() {
 }))]
 #[tokio::test]
 async fn should_return_401_if_incorrect_credentials(incorrect_body: Value) {
-    let (mut app, __, _) = prepare_200_case(true).await;
+    let (mut app, _, _) = prepare_200_case(true).await;
     let response = app.verify_2fa(&incorrect_body).await;
     _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
     app.clean_up().await;
@@ -111,3 +114,58 @@ async fn should_return_401_if_old_code() {
     _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_record_login_once_the_code_is_verified() {
+    let (mut app, verify_2fa_body, login_body) = prepare_200_case(true).await;
+    let email = Email::parse(Secret::new(
+        login_body["email"].as_str().unwrap().to_owned(),
+    ))
+    .unwrap();
+    // A correct password alone does not complete the login
+    let user = app.user_store.get_user(&email).await.unwrap();
+    assert_eq!(user.last_login_at, None);
+
+    let mut wrong_code = verify_2fa_body.clone();
+    wrong_code["2FACode"] = match verify_2fa_body["2FACode"].as_str() {
+        Some("123456") => Value::from("654321"),
+        _ => Value::from("123456"),
+    };
+    let response = app.verify_2fa(&wrong_code).await;
+    _assert_eq_status_code(&response, StatusCode::UNAUTHORIZED);
+    let user = app.user_store.get_user(&email).await.unwrap();
+    assert_eq!(user.failed_login_count, 1);
+
+    let response = app.verify_2fa(&verify_2fa_body).await;
+    _assert_eq_status_code(&response, StatusCode::OK);
+    let user = app.user_store.get_user(&email).await.unwrap();
+    assert_eq!(user.failed_login_count, 0);
+    assert!(user.last_login_at.is_some());
+    assert_eq!(user.last_login_ip.as_deref(), Some("127.0.0.1"));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_suspended_while_the_code_was_pending() {
+    let (mut app, verify_2fa_body, login_body) = prepare_200_case(true).await;
+    let email = Email::parse(Secret::new(
+        login_body["email"].as_str().unwrap().to_owned(),
+    ))
+    .unwrap();
+    app.user_store
+        .set_status(
+            &email,
+            &StatusChange::new(AccountStatus::Suspended, "admin@example.com"),
+        )
+        .await
+        .unwrap();
+
+    let response = app.verify_2fa(&verify_2fa_body).await;
+    _assert_eq_status_code(&response, StatusCode::FORBIDDEN);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+    // The code cannot be replayed once the account is active again
+    assert!(app.two_fa_code_store.get_code(&email).await.is_err());
+    app.clean_up().await;
+}
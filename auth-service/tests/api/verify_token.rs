@@ -1,5 +1,7 @@
-use crate::helpers::{TestApp, _assert_eq_status_code, get_random_email};
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use crate::helpers::{_assert_eq_status_code, get_random_email, TestApp};
+use std::time::Duration;
+
+use auth_service::utils::{auth::TokenHash, constants::JWT_COOKIE_NAME};
 use reqwest::StatusCode;
 use secrecy::Secret;
 use serde_json::Value;
@@ -98,12 +100,14 @@ async fn should_return_401_if_banned_token() {
         .expect("No auth cookie found");
 
     // ban current token
-    if let Err(_) = app
+    if app
         .banned_token_store
-        .write()
-        .await
-        .insert(Secret::new(auth_cookie.value().to_string()))
+        .insert(
+            &TokenHash::of(&Secret::new(auth_cookie.value().to_string())),
+            Duration::from_secs(60),
+        )
         .await
+        .is_err()
     {
         panic!("Ban token store addition feature is not working as expected")
     }